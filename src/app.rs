@@ -1,10 +1,11 @@
-use std::sync::Arc;
+use std::{collections::BTreeSet, sync::Arc};
 
 use anyhow::Result;
 use dominator::{Dom, clone, events, html, with_node};
 use futures_signals::{
     map_ref,
     signal::{Mutable, Signal, SignalExt},
+    signal_vec::{MutableVec, SignalVecExt},
 };
 use gloo_file::{Blob, futures::read_as_bytes};
 use wasm_bindgen::{JsCast, UnwrapThrowExt};
@@ -12,33 +13,93 @@ use wasm_bindgen_futures::spawn_local;
 use web_sys::{Element, HtmlAnchorElement, HtmlElement, HtmlInputElement};
 
 use crate::{
-    css::{MODAL_CONTENT_STYLE, MODAL_STYLE},
-    data::{Aeat720Information, PersonalInformation},
+    crypto_table::CryptoTable,
+    css::{MODAL_CONTENT_STYLE, MODAL_STYLE, WARNING_PANEL_CLASS},
+    csv_importer::CsvImporter,
+    data::{
+        Aeat720Information, Aeat720Records, Aeat721Information, Aeat721Records, DEFAULT_YEAR,
+        PersonalInformation,
+    },
+    error::ImportWarning,
+    import_preview::{AcceptedImport, ImportPreview},
+    importer::ImportResult,
+    ledger::Ledger,
+    parsers::generic_csv::CsvMapping,
     personal_info::PersonalInfoViewer,
+    project::Project,
+    reports::aeat_720::InvalidIsin,
     table::Table,
-    utils::{file_importer, web},
+    utils::{file_importer, generic_csv_importer, web},
+    wealth_tax::WealthTaxViewer,
 };
 
+const LEDGER_STORAGE_KEY: &str = "burocratin.ledger";
+const SESSION_STORAGE_KEY: &str = "burocratin.session";
+
 pub struct App {
     current_error: Mutable<Option<String>>,
+    import_warnings: MutableVec<ImportWarning>,
     personal_info: Mutable<PersonalInformation>,
+    ledger: Mutable<Ledger>,
     aeat720_form_path: Mutable<Option<String>>,
+    aeat721_form_path: Mutable<Option<String>>,
     personal_info_viewer: Arc<PersonalInfoViewer>,
     table: Arc<Table>,
+    crypto_table: Arc<CryptoTable>,
+    csv_importer: Arc<CsvImporter>,
+    import_preview: Arc<ImportPreview>,
+    wealth_tax_viewer: Arc<WealthTaxViewer>,
     modal_visible: Mutable<bool>,
+    project_password: Mutable<String>,
+    encrypted_project: Mutable<Option<Vec<u8>>>,
+    /// Whether the saved session or acquisition history could not be
+    /// loaded, in which case they are not overwritten until the user starts
+    /// over.
+    storage_failed: bool,
 }
 
 impl App {
     pub fn new() -> Arc<Self> {
-        let personal_info = Mutable::new(PersonalInformation::default());
+        let session = Self::load_session();
+        let ledger = web::try_load_item(LEDGER_STORAGE_KEY);
+        let storage_failed = session.is_err() || ledger.is_err();
+        let current_error = if storage_failed {
+            log::error!(
+                "Unable to load saved data: {:?} {:?}",
+                session.as_ref().err(),
+                ledger.as_ref().err()
+            );
+            Some(
+                "No se han podido recuperar los datos guardados en el navegador. No se guardarán los cambios hasta que empieces de nuevo.".to_string(),
+            )
+        } else {
+            None
+        };
+        let session = session
+            .unwrap_or_else(|_| Self::session(PersonalInformation::default(), vec![], vec![]));
+        let personal_info = Mutable::new(session.personal_info);
+        let table = Table::new();
+        table.extend_rows(session.records);
+        let crypto_table = CryptoTable::new();
+        crypto_table.extend_rows(session.crypto_records);
 
         Arc::new(Self {
-            current_error: Mutable::new(None),
+            current_error: Mutable::new(current_error),
+            import_warnings: MutableVec::new(),
             personal_info: personal_info.clone(),
+            ledger: Mutable::new(ledger.ok().flatten().unwrap_or_else(Ledger::new)),
             aeat720_form_path: Mutable::new(None),
+            aeat721_form_path: Mutable::new(None),
             personal_info_viewer: PersonalInfoViewer::new(personal_info.clone()),
-            table: Table::new(),
-            modal_visible: Mutable::new(false),
+            wealth_tax_viewer: WealthTaxViewer::new(table.clone(), crypto_table.clone()),
+            table,
+            crypto_table,
+            csv_importer: CsvImporter::new(),
+            import_preview: ImportPreview::new(),
+            modal_visible: Mutable::new(storage_failed),
+            project_password: Mutable::new(String::new()),
+            encrypted_project: Mutable::new(None),
+            storage_failed,
         })
     }
 
@@ -50,32 +111,350 @@ impl App {
         }
     }
 
-    fn import_file(this: &Arc<Self>, content: Vec<u8>) {
-        let import_data = file_importer(content);
-        match import_data {
-            Ok(records) => {
-                this.table.extend_rows(records);
+    fn declared_year(this: &Arc<Self>) -> usize {
+        match this.personal_info.lock_ref().year {
+            0 => DEFAULT_YEAR,
+            year => year,
+        }
+    }
+
+    /// Session of the data typed and imported by the user, a project
+    /// without the acquisition history, saved on its own.
+    fn session(
+        personal_info: PersonalInformation,
+        records: Aeat720Records,
+        crypto_records: Aeat721Records,
+    ) -> Project {
+        Project::new(personal_info, records, crypto_records, Ledger::new())
+    }
+
+    /// Session saved in the browser, in the versioned format of the project
+    /// files so sessions of previous versions are upgraded.
+    fn load_session() -> Result<Project> {
+        match web::load_text(SESSION_STORAGE_KEY)? {
+            Some(content) => Project::from_json(content.as_bytes()),
+            None => Ok(Self::session(
+                PersonalInformation::default(),
+                vec![],
+                vec![],
+            )),
+        }
+    }
+
+    fn save_session(session: &Project) -> Result<()> {
+        web::store_text(SESSION_STORAGE_KEY, &String::from_utf8(session.to_json()?)?)
+    }
+
+    /// Saves the session every time the personal information or the rows of
+    /// the tables change, unless the saved one could not be loaded.
+    fn store_session(this: &Arc<Self>) -> impl Future<Output = ()> + use<> {
+        let storage_failed = this.storage_failed;
+        map_ref! {
+            let personal_info = this.personal_info.signal_cloned(),
+            let records = this.table.records_signal(),
+            let crypto_records = this.crypto_table.records_signal() => Self::session(
+                personal_info.clone(),
+                records.clone(),
+                crypto_records.clone(),
+            )
+        }
+        .for_each(move |session| {
+            if storage_failed {
+                log::debug!("Session not saved, the stored one could not be loaded");
+            } else if let Err(err) = Self::save_session(&session) {
+                log::error!("Unable to save session: {}", err);
+            }
+            async {}
+        })
+    }
+
+    /// Deletes the saved session and acquisition history and reloads the
+    /// page, after the user confirms it.
+    fn start_over() {
+        let window = gloo_utils::window();
+        let confirmed = window
+            .confirm_with_message(
+                "Se borrarán los datos personales, los movimientos y el historial de operaciones guardados. ¿Continuar?",
+            )
+            .unwrap_or(false);
+        if !confirmed {
+            return;
+        }
+        for key in [SESSION_STORAGE_KEY, LEDGER_STORAGE_KEY] {
+            if let Err(err) = web::delete_item(key) {
+                log::error!("Unable to delete saved data: {}", err);
+            }
+        }
+        if let Err(err) = window.location().reload() {
+            log::error!("Unable to reload page: {:?}", err);
+        }
+    }
+
+    /// Downloads the personal information, the rows of the tables and the
+    /// acquisition history as a project file, encrypted when the user gives
+    /// a password.
+    fn export_project(this: &Arc<Self>) -> Result<()> {
+        let project = Project::new(
+            this.personal_info.get_cloned(),
+            this.table.get_records(),
+            this.crypto_table.get_records(),
+            this.ledger.get_cloned(),
+        );
+        let path = web::generate_project(&project, &this.project_password.lock_ref())?;
+        let elem: Element = gloo_utils::document().create_element("a").unwrap_throw();
+        let link: HtmlAnchorElement = elem.dyn_into().unwrap_throw();
+        link.set_href(&path);
+        let filename = format!("proyecto-burocratin-{}.json", Self::declared_year(this));
+        let _ = link.set_attribute("download", &filename);
+        link.click();
+        web::delete_path(path)
+    }
+
+    /// Opens a project file, encrypted ones wait for the user to type their
+    /// password.
+    fn open_project(this: &Arc<Self>, content: Vec<u8>) {
+        if Project::is_encrypted(&content) {
+            this.encrypted_project.set(Some(content));
+            return;
+        }
+        match Project::from_json(&content) {
+            Ok(project) => Self::replace_session(this, project),
+            Err(err) => {
+                log::error!("Unable to open project: {:#}", err);
+                *this.current_error.lock_mut() = Some(
+                    "El fichero no es un proyecto válido o es de una versión más reciente de la aplicación.".to_string(),
+                );
+                this.modal_visible.set(true);
+            }
+        }
+    }
+
+    fn open_encrypted_project(this: &Arc<Self>, password: &str) {
+        let content = match this.encrypted_project.replace(None) {
+            Some(content) => content,
+            None => return,
+        };
+        match Project::from_encrypted_json(&content, password) {
+            Ok(project) => Self::replace_session(this, project),
+            Err(err) => {
+                log::error!("Unable to open encrypted project: {:#}", err);
+                *this.current_error.lock_mut() =
+                    Some("Contraseña incorrecta o fichero de proyecto dañado.".to_string());
+                this.modal_visible.set(true);
+            }
+        }
+    }
+
+    /// Replaces the saved session and acquisition history with the ones of
+    /// a project file and reloads the page, after the user confirms it.
+    fn replace_session(this: &Arc<Self>, project: Project) {
+        let window = gloo_utils::window();
+        let confirmed = window
+            .confirm_with_message(
+                "Los datos personales, los movimientos y el historial de operaciones se sustituirán por los del proyecto. ¿Continuar?",
+            )
+            .unwrap_or(false);
+        if !confirmed {
+            return;
+        }
+        let session = Self::session(
+            project.personal_info,
+            project.records,
+            project.crypto_records,
+        );
+        if let Err(err) = Self::save_session(&session)
+            .and_then(|_| web::store_item(LEDGER_STORAGE_KEY, &project.ledger))
+        {
+            log::error!("Unable to save project: {}", err);
+            *this.current_error.lock_mut() = Some("Error guardando el proyecto".to_string());
+            this.modal_visible.set(true);
+            return;
+        }
+        if let Err(err) = window.location().reload() {
+            log::error!("Unable to reload page: {:?}", err);
+        }
+    }
+
+    fn add_imported_records(
+        this: &Arc<Self>,
+        records: Aeat720Records,
+        crypto_records: Aeat721Records,
+        warnings: Vec<ImportWarning>,
+        year: usize,
+    ) {
+        this.table.extend_rows(records);
+        this.crypto_table.extend_rows(crypto_records);
+        let ledger = this.ledger.lock_ref();
+        this.table.update_first_tx_dates(&ledger, year);
+        if this.storage_failed {
+            log::debug!("Acquisition history not saved, the stored one could not be loaded");
+        } else if let Err(err) = web::store_item(LEDGER_STORAGE_KEY, &*ledger) {
+            log::error!("Unable to save acquisition history: {}", err);
+        }
+        this.import_warnings.lock_mut().replace_cloned(warnings);
+    }
+
+    /// Adds the accepted records, removing first the rows of their brokers
+    /// when the user chose to replace them.
+    fn accept_import(this: &Arc<Self>, accepted: AcceptedImport) {
+        if accepted.replace {
+            let brokers: BTreeSet<_> = accepted.records.iter().map(|r| &r.broker.name).collect();
+            for broker in brokers {
+                this.table.remove_broker_rows(broker);
+            }
+            let custodians: BTreeSet<_> = accepted
+                .crypto_records
+                .iter()
+                .map(|r| &r.custodian.name)
+                .collect();
+            for custodian in custodians {
+                this.crypto_table.remove_custodian_rows(custodian);
+            }
+        }
+        this.ledger.set(accepted.ledger);
+        let year = Self::declared_year(this);
+        Self::add_imported_records(
+            this,
+            accepted.records,
+            accepted.crypto_records,
+            accepted.warnings,
+            year,
+        );
+    }
+
+    /// Shows the records of `result` to the user before adding them, files
+    /// without records, like previous AEAT 720 forms, only update `ledger`.
+    fn preview_import(this: &Arc<Self>, result: ImportResult, ledger: Ledger) {
+        if result.records.is_empty() && result.crypto_records.is_empty() {
+            Self::accept_import(
+                this,
+                AcceptedImport {
+                    records: vec![],
+                    crypto_records: vec![],
+                    warnings: result.warnings,
+                    ledger,
+                    replace: false,
+                },
+            );
+        } else {
+            this.import_preview.show(
+                result,
+                ledger,
+                &this.table.get_records(),
+                &this.crypto_table.get_records(),
+            );
+        }
+    }
+
+    /// Asks the country of the brokers the imported file does not locate,
+    /// the rows of the ones left unanswered keep the country empty to be
+    /// filled in the table.
+    fn ask_broker_countries(result: &mut ImportResult) {
+        let window = gloo_utils::window();
+        for broker in result.brokers_without_country() {
+            let country = window
+                .prompt_with_message(&format!(
+                    "El fichero no indica el país de {}. Introduce su código de país (ISO 3166, por ejemplo US):",
+                    broker
+                ))
+                .ok()
+                .flatten()
+                .unwrap_or_default();
+            if country.trim().len() == 2 {
+                result.set_broker_country(&broker, &country);
+            }
+        }
+    }
+
+    fn import_file(this: &Arc<Self>, content: Vec<u8>, filename: &str) {
+        let year = Self::declared_year(this);
+        let mut ledger = this.ledger.get_cloned();
+        match file_importer(content, &mut ledger, year) {
+            Ok(mut result) => {
+                Self::ask_broker_countries(&mut result);
+                result.set_filename(filename);
+                Self::preview_import(this, result, ledger)
             }
             Err(error) => {
-                *this.current_error.lock_mut() = Some(error.to_string());
+                *this.current_error.lock_mut() = Some(error.localized());
                 this.modal_visible.set(true);
             }
         }
     }
 
-    fn generate_720_file(this: &Arc<Self>) -> Result<()> {
+    fn import_generic_csv(
+        this: &Arc<Self>,
+        content: Vec<u8>,
+        filename: &str,
+        mapping: &CsvMapping,
+    ) -> Result<()> {
+        let year = Self::declared_year(this);
+        let mut ledger = this.ledger.get_cloned();
+        let mut result = generic_csv_importer(content, mapping, &mut ledger, year)?;
+        result.set_filename(filename);
+        Self::preview_import(this, result, ledger);
+        Ok(())
+    }
+
+    /// Whether the user accepts to declare the `unvalued` records valued at
+    /// zero, asked only when there are any.
+    fn confirm_unvalued(unvalued: usize) -> bool {
+        unvalued == 0
+            || gloo_utils::window()
+                .confirm_with_message(&format!(
+                    "{} filas tienen valor 0 €, normalmente porque el informe no da su valor a 31 de diciembre. ¿Generar el fichero igualmente?",
+                    unvalued
+                ))
+                .unwrap_or(false)
+    }
+
+    /// Generates the AEAT 720 form, `Ok(false)` when the user did not accept
+    /// the records valued at zero.
+    fn generate_720_file(this: &Arc<Self>) -> Result<bool> {
+        let records = this.table.get_records();
+        let unvalued = records.iter().filter(|r| r.value_in_euro.is_zero()).count();
+        if !Self::confirm_unvalued(unvalued) {
+            return Ok(false);
+        }
         let old_path = (*this.aeat720_form_path.lock_ref()).clone();
         let old_path = old_path.map_or("".to_owned(), |x| x);
-        let path = web::generate_720(&Aeat720Information {
-            records: this.table.get_records(),
-            personal_info: this.personal_info.get_cloned(),
-        })?;
+        let path = web::generate_720(
+            &Aeat720Information {
+                records,
+                personal_info: this.personal_info.get_cloned(),
+            },
+            unvalued > 0,
+        )?;
         if !old_path.is_empty() {
             let _ = web::delete_path(old_path);
         }
 
         *this.aeat720_form_path.lock_mut() = Some(path);
-        Ok(())
+        Ok(true)
+    }
+
+    /// Generates the AEAT 721 form, `Ok(false)` when the user did not accept
+    /// the records valued at zero.
+    fn generate_721_file(this: &Arc<Self>) -> Result<bool> {
+        let records = this.crypto_table.get_records();
+        let unvalued = records.iter().filter(|r| r.is_unvalued()).count();
+        if !Self::confirm_unvalued(unvalued) {
+            return Ok(false);
+        }
+        let old_path = this.aeat721_form_path.lock_ref().clone();
+        let path = web::generate_721(
+            &Aeat721Information {
+                records,
+                personal_info: this.personal_info.get_cloned(),
+            },
+            unvalued > 0,
+        )?;
+        if let Some(old_path) = old_path {
+            let _ = web::delete_path(old_path);
+        }
+
+        *this.aeat721_form_path.lock_mut() = Some(path);
+        Ok(true)
     }
 
     fn render_import_button(this: &Arc<Self>) -> Dom {
@@ -94,8 +473,8 @@ impl App {
           .child(
             html!("input" => HtmlInputElement, {
               .attr("id", "import_report")
-              .attr("alt", "Botón para importar ficheros de Interactive brokers o Degiro")
-              .attr("accept", "text/html,text/csv,application/pdf,application/zip,.zip,.pdf,.csv,.html")
+              .attr("alt", "Botón para importar ficheros de brokers o exchanges de criptomonedas")
+              .attr("accept", "text/html,text/csv,text/plain,application/pdf,application/zip,application/vnd.openxmlformats-officedocument.spreadsheetml.sheet,application/x-ofx,.zip,.pdf,.csv,.html,.txt,.xlsx,.ofx,.qfx")
               .attr("type", "file")
               .style("display", "none")
               .with_node!(element => {
@@ -118,9 +497,10 @@ impl App {
                       return;
                     }
                   };
+                  let filename = file_data.name();
                   let blob = Blob::from(file_data);
                   spawn_local(clone!(this => async move {
-                    App::import_file(&this, read_as_bytes(&blob).await.unwrap());
+                    App::import_file(&this, read_as_bytes(&blob).await.unwrap(), &filename);
                   }));
                   element.set_value("");
                 }))
@@ -138,12 +518,131 @@ impl App {
             .with_node!(_element => {
               .event(clone!(this => move |_: events::Click| {
                 this.table.clear();
+                this.crypto_table.clear();
               }))
             })
           }))
         })
     }
 
+    fn render_start_over_button() -> Dom {
+        html!("span", {
+          .child(html!("input" => HtmlInputElement, {
+            .attr("type", "button")
+            .attr("value", "Empezar de nuevo")
+            .event(|_: events::Click| {
+              App::start_over();
+            })
+          }))
+        })
+    }
+
+    fn render_export_project_button(this: &Arc<Self>) -> Dom {
+        html!("span", {
+          .child(html!("input" => HtmlInputElement, {
+            .attr("type", "password")
+            .attr("autocomplete", "new-password")
+            .attr("placeholder", "Contraseña (opcional)")
+            .attr("alt", "Contraseña para cifrar el proyecto exportado")
+            .with_node!(element => {
+              .event(clone!(this => move |_: events::Input| {
+                this.project_password.set(element.value());
+              }))
+            })
+          }))
+          .child(html!("input" => HtmlInputElement, {
+            .attr("type", "button")
+            .attr("value", "Exportar proyecto")
+            .event(clone!(this => move |_: events::Click| {
+              if let Err(err) = App::export_project(&this) {
+                log::error!("Unable to export project: {}", err);
+                *this.current_error.lock_mut() = Some(
+                  format!("No se ha podido exportar el proyecto: {}", err));
+                this.modal_visible.set(true);
+              }
+            }))
+          }))
+        })
+    }
+
+    fn render_open_project_button(this: &Arc<Self>) -> Dom {
+        html!("span", {
+          .child(
+            html!("button", {
+              .child(
+                html!("label", {
+                  .style("cursor", "pointer")
+                  .attr("for", "open_project")
+                  .text("Abrir proyecto")
+              })
+            )})
+          )
+          .child(
+            html!("input" => HtmlInputElement, {
+              .attr("id", "open_project")
+              .attr("alt", "Botón para abrir un proyecto exportado")
+              .attr("accept", "application/json,.json")
+              .attr("type", "file")
+              .style("display", "none")
+              .with_node!(element => {
+                .event(clone!(this => move |_: events::Change| {
+                  let file_data = match element.files().and_then(|file_list| file_list.get(0)) {
+                    Some(data) => data,
+                    None => {
+                      *this.current_error.lock_mut() = Some(
+                        "Error obteniendo fichero".to_string());
+                      this.modal_visible.set(true);
+                      return;
+                    }
+                  };
+                  let blob = Blob::from(file_data);
+                  spawn_local(clone!(this => async move {
+                    App::open_project(&this, read_as_bytes(&blob).await.unwrap());
+                  }));
+                  element.set_value("");
+                }))
+              })
+            })
+          )
+        })
+    }
+
+    /// Password of the encrypted project file being opened.
+    fn render_project_password(this: &Arc<Self>) -> Dom {
+        let password = Mutable::new(String::new());
+        html!("div", {
+            .class(&*WARNING_PANEL_CLASS)
+            .visible_signal(this.encrypted_project.signal_ref(|content| content.is_some()))
+            .child(html!("p", {
+                .text("El proyecto está cifrado, introduce su contraseña: ")
+                .child(html!("input" => HtmlInputElement, {
+                    .attr("type", "password")
+                    .attr("autocomplete", "current-password")
+                    .attr("placeholder", "Contraseña")
+                    .prop_signal("value", password.signal_cloned())
+                    .with_node!(element => {
+                        .event(clone!(password => move |_: events::Input| {
+                            password.set(element.value());
+                        }))
+                    })
+                }))
+                .child(html!("button", {
+                    .text("Abrir")
+                    .event(clone!(this, password => move |_: events::Click| {
+                        App::open_encrypted_project(&this, &password.replace(String::new()));
+                    }))
+                }))
+                .child(html!("button", {
+                    .text("Cancelar")
+                    .event(clone!(this, password => move |_: events::Click| {
+                        password.set(String::new());
+                        this.encrypted_project.set(None);
+                    }))
+                }))
+            }))
+        })
+    }
+
     fn render_insert_button(this: &Arc<Self>) -> Dom {
         html!("span", {
           .child(html!("input" => HtmlInputElement, {
@@ -170,7 +669,14 @@ impl App {
                       .with_node!(_element => {
                         .event(clone!(this => move |_: events::Click| {
                           let result = App::generate_720_file(&this);
-                          if result.is_ok() {
+                          if let Err(err) = result {
+                            let mut message = format!("No se ha podido generar el modelo 720: {}", err);
+                            if err.downcast_ref::<InvalidIsin>().is_some() {
+                              message.push_str(". Revisa que todas las filas tengan un ISIN válido.");
+                            }
+                            *this.current_error.lock_mut() = Some(message);
+                            this.modal_visible.set(true);
+                          } else if let Ok(true) = result {
                             let file_path = this.aeat720_form_path.lock_ref().clone().unwrap();
                             let elem: Element = gloo_utils::document().create_element("a").unwrap_throw();
                             let link: HtmlAnchorElement = elem.dyn_into().unwrap_throw();
@@ -196,6 +702,68 @@ impl App {
         })
     }
 
+    fn render_download_721_button(this: &Arc<Self>) -> Dom {
+        html!("section", {
+         .child_signal(
+           this.crypto_table.table_rows_not_empty().map(clone!(this => move |x| {
+              if x {
+                  Some(
+                    html!("button" => HtmlElement, {
+                      .attr("type", "button")
+                      .text("Descargar informe AEAT 721")
+                      .with_node!(_element => {
+                        .event(clone!(this => move |_: events::Click| {
+                          let result = App::generate_721_file(&this);
+                          if let Err(err) = result {
+                            *this.current_error.lock_mut() = Some(
+                              format!("No se ha podido generar el modelo 721: {}", err));
+                            this.modal_visible.set(true);
+                          } else if let Ok(true) = result {
+                            let file_path = this.aeat721_form_path.lock_ref().clone().unwrap();
+                            let elem: Element = gloo_utils::document().create_element("a").unwrap_throw();
+                            let link: HtmlAnchorElement = elem.dyn_into().unwrap_throw();
+                            link.set_href(&file_path);
+                            let _ = link.set_attribute("download", "fichero-721.txt");
+                            link.click();
+                          }
+                        }))
+                      })
+                    })
+                  )
+             } else {
+               None
+             }
+          })))
+        })
+    }
+
+    /// Data of the last import left out or changed by the parsers, which
+    /// the user has to review in the tables.
+    fn render_import_warnings(this: &Arc<Self>) -> Dom {
+        html!("div", {
+            .class(&*WARNING_PANEL_CLASS)
+            .visible_signal(this.import_warnings.signal_vec_cloned().is_empty().map(|empty| !empty))
+            .child(html!("p", {
+                .text("Revisa estos datos de la última importación:")
+            }))
+            .child(html!("ul", {
+                .children_signal_vec(this.import_warnings.signal_vec_cloned().map(|warning| {
+                    html!("li", {
+                        .text(&warning.localized())
+                    })
+                }))
+            }))
+            .child(html!("p", {
+                .child(html!("button", {
+                    .text("Cerrar avisos")
+                    .event(clone!(this => move |_: events::Click| {
+                        this.import_warnings.lock_mut().clear();
+                    }))
+                }))
+            }))
+        })
+    }
+
     fn render_modal_error(this: &Arc<Self>) -> Dom {
         html!("div", {
             .class(&*MODAL_STYLE)
@@ -211,6 +779,7 @@ impl App {
     }
     pub fn render(this: Arc<Self>) -> Dom {
         html!("div", {
+            .future(App::store_session(&this))
             .child(App::render_modal_error(&this))
             .child(html!("h2", {
                 .text("Paso 1: Rellena datos personales.")
@@ -223,6 +792,9 @@ impl App {
             .child(
                Table::render(&this.table)
             )
+            .child(
+               CryptoTable::render(&this.crypto_table)
+            )
             .child(
                 App::render_import_button(&this)
             )
@@ -232,10 +804,32 @@ impl App {
             .child(
                 App::render_insert_button(&this)
             )
+            .child(
+                App::render_start_over_button()
+            )
+            .child(
+                App::render_export_project_button(&this)
+            )
+            .child(
+                App::render_open_project_button(&this)
+            )
+            .child(App::render_project_password(&this))
+            .child(CsvImporter::render(
+                &this.csv_importer,
+                clone!(this => move |content, filename, mapping| {
+                    App::import_generic_csv(&this, content, filename, mapping)
+                }),
+            ))
+            .child(ImportPreview::render(
+                &this.import_preview,
+                clone!(this => move |accepted| App::accept_import(&this, accepted)),
+            ))
+            .child(App::render_import_warnings(&this))
             .child(html!("h2", {
                 .text("Paso 3: Revisa las fechas de 1º adquisición y los datos importados y descarga el fichero generado.")
             }))
             .child(App::render_download_button(&this))
+            .child(App::render_download_721_button(&this))
             .child(html!("h2", {
                 .text("Paso 4: Finalmente importe el fichero descargado con el modelo 720 en la ")
                 .child(html!("a", {
@@ -247,6 +841,10 @@ impl App {
                 }))
                 .text(" y revise el código de domiciliación del país de las empresas, por defecto cogerá el del ISIN, pero esto no siempre es correcto.")
             }))
+            .child(html!("h2", {
+                .text("Opcional: resumen de los valores para el Impuesto sobre el Patrimonio (modelo 714).")
+            }))
+            .child(WealthTaxViewer::render(&this.wealth_tax_viewer))
         })
     }
 }
@@ -0,0 +1,421 @@
+//! Command line interface, generates the AEAT 720 and 721 forms from the
+//! broker reports without a browser.
+
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    data::{
+        Aeat720Information, Aeat720Records, Aeat721Information, Aeat721Records, DEFAULT_YEAR,
+        PersonalInformation,
+    },
+    importer::Importer,
+    reports::{aeat_720::Aeat720Report, aeat_721::Aeat721Report},
+    utils::usize_to_date,
+};
+
+const USAGE: &str = "Usage: burocratin [OPTIONS] REPORT...
+
+Imports the broker reports, crypto exchange exports and previous AEAT 720
+forms given, prints the records found and writes the AEAT 720 form, and the
+AEAT 721 one when virtual currencies are found.
+
+Options:
+      --config FILE      JSON file with the personal information
+                         (name, surname, nif, year and phone)
+      --nif NIF          NIF of the declarant
+      --name NAME        name of the declarant
+      --surname SURNAME  surnames of the declarant
+      --year YEAR        declared year
+      --phone PHONE      contact phone
+      --country CODE     country (ISO 3166 alpha-2) of the brokers whose
+                         reports do not give it, like some OFX statements
+      --accept-unvalued  file the records valued at zero, warning about
+                         them instead of failing
+  -o, --output FILE      AEAT 720 form path [default: fichero-720.txt]
+      --output-721 FILE  AEAT 721 form path [default: fichero-721.txt]
+  -h, --help             print this help";
+
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    personal_info: PersonalInformation,
+    reports: Vec<PathBuf>,
+    output: Option<PathBuf>,
+    output_721: Option<PathBuf>,
+    /// Country of the brokers the reports do not locate.
+    country: Option<String>,
+    /// Whether the records valued at zero are filed.
+    accept_unvalued: bool,
+    help: bool,
+}
+
+impl Options {
+    /// Flags take precedence over the configuration file, whatever their
+    /// order.
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut options = Options::default();
+        let mut config = None;
+        let mut flags = PersonalInformation::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .with_context(|| format!("Missing value for {}", arg))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => options.help = true,
+                "--config" => config = Some(PathBuf::from(value()?)),
+                "--nif" => flags.nif = value()?,
+                "--name" => flags.name = value()?,
+                "--surname" => flags.surname = value()?,
+                "--year" => {
+                    let year = value()?;
+                    flags.year = year
+                        .parse()
+                        .with_context(|| format!("Invalid year {}", year))?;
+                }
+                "--phone" => flags.phone = value()?,
+                "--country" => options.country = Some(value()?),
+                "--accept-unvalued" => options.accept_unvalued = true,
+                "-o" | "--output" => options.output = Some(PathBuf::from(value()?)),
+                "--output-721" => options.output_721 = Some(PathBuf::from(value()?)),
+                _ if arg.starts_with('-') => bail!("Unknown option {}\n\n{}", arg, USAGE),
+                _ => options.reports.push(PathBuf::from(arg)),
+            }
+        }
+
+        if let Some(config) = config {
+            let content = fs::read_to_string(&config)
+                .with_context(|| format!("Unable to read {}", config.display()))?;
+            options.personal_info = serde_json::from_str(&content)
+                .with_context(|| format!("Invalid configuration file {}", config.display()))?;
+        }
+        let info = &mut options.personal_info;
+        for (field, flag) in [
+            (&mut info.nif, flags.nif),
+            (&mut info.name, flags.name),
+            (&mut info.surname, flags.surname),
+            (&mut info.phone, flags.phone),
+        ] {
+            if !flag.is_empty() {
+                *field = flag;
+            }
+        }
+        if flags.year != 0 {
+            info.year = flags.year;
+        }
+        if info.year == 0 {
+            info.year = DEFAULT_YEAR;
+        }
+
+        Ok(options)
+    }
+}
+
+fn print_records(records: &Aeat720Records, crypto_records: &Aeat721Records) {
+    for record in records {
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            record.company.isin,
+            record.company.name,
+            record.broker.name,
+            record.broker.country_code,
+            usize_to_date(record.first_tx_date).map_or(String::new(), |date| date.to_string()),
+            record.quantity,
+            record.value_in_euro,
+            record.percentage,
+        );
+    }
+    for record in crypto_records {
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            record.currency,
+            record.name,
+            record.custodian.name,
+            record.custodian.country_code,
+            usize_to_date(record.first_tx_date).map_or(String::new(), |date| date.to_string()),
+            record.quantity,
+            record.value_in_euro,
+            record.percentage,
+        );
+    }
+}
+
+/// Fails listing the records that can not be filed as they are, those
+/// without a valid ISIN, without broker country or valued at zero, to be
+/// fixed in the reports or the web application. Records valued at zero are
+/// only warned about when `accept_unvalued`.
+fn check_records(
+    records: &Aeat720Records,
+    crypto_records: &Aeat721Records,
+    accept_unvalued: bool,
+) -> Result<()> {
+    let mut issues = vec![];
+    let mut unvalued = vec![];
+    for record in records {
+        if isin::parse(&record.company.isin).is_err() {
+            issues.push(format!(
+                "{} has no valid ISIN ({:?})",
+                record.company.name, record.company.isin
+            ));
+        }
+        if record.broker.country_code.is_empty() {
+            issues.push(format!(
+                "{} has no country for broker {}, give it with --country",
+                record.company.name, record.broker.name
+            ));
+        }
+        if record.value_in_euro.is_zero() {
+            unvalued.push(format!("{} is valued at zero", record.company.name));
+        }
+    }
+    for record in crypto_records {
+        if record.is_unvalued() {
+            unvalued.push(format!("{} is valued at zero", record.name));
+        }
+    }
+    if accept_unvalued {
+        for issue in unvalued {
+            eprintln!("Warning: {}", issue);
+        }
+    } else {
+        issues.extend(unvalued);
+    }
+    if !issues.is_empty() {
+        bail!(
+            "Unable to generate the forms, {} records can not be filed:\n  {}",
+            issues.len(),
+            issues.join("\n  ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Runs the command line tool with the arguments following the program
+/// name.
+pub fn run(args: impl IntoIterator<Item = String>) -> Result<()> {
+    let options = Options::parse(args)?;
+    if options.help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    if options.reports.is_empty() {
+        bail!("No reports given\n\n{}", USAGE);
+    }
+
+    let mut importer = Importer::new(options.personal_info.year);
+    let mut records = vec![];
+    let mut crypto_records = vec![];
+    for report in &options.reports {
+        let content =
+            fs::read(report).with_context(|| format!("Unable to read {}", report.display()))?;
+        let mut result = importer
+            .import(&content)
+            .with_context(|| format!("Unable to import {}", report.display()))?;
+        if let Some(country) = &options.country {
+            for broker in result.brokers_without_country() {
+                result.set_broker_country(&broker, country);
+            }
+        }
+        if let Some(filename) = report.file_name() {
+            result.set_filename(&filename.to_string_lossy());
+        }
+        for warning in result.warnings {
+            eprintln!("{}: {}", report.display(), warning);
+        }
+        records.extend(result.records);
+        crypto_records.extend(result.crypto_records);
+    }
+    importer.update_first_tx_dates(&mut records);
+    print_records(&records, &crypto_records);
+    check_records(&records, &crypto_records, options.accept_unvalued)?;
+
+    let output = options
+        .output
+        .unwrap_or_else(|| PathBuf::from("fichero-720.txt"));
+    let info = Aeat720Information {
+        records,
+        personal_info: options.personal_info.clone(),
+    };
+    let form = if options.accept_unvalued {
+        Aeat720Report::new_accepting_unvalued(&info)?
+    } else {
+        Aeat720Report::new(&info)?
+    }
+    .generate()?;
+    fs::write(&output, form).with_context(|| format!("Unable to write {}", output.display()))?;
+
+    if !crypto_records.is_empty() {
+        let output = options
+            .output_721
+            .unwrap_or_else(|| PathBuf::from("fichero-721.txt"));
+        let info = Aeat721Information {
+            records: crypto_records,
+            personal_info: options.personal_info,
+        };
+        let form = if options.accept_unvalued {
+            Aeat721Report::new_accepting_unvalued(&info)?
+        } else {
+            Aeat721Report::new(&info)?
+        }
+        .generate()?;
+        fs::write(&output, form)
+            .with_context(|| format!("Unable to write {}", output.display()))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_options_test() {
+        let options = Options::parse(args(&[
+            "--nif",
+            "12345678Z",
+            "--name",
+            "Juan",
+            "--surname",
+            "Español Español",
+            "--year",
+            "2023",
+            "--phone",
+            "600000000",
+            "-o",
+            "720.txt",
+            "ib.html",
+            "degiro.pdf",
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            options,
+            Options {
+                personal_info: PersonalInformation {
+                    name: String::from("Juan"),
+                    surname: String::from("Español Español"),
+                    nif: String::from("12345678Z"),
+                    year: 2023,
+                    phone: String::from("600000000"),
+                },
+                reports: vec![PathBuf::from("ib.html"), PathBuf::from("degiro.pdf")],
+                output: Some(PathBuf::from("720.txt")),
+                output_721: None,
+                country: None,
+                accept_unvalued: false,
+                help: false,
+            }
+        );
+
+        assert_eq!(
+            Options::parse(args(&["ib.html"]))
+                .unwrap()
+                .personal_info
+                .year,
+            DEFAULT_YEAR
+        );
+        assert!(Options::parse(args(&["--year"])).is_err());
+        assert!(Options::parse(args(&["--unknown", "ib.html"])).is_err());
+    }
+
+    #[test]
+    fn run_test() {
+        let output =
+            std::env::temp_dir().join(format!("burocratin-720-{}.txt", std::process::id()));
+        run(args(&[
+            "--nif",
+            "12345678Z",
+            "--name",
+            "Juan",
+            "--surname",
+            "Español Español",
+            "--year",
+            "2023",
+            "-o",
+            &output.to_string_lossy(),
+            "src/parsers/testdata/ib_test.csv",
+        ]))
+        .unwrap();
+        // ISO-8859-15 encoded, like the AEAT expects it.
+        let form = fs::read(&output).unwrap();
+        let form = String::from_utf8_lossy(&form);
+        fs::remove_file(&output).unwrap();
+        let lines: Vec<&str> = form.lines().collect();
+        assert!(lines[0].starts_with("17202023"));
+        assert_eq!(lines.len(), 16);
+        assert!(lines[1..].iter().all(|line| line.starts_with("27202023")));
+
+        // The HTML report gives the CUSIP of the ADRs instead of the ISIN.
+        let err = run(args(&[
+            "-o",
+            &output.to_string_lossy(),
+            "src/parsers/testdata/ib_test.html",
+        ]))
+        .unwrap_err();
+        assert!(err.to_string().contains("JD.COM INC-ADR"));
+        assert!(!output.exists());
+
+        // Statements in currencies without reference rate are only filed
+        // when accepting the records valued at zero.
+        let report =
+            std::env::temp_dir().join(format!("burocratin-ofx-{}.ofx", std::process::id()));
+        fs::write(
+            &report,
+            "OFXHEADER:100
+<OFX>
+<SIGNONMSGSRSV1><SONRS><FI><ORG>Example Securities<FID>1</FI></SONRS></SIGNONMSGSRSV1>
+<INVSTMTMSGSRSV1><INVSTMTTRNRS><INVSTMTRS>
+<DTASOF>20231231<CURDEF>ARS
+<INVPOSLIST>
+<POSSTOCK><INVPOS>
+<SECID><UNIQUEID>037833100<UNIQUEIDTYPE>CUSIP</SECID>
+<UNITS>10<UNITPRICE>150<MKTVAL>1500
+</INVPOS></POSSTOCK>
+</INVPOSLIST>
+</INVSTMTRS></INVSTMTTRNRS></INVSTMTMSGSRSV1>
+<SECLISTMSGSRSV1><SECLIST>
+<STOCKINFO><SECINFO>
+<SECID><UNIQUEID>037833100<UNIQUEIDTYPE>CUSIP</SECID>
+<SECNAME>APPLE INC<TICKER>AAPL
+</SECINFO></STOCKINFO>
+</SECLIST></SECLISTMSGSRSV1>
+</OFX>
+",
+        )
+        .unwrap();
+        let unvalued_args = |accept_unvalued: bool| {
+            let mut unvalued_args = args(&[
+                "--year",
+                "2023",
+                "--country",
+                "GB",
+                "-o",
+                &output.to_string_lossy(),
+                &report.to_string_lossy(),
+            ]);
+            if accept_unvalued {
+                unvalued_args.push(String::from("--accept-unvalued"));
+            }
+            unvalued_args
+        };
+        let err = run(unvalued_args(false)).unwrap_err();
+        assert!(err.to_string().contains("APPLE INC is valued at zero"));
+        assert!(!output.exists());
+
+        run(unvalued_args(true)).unwrap();
+        let form = fs::read(&output).unwrap();
+        fs::remove_file(&output).unwrap();
+        fs::remove_file(&report).unwrap();
+        let form = String::from_utf8_lossy(&form);
+        assert_eq!(form.lines().count(), 2);
+    }
+}
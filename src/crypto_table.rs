@@ -0,0 +1,541 @@
+use std::sync::Arc;
+
+use chrono::NaiveDate;
+use dominator::{Dom, clone, events, html, with_node};
+use futures_signals::{
+    map_ref,
+    signal::{Mutable, Signal, SignalExt},
+    signal_vec::{MutableVec, SignalVecExt},
+};
+use rust_decimal::Decimal;
+use web_sys::{HtmlElement, HtmlInputElement};
+
+use crate::{
+    css::{TABLE_CAPTION, TABLE_HEADER, TABLE_ROW, TABLE_STYLE},
+    data::{
+        Aeat721Record, BrokerInformation, DEFAULT_BROKER, DEFAULT_LOCALE,
+        DEFAULT_NUMBER_OF_DECIMALS, DEFAULT_YEAR,
+    },
+    utils::{
+        date_to_usize,
+        decimal::{decimal_to_str_locale, valid_str_number_with_decimals},
+        icons::{render_svg_plus_icon, render_svg_trash_icon},
+        usize_to_date,
+    },
+};
+
+/// Units of virtual currencies are declared with up to 10 decimals.
+const UNITS_NUMBER_OF_DECIMALS: u16 = 10;
+
+const CURRENCY_NOT_VALID_ERR_MSG: &str = "Identificador no válido";
+const VALUE_NOT_VALID_ERR_MSG: &str = "Valor (€) no válido";
+const UNITS_NOT_VALID_ERR_MSG: &str = "Unidades no válidas";
+const PERCENT_NOT_VALID_ERR_MSG: &str = "Porcentaje no válido";
+
+#[derive(Debug, Clone)]
+struct Aeat721RecordInfo {
+    record: Aeat721Record,
+    currency_err_msg: Mutable<Option<&'static str>>,
+    value_err_msg: Mutable<Option<&'static str>>,
+    units_err_msg: Mutable<Option<&'static str>>,
+    percent_err_msg: Mutable<Option<&'static str>>,
+}
+
+/// Virtual currencies held abroad to declare in the AEAT 721 form.
+pub struct CryptoTable {
+    headers: Vec<&'static str>,
+    data: MutableVec<Mutable<Aeat721RecordInfo>>,
+}
+
+impl CryptoTable {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            headers: vec![
+                "Moneda virtual",
+                "Identificador",
+                "Custodio",
+                "Cód. país",
+                "Fecha 1ª adquisición",
+                "Valor (€) a 31/12",
+                "Unidades",
+                "Porcentaje",
+            ],
+            data: MutableVec::new(),
+        })
+    }
+
+    pub fn table_rows_not_empty(&self) -> impl Signal<Item = bool> + use<> {
+        self.data
+            .signal_vec_cloned()
+            .to_signal_map(|x| !x.is_empty())
+    }
+
+    pub fn extend_rows(&self, records: Vec<Aeat721Record>) {
+        for record in records.into_iter() {
+            // Valuations are not in the exchanges exports, so they start
+            // flagged until the user types them.
+            let value_err_msg = if record.is_unvalued() {
+                Some(VALUE_NOT_VALID_ERR_MSG)
+            } else {
+                None
+            };
+            self.data
+                .lock_mut()
+                .push_cloned(Mutable::new(Aeat721RecordInfo {
+                    record,
+                    currency_err_msg: Mutable::new(None),
+                    value_err_msg: Mutable::new(value_err_msg),
+                    units_err_msg: Mutable::new(None),
+                    percent_err_msg: Mutable::new(None),
+                }));
+        }
+    }
+
+    fn create_default_record() -> Aeat721RecordInfo {
+        let record = Aeat721Record {
+            name: "Nueva moneda virtual".to_string(),
+            currency: "".to_string(),
+            quantity: Decimal::ONE,
+            value_in_euro: Decimal::ZERO,
+            first_tx_date: date_to_usize(DEFAULT_YEAR as i32, 1, 1),
+            extinction_date: 0,
+            custodian: DEFAULT_BROKER.clone(),
+            percentage: Decimal::ONE_HUNDRED,
+        };
+        Aeat721RecordInfo {
+            record,
+            currency_err_msg: Mutable::new(Some(CURRENCY_NOT_VALID_ERR_MSG)),
+            value_err_msg: Mutable::new(Some(VALUE_NOT_VALID_ERR_MSG)),
+            units_err_msg: Mutable::new(None),
+            percent_err_msg: Mutable::new(None),
+        }
+    }
+
+    /// Records of the rows, sent again on every change of any of them.
+    pub fn records_signal(&self) -> impl Signal<Item = Vec<Aeat721Record>> + use<> {
+        self.data
+            .signal_vec_cloned()
+            .map_signal(|row| row.signal_ref(|info| info.record.clone()))
+            .to_signal_cloned()
+    }
+
+    pub fn get_records(&self) -> Vec<Aeat721Record> {
+        let mut result = vec![];
+        for record in self.data.lock_ref().iter() {
+            result.push(record.lock_ref().record.clone());
+        }
+        result
+    }
+
+    pub fn clear(&self) {
+        self.data.lock_mut().clear();
+    }
+
+    /// Removes the rows held at the entity named `name`.
+    pub fn remove_custodian_rows(&self, name: &str) {
+        self.data
+            .lock_mut()
+            .retain(|row| row.lock_ref().record.custodian.name != name);
+    }
+
+    fn render_header_cells(this: &Arc<Self>) -> Vec<Dom> {
+        this.headers
+            .iter()
+            .map(|header_cell| {
+                html!("th", {
+                  .attr("scope", "col")
+                  .attr("role", "columnheader")
+                  .style("vertical-align", "bottom")
+                  .style("font-weight", "bold")
+                  .style("background-color", "#ddd")
+                  .text(header_cell)
+                })
+            })
+            .collect()
+    }
+
+    fn render_header(this: &Arc<Self>) -> Dom {
+        html!("thead", {
+          .class(&*TABLE_HEADER)
+          .child(
+            html!("tr", {
+              .child(
+                html!("th", {
+                  .attr("scope", "col")
+                  .attr("role", "columnheader")
+                  .style("vertical-align", "bottom")
+                  .style("font-weight", "bold")
+                  .style("background-color", "#ddd")
+                  .text("#")
+                })
+              )
+              .children(Self::render_header_cells(this))
+              .child(
+                html!("th", {
+                  .attr("scope", "col")
+                  .attr("role", "columnheader")
+                  .style("vertical-align", "bottom")
+                  .style("font-weight", "bold")
+                  .style("background-color", "#ddd")
+                  .child(html!("span" => HtmlElement, {
+                    .child(render_svg_plus_icon("red", "24"))
+                    .with_node!(_element => {
+                      .event(clone!(this => move |_: events::Click| {
+                        let record_info = Mutable::new(Self::create_default_record());
+                        this.data.lock_mut().insert_cloned(0, record_info);
+                      }))
+                    })
+                  }))
+                })
+              )
+            })
+          )
+        })
+    }
+
+    fn name_cell(record: &Mutable<Aeat721RecordInfo>) -> impl Signal<Item = Option<Dom>> + use<> {
+        record.signal_ref(clone!(record => move |r| {
+            Some(
+              html!("td", {
+                .child(
+                  html!("input" => HtmlInputElement, {
+                    .attr("type", "text")
+                    .attr("size", "20")
+                    .attr("maxlength", "62")
+                    .attr("value", &r.record.name)
+                    .with_node!(element => {
+                      .event(clone!(record => move |_: events::Change| {
+                        record.lock_mut().record.name = element.value();
+                      }))
+                    })
+                  })
+                )
+              })
+            )
+        }))
+    }
+
+    fn currency_cell(
+        record: &Mutable<Aeat721RecordInfo>,
+    ) -> impl Signal<Item = Option<Dom>> + use<> {
+        record.signal_ref(clone!(record => move |r| {
+            Some(
+              html!("td", {
+                .child(html!("input" => HtmlInputElement, {
+                    .style("display", "block")
+                    .attr("type", "text")
+                    .attr("size", "8")
+                    .attr("maxlength", "24")
+                    .attr("value", &r.record.currency)
+                    .with_node!(element => {
+                      .event(clone!(record => move |_: events::Change| {
+                        let currency = element.value().trim().to_uppercase();
+                        if !currency.is_empty() {
+                          *record.lock_mut().currency_err_msg.lock_mut() = None;
+                        } else {
+                          *record.lock_mut().currency_err_msg.lock_mut() = Some(CURRENCY_NOT_VALID_ERR_MSG);
+                          let _ = element.focus();
+                        }
+                        record.lock_mut().record.currency = currency;
+                      }))
+                    })
+                }))
+                .child(html!("span", {
+                    .style("color", "red")
+                    .style("font-size", "small")
+                    .text_signal(record.lock_ref().currency_err_msg.signal_ref(|t| t.unwrap_or("")))
+                }))
+              })
+            )
+        }))
+    }
+
+    fn custodian_name_cell(
+        record: &Mutable<Aeat721RecordInfo>,
+    ) -> impl Signal<Item = Option<Dom>> + use<> {
+        record.signal_ref(clone!(record => move |r| {
+            Some(
+              html!("td", {
+                .child(
+                  html!("input" => HtmlInputElement, {
+                    .attr("type", "text")
+                    .attr("size", "20")
+                    .attr("maxlength", "41")
+                    .attr("value", &r.record.custodian.name)
+                    .with_node!(element => {
+                      .event(clone!(record => move |_: events::Change| {
+                        let country_code = record.lock_ref().record.custodian.country_code.clone();
+                        record.lock_mut().record.custodian =
+                          Arc::new(BrokerInformation::new(element.value(), country_code));
+                      }))
+                    })
+                  })
+                )
+              })
+            )
+        }))
+    }
+
+    fn custodian_country_code_cell(
+        record: &Mutable<Aeat721RecordInfo>,
+    ) -> impl Signal<Item = Option<Dom>> + use<> {
+        record.signal_ref(clone!(record => move |r| {
+            Some(
+              html!("td", {
+                .child(
+                  html!("input" => HtmlInputElement, {
+                    .attr("type", "text")
+                    .attr("size", "2")
+                    .attr("maxlength", "2")
+                    .attr("value", &r.record.custodian.country_code)
+                    .with_node!(element => {
+                      .event(clone!(record => move |_: events::Change| {
+                        let name = record.lock_ref().record.custodian.name.clone();
+                        record.lock_mut().record.custodian =
+                          Arc::new(BrokerInformation::new(name, element.value().to_uppercase()));
+                      }))
+                    })
+                  })
+                )
+              })
+            )
+        }))
+    }
+
+    fn date_cell(record: &Mutable<Aeat721RecordInfo>) -> impl Signal<Item = Option<Dom>> + use<> {
+        record.signal_ref(clone!(record => move |r| {
+          let first_tx_date = r.record.first_tx_date;
+          let date = usize_to_date(first_tx_date)
+              .map_or("".to_string(), |d| d.format("%Y-%m-%d").to_string());
+            Some(
+              html!("td", {
+                .child(html!("input" => HtmlInputElement, {
+                  .attr("type", "date")
+                  .attr("value", &date)
+                  .with_node!(element => {
+                      .event(clone!(record => move |_: events::Change| {
+                        let parsed_date = NaiveDate::parse_from_str(&element.value(), "%Y-%m-%d").unwrap();
+                        record.lock_mut().record.first_tx_date =
+                          parsed_date.format("%Y%m%d").to_string().parse::<usize>().unwrap_or(first_tx_date);
+                      }))
+                    })
+                }))
+              })
+            )
+        }))
+    }
+
+    fn value_cell(record: &Mutable<Aeat721RecordInfo>) -> impl Signal<Item = Option<Dom>> + use<> {
+        record.signal_ref(clone!(record => move |r| {
+            Some(html!("td", {
+              .child(html!("input" => HtmlInputElement, {
+                .style("text-align", "right")
+                .attr("type", "text")
+                .attr("size", "9")
+                .attr("maxlength", "15")
+                .attr("value", &decimal_to_str_locale(&r.record.value_in_euro, DEFAULT_LOCALE))
+                .with_node!(element => {
+                  .event(clone!(record => move |_: events::Input| {
+                    if valid_str_number_with_decimals(&element.value(), DEFAULT_NUMBER_OF_DECIMALS, DEFAULT_LOCALE) {
+                        *record.lock_mut().value_err_msg.lock_mut() = None;
+                    } else {
+                        *record.lock_mut().value_err_msg.lock_mut() = Some(VALUE_NOT_VALID_ERR_MSG);
+                    }
+                  }))
+                })
+                .with_node!(element => {
+                  .event(clone!(record => move |_: events::Change| {
+                    let money_str = element.value().replace(DEFAULT_LOCALE.decimal(), ".");
+                    if valid_str_number_with_decimals(&money_str, DEFAULT_NUMBER_OF_DECIMALS, DEFAULT_LOCALE)
+                      && let Ok(money) = money_str.parse::<Decimal>()
+                    {
+                      *record.lock_mut().value_err_msg.lock_mut() = None;
+                      record.lock_mut().record.value_in_euro = money;
+                      return
+                    }
+                    *record.lock_mut().value_err_msg.lock_mut() = Some(VALUE_NOT_VALID_ERR_MSG);
+                    record.lock_mut().record.value_in_euro = Decimal::ZERO;
+                    let _ = element.focus();
+                  }))
+                })
+              }))
+              .child(html!("span", {
+                .style("color", "red")
+                .style("font-size", "small")
+                .text_signal(record.lock_ref().value_err_msg.signal_ref(|t| t.unwrap_or("")))
+              }))
+            }))
+        }))
+    }
+
+    fn units_cell(record: &Mutable<Aeat721RecordInfo>) -> impl Signal<Item = Option<Dom>> + use<> {
+        record.signal_ref(clone!(record => move |r| {
+            Some(html!("td", {
+              .child(html!("input" => HtmlInputElement, {
+                .style("text-align", "right")
+                .attr("type", "text")
+                .attr("size", "12")
+                .attr("maxlength", "23")
+                .attr("value", &decimal_to_str_locale(&r.record.quantity, DEFAULT_LOCALE))
+                .with_node!(element => {
+                  .event(clone!(record => move |_: events::Input| {
+                    if valid_str_number_with_decimals(&element.value(), UNITS_NUMBER_OF_DECIMALS, DEFAULT_LOCALE) {
+                        *record.lock_mut().units_err_msg.lock_mut() = None;
+                    } else {
+                        *record.lock_mut().units_err_msg.lock_mut() = Some(UNITS_NOT_VALID_ERR_MSG);
+                    }
+                  }))
+                })
+                .with_node!(element => {
+                  .event(clone!(record => move |_: events::Change| {
+                    let units_str = element.value().replace(DEFAULT_LOCALE.decimal(), ".");
+                    if valid_str_number_with_decimals(&units_str, UNITS_NUMBER_OF_DECIMALS, DEFAULT_LOCALE)
+                      && let Ok(units) = units_str.parse::<Decimal>()
+                    {
+                      *record.lock_mut().units_err_msg.lock_mut() = None;
+                      record.lock_mut().record.quantity = units;
+                      return
+                    }
+                    *record.lock_mut().units_err_msg.lock_mut() = Some(UNITS_NOT_VALID_ERR_MSG);
+                    let _ = element.focus();
+                  }))
+                })
+              }))
+              .child(html!("span", {
+                .style("color", "red")
+                .style("font-size", "small")
+                .text_signal(record.lock_ref().units_err_msg.signal_ref(|t| t.unwrap_or("")))
+              }))
+            }))
+        }))
+    }
+
+    fn percentage_cell(
+        record: &Mutable<Aeat721RecordInfo>,
+    ) -> impl Signal<Item = Option<Dom>> + use<> {
+        record.signal_ref(clone!(record => move |r| {
+            Some(html!("td", {
+              .child(html!("input" => HtmlInputElement, {
+                .style("text-align", "right")
+                .attr("type", "text")
+                .attr("size", "4")
+                .attr("maxlength", "6")
+                .attr("value", &r.record.percentage.to_string())
+                .with_node!(element => {
+                  .event(clone!(record => move |_: events::Input| {
+                    if valid_str_number_with_decimals(&element.value(), DEFAULT_NUMBER_OF_DECIMALS, DEFAULT_LOCALE) {
+                        *record.lock_mut().percent_err_msg.lock_mut() = None;
+                    } else {
+                        *record.lock_mut().percent_err_msg.lock_mut() = Some(PERCENT_NOT_VALID_ERR_MSG);
+                    }
+                  }))
+                })
+                .with_node!(element => {
+                  .event(clone!(record => move |_: events::Change| {
+                    let percentage_str = element.value().replace(DEFAULT_LOCALE.decimal(), ".");
+                    if valid_str_number_with_decimals(&percentage_str, DEFAULT_NUMBER_OF_DECIMALS, DEFAULT_LOCALE)
+                      && let Ok(percentage) = percentage_str.parse::<Decimal>()
+                      && percentage.gt(&Decimal::ZERO)
+                      && percentage.le(&Decimal::ONE_HUNDRED)
+                    {
+                      *record.lock_mut().percent_err_msg.lock_mut() = None;
+                      record.lock_mut().record.percentage = percentage;
+                      return;
+                    }
+                    *record.lock_mut().percent_err_msg.lock_mut() = Some(PERCENT_NOT_VALID_ERR_MSG);
+                    record.lock_mut().record.percentage = Decimal::ONE_HUNDRED;
+                    let _ = element.focus();
+                  }))
+                })
+              }))
+              .text(" % ")
+              .child(html!("span", {
+                .style("color", "red")
+                .style("font-size", "small")
+                .text_signal(record.lock_ref().percent_err_msg.signal_ref(|t| t.unwrap_or("")))
+              }))
+            }))
+        }))
+    }
+
+    fn actions_cell(
+        this: &Arc<Self>,
+        index: usize,
+        record: &Mutable<Aeat721RecordInfo>,
+    ) -> impl Signal<Item = Option<Dom>> + use<> {
+        record.signal_ref(clone!(this => move |_r| {
+            let delete_span = html!("span" => HtmlElement, {
+              .child(render_svg_trash_icon("red", "24"))
+              .with_node!(_element => {
+                .event(clone!(this => move |_: events::Click| {
+                  this.data.lock_mut().remove(index);
+                }))
+              })
+            });
+
+            Some(
+              html!("td", {
+                .child(delete_span)
+              })
+            )
+        }))
+    }
+
+    fn render_row(this: &Arc<Self>, index: usize, record: &Mutable<Aeat721RecordInfo>) -> Dom {
+        html!("tr", {
+          .class(&*TABLE_ROW)
+          .child(
+            html!("td", {
+              .text(&format!("{}", index + 1))
+            })
+          )
+          .child_signal(Self::name_cell(record))
+          .child_signal(Self::currency_cell(record))
+          .child_signal(Self::custodian_name_cell(record))
+          .child_signal(Self::custodian_country_code_cell(record))
+          .child_signal(Self::date_cell(record))
+          .child_signal(Self::value_cell(record))
+          .child_signal(Self::units_cell(record))
+          .child_signal(Self::percentage_cell(record))
+          .child_signal(Self::actions_cell(this, index, record))
+        })
+    }
+
+    fn render_body(this: &Arc<Self>) -> Dom {
+        html!("tbody", {
+          .children_signal_vec(this.data.signal_vec_cloned()
+            .enumerate().map(clone!(this => move |(index, record)| {
+              let i = index.get().unwrap_or(usize::MAX);
+              CryptoTable::render_row(&this, i, &record)
+           }))
+          )
+        })
+    }
+
+    fn is_needed_to_rerender_rows(this: &Arc<Self>) -> impl Signal<Item = bool> + use<> {
+        map_ref! {
+            let records_len = this.data.signal_vec_cloned().to_signal_map(|x| x.len()) => {
+              log::debug!("Rerendering crypto rows, new rows: {}", records_len);
+              true
+            }
+        }
+    }
+
+    pub fn render(this: &Arc<Self>) -> Dom {
+        html!("table", {
+         .class(&*TABLE_STYLE)
+         .child(
+            html!("caption", {
+              .class(&*TABLE_CAPTION)
+              .text("Monedas virtuales custodiadas en el extranjero (modelo 721).")
+            })
+          )
+          .child(Self::render_header(this))
+          .child_signal(Self::is_needed_to_rerender_rows(this).map(
+            clone!(this => move |_x| {
+              Some(Self::render_body(&this))
+            }))
+          )
+        })
+    }
+}
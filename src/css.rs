@@ -43,6 +43,16 @@ pub static ERROR_PARAGRAPH_CLASS: LazyLock<String> = LazyLock::new(|| {
     }
 });
 
+pub static WARNING_PANEL_CLASS: LazyLock<String> = LazyLock::new(|| {
+    class! {
+        .style("color", "#8a6d3b")
+        .style("background", "#fcf8e3")
+        .style("border", "1px solid #c9a65b")
+        .style("padding", "0px 10px")
+        .style("margin", "10px 0px")
+    }
+});
+
 pub static FLEX_CONTAINER_CLASS: LazyLock<String> = LazyLock::new(|| {
     class! {
         .style("display", "flex")
@@ -113,6 +123,18 @@ pub static TABLE_HEADER: LazyLock<String> = LazyLock::new(|| {
     }
 });
 
+pub static DUPLICATE_CELL_CLASS: LazyLock<String> = LazyLock::new(|| {
+    class! {
+        .style("background-color", "#fcf8e3")
+    }
+});
+
+pub static CONFLICT_CELL_CLASS: LazyLock<String> = LazyLock::new(|| {
+    class! {
+        .style("background-color", "#f2dede")
+    }
+});
+
 pub static TABLE_ROW: LazyLock<String> = LazyLock::new(|| {
     class! {
         .style("font-size", "small")
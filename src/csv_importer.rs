@@ -0,0 +1,386 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use dominator::{Dom, clone, events, html, with_node};
+use futures_signals::signal::{Mutable, SignalExt};
+use gloo_file::{Blob, futures::read_as_bytes};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+
+use crate::{
+    css::{TABLE_CAPTION, TABLE_HEADER, TABLE_ROW},
+    error::ImportError,
+    parsers::generic_csv::{
+        CsvField, CsvMapping, DATE_FORMATS, DecimalSeparator, GenericCSVParser,
+    },
+    utils::{decode_text, web},
+};
+
+const CSV_PROFILES_STORAGE_KEY: &str = "burocratin.csv_profiles";
+
+/// Import of the CSV files of brokers without parser, mapping their columns
+/// by hand. Mappings are saved as named profiles to reuse them next year.
+pub struct CsvImporter {
+    content: Mutable<Option<Vec<u8>>>,
+    filename: Mutable<String>,
+    headers: Mutable<Vec<String>>,
+    mapping: Mutable<CsvMapping>,
+    profiles: Mutable<BTreeMap<String, CsvMapping>>,
+    profile_name: Mutable<String>,
+    error: Mutable<Option<String>>,
+}
+
+impl CsvImporter {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            content: Mutable::new(None),
+            filename: Mutable::new(String::new()),
+            headers: Mutable::new(vec![]),
+            mapping: Mutable::new(CsvMapping::default()),
+            profiles: Mutable::new(web::load_item(CSV_PROFILES_STORAGE_KEY).unwrap_or_default()),
+            profile_name: Mutable::new(String::new()),
+            error: Mutable::new(None),
+        })
+    }
+
+    fn load_file(this: &Arc<Self>, content: Vec<u8>, filename: String) {
+        match GenericCSVParser::headers(&decode_text(content.clone())) {
+            Ok(headers) => {
+                // Profiles matching the columns of the file are picked up
+                // automatically.
+                let profile = this.profiles.lock_ref().iter().find_map(|(name, mapping)| {
+                    mapping
+                        .columns
+                        .values()
+                        .all(|column| headers.contains(column))
+                        .then(|| (name.clone(), mapping.clone()))
+                });
+                if let Some((name, mapping)) = profile {
+                    this.profile_name.set(name);
+                    this.mapping.set(mapping);
+                }
+                this.headers.set(headers);
+                this.content.set(Some(content));
+                this.filename.set(filename);
+                this.error.set(None);
+            }
+            Err(err) => this.error.set(Some(err.to_string())),
+        }
+    }
+
+    fn store_profiles(this: &Arc<Self>, profiles: BTreeMap<String, CsvMapping>) {
+        if let Err(err) = web::store_item(CSV_PROFILES_STORAGE_KEY, &profiles) {
+            log::error!("Unable to save CSV profiles: {}", err);
+        }
+        this.profiles.set(profiles);
+    }
+
+    fn save_profile(this: &Arc<Self>) {
+        let name = this.profile_name.get_cloned().trim().to_string();
+        if name.is_empty() {
+            this.error
+                .set(Some("Indica un nombre para guardar el perfil".to_string()));
+            return;
+        }
+        let mut profiles = this.profiles.get_cloned();
+        profiles.insert(name, this.mapping.get_cloned());
+        Self::store_profiles(this, profiles);
+        this.error.set(None);
+    }
+
+    fn delete_profile(this: &Arc<Self>) {
+        let mut profiles = this.profiles.get_cloned();
+        if profiles
+            .remove(this.profile_name.lock_ref().trim())
+            .is_some()
+        {
+            Self::store_profiles(this, profiles);
+        }
+        this.profile_name.set(String::new());
+    }
+
+    fn close(this: &Arc<Self>) {
+        this.content.set(None);
+        this.headers.set(vec![]);
+        this.error.set(None);
+    }
+
+    fn render_load_button(this: &Arc<Self>) -> Dom {
+        html!("span", {
+          .child(
+            html!("button", {
+              .child(
+                html!("label", {
+                  .style("cursor", "pointer")
+                  .attr("for", "import_generic_csv")
+                  .text("Importar CSV de otro broker")
+              })
+            )})
+          )
+          .child(
+            html!("input" => HtmlInputElement, {
+              .attr("id", "import_generic_csv")
+              .attr("alt", "Botón para importar un CSV de un broker eligiendo sus columnas")
+              .attr("accept", "text/csv,text/plain,.csv,.txt")
+              .attr("type", "file")
+              .style("display", "none")
+              .with_node!(element => {
+                .event(clone!(this => move |_: events::Change| {
+                  let Some(file_data) = element.files().and_then(|files| files.get(0)) else {
+                    this.error.set(Some("Error obteniendo fichero".to_string()));
+                    return;
+                  };
+                  let filename = file_data.name();
+                  let blob = Blob::from(file_data);
+                  spawn_local(clone!(this => async move {
+                    match read_as_bytes(&blob).await {
+                      Ok(content) => CsvImporter::load_file(&this, content, filename),
+                      Err(_) => this.error.set(Some("Error subiendo fichero".to_string())),
+                    }
+                  }));
+                  element.set_value("");
+                }))
+              })
+            })
+          )
+        })
+    }
+
+    fn render_option(value: &str, text: &str, selected: bool) -> Dom {
+        html!("option", {
+            .attr("value", value)
+            .apply_if(selected, |dom| dom.attr("selected", ""))
+            .text(text)
+        })
+    }
+
+    fn render_column_row(this: &Arc<Self>, field: CsvField, mapping: &CsvMapping) -> Dom {
+        let selected = mapping.columns.get(&field).cloned().unwrap_or_default();
+        html!("tr", {
+            .class(&*TABLE_ROW)
+            .child(html!("td", { .text(field.label()) }))
+            .child(html!("td", {
+                .child(html!("select" => HtmlSelectElement, {
+                    .child(Self::render_option("", "Sin columna", selected.is_empty()))
+                    .children(this.headers.lock_ref().iter().map(|header| {
+                        Self::render_option(header, header, *header == selected)
+                    }))
+                    .with_node!(element => {
+                        .event(clone!(this => move |_: events::Change| {
+                            let column = element.value();
+                            let mut mapping = this.mapping.lock_mut();
+                            if column.is_empty() {
+                                mapping.columns.remove(&field);
+                            } else {
+                                mapping.columns.insert(field, column);
+                            }
+                        }))
+                    })
+                }))
+            }))
+        })
+    }
+
+    fn render_format_rows(this: &Arc<Self>, mapping: &CsvMapping) -> Vec<Dom> {
+        vec![
+            html!("tr", {
+                .class(&*TABLE_ROW)
+                .child(html!("td", { .text("Separador decimal") }))
+                .child(html!("td", {
+                    .child(html!("select" => HtmlSelectElement, {
+                        .child(Self::render_option(
+                            "point",
+                            "Punto (1,234.56)",
+                            mapping.decimal_separator == DecimalSeparator::Point,
+                        ))
+                        .child(Self::render_option(
+                            "comma",
+                            "Coma (1.234,56)",
+                            mapping.decimal_separator == DecimalSeparator::Comma,
+                        ))
+                        .with_node!(element => {
+                            .event(clone!(this => move |_: events::Change| {
+                                this.mapping.lock_mut().decimal_separator = match element.value().as_str() {
+                                    "comma" => DecimalSeparator::Comma,
+                                    _ => DecimalSeparator::Point,
+                                };
+                            }))
+                        })
+                    }))
+                }))
+            }),
+            html!("tr", {
+                .class(&*TABLE_ROW)
+                .child(html!("td", { .text("Formato de fecha") }))
+                .child(html!("td", {
+                    .child(html!("select" => HtmlSelectElement, {
+                        .children(DATE_FORMATS.iter().map(|(format, text)| {
+                            Self::render_option(format, text, *format == mapping.date_format)
+                        }))
+                        .with_node!(element => {
+                            .event(clone!(this => move |_: events::Change| {
+                                this.mapping.lock_mut().date_format = element.value();
+                            }))
+                        })
+                    }))
+                }))
+            }),
+        ]
+    }
+
+    fn render_text_row(
+        this: &Arc<Self>,
+        label: &str,
+        value: &str,
+        maxlength: &str,
+        update: fn(&mut CsvMapping, String),
+    ) -> Dom {
+        html!("tr", {
+            .class(&*TABLE_ROW)
+            .child(html!("td", { .text(label) }))
+            .child(html!("td", {
+                .child(html!("input" => HtmlInputElement, {
+                    .attr("type", "text")
+                    .attr("maxlength", maxlength)
+                    .attr("value", value)
+                    .with_node!(element => {
+                        .event(clone!(this => move |_: events::Change| {
+                            update(&mut this.mapping.lock_mut(), element.value());
+                        }))
+                    })
+                }))
+            }))
+        })
+    }
+
+    fn render_mapping(this: &Arc<Self>, mapping: &CsvMapping) -> Dom {
+        html!("table", {
+            .child(html!("caption", {
+                .class(&*TABLE_CAPTION)
+                .text("Columnas del CSV. Si eliges la columna de fecha, cada fila se importa como una operación; si no, como la posición a 31/12.")
+            }))
+            .child(html!("thead", {
+                .class(&*TABLE_HEADER)
+                .child(html!("tr", {
+                    .children(["Dato", "Columna"].iter().map(|header| {
+                        html!("th", {
+                            .attr("scope", "col")
+                            .style("background-color", "#ddd")
+                            .text(header)
+                        })
+                    }))
+                }))
+            }))
+            .child(html!("tbody", {
+                .children(CsvField::ALL.iter().map(|field| {
+                    Self::render_column_row(this, *field, mapping)
+                }))
+                .children(Self::render_format_rows(this, mapping))
+                .child(Self::render_text_row(
+                    this,
+                    "Nombre del broker",
+                    &mapping.broker_name,
+                    "40",
+                    |mapping, name| mapping.broker_name = name,
+                ))
+                .child(Self::render_text_row(
+                    this,
+                    "Cód. país del broker",
+                    &mapping.broker_country,
+                    "2",
+                    |mapping, country| mapping.broker_country = country,
+                ))
+            }))
+        })
+    }
+
+    fn render_profiles(this: &Arc<Self>) -> Dom {
+        html!("div", {
+            .child_signal(this.profiles.signal_cloned().map(clone!(this => move |profiles| {
+                Some(html!("select" => HtmlSelectElement, {
+                    .child(Self::render_option("", "Perfiles guardados", true))
+                    .children(profiles.keys().map(|name| Self::render_option(name, name, false)))
+                    .with_node!(element => {
+                        .event(clone!(this => move |_: events::Change| {
+                            let name = element.value();
+                            if let Some(mapping) = this.profiles.lock_ref().get(&name) {
+                                this.mapping.set(mapping.clone());
+                                this.profile_name.set(name);
+                            }
+                        }))
+                    })
+                }))
+            })))
+            .child(html!("input" => HtmlInputElement, {
+                .attr("type", "text")
+                .attr("placeholder", "Nombre del perfil")
+                .prop_signal("value", this.profile_name.signal_cloned())
+                .with_node!(element => {
+                    .event(clone!(this => move |_: events::Change| {
+                        this.profile_name.set(element.value());
+                    }))
+                })
+            }))
+            .child(html!("input" => HtmlInputElement, {
+                .attr("type", "button")
+                .attr("value", "Guardar perfil")
+                .event(clone!(this => move |_: events::Click| {
+                    Self::save_profile(&this);
+                }))
+            }))
+            .child(html!("input" => HtmlInputElement, {
+                .attr("type", "button")
+                .attr("value", "Borrar perfil")
+                .event(clone!(this => move |_: events::Click| {
+                    Self::delete_profile(&this);
+                }))
+            }))
+        })
+    }
+
+    /// `on_import` receives the file, its name and the mapping chosen,
+    /// closing the mapping when it succeeds.
+    pub fn render<F>(this: &Arc<Self>, on_import: F) -> Dom
+    where
+        F: Fn(Vec<u8>, &str, &CsvMapping) -> anyhow::Result<()> + Clone + 'static,
+    {
+        html!("section", {
+            .child(Self::render_load_button(this))
+            .child_signal(this.content.signal_ref(|content| content.is_some()).map(clone!(this => move |loaded| {
+                if !loaded {
+                    return None;
+                }
+                Some(html!("div", {
+                    .child(Self::render_profiles(&this))
+                    .child_signal(this.mapping.signal_cloned().map(clone!(this => move |mapping| {
+                        Some(Self::render_mapping(&this, &mapping))
+                    })))
+                    .child(html!("input" => HtmlInputElement, {
+                        .attr("type", "button")
+                        .attr("value", "Importar CSV")
+                        .event(clone!(this, on_import => move |_: events::Click| {
+                            let Some(content) = this.content.get_cloned() else {
+                                return;
+                            };
+                            match on_import(content, &this.filename.lock_ref(), &this.mapping.lock_ref()) {
+                                Ok(()) => Self::close(&this),
+                                Err(err) => this.error.set(Some(ImportError::from(err).localized())),
+                            }
+                        }))
+                    }))
+                    .child(html!("input" => HtmlInputElement, {
+                        .attr("type", "button")
+                        .attr("value", "Cancelar")
+                        .event(clone!(this => move |_: events::Click| {
+                            Self::close(&this);
+                        }))
+                    }))
+                }))
+            })))
+            .child(html!("span", {
+                .style("color", "red")
+                .style("font-size", "small")
+                .text_signal(this.error.signal_ref(|error| error.clone().unwrap_or_default()))
+            }))
+        })
+    }
+}
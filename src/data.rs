@@ -1,4 +1,6 @@
-use chrono::NaiveDate;
+//! Data read from the broker reports and written to the AEAT forms.
+
+use chrono::{DateTime, NaiveDate, Utc};
 use num_format::Locale;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
@@ -7,15 +9,33 @@ use std::{
     sync::{Arc, LazyLock},
 };
 
+use crate::error::Location;
+
+/// Trades of a report.
 pub type AccountNotes = Vec<AccountNote>;
+/// Positions of a report.
 pub type BalanceNotes = Vec<BalanceNote>;
+/// Corporate actions of a report.
+pub type CorporateActionNotes = Vec<CorporateActionNote>;
+/// Securities of the AEAT 720 form.
 pub type Aeat720Records = Vec<Aeat720Record>;
+/// Positions of previous AEAT 720 forms.
+pub type Aeat720Declarations = Vec<Aeat720Declaration>;
+/// Balance changes of a crypto exchange export.
+pub type CryptoNotes = Vec<CryptoNote>;
+/// Virtual currencies of the AEAT 721 form.
+pub type Aeat721Records = Vec<Aeat721Record>;
 
+/// Year declared when none is given.
 pub const DEFAULT_YEAR: usize = 2024;
+/// ISO 3166 code of Spain.
 pub const SPAIN_COUNTRY_CODE: &str = "ES";
+/// Locale of the numbers shown to the user.
 pub const DEFAULT_LOCALE: &Locale = &Locale::es;
+/// Decimals of the amounts in euros.
 pub const DEFAULT_NUMBER_OF_DECIMALS: u16 = 2;
 
+/// Broker of the records added by hand.
 pub static DEFAULT_BROKER: LazyLock<Arc<BrokerInformation>> = LazyLock::new(|| {
     Arc::new(BrokerInformation {
         name: "Desconocido".to_string(),
@@ -23,9 +43,12 @@ pub static DEFAULT_BROKER: LazyLock<Arc<BrokerInformation>> = LazyLock::new(|| {
     })
 });
 
-#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+/// Direction of a trade.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
 pub enum BrokerOperation {
+    /// Shares acquired.
     Buy,
+    /// Shares transferred.
     Sell,
 }
 
@@ -40,25 +63,42 @@ impl From<&str> for BrokerOperation {
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+/// Security identification.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
 pub struct CompanyInfo {
+    /// Name as given by the broker.
     pub name: String,
+    /// ISIN, empty when the report does not give it.
     pub isin: String,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+/// Trade of a security.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
 pub struct AccountNote {
+    /// Trade date.
     pub date: NaiveDate,
+    /// Security traded.
     pub company: CompanyInfo,
+    /// Buy or sell.
     pub operation: BrokerOperation,
+    /// Number of shares, always positive.
     pub quantity: Decimal,
+    /// Price of each share.
     pub price: Decimal,
+    /// Value of the shares traded.
     pub value: Decimal,
+    /// Fees of the trade.
     pub commision: Decimal,
+    /// Broker of the trade.
     pub broker: Arc<BrokerInformation>,
+    /// Account in the broker, empty when the report is not consolidated
+    /// from several accounts.
+    #[serde(default)]
+    pub account: String,
 }
 
 impl AccountNote {
+    /// Trade not tied to an account of the broker.
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         date: NaiveDate,
@@ -79,22 +119,123 @@ impl AccountNote {
             value,
             commision,
             broker: Arc::clone(broker),
+            account: String::new(),
         }
     }
 }
 
+/// Kind of financial instrument as classified by the broker.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub enum AssetCategory {
+    /// Shares of companies.
+    #[default]
+    Stock,
+    /// Investment funds and ETFs.
+    Fund,
+    /// Debt securities.
+    Bond,
+    /// Warrants.
+    Warrant,
+    /// Structured products like certificates.
+    StructuredProduct,
+    /// Options, not declared.
+    Option,
+    /// Futures, not declared.
+    Future,
+    /// Instruments of other kinds.
+    Other,
+    /// Category of the broker not recognized, not declared until the user
+    /// reviews it.
+    Unknown,
+}
+
+impl AssetCategory {
+    /// Every category, in the order offered to the user.
+    pub const ALL: [AssetCategory; 9] = [
+        AssetCategory::Stock,
+        AssetCategory::Fund,
+        AssetCategory::Bond,
+        AssetCategory::Warrant,
+        AssetCategory::StructuredProduct,
+        AssetCategory::Option,
+        AssetCategory::Future,
+        AssetCategory::Other,
+        AssetCategory::Unknown,
+    ];
+
+    /// Derivatives are not securities held in custody, so they are not
+    /// declared in the AEAT 720 form.
+    pub fn is_declarable(&self) -> bool {
+        !matches!(
+            self,
+            AssetCategory::Option | AssetCategory::Future | AssetCategory::Unknown
+        )
+    }
+
+    /// AEAT 720 asset type: 'I' for the shares of collective investment
+    /// institutions (funds and ETFs), 'V' for the rest of securities.
+    pub fn aeat720_asset_type(&self) -> &'static str {
+        match self {
+            AssetCategory::Fund => "I",
+            _ => "V",
+        }
+    }
+
+    /// AEAT 720 subtype of the asset type: '0' for the 'I' one, and for the
+    /// 'V' one '1' for equity or '2' for the transfer of own capital to third
+    /// parties (debt securities).
+    pub fn aeat720_asset_subtype(&self) -> usize {
+        match self {
+            AssetCategory::Fund => 0,
+            AssetCategory::Bond => 2,
+            _ => 1,
+        }
+    }
+
+    /// Name of the category shown to the user.
+    pub fn description(&self) -> &'static str {
+        match self {
+            AssetCategory::Stock => "Acciones",
+            AssetCategory::Fund => "Fondos",
+            AssetCategory::Bond => "Bonos",
+            AssetCategory::Warrant => "Warrants",
+            AssetCategory::StructuredProduct => "Productos estructurados",
+            AssetCategory::Option => "Opciones",
+            AssetCategory::Future => "Futuros",
+            AssetCategory::Other => "Otros",
+            AssetCategory::Unknown => "Sin clasificar",
+        }
+    }
+}
+
+/// Position held at the end of the period of a report.
 #[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
 pub struct BalanceNote {
+    /// Security held.
     pub company: CompanyInfo,
+    /// Market where it is traded, empty when unknown.
     pub market: String,
+    /// Number of shares.
     pub quantity: Decimal,
+    /// Currency of the price.
     pub currency: String,
+    /// Price of each share.
     pub price: Decimal,
+    /// Value of the position in euros.
     pub value_in_euro: Decimal,
+    /// Broker holding the position.
     pub broker: Arc<BrokerInformation>,
+    /// Kind of instrument.
+    #[serde(default)]
+    pub category: AssetCategory,
+    /// Account in the broker, empty when the report is not consolidated
+    /// from several accounts.
+    #[serde(default)]
+    pub account: String,
 }
 
 impl BalanceNote {
+    /// Position of a stock not tied to an account of the broker.
     pub fn new(
         company: CompanyInfo,
         market: String,
@@ -112,17 +253,92 @@ impl BalanceNote {
             price,
             value_in_euro,
             broker: Arc::clone(broker),
+            category: AssetCategory::default(),
+            account: String::new(),
         }
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Deserialize, Serialize)]
+/// Corporate action as applied to a held security, `ratio` being the number
+/// of new shares received for each share held.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[allow(missing_docs)]
+pub enum CorporateActionKind {
+    /// Shares split or reverse split.
+    Split { ratio: Decimal },
+    /// Same security under a new ISIN.
+    IsinChange {
+        new_company: CompanyInfo,
+        ratio: Decimal,
+    },
+    /// Shares exchanged for the ones of the absorbing company.
+    Merger {
+        new_company: CompanyInfo,
+        ratio: Decimal,
+    },
+    /// Shares of a new company received while keeping the held ones.
+    SpinOff {
+        new_company: CompanyInfo,
+        ratio: Decimal,
+    },
+}
+
+/// Corporate action of a report.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct CorporateActionNote {
+    /// Date it was applied.
+    pub date: NaiveDate,
+    /// Security held before it.
+    pub company: CompanyInfo,
+    /// What it did to the held shares.
+    pub kind: CorporateActionKind,
+    /// Broker holding the security.
+    pub broker: Arc<BrokerInformation>,
+    /// Account in the broker, empty when the report is not consolidated
+    /// from several accounts.
+    #[serde(default)]
+    pub account: String,
+}
+
+impl CorporateActionNote {
+    /// Corporate action not tied to an account of the broker.
+    pub fn new(
+        date: NaiveDate,
+        company: CompanyInfo,
+        kind: CorporateActionKind,
+        broker: &Arc<BrokerInformation>,
+    ) -> CorporateActionNote {
+        CorporateActionNote {
+            date,
+            company,
+            kind,
+            broker: Arc::clone(broker),
+            account: String::new(),
+        }
+    }
+
+    /// Security received by the holders of `company`, if any.
+    pub fn new_company(&self) -> Option<&CompanyInfo> {
+        match &self.kind {
+            CorporateActionKind::Split { .. } => None,
+            CorporateActionKind::IsinChange { new_company, .. }
+            | CorporateActionKind::Merger { new_company, .. }
+            | CorporateActionKind::SpinOff { new_company, .. } => Some(new_company),
+        }
+    }
+}
+
+/// Entity holding the assets.
+#[derive(Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
 pub struct BrokerInformation {
+    /// Name of the entity.
     pub name: String,
+    /// ISO 3166 code of the country of the entity.
     pub country_code: String,
 }
 
 impl BrokerInformation {
+    /// Entity named `name` in the country `cc`.
     pub fn new(name: String, cc: String) -> Self {
         Self {
             name,
@@ -131,32 +347,266 @@ impl BrokerInformation {
     }
 }
 
+/// Where the first acquisition date of a record comes from.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Deserialize, Serialize)]
+pub enum FirstTxDateSource {
+    /// Not found, to be reviewed.
+    #[default]
+    Default,
+    /// First trade of the imported report.
+    Report,
+    /// Trades history of previous imports.
+    Trades,
+    /// Previous AEAT 720 form.
+    Aeat720,
+    /// Typed by the user.
+    Manual,
+}
+
+impl FirstTxDateSource {
+    /// Name of the source shown to the user.
+    pub fn description(&self) -> &'static str {
+        match self {
+            FirstTxDateSource::Default => "Por defecto, revisar",
+            FirstTxDateSource::Report => "Primera operación del informe",
+            FirstTxDateSource::Trades => "Historial de operaciones",
+            FirstTxDateSource::Aeat720 => "Modelo 720 anterior",
+            FirstTxDateSource::Manual => "Introducida manualmente",
+        }
+    }
+}
+
+/// Where a record comes from, to trace it back to the broker document.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Provenance {
+    /// Name of the imported file, empty when not known.
+    pub filename: String,
+    /// Parser that read the record, empty for the records typed by hand.
+    pub parser: String,
+    /// Place of the file where the security is first listed.
+    pub location: Option<Location>,
+    /// When the record was imported.
+    pub imported_at: Option<DateTime<Utc>>,
+    /// Whether the user changed the record after importing or adding it.
+    pub edited: bool,
+}
+
+impl Provenance {
+    /// Whether the record was typed by hand instead of imported.
+    pub fn is_manual(&self) -> bool {
+        self.parser.is_empty()
+    }
+
+    /// Origin of the record shown to the user.
+    pub fn description(&self) -> String {
+        if self.is_manual() {
+            return String::from("Introducido manualmente");
+        }
+        let mut description = format!("Importado con el lector {}", self.parser);
+        if !self.filename.is_empty() {
+            description.push_str(&format!(" del fichero {}", self.filename));
+        }
+        if let Some(location) = &self.location {
+            description.push_str(&format!(", {}", location.localized()));
+        }
+        if let Some(imported_at) = self.imported_at {
+            description.push_str(&format!(
+                ", el {} UTC",
+                imported_at.format("%Y-%m-%d %H:%M")
+            ));
+        }
+        if self.edited {
+            description.push_str(". Modificado a mano después de importarlo");
+        }
+        description
+    }
+}
+
+/// Security to declare in the AEAT 720 form.
 #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub struct Aeat720Record {
+    /// Security held.
     pub company: CompanyInfo,
+    /// Number of shares at 31 December.
     pub quantity: Decimal,
+    /// Value in euros at 31 December.
     pub value_in_euro: Decimal,
+    /// First acquisition date as `YYYYMMDD`.
     pub first_tx_date: usize,
+    /// Broker holding the security.
     pub broker: Arc<BrokerInformation>,
+    /// Percentage owned by the declarant.
     pub percentage: Decimal,
+    /// Where `first_tx_date` comes from.
+    #[serde(default)]
+    pub first_tx_date_source: FirstTxDateSource,
+    /// Kind of instrument.
+    #[serde(default)]
+    pub category: AssetCategory,
+    /// Account in the broker, empty when the report is not consolidated
+    /// from several accounts.
+    #[serde(default)]
+    pub account: String,
+    /// Where the record comes from.
+    #[serde(default)]
+    pub provenance: Provenance,
+}
+
+impl Aeat720Record {
+    /// Whether `other` is the same position, that is, the same security at
+    /// the same broker and account. Securities are compared by ISIN or by
+    /// name when any of them lacks it.
+    pub fn is_same_position(&self, other: &Aeat720Record) -> bool {
+        let same_company = if self.company.isin.is_empty() || other.company.isin.is_empty() {
+            self.company.name == other.company.name
+        } else {
+            self.company.isin == other.company.isin
+        };
+        same_company && self.broker.name == other.broker.name && self.account == other.account
+    }
+}
+
+/// Position declared in an already submitted AEAT 720 form.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Aeat720Declaration {
+    /// Year of the form.
+    pub year: usize,
+    /// Security declared.
+    pub company: CompanyInfo,
+    /// Country code of the depository entity.
+    pub country_code: String,
+    /// Account of the securities at the depository entity, when declared.
+    #[serde(default)]
+    pub account: String,
+    /// First acquisition date declared.
+    pub first_tx_date: NaiveDate,
+    /// Whether the position was declared as disposed of during the year.
+    #[serde(default)]
+    pub closed: bool,
+    /// Date the position was extinguished, when declared.
+    #[serde(default)]
+    pub extinction_date: Option<NaiveDate>,
+    /// Number of securities declared, unknown in the declarations saved by
+    /// older versions.
+    #[serde(default)]
+    pub quantity: Option<Decimal>,
 }
 
+/// Declarant of the forms.
 #[derive(Debug, Default, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub struct PersonalInformation {
+    /// Name.
     pub name: String,
+    /// Surnames.
     pub surname: String,
+    /// NIF.
     pub nif: String,
+    /// Declared year.
     pub year: usize,
+    /// Contact phone.
     pub phone: String,
 }
 
+/// Content of the AEAT 720 form.
 #[derive(Debug, Eq, Default, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Aeat720Information {
+    /// Securities declared.
     pub records: Vec<Aeat720Record>,
+    /// Declarant.
     pub personal_info: PersonalInformation,
 }
 
 impl Aeat720Information {
+    /// Surnames followed by the name of the declarant.
+    pub fn full_name(&self) -> String {
+        self.personal_info.surname.clone() + " " + &self.personal_info.name[..]
+    }
+}
+
+/// Change in the balance of a virtual currency held by an exchange, positive
+/// when received and negative when sent or sold.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct CryptoNote {
+    /// Date of the change.
+    pub date: NaiveDate,
+    /// Identifier of the virtual currency.
+    pub currency: String,
+    /// Units received or, when negative, removed.
+    pub quantity: Decimal,
+    /// Exchange holding the currency.
+    pub custodian: Arc<BrokerInformation>,
+}
+
+impl CryptoNote {
+    /// Change of `quantity` units of `currency`.
+    pub fn new(
+        date: NaiveDate,
+        currency: String,
+        quantity: Decimal,
+        custodian: &Arc<BrokerInformation>,
+    ) -> CryptoNote {
+        CryptoNote {
+            date,
+            currency,
+            quantity,
+            custodian: Arc::clone(custodian),
+        }
+    }
+}
+
+/// Virtual currency balance to declare in the AEAT 721 form, `currency` being
+/// its identifier (ticker) and `name` its denomination.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Aeat721Record {
+    /// Denomination of the virtual currency.
+    pub name: String,
+    /// Identifier of the virtual currency.
+    pub currency: String,
+    /// Units at 31 December.
+    pub quantity: Decimal,
+    /// Value in euros at 31 December.
+    pub value_in_euro: Decimal,
+    /// First acquisition date as `YYYYMMDD`.
+    pub first_tx_date: usize,
+    /// Date as `YYYYMMDD` the currency stopped being held during the
+    /// declared year, zero while it is still held.
+    #[serde(default)]
+    pub extinction_date: usize,
+    /// Exchange holding the currency.
+    pub custodian: Arc<BrokerInformation>,
+    /// Percentage owned by the declarant.
+    pub percentage: Decimal,
+}
+
+impl Aeat721Record {
+    /// Whether `other` is the same virtual currency at the same custodian.
+    pub fn is_same_currency(&self, other: &Aeat721Record) -> bool {
+        self.currency == other.currency && self.custodian.name == other.custodian.name
+    }
+
+    /// Whether the currency stopped being held during the declared year.
+    pub fn is_extinct(&self) -> bool {
+        self.extinction_date != 0
+    }
+
+    /// Whether the currency is still held but its value at 31 December is
+    /// missing.
+    pub fn is_unvalued(&self) -> bool {
+        self.value_in_euro.is_zero() && !self.is_extinct()
+    }
+}
+
+/// Content of the AEAT 721 form.
+#[derive(Debug, Eq, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Aeat721Information {
+    /// Virtual currencies declared.
+    pub records: Vec<Aeat721Record>,
+    /// Declarant.
+    pub personal_info: PersonalInformation,
+}
+
+impl Aeat721Information {
+    /// Surnames followed by the name of the declarant.
     pub fn full_name(&self) -> String {
         self.personal_info.surname.clone() + " " + &self.personal_info.name[..]
     }
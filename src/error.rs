@@ -0,0 +1,522 @@
+//! Errors and warnings importing the broker reports, with the place of the
+//! file where they were found.
+
+use std::{
+    fmt,
+    num::{ParseFloatError, ParseIntError},
+};
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// Place of a file where a row could not be read, or where a record was
+/// found.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Location {
+    /// Section or table of the report holding the row.
+    pub section: Option<String>,
+    /// Line of the file, or of the text extracted from a PDF, starting at 1.
+    pub line: Option<usize>,
+    /// Page of a PDF, starting at 1.
+    pub page: Option<usize>,
+}
+
+impl Location {
+    pub(crate) fn line(line: usize) -> Self {
+        Self {
+            line: Some(line),
+            ..Default::default()
+        }
+    }
+
+    /// Line where a CSV record begins.
+    pub(crate) fn record(record: &csv::StringRecord) -> Self {
+        Self {
+            line: record.position().map(|position| position.line() as usize),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn in_section(mut self, section: &str) -> Self {
+        self.section = Some(section.to_string());
+        self
+    }
+
+    pub(crate) fn localized(&self) -> String {
+        let mut parts = vec![];
+        if let Some(page) = self.page {
+            parts.push(format!("página {}", page));
+        }
+        if let Some(line) = self.line {
+            parts.push(format!("línea {}", line));
+        }
+        let mut location = parts.join(", ");
+        if let Some(section) = &self.section {
+            if location.is_empty() {
+                location = format!("sección {}", section);
+            } else {
+                location.push_str(&format!(" de la sección {}", section));
+            }
+        }
+        location
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![];
+        if let Some(page) = self.page {
+            parts.push(format!("page {}", page));
+        }
+        if let Some(line) = self.line {
+            parts.push(format!("line {}", line));
+        }
+        if let Some(section) = &self.section {
+            parts.push(format!("section {}", section));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// What is wrong in a row that could not be read.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RowIssue {
+    /// A date is not in the format of the report.
+    InvalidDate,
+    /// A quantity, price or amount is not a number.
+    InvalidNumber,
+    /// The row does not have the layout of the report.
+    UnexpectedLayout,
+    /// Any other problem, described by the parser.
+    Other(String),
+}
+
+impl RowIssue {
+    /// Finds the cause of a parser error, the description of the parser is
+    /// kept when it is neither a date nor a number.
+    fn from_error(err: &anyhow::Error) -> Self {
+        for cause in err.chain() {
+            if let Some(issue) = cause.downcast_ref::<RowIssue>() {
+                return issue.clone();
+            }
+            if cause.is::<chrono::ParseError>() {
+                return RowIssue::InvalidDate;
+            }
+            if cause.is::<rust_decimal::Error>()
+                || cause.is::<ParseIntError>()
+                || cause.is::<ParseFloatError>()
+            {
+                return RowIssue::InvalidNumber;
+            }
+        }
+        RowIssue::Other(err.to_string())
+    }
+
+    fn localized(&self) -> String {
+        match self {
+            RowIssue::InvalidDate => String::from("fecha inválida"),
+            RowIssue::InvalidNumber => String::from("número inválido"),
+            RowIssue::UnexpectedLayout => String::from("formato de fila inesperado"),
+            RowIssue::Other(description) => description.clone(),
+        }
+    }
+}
+
+impl fmt::Display for RowIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RowIssue::InvalidDate => write!(f, "invalid date"),
+            RowIssue::InvalidNumber => write!(f, "invalid number"),
+            RowIssue::UnexpectedLayout => write!(f, "unexpected row layout"),
+            RowIssue::Other(description) => write!(f, "{}", description),
+        }
+    }
+}
+
+/// Reasons an import can fail.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ImportError {
+    /// The file is not from a supported broker, exchange or form, with the
+    /// type detected from its content when known.
+    UnrecognizedFormat(Option<String>),
+    /// A section the report must have is not in the file.
+    MissingSection(String),
+    /// A column the report must have is not in its header.
+    MissingColumn(String),
+    /// A row of the file could not be read.
+    MalformedRow {
+        /// Where the row is.
+        location: Location,
+        /// Text of the row.
+        raw: String,
+        /// What is wrong in it.
+        issue: RowIssue,
+    },
+    /// The report holds an asset that can not be declared from it.
+    UnsupportedAsset(String),
+    /// The text of the file is not in the encoding of its format, named.
+    Encoding(String),
+    /// Any other failure reading the file.
+    Other(String),
+}
+
+impl ImportError {
+    /// Message in Spanish for the users of the browser application, telling
+    /// them what to check in the file.
+    pub fn localized(&self) -> String {
+        match self {
+            ImportError::UnrecognizedFormat(Some(kind)) => format!(
+                "El fichero ({}) no es de ningún formato soportado. Sube el informe tal y como lo descargas del broker.",
+                kind
+            ),
+            ImportError::UnrecognizedFormat(None) => String::from(
+                "El fichero no es de ningún formato soportado. Sube el informe tal y como lo descargas del broker.",
+            ),
+            ImportError::MissingSection(section) => format!(
+                "No se ha encontrado la sección {} en el fichero. Comprueba que el informe la incluye y cubre todo el año.",
+                section
+            ),
+            ImportError::MissingColumn(column) => format!(
+                "No se ha encontrado la columna {} en el fichero. Vuelve a exportarlo desde el broker sin quitar columnas.",
+                column
+            ),
+            ImportError::MalformedRow {
+                location,
+                raw,
+                issue,
+            } => {
+                let mut message = format!("{}: {}", location.localized(), issue.localized());
+                if !raw.is_empty() {
+                    message.push_str(&format!(" («{}»)", raw));
+                }
+                message
+            }
+            ImportError::UnsupportedAsset(asset) => format!(
+                "No se puede declarar {} a partir de este fichero, añádelo a mano en la tabla.",
+                asset
+            ),
+            ImportError::Encoding(kind) => format!(
+                "El fichero {} no tiene la codificación esperada. Vuelve a descargarlo del broker sin abrirlo antes con otro programa.",
+                kind
+            ),
+            ImportError::Other(description) => description.clone(),
+        }
+    }
+
+    /// Places the error of a parser in the row `raw` of the file, errors
+    /// already classified are kept as they are.
+    pub(crate) fn row(err: anyhow::Error, location: Location, raw: &str) -> Self {
+        match err.downcast::<ImportError>() {
+            Ok(err) => err,
+            Err(err) => ImportError::MalformedRow {
+                location,
+                raw: raw.trim().to_string(),
+                issue: RowIssue::from_error(&err),
+            },
+        }
+    }
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::UnrecognizedFormat(Some(kind)) => {
+                write!(f, "Unsupported file format ({})", kind)
+            }
+            ImportError::UnrecognizedFormat(None) => write!(f, "Unsupported file format"),
+            ImportError::MissingSection(section) => write!(f, "Section {} not found", section),
+            ImportError::MissingColumn(column) => write!(f, "Column {} not found", column),
+            ImportError::MalformedRow {
+                location,
+                raw,
+                issue,
+            } => write!(f, "{}: {} ({})", location, issue, raw),
+            ImportError::UnsupportedAsset(asset) => write!(f, "Unsupported asset {}", asset),
+            ImportError::Encoding(kind) => write!(f, "Invalid text encoding of {}", kind),
+            ImportError::Other(description) => write!(f, "{}", description),
+        }
+    }
+}
+
+impl std::error::Error for RowIssue {}
+
+impl std::error::Error for ImportError {}
+
+impl From<anyhow::Error> for ImportError {
+    /// Finds the import error raised by a parser, even under added context.
+    fn from(err: anyhow::Error) -> Self {
+        err.chain()
+            .find_map(|cause| cause.downcast_ref::<ImportError>())
+            .cloned()
+            .unwrap_or_else(|| ImportError::Other(format!("{:#}", err)))
+    }
+}
+
+/// Data of a report left out or changed by an import that succeeded, to be
+/// reviewed by hand.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ImportWarning {
+    /// Open positions left out of the AEAT 720 form for not being
+    /// declarable securities, like derivatives, with their category.
+    ExcludedPositions(Vec<String>),
+    /// Names of the positions imported without ISIN.
+    MissingIsin(Vec<String>),
+    /// Names of the positions imported without value at the end of the
+    /// year, like the ones rebuilt from the trades history.
+    UnvaluedPositions(Vec<String>),
+    /// Row without security left out, like the cash of a portfolio.
+    SkippedRow {
+        /// Where the row is.
+        location: Location,
+        /// Text of the row.
+        raw: String,
+    },
+    /// Transaction of a crypto export whose effect on the balances could
+    /// not be read, left out or imported only in part.
+    UnreadTransaction {
+        /// Where the row is.
+        location: Location,
+        /// Text of the row.
+        raw: String,
+    },
+    /// Values of the positions in the currency given converted to euros
+    /// with the total in euros of their section.
+    RescaledSection(String),
+    /// Positions left without value for not having the reference rate of
+    /// their currency at the end of the year.
+    MissingExchangeRate {
+        /// Currency of the positions.
+        currency: String,
+        /// Year of the rate.
+        year: usize,
+    },
+    /// Quantity of a position in the report differing from the one given by
+    /// the trades history.
+    QuantityMismatch {
+        /// Name of the security.
+        name: String,
+        /// Quantity in the report.
+        report: Decimal,
+        /// Quantity from the trades history.
+        trades: Decimal,
+    },
+    /// Shares of a security sold that the trades history does not show
+    /// bought, held since before the oldest imported report.
+    IncompleteHistory {
+        /// Name of the security.
+        name: String,
+        /// Shares sold beyond the trades history.
+        quantity: Decimal,
+    },
+}
+
+impl ImportWarning {
+    pub(crate) fn skipped_record(record: &csv::StringRecord) -> Self {
+        ImportWarning::SkippedRow {
+            location: Location::record(record),
+            raw: record_text(record).trim().to_string(),
+        }
+    }
+
+    /// Transaction of `record`, read from a CSV starting `offset` lines
+    /// below the beginning of the file.
+    pub(crate) fn unread_record(record: &csv::StringRecord, offset: usize) -> Self {
+        let mut location = Location::record(record);
+        location.line = location.line.map(|line| line + offset);
+        ImportWarning::UnreadTransaction {
+            location,
+            raw: record_text(record).trim().to_string(),
+        }
+    }
+
+    /// Message in Spanish for the users of the browser application.
+    pub fn localized(&self) -> String {
+        match self {
+            ImportWarning::ExcludedPositions(positions) => format!(
+                "Posiciones excluidas del modelo 720 por no ser valores declarables: {}",
+                positions.join(", ")
+            ),
+            ImportWarning::MissingIsin(names) => format!(
+                "{} posiciones sin ISIN, complétalo en la tabla: {}",
+                names.len(),
+                names.join(", ")
+            ),
+            ImportWarning::UnvaluedPositions(names) => format!(
+                "{} posiciones sin valorar a 31 de diciembre, introduce su valor en la tabla: {}",
+                names.len(),
+                names.join(", ")
+            ),
+            ImportWarning::SkippedRow { location, raw } => format!(
+                "{}: fila sin valor ignorada («{}»)",
+                location.localized(),
+                raw
+            ),
+            ImportWarning::UnreadTransaction { location, raw } => format!(
+                "{}: movimiento no reconocido, revisa el saldo en la tabla («{}»)",
+                location.localized(),
+                raw
+            ),
+            ImportWarning::RescaledSection(currency) => format!(
+                "Valores de la sección {} recalculados en euros con el total de la sección",
+                currency
+            ),
+            ImportWarning::MissingExchangeRate { currency, year } => format!(
+                "No hay cambio del BCE de {} a 31 de diciembre de {}, introduce el valor en euros de sus posiciones en la tabla",
+                currency, year
+            ),
+            ImportWarning::QuantityMismatch {
+                name,
+                report,
+                trades,
+            } => format!(
+                "La cantidad de {} en el informe ({}) no coincide con la de las operaciones ({})",
+                name, report, trades
+            ),
+            ImportWarning::IncompleteHistory { name, quantity } => format!(
+                "Se han vendido {} títulos de {} comprados antes del informe más antiguo, importa los informes anteriores o revisa su cantidad en la tabla",
+                quantity, name
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ImportWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportWarning::ExcludedPositions(positions) => write!(
+                f,
+                "Positions excluded for not being declarable securities: {}",
+                positions.join(", ")
+            ),
+            ImportWarning::MissingIsin(names) => write!(
+                f,
+                "{} positions without ISIN: {}",
+                names.len(),
+                names.join(", ")
+            ),
+            ImportWarning::UnvaluedPositions(names) => write!(
+                f,
+                "{} positions without value at year end: {}",
+                names.len(),
+                names.join(", ")
+            ),
+            ImportWarning::SkippedRow { location, raw } => {
+                write!(f, "{}: row without security skipped ({})", location, raw)
+            }
+            ImportWarning::UnreadTransaction { location, raw } => {
+                write!(f, "{}: unrecognized transaction ({})", location, raw)
+            }
+            ImportWarning::RescaledSection(currency) => {
+                write!(f, "{} section rescaled by its total in euros", currency)
+            }
+            ImportWarning::MissingExchangeRate { currency, year } => write!(
+                f,
+                "No ECB reference rate of {} at the end of {}, positions left without value",
+                currency, year
+            ),
+            ImportWarning::QuantityMismatch {
+                name,
+                report,
+                trades,
+            } => write!(
+                f,
+                "Quantity of {} in the report ({}) differs from the trades history ({})",
+                name, report, trades
+            ),
+            ImportWarning::IncompleteHistory { name, quantity } => write!(
+                f,
+                "{} shares of {} sold were bought before the oldest imported report",
+                quantity, name
+            ),
+        }
+    }
+}
+
+fn record_text(record: &csv::StringRecord) -> String {
+    record.iter().collect::<Vec<_>>().join(",")
+}
+
+/// Adds the row being read to the errors of the parsers.
+pub(crate) trait RowContext<T> {
+    /// Places the error in the row `raw` found at `location`.
+    fn row_context(self, location: Location, raw: &str) -> anyhow::Result<T>;
+
+    /// Places the error in a CSV record.
+    fn record_context(self, record: &csv::StringRecord) -> anyhow::Result<T>;
+}
+
+impl<T> RowContext<T> for anyhow::Result<T> {
+    fn row_context(self, location: Location, raw: &str) -> anyhow::Result<T> {
+        self.map_err(|err| ImportError::row(err, location, raw).into())
+    }
+
+    fn record_context(self, record: &csv::StringRecord) -> anyhow::Result<T> {
+        self.map_err(|err| {
+            ImportError::row(err, Location::record(record), &record_text(record)).into()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn localized_row_test() {
+        let err =
+            NaiveDate::parse_from_str("2023-13-01", "%Y-%m-%d").context("Invalid date 2023-13-01");
+        let err = ImportError::from(
+            err.row_context(
+                Location::line(57).in_section("Operaciones"),
+                "Operaciones,Data,Order,Acciones,USD,AAPL,2023-13-01",
+            )
+            .unwrap_err(),
+        );
+
+        assert_eq!(
+            err.localized(),
+            "línea 57 de la sección Operaciones: fecha inválida («Operaciones,Data,Order,Acciones,USD,AAPL,2023-13-01»)"
+        );
+        assert_eq!(
+            err.to_string(),
+            "line 57, section Operaciones: invalid date (Operaciones,Data,Order,Acciones,USD,AAPL,2023-13-01)"
+        );
+    }
+
+    #[test]
+    fn from_anyhow_test() {
+        let err = anyhow::Error::new(ImportError::MissingSection(String::from("Trades")))
+            .context("Unable to import ib.csv");
+        assert_eq!(
+            ImportError::from(err),
+            ImportError::MissingSection(String::from("Trades"))
+        );
+
+        let err = anyhow::anyhow!("Unexpected end of file");
+        assert_eq!(
+            ImportError::from(err),
+            ImportError::Other(String::from("Unexpected end of file"))
+        );
+    }
+
+    #[test]
+    fn record_context_test() {
+        let content = "Date,Quantity\n2023-01-05,1\n2023-01-06,one\n";
+        let mut rdr = csv::Reader::from_reader(content.as_bytes());
+        let record = rdr.records().nth(1).unwrap().unwrap();
+        let result: anyhow::Result<rust_decimal::Decimal> = record[1]
+            .parse::<rust_decimal::Decimal>()
+            .map_err(anyhow::Error::from);
+
+        assert_eq!(
+            ImportError::from(result.record_context(&record).unwrap_err()),
+            ImportError::MalformedRow {
+                location: Location::line(3),
+                raw: String::from("2023-01-06,one"),
+                issue: RowIssue::InvalidNumber,
+            }
+        );
+    }
+}
@@ -0,0 +1,271 @@
+use std::{collections::BTreeSet, sync::Arc};
+
+use dominator::{Dom, clone, events, html, with_node};
+use futures_signals::signal::{Mutable, SignalExt};
+use web_sys::HtmlInputElement;
+
+use crate::{
+    css::{DUPLICATE_CELL_CLASS, TABLE_CAPTION, TABLE_HEADER, TABLE_ROW, WARNING_PANEL_CLASS},
+    data::{Aeat720Record, Aeat720Records, Aeat721Record, Aeat721Records, DEFAULT_LOCALE},
+    error::ImportWarning,
+    importer::ImportResult,
+    ledger::Ledger,
+    utils::decimal::decimal_to_str_locale,
+};
+
+/// Records accepted by the user from the preview of an import.
+pub struct AcceptedImport {
+    pub records: Aeat720Records,
+    pub crypto_records: Aeat721Records,
+    pub warnings: Vec<ImportWarning>,
+    /// Ledger with the trades of the imported file.
+    pub ledger: Ledger,
+    /// Whether the rows of the imported brokers already in the tables are
+    /// removed before adding the new ones.
+    pub replace: bool,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Acceptance {
+    All,
+    Selected,
+    Replace,
+}
+
+struct PreviewRow {
+    cells: [String; 5],
+    duplicate: bool,
+    selected: Mutable<bool>,
+}
+
+impl PreviewRow {
+    fn new(cells: [String; 5], duplicate: bool) -> Self {
+        Self {
+            cells,
+            duplicate,
+            selected: Mutable::new(!duplicate),
+        }
+    }
+}
+
+/// Imported file waiting for the user, the ledger is a copy of the one of
+/// the app with the trades of the file, so cancelling leaves it untouched.
+struct PendingImport {
+    result: ImportResult,
+    ledger: Ledger,
+    /// Rows of the securities followed by the ones of the virtual currencies.
+    rows: Vec<PreviewRow>,
+}
+
+impl PendingImport {
+    fn brokers(&self) -> Vec<String> {
+        let brokers = self
+            .result
+            .records
+            .iter()
+            .map(|record| record.broker.name.clone())
+            .chain(
+                self.result
+                    .crypto_records
+                    .iter()
+                    .map(|record| record.custodian.name.clone()),
+            )
+            .collect::<BTreeSet<_>>();
+        brokers.into_iter().collect()
+    }
+}
+
+/// Preview of the records of an imported file, with the rows already in
+/// the tables unselected, before adding them.
+pub struct ImportPreview {
+    pending: Mutable<Option<Arc<PendingImport>>>,
+}
+
+impl ImportPreview {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            pending: Mutable::new(None),
+        })
+    }
+
+    /// Shows `result` comparing it with the rows `existing` and
+    /// `existing_crypto` of the tables.
+    pub fn show(
+        &self,
+        result: ImportResult,
+        ledger: Ledger,
+        existing: &[Aeat720Record],
+        existing_crypto: &[Aeat721Record],
+    ) {
+        let number = |number| decimal_to_str_locale(number, DEFAULT_LOCALE);
+        let rows = result
+            .records
+            .iter()
+            .map(|record| {
+                PreviewRow::new(
+                    [
+                        record.company.name.clone(),
+                        record.company.isin.clone(),
+                        record.broker.name.clone(),
+                        number(&record.quantity),
+                        number(&record.value_in_euro),
+                    ],
+                    existing.iter().any(|row| row.is_same_position(record)),
+                )
+            })
+            .chain(result.crypto_records.iter().map(|record| {
+                PreviewRow::new(
+                    [
+                        record.name.clone(),
+                        record.currency.clone(),
+                        record.custodian.name.clone(),
+                        number(&record.quantity),
+                        number(&record.value_in_euro),
+                    ],
+                    existing_crypto
+                        .iter()
+                        .any(|row| row.is_same_currency(record)),
+                )
+            }))
+            .collect();
+        self.pending.set(Some(Arc::new(PendingImport {
+            result,
+            ledger,
+            rows,
+        })));
+    }
+
+    fn accept(&self, acceptance: Acceptance) -> Option<AcceptedImport> {
+        let pending = self.pending.replace(None)?;
+        let is_selected =
+            |index: usize| acceptance != Acceptance::Selected || pending.rows[index].selected.get();
+        let offset = pending.result.records.len();
+        Some(AcceptedImport {
+            records: pending
+                .result
+                .records
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| is_selected(*index))
+                .map(|(_, record)| record.clone())
+                .collect(),
+            crypto_records: pending
+                .result
+                .crypto_records
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| is_selected(offset + index))
+                .map(|(_, record)| record.clone())
+                .collect(),
+            warnings: pending.result.warnings.clone(),
+            ledger: pending.ledger.clone(),
+            replace: acceptance == Acceptance::Replace,
+        })
+    }
+
+    fn render_row(row: &PreviewRow) -> Dom {
+        let selected = row.selected.clone();
+        html!("tr", {
+            .class(&*TABLE_ROW)
+            .child(html!("td", {
+                .child(html!("input" => HtmlInputElement, {
+                    .attr("type", "checkbox")
+                    .prop("checked", row.selected.get())
+                    .with_node!(element => {
+                        .event(clone!(selected => move |_: events::Change| {
+                            selected.set(element.checked());
+                        }))
+                    })
+                }))
+            }))
+            .children(row.cells.iter().map(|cell| html!("td", { .text(cell) })))
+            .child(html!("td", {
+                .apply_if(row.duplicate, |dom| dom.class(&*DUPLICATE_CELL_CLASS))
+                .text(if row.duplicate { "Ya está en la tabla" } else { "Nueva" })
+            }))
+        })
+    }
+
+    fn render_button<F>(this: &Arc<Self>, text: &str, acceptance: Acceptance, on_accept: F) -> Dom
+    where
+        F: Fn(AcceptedImport) + Clone + 'static,
+    {
+        html!("input" => HtmlInputElement, {
+            .attr("type", "button")
+            .attr("value", text)
+            .event(clone!(this => move |_: events::Click| {
+                if let Some(accepted) = this.accept(acceptance) {
+                    on_accept(accepted);
+                }
+            }))
+        })
+    }
+
+    fn render_pending<F>(this: &Arc<Self>, pending: &PendingImport, on_accept: F) -> Dom
+    where
+        F: Fn(AcceptedImport) + Clone + 'static,
+    {
+        let brokers = pending.brokers().join(", ");
+        html!("div", {
+            .child(html!("table", {
+                .child(html!("caption", {
+                    .class(&*TABLE_CAPTION)
+                    .text(&format!(
+                        "Vista previa del fichero {} de {}. Las filas con el mismo ISIN, broker y cuenta que otra de la tabla no están marcadas.",
+                        pending.result.format, brokers
+                    ))
+                }))
+                .child(html!("thead", {
+                    .class(&*TABLE_HEADER)
+                    .child(html!("tr", {
+                        .children(["Añadir", "Nombre", "ISIN / Moneda", "Broker", "Cantidad", "Valor (€)", "Estado"].iter().map(|header| {
+                            html!("th", {
+                                .attr("scope", "col")
+                                .style("background-color", "#ddd")
+                                .text(header)
+                            })
+                        }))
+                    }))
+                }))
+                .child(html!("tbody", {
+                    .children(pending.rows.iter().map(Self::render_row))
+                }))
+            }))
+            .apply_if(!pending.result.warnings.is_empty(), |dom| {
+                dom.child(html!("ul", {
+                    .class(&*WARNING_PANEL_CLASS)
+                    .children(pending.result.warnings.iter().map(|warning| {
+                        html!("li", { .text(&warning.localized()) })
+                    }))
+                }))
+            })
+            .child(Self::render_button(this, "Añadir todo", Acceptance::All, on_accept.clone()))
+            .child(Self::render_button(this, "Añadir seleccionados", Acceptance::Selected, on_accept.clone()))
+            .child(Self::render_button(
+                this,
+                &format!("Reemplazar filas de {}", brokers),
+                Acceptance::Replace,
+                on_accept,
+            ))
+            .child(html!("input" => HtmlInputElement, {
+                .attr("type", "button")
+                .attr("value", "Cancelar")
+                .event(clone!(this => move |_: events::Click| {
+                    this.pending.set(None);
+                }))
+            }))
+        })
+    }
+
+    /// `on_accept` receives the records chosen by the user.
+    pub fn render<F>(this: &Arc<Self>, on_accept: F) -> Dom
+    where
+        F: Fn(AcceptedImport) + Clone + 'static,
+    {
+        html!("section", {
+            .child_signal(this.pending.signal_cloned().map(clone!(this => move |pending| {
+                pending.map(|pending| Self::render_pending(&this, &pending, on_accept.clone()))
+            })))
+        })
+    }
+}
@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use crate::{
+    data::{Aeat720Records, Aeat721Records, BrokerInformation, FirstTxDateSource},
+    error::{ImportError, ImportWarning},
+    ledger::Ledger,
+    parsers::generic_csv::CsvMapping,
+    utils::{file_importer, generic_csv_importer, resolve_first_tx_date},
+};
+
+/// Records read from a file, ready for the AEAT 720 and 721 forms.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ImportResult {
+    /// Format of the document detected from its content, like `PDF` or `CSV`.
+    pub format: String,
+    /// Securities held abroad at the end of the year.
+    pub records: Aeat720Records,
+    /// Virtual currencies held abroad at the end of the year.
+    pub crypto_records: Aeat721Records,
+    /// Data of the file left out or changed, to review by hand.
+    pub warnings: Vec<ImportWarning>,
+}
+
+impl ImportResult {
+    /// Records the name of the imported file in the provenance of the
+    /// records.
+    pub fn set_filename(&mut self, filename: &str) {
+        for record in self.records.iter_mut() {
+            record.provenance.filename = filename.to_string();
+        }
+    }
+
+    /// Brokers of the records whose country the file does not give, like
+    /// the ones of some OFX statements, to be asked to the user.
+    pub fn brokers_without_country(&self) -> Vec<String> {
+        let mut brokers: Vec<String> = vec![];
+        for record in &self.records {
+            if record.broker.country_code.is_empty() && !brokers.contains(&record.broker.name) {
+                brokers.push(record.broker.name.clone());
+            }
+        }
+        brokers
+    }
+
+    /// Sets `country_code` as the country of the records of the broker
+    /// `name` without country.
+    pub fn set_broker_country(&mut self, name: &str, country_code: &str) {
+        let broker = Arc::new(BrokerInformation::new(
+            name.to_string(),
+            country_code.trim().to_uppercase(),
+        ));
+        for record in self.records.iter_mut() {
+            if record.broker.name == name && record.broker.country_code.is_empty() {
+                record.broker = Arc::clone(&broker);
+            }
+        }
+    }
+}
+
+/// Imports several files of the same declarant, keeping the trades history
+/// between them to find the first acquisition dates, like a previous AEAT
+/// 720 form imported together with the broker reports.
+#[derive(Debug)]
+pub struct Importer {
+    ledger: Ledger,
+    year: usize,
+}
+
+impl Importer {
+    /// Importer of the files of the declared `year`.
+    pub fn new(year: usize) -> Self {
+        Self {
+            ledger: Ledger::new(),
+            year,
+        }
+    }
+
+    /// Reads a broker report, crypto exchange export or previous AEAT 720
+    /// form, the format is detected from its content.
+    pub fn import(&mut self, content: &[u8]) -> Result<ImportResult, ImportError> {
+        file_importer(content.to_vec(), &mut self.ledger, self.year)
+    }
+
+    /// Reads the CSV of a broker without parser, with the columns of
+    /// `mapping`.
+    pub fn import_csv(
+        &mut self,
+        content: &[u8],
+        mapping: &CsvMapping,
+    ) -> Result<ImportResult, ImportError> {
+        generic_csv_importer(content.to_vec(), mapping, &mut self.ledger, self.year)
+    }
+
+    /// Updates the first acquisition dates of records imported before the
+    /// files that have their trades.
+    pub fn update_first_tx_dates(&self, records: &mut Aeat720Records) {
+        for record in records.iter_mut() {
+            if record.first_tx_date_source == FirstTxDateSource::Manual {
+                continue;
+            }
+            let (first_tx_date, source) = resolve_first_tx_date(
+                &record.company,
+                &record.broker,
+                &record.account,
+                &[],
+                &self.ledger,
+                self.year,
+            );
+            if source != FirstTxDateSource::Default {
+                record.first_tx_date = first_tx_date;
+                record.first_tx_date_source = source;
+            }
+        }
+    }
+}
+
+/// Reads a single file of the declared `year`, see [`Importer::import`].
+pub fn import(content: &[u8], year: usize) -> Result<ImportResult, ImportError> {
+    Importer::new(year).import(content)
+}
@@ -0,0 +1,846 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::{Bound, RangeBounds},
+    sync::Arc,
+};
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::data::{
+    AccountNote, AccountNotes, Aeat720Declaration, Aeat720Declarations, BrokerInformation,
+    BrokerOperation, CompanyInfo, CorporateActionKind, CorporateActionNote, CorporateActionNotes,
+    FirstTxDateSource,
+};
+
+/// Position obtained replaying the account notes of a security at a given date.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LedgerPosition {
+    pub company: CompanyInfo,
+    pub broker: Arc<BrokerInformation>,
+    pub account: String,
+    pub quantity: Decimal,
+    /// First acquisition of the current holding period in the trades
+    /// history, `None` while the position is closed.
+    pub first_tx_date: Option<NaiveDate>,
+    /// Shares sold beyond the trades history, bought before its oldest
+    /// report. How many of them are still held is unknown.
+    pub unknown_quantity: Decimal,
+    /// Last time the position was fully sold before the current holding period.
+    pub closed_at: Option<NaiveDate>,
+}
+
+/// Trade history and previous declarations accumulated from every imported
+/// report, kept between sessions to resolve first acquisition dates.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Ledger {
+    notes: AccountNotes,
+    #[serde(default)]
+    declarations: Aeat720Declarations,
+    #[serde(default)]
+    corporate_actions: CorporateActionNotes,
+}
+
+type PositionKey = (String, String, String);
+
+enum LedgerEvent<'a> {
+    Trade(&'a AccountNote),
+    CorporateAction(&'a CorporateActionNote),
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn position_key(
+        company: &CompanyInfo,
+        broker: &BrokerInformation,
+        account: &str,
+    ) -> PositionKey {
+        let id = if company.isin.is_empty() {
+            company.name.clone()
+        } else {
+            company.isin.clone()
+        };
+        (id, broker.name.clone(), account.to_string())
+    }
+
+    /// ISINs already known of the securities, by their name and broker.
+    fn known_isins(&self) -> HashMap<(&str, &BrokerInformation), &str> {
+        let mut isins = HashMap::new();
+        for note in self
+            .notes
+            .iter()
+            .filter(|note| !note.company.isin.is_empty())
+        {
+            isins
+                .entry((note.company.name.as_str(), note.broker.as_ref()))
+                .or_insert(note.company.isin.as_str());
+        }
+        isins
+    }
+
+    /// Gives the ISIN of `company` to its notes at `broker` without it, the
+    /// ones of the reports only naming the security by its symbol.
+    pub fn assign_isin(&mut self, company: &CompanyInfo, broker: &BrokerInformation) {
+        for note in self.notes.iter_mut() {
+            if note.company.isin.is_empty()
+                && note.company.name == company.name
+                && *note.broker == *broker
+            {
+                note.company.isin = company.isin.clone();
+            }
+        }
+    }
+
+    /// Adds the notes of a report to the ledger.
+    ///
+    /// Reports from consecutive years usually overlap, so a note is only added
+    /// when the report contains it more times than the ledger already does.
+    /// Notes without ISIN take the one already known of their security.
+    pub fn add_notes(&mut self, notes: &[AccountNote]) {
+        let known_isins = self.known_isins();
+        let notes = notes
+            .iter()
+            .map(|note| {
+                let mut note = note.clone();
+                if note.company.isin.is_empty()
+                    && let Some(isin) =
+                        known_isins.get(&(note.company.name.as_str(), note.broker.as_ref()))
+                {
+                    note.company.isin = isin.to_string();
+                }
+                note
+            })
+            .collect::<Vec<_>>();
+
+        let mut in_report: HashMap<&AccountNote, usize> = HashMap::new();
+        for note in notes.iter() {
+            *in_report.entry(note).or_default() += 1;
+        }
+        let mut in_ledger: HashMap<&AccountNote, usize> = HashMap::new();
+        for note in self.notes.iter() {
+            *in_ledger.entry(note).or_default() += 1;
+        }
+        let mut new_notes = vec![];
+        for note in notes.iter() {
+            let known = in_ledger.entry(note).or_default();
+            if *known < in_report[note] {
+                *known += 1;
+                new_notes.push(note.clone());
+            }
+        }
+
+        self.notes.extend(new_notes);
+        self.notes.sort_by_key(|note| note.date);
+    }
+
+    /// Adds the positions of a previously submitted AEAT 720 form.
+    pub fn add_declarations(&mut self, declarations: &[Aeat720Declaration]) {
+        for declaration in declarations {
+            if !self.declarations.contains(declaration) {
+                self.declarations.push(declaration.clone());
+            }
+        }
+    }
+
+    pub fn add_corporate_actions(&mut self, actions: &[CorporateActionNote]) {
+        for action in actions {
+            if !self.corporate_actions.contains(action) {
+                self.corporate_actions.push(action.clone());
+            }
+        }
+        self.corporate_actions.sort_by_key(|action| action.date);
+    }
+
+    fn position_entry<'a>(
+        positions: &'a mut BTreeMap<PositionKey, LedgerPosition>,
+        company: &CompanyInfo,
+        broker: &Arc<BrokerInformation>,
+        account: &str,
+    ) -> &'a mut LedgerPosition {
+        positions
+            .entry(Self::position_key(company, broker, account))
+            .or_insert_with(|| LedgerPosition {
+                company: company.clone(),
+                broker: Arc::clone(broker),
+                account: account.to_string(),
+                quantity: Decimal::ZERO,
+                first_tx_date: None,
+                unknown_quantity: Decimal::ZERO,
+                closed_at: None,
+            })
+    }
+
+    fn apply_trade(positions: &mut BTreeMap<PositionKey, LedgerPosition>, note: &AccountNote) {
+        let position = Self::position_entry(positions, &note.company, &note.broker, &note.account);
+
+        match note.operation {
+            BrokerOperation::Buy => {
+                if position.quantity.is_zero() {
+                    position.first_tx_date = Some(note.date);
+                }
+                position.quantity += note.quantity;
+            }
+            BrokerOperation::Sell => {
+                position.quantity -= note.quantity;
+                if position.quantity.is_zero() {
+                    position.first_tx_date = None;
+                    position.closed_at = Some(note.date);
+                } else if position.quantity.is_sign_negative() {
+                    // Selling more than we know of, the position was
+                    // opened before the oldest imported report. It is not
+                    // known to be closed, so previous declarations still
+                    // apply to it.
+                    position.unknown_quantity -= position.quantity;
+                    position.quantity = Decimal::ZERO;
+                    position.first_tx_date = None;
+                }
+            }
+        }
+    }
+
+    /// Moves the shares of `action.company` into the security received,
+    /// which inherits the acquisition date of the original position.
+    fn transfer_position(
+        positions: &mut BTreeMap<PositionKey, LedgerPosition>,
+        action: &CorporateActionNote,
+        new_company: &CompanyInfo,
+        ratio: Decimal,
+        keep_original: bool,
+    ) {
+        let original =
+            Self::position_entry(positions, &action.company, &action.broker, &action.account);
+        if original.quantity.is_zero() {
+            log::debug!(
+                "Corporate action for {} without known position",
+                action.company.isin
+            );
+            return;
+        }
+        let quantity = original.quantity * ratio;
+        let first_tx_date = original.first_tx_date;
+        let closed_at = original.closed_at;
+        if !keep_original {
+            original.quantity = Decimal::ZERO;
+            original.first_tx_date = None;
+            original.closed_at = Some(action.date);
+        }
+
+        let position =
+            Self::position_entry(positions, new_company, &action.broker, &action.account);
+        if position.quantity.is_zero() {
+            position.first_tx_date = first_tx_date;
+            position.closed_at = closed_at;
+        } else {
+            position.first_tx_date = match (position.first_tx_date, first_tx_date) {
+                (Some(current), Some(inherited)) => Some(current.min(inherited)),
+                (current, inherited) => current.or(inherited),
+            };
+        }
+        position.quantity += quantity;
+    }
+
+    fn apply_corporate_action(
+        positions: &mut BTreeMap<PositionKey, LedgerPosition>,
+        action: &CorporateActionNote,
+    ) {
+        match &action.kind {
+            CorporateActionKind::Split { ratio } => {
+                let position = Self::position_entry(
+                    positions,
+                    &action.company,
+                    &action.broker,
+                    &action.account,
+                );
+                position.quantity *= ratio;
+            }
+            CorporateActionKind::IsinChange { new_company, ratio }
+            | CorporateActionKind::Merger { new_company, ratio } => {
+                Self::transfer_position(positions, action, new_company, *ratio, false);
+            }
+            CorporateActionKind::SpinOff { new_company, ratio } => {
+                Self::transfer_position(positions, action, new_company, *ratio, true);
+            }
+        }
+    }
+
+    /// Notes and corporate actions of the dates in `dates`, in the order
+    /// they are applied.
+    fn events(&self, dates: impl RangeBounds<NaiveDate>) -> Vec<LedgerEvent<'_>> {
+        let mut events: Vec<(NaiveDate, LedgerEvent<'_>)> = self
+            .notes
+            .iter()
+            .map(|note| (note.date, LedgerEvent::Trade(note)))
+            .chain(
+                self.corporate_actions
+                    .iter()
+                    .map(|action| (action.date, LedgerEvent::CorporateAction(action))),
+            )
+            .filter(|(event_date, _)| dates.contains(event_date))
+            .collect();
+        // Stable sort, trades of a day are applied before its corporate actions.
+        events.sort_by_key(|(event_date, _)| *event_date);
+
+        events.into_iter().map(|(_, event)| event).collect()
+    }
+
+    /// Replays all the notes and corporate actions until `date` (included)
+    /// and returns the positions still open, sorted by ISIN.
+    pub fn positions_at(&self, date: NaiveDate) -> Vec<LedgerPosition> {
+        self.replay(date)
+            .into_values()
+            .filter(|position| position.quantity > Decimal::ZERO)
+            .collect()
+    }
+
+    /// Positions with more shares sold until `date` (included) than the
+    /// trades history shows bought, sorted by ISIN. They were held before
+    /// the oldest imported report, so their quantity may be wrong.
+    pub fn incomplete_positions_at(&self, date: NaiveDate) -> Vec<LedgerPosition> {
+        self.replay(date)
+            .into_values()
+            .filter(|position| position.unknown_quantity > Decimal::ZERO)
+            .collect()
+    }
+
+    fn replay(&self, date: NaiveDate) -> BTreeMap<PositionKey, LedgerPosition> {
+        let mut positions: BTreeMap<PositionKey, LedgerPosition> = BTreeMap::new();
+
+        for event in self.events(..=date) {
+            match event {
+                LedgerEvent::Trade(note) => Self::apply_trade(&mut positions, note),
+                LedgerEvent::CorporateAction(action) => {
+                    Self::apply_corporate_action(&mut positions, action)
+                }
+            }
+        }
+
+        positions
+    }
+
+    /// Position of a security in a broker account at `date`, if still open.
+    pub fn position_at(
+        &self,
+        company: &CompanyInfo,
+        broker: &BrokerInformation,
+        account: &str,
+        date: NaiveDate,
+    ) -> Option<LedgerPosition> {
+        let key = Self::position_key(company, broker, account);
+        self.positions_at(date).into_iter().find(|position| {
+            Self::position_key(&position.company, &position.broker, &position.account) == key
+        })
+    }
+
+    /// ISIN of a security and of every security it comes from through
+    /// ISIN changes, mergers or spin-offs until `date`.
+    fn predecessors(&self, isin: &str, date: NaiveDate) -> Vec<String> {
+        let mut result = vec![isin.to_string()];
+        let mut index = 0;
+
+        while index < result.len() {
+            for action in self.corporate_actions.iter() {
+                if action.date <= date
+                    && action
+                        .new_company()
+                        .is_some_and(|new_company| new_company.isin == result[index])
+                    && !result.contains(&action.company.isin)
+                {
+                    result.push(action.company.isin.clone());
+                }
+            }
+            index += 1;
+        }
+
+        result
+    }
+
+    /// Whether `declaration` can be of the position held at `broker` in
+    /// `account`. The forms do not name the broker but the country of the
+    /// depository entity, and the account when it was declared.
+    fn same_depository(
+        declaration: &Aeat720Declaration,
+        broker: &BrokerInformation,
+        account: &str,
+    ) -> bool {
+        let matches = |declared: &str, held: &str| {
+            declared.is_empty() || held.is_empty() || declared.eq_ignore_ascii_case(held)
+        };
+        matches(&declaration.country_code, &broker.country_code)
+            && matches(&declaration.account, account)
+    }
+
+    /// Whether the securities of `declaration` are still held at `date`,
+    /// replaying from the declared quantity the trades and corporate actions
+    /// of the position after the declared year. Selling them all closes the
+    /// holding period the declaration is of.
+    fn declared_shares_held(
+        &self,
+        declaration: &Aeat720Declaration,
+        isins: &[String],
+        broker: &BrokerInformation,
+        account: &str,
+        date: NaiveDate,
+    ) -> bool {
+        let Some(mut quantity) = declaration.quantity else {
+            return true;
+        };
+        let Some(year_end) = NaiveDate::from_ymd_opt(declaration.year as i32, 12, 31) else {
+            return true;
+        };
+        let same_position = |company: &CompanyInfo, held_at: &BrokerInformation, held_in: &str| {
+            isins.contains(&company.isin) && held_at.name == broker.name && held_in == account
+        };
+
+        if quantity <= Decimal::ZERO {
+            return false;
+        }
+        for event in self.events((Bound::Excluded(year_end), Bound::Included(date))) {
+            match event {
+                LedgerEvent::Trade(note)
+                    if same_position(&note.company, &note.broker, &note.account) =>
+                {
+                    match note.operation {
+                        BrokerOperation::Buy => quantity += note.quantity,
+                        BrokerOperation::Sell => quantity -= note.quantity,
+                    }
+                }
+                LedgerEvent::CorporateAction(action)
+                    if same_position(&action.company, &action.broker, &action.account) =>
+                {
+                    match &action.kind {
+                        CorporateActionKind::Split { ratio }
+                        | CorporateActionKind::IsinChange { ratio, .. }
+                        | CorporateActionKind::Merger { ratio, .. } => quantity *= ratio,
+                        CorporateActionKind::SpinOff { .. } => {}
+                    }
+                }
+                _ => {}
+            }
+            if quantity <= Decimal::ZERO {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Earliest date of the holding period of a security open at `date`.
+    ///
+    /// Trades tell when the position was opened after being fully sold, and
+    /// previous 720 forms of the same depository carry dates older than the
+    /// imported trades, unless they declare it disposed of before the current
+    /// holding period or all the securities declared were sold since.
+    pub fn first_acquisition(
+        &self,
+        company: &CompanyInfo,
+        broker: &BrokerInformation,
+        account: &str,
+        date: NaiveDate,
+    ) -> Option<(NaiveDate, FirstTxDateSource)> {
+        let position = self.position_at(company, broker, account, date);
+        let closed_at = position.as_ref().and_then(|position| position.closed_at);
+        let traded = position.and_then(|position| position.first_tx_date);
+
+        let declared = if company.isin.is_empty() {
+            None
+        } else {
+            let isins = self.predecessors(&company.isin, date);
+            self.declarations
+                .iter()
+                .filter(|declaration| isins.contains(&declaration.company.isin))
+                .filter(|declaration| Self::same_depository(declaration, broker, account))
+                .filter(|declaration| declaration.first_tx_date <= date)
+                .filter(|declaration| {
+                    closed_at.is_none_or(|closed_at| declaration.first_tx_date > closed_at)
+                })
+                .filter(|declaration| !declaration.closed)
+                .filter(|declaration| {
+                    declaration.extinction_date.is_none_or(|extinction_date| {
+                        traded.is_none_or(|traded| extinction_date >= traded)
+                    })
+                })
+                .filter(|declaration| {
+                    self.declared_shares_held(declaration, &isins, broker, account, date)
+                })
+                .map(|declaration| declaration.first_tx_date)
+                .min()
+        };
+
+        match (traded, declared) {
+            (Some(traded), Some(declared)) if declared < traded => {
+                Some((declared, FirstTxDateSource::Aeat720))
+            }
+            (Some(traded), _) => Some((traded, FirstTxDateSource::Trades)),
+            (None, Some(declared)) => Some((declared, FirstTxDateSource::Aeat720)),
+            (None, None) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn broker() -> Arc<BrokerInformation> {
+        Arc::new(BrokerInformation::new(
+            String::from("Interactive Brokers"),
+            String::from("IE"),
+        ))
+    }
+
+    fn company() -> CompanyInfo {
+        CompanyInfo {
+            name: String::from("ADYEN NV"),
+            isin: String::from("NL0012969182"),
+        }
+    }
+
+    fn note(
+        date: (i32, u32, u32),
+        operation: BrokerOperation,
+        quantity: i64,
+        broker: &Arc<BrokerInformation>,
+    ) -> AccountNote {
+        AccountNote::new(
+            NaiveDate::from_ymd_opt(date.0, date.1, date.2).unwrap(),
+            company(),
+            operation,
+            Decimal::new(quantity, 0),
+            Decimal::new(1000, 0),
+            Decimal::new(quantity * 1000, 0),
+            Decimal::ONE,
+            broker,
+        )
+    }
+
+    #[test]
+    fn test_positions_at_year_end() {
+        let broker = broker();
+        let mut ledger = Ledger::new();
+        ledger.add_notes(&[
+            note((2021, 3, 1), BrokerOperation::Buy, 10, &broker),
+            note((2022, 5, 1), BrokerOperation::Sell, 10, &broker),
+        ]);
+        ledger.add_notes(&[
+            note((2023, 2, 1), BrokerOperation::Buy, 5, &broker),
+            note((2023, 6, 1), BrokerOperation::Buy, 5, &broker),
+            note((2025, 1, 10), BrokerOperation::Sell, 10, &broker),
+        ]);
+
+        let positions = ledger.positions_at(NaiveDate::from_ymd_opt(2024, 12, 31).unwrap());
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions[0].quantity, Decimal::new(10, 0));
+        assert_eq!(
+            positions[0].first_tx_date,
+            NaiveDate::from_ymd_opt(2023, 2, 1)
+        );
+
+        let positions = ledger.positions_at(NaiveDate::from_ymd_opt(2021, 12, 31).unwrap());
+        assert_eq!(
+            positions[0].first_tx_date,
+            NaiveDate::from_ymd_opt(2021, 3, 1)
+        );
+
+        assert!(
+            ledger
+                .positions_at(NaiveDate::from_ymd_opt(2025, 12, 31).unwrap())
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_positions_per_account() {
+        let broker = broker();
+        let mut ledger = Ledger::new();
+        let mut ira = note((2022, 4, 1), BrokerOperation::Buy, 3, &broker);
+        ira.account = String::from("U2111111");
+        let mut individual = note((2021, 3, 1), BrokerOperation::Buy, 10, &broker);
+        individual.account = String::from("U1111111");
+        ledger.add_notes(&[individual, ira]);
+
+        let year_end = NaiveDate::from_ymd_opt(2022, 12, 31).unwrap();
+        let positions = ledger.positions_at(year_end);
+        assert_eq!(positions.len(), 2);
+        assert_eq!(positions[0].account, "U1111111");
+        assert_eq!(positions[0].quantity, Decimal::new(10, 0));
+        assert_eq!(positions[1].account, "U2111111");
+        assert_eq!(positions[1].quantity, Decimal::new(3, 0));
+        assert_eq!(
+            ledger.first_acquisition(&company(), &broker, "U2111111", year_end),
+            Some((
+                NaiveDate::from_ymd_opt(2022, 4, 1).unwrap(),
+                FirstTxDateSource::Trades
+            ))
+        );
+    }
+
+    #[test]
+    fn test_overlapping_reports() {
+        let broker = broker();
+        let mut ledger = Ledger::new();
+        let first = note((2023, 2, 1), BrokerOperation::Buy, 5, &broker);
+        let second = note((2023, 6, 1), BrokerOperation::Buy, 5, &broker);
+        ledger.add_notes(&[first.clone(), second.clone()]);
+        ledger.add_notes(&[second.clone(), second.clone()]);
+
+        assert_eq!(ledger.notes, vec![first, second.clone(), second]);
+    }
+
+    #[test]
+    fn test_history_starting_with_open_position() {
+        let broker = broker();
+        let mut ledger = Ledger::new();
+        ledger.add_notes(&[
+            note((2023, 2, 1), BrokerOperation::Sell, 5, &broker),
+            note((2023, 6, 1), BrokerOperation::Buy, 3, &broker),
+        ]);
+
+        let position = ledger
+            .position_at(
+                &company(),
+                &broker,
+                "",
+                NaiveDate::from_ymd_opt(2023, 12, 31).unwrap(),
+            )
+            .unwrap();
+        assert_eq!(position.quantity, Decimal::new(3, 0));
+        assert_eq!(position.first_tx_date, NaiveDate::from_ymd_opt(2023, 6, 1));
+        assert_eq!(position.unknown_quantity, Decimal::new(5, 0));
+
+        let incomplete =
+            ledger.incomplete_positions_at(NaiveDate::from_ymd_opt(2023, 12, 31).unwrap());
+        assert_eq!(incomplete, vec![position]);
+        assert!(
+            ledger
+                .incomplete_positions_at(NaiveDate::from_ymd_opt(2022, 12, 31).unwrap())
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_corporate_actions() {
+        let broker = broker();
+        let new_company = CompanyInfo {
+            name: String::from("ADYEN NV NEW"),
+            isin: String::from("NL0000000001"),
+        };
+        let spun_company = CompanyInfo {
+            name: String::from("ADYEN SPIN"),
+            isin: String::from("NL0000000002"),
+        };
+        let mut ledger = Ledger::new();
+        ledger.add_notes(&[note((2021, 3, 1), BrokerOperation::Buy, 10, &broker)]);
+        ledger.add_corporate_actions(&[
+            CorporateActionNote::new(
+                NaiveDate::from_ymd_opt(2022, 1, 10).unwrap(),
+                company(),
+                CorporateActionKind::Split {
+                    ratio: Decimal::new(4, 0),
+                },
+                &broker,
+            ),
+            CorporateActionNote::new(
+                NaiveDate::from_ymd_opt(2023, 5, 2).unwrap(),
+                company(),
+                CorporateActionKind::IsinChange {
+                    new_company: new_company.clone(),
+                    ratio: Decimal::ONE,
+                },
+                &broker,
+            ),
+            CorporateActionNote::new(
+                NaiveDate::from_ymd_opt(2024, 7, 1).unwrap(),
+                new_company.clone(),
+                CorporateActionKind::SpinOff {
+                    new_company: spun_company.clone(),
+                    ratio: Decimal::new(5, 1),
+                },
+                &broker,
+            ),
+        ]);
+
+        let positions = ledger.positions_at(NaiveDate::from_ymd_opt(2024, 12, 31).unwrap());
+        let first_tx_date = NaiveDate::from_ymd_opt(2021, 3, 1);
+        assert_eq!(positions.len(), 2);
+        assert_eq!(positions[0].company, new_company);
+        assert_eq!(positions[0].quantity, Decimal::new(40, 0));
+        assert_eq!(positions[0].first_tx_date, first_tx_date);
+        assert_eq!(positions[1].company, spun_company);
+        assert_eq!(positions[1].quantity, Decimal::new(20, 0));
+        assert_eq!(positions[1].first_tx_date, first_tx_date);
+    }
+
+    #[test]
+    fn test_declared_before_isin_change() {
+        let broker = broker();
+        let new_company = CompanyInfo {
+            name: String::from("ADYEN NV NEW"),
+            isin: String::from("NL0000000001"),
+        };
+        let mut ledger = Ledger::new();
+        ledger.add_declarations(&[declaration(2022, (2018, 4, 5))]);
+        ledger.add_corporate_actions(&[CorporateActionNote::new(
+            NaiveDate::from_ymd_opt(2023, 5, 2).unwrap(),
+            company(),
+            CorporateActionKind::IsinChange {
+                new_company: new_company.clone(),
+                ratio: Decimal::ONE,
+            },
+            &broker,
+        )]);
+
+        assert_eq!(
+            ledger.first_acquisition(
+                &new_company,
+                &broker,
+                "",
+                NaiveDate::from_ymd_opt(2024, 12, 31).unwrap()
+            ),
+            Some((
+                NaiveDate::from_ymd_opt(2018, 4, 5).unwrap(),
+                FirstTxDateSource::Aeat720
+            ))
+        );
+    }
+
+    fn declaration(year: usize, date: (i32, u32, u32)) -> Aeat720Declaration {
+        Aeat720Declaration {
+            year,
+            company: company(),
+            country_code: String::from("IE"),
+            account: String::new(),
+            first_tx_date: NaiveDate::from_ymd_opt(date.0, date.1, date.2).unwrap(),
+            closed: false,
+            extinction_date: None,
+            quantity: None,
+        }
+    }
+
+    #[test]
+    fn test_first_acquisition() {
+        let broker = broker();
+        let year_end = NaiveDate::from_ymd_opt(2024, 12, 31).unwrap();
+        let mut ledger = Ledger::new();
+        ledger.add_notes(&[note((2024, 6, 1), BrokerOperation::Buy, 5, &broker)]);
+
+        assert_eq!(
+            ledger.first_acquisition(&company(), &broker, "", year_end),
+            Some((
+                NaiveDate::from_ymd_opt(2024, 6, 1).unwrap(),
+                FirstTxDateSource::Trades
+            ))
+        );
+
+        // Declarations of other depositories are not of this position
+        let mut other_country = declaration(2022, (2018, 1, 2));
+        other_country.country_code = String::from("DE");
+        let mut other_account = declaration(2022, (2017, 1, 2));
+        other_account.account = String::from("U9999999");
+        ledger.add_declarations(&[
+            declaration(2023, (2019, 3, 4)),
+            other_country,
+            other_account,
+        ]);
+        assert_eq!(
+            ledger.first_acquisition(&company(), &broker, "", year_end),
+            Some((
+                NaiveDate::from_ymd_opt(2017, 1, 2).unwrap(),
+                FirstTxDateSource::Aeat720
+            ))
+        );
+        assert_eq!(
+            ledger.first_acquisition(&company(), &broker, "U2111111", year_end),
+            Some((
+                NaiveDate::from_ymd_opt(2019, 3, 4).unwrap(),
+                FirstTxDateSource::Aeat720
+            ))
+        );
+
+        // Fully selling a known position discards the older declarations
+        ledger.add_notes(&[
+            note((2024, 1, 10), BrokerOperation::Buy, 5, &broker),
+            note((2024, 2, 1), BrokerOperation::Sell, 5, &broker),
+        ]);
+        assert_eq!(
+            ledger.first_acquisition(&company(), &broker, "", year_end),
+            Some((
+                NaiveDate::from_ymd_opt(2024, 6, 1).unwrap(),
+                FirstTxDateSource::Trades
+            ))
+        );
+    }
+
+    #[test]
+    fn test_first_acquisition_selling_undeclared_shares() {
+        let broker = broker();
+        let year_end = NaiveDate::from_ymd_opt(2023, 12, 31).unwrap();
+        let mut ledger = Ledger::new();
+        let mut declared = declaration(2022, (2019, 4, 1));
+        declared.quantity = Some(Decimal::new(20, 0));
+        ledger.add_declarations(&[declared]);
+        // The shares sold were bought before the oldest imported report
+        ledger.add_notes(&[
+            note((2023, 2, 1), BrokerOperation::Sell, 10, &broker),
+            note((2023, 6, 1), BrokerOperation::Buy, 5, &broker),
+        ]);
+
+        assert_eq!(
+            ledger.first_acquisition(&company(), &broker, "", year_end),
+            Some((
+                NaiveDate::from_ymd_opt(2019, 4, 1).unwrap(),
+                FirstTxDateSource::Aeat720
+            ))
+        );
+
+        // Selling all the shares declared closes the declared position
+        let mut ledger = Ledger::new();
+        let mut declared = declaration(2022, (2019, 4, 1));
+        declared.quantity = Some(Decimal::new(10, 0));
+        ledger.add_declarations(&[declared]);
+        ledger.add_notes(&[
+            note((2023, 2, 1), BrokerOperation::Sell, 10, &broker),
+            note((2023, 6, 1), BrokerOperation::Buy, 5, &broker),
+        ]);
+
+        assert_eq!(
+            ledger.first_acquisition(&company(), &broker, "", year_end),
+            Some((
+                NaiveDate::from_ymd_opt(2023, 6, 1).unwrap(),
+                FirstTxDateSource::Trades
+            ))
+        );
+    }
+
+    #[test]
+    fn test_first_acquisition_disposed_declarations() {
+        let broker = broker();
+        let year_end = NaiveDate::from_ymd_opt(2024, 12, 31).unwrap();
+        let bought = Some((
+            NaiveDate::from_ymd_opt(2024, 6, 1).unwrap(),
+            FirstTxDateSource::Trades,
+        ));
+        let mut ledger = Ledger::new();
+        ledger.add_notes(&[note((2024, 6, 1), BrokerOperation::Buy, 5, &broker)]);
+
+        let mut closed = declaration(2022, (2018, 1, 2));
+        closed.closed = true;
+        closed.extinction_date = NaiveDate::from_ymd_opt(2022, 9, 1);
+        ledger.add_declarations(&[closed]);
+        assert_eq!(
+            ledger.first_acquisition(&company(), &broker, "", year_end),
+            bought
+        );
+
+        let mut extinguished = declaration(2023, (2019, 1, 2));
+        extinguished.extinction_date = NaiveDate::from_ymd_opt(2023, 3, 1);
+        ledger.add_declarations(&[extinguished]);
+        assert_eq!(
+            ledger.first_acquisition(&company(), &broker, "", year_end),
+            bought
+        );
+    }
+}
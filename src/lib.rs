@@ -7,19 +7,74 @@
 )]
 #![allow(clippy::inconsistent_digit_grouping)]
 //! helps with burocracy
+//!
+//! Besides the browser application, the crate can be used as a library to
+//! read broker reports and generate the AEAT forms:
+//!
+//! ```no_run
+//! use burocratin::{
+//!     data::{Aeat720Information, PersonalInformation},
+//!     import,
+//!     reports::aeat_720::Aeat720Report,
+//! };
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let result = import(&std::fs::read("ib.html")?, 2023)?;
+//! let form = Aeat720Report::new(&Aeat720Information {
+//!     records: result.records,
+//!     personal_info: PersonalInformation::default(),
+//! })?
+//! .generate()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`reports::aeat_720::Aeat720Report::new`] fails on the records valued at
+//! zero and on the ones without a valid ISIN, which earlier versions wrote
+//! to the form. [`reports::aeat_720::Aeat720Report::new_accepting_unvalued`]
+//! still accepts the records valued at zero.
+//!
+//! The browser application sits behind the `web` feature, enabled by
+//! default, so the library builds on native targets without it.
 
+#[cfg(feature = "web")]
 use app::App;
+#[cfg(feature = "web")]
 use wasm_bindgen::prelude::*;
 
+#[cfg(feature = "web")]
 mod app;
+// Only for the burocratin binary, not part of the library API.
+#[doc(hidden)]
+pub mod cli;
+#[cfg(feature = "web")]
+mod crypto_table;
+#[cfg(feature = "web")]
 mod css;
-mod data;
-mod parsers;
+#[cfg(feature = "web")]
+mod csv_importer;
+pub mod data;
+mod error;
+#[cfg(feature = "web")]
+mod import_preview;
+mod importer;
+mod ledger;
+pub mod parsers;
+#[cfg(feature = "web")]
 mod personal_info;
-mod reports;
+#[cfg(feature = "web")]
+mod project;
+pub mod reports;
+#[cfg(feature = "web")]
 mod table;
 mod utils;
+#[cfg(feature = "web")]
+mod wealth_tax;
 
+pub use error::{ImportError, ImportWarning, Location, RowIssue};
+pub use importer::{ImportResult, Importer, import};
+
+#[cfg(feature = "web")]
 #[wasm_bindgen(start)]
 /// Main entry point for burocratin app
 pub async fn main_js() -> Result<(), JsValue> {
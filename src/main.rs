@@ -0,0 +1,13 @@
+//! Command line tool generating the AEAT 720 form from the broker reports.
+
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    match burocratin::cli::run(std::env::args().skip(1)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {:#}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
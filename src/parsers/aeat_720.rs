@@ -0,0 +1,167 @@
+use std::str::FromStr;
+
+use anyhow::{Result, bail};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use crate::data::{Aeat720Declaration, Aeat720Declarations, CompanyInfo};
+
+pub(crate) const AEAT_720_FILE_BEGIN: &str = "1720";
+
+/// Parser of already submitted AEAT 720 forms, used to recover the first
+/// acquisition date of the securities declared in previous years.
+#[derive(Debug)]
+pub struct Aeat720Parser {
+    content: String,
+}
+
+impl Aeat720Parser {
+    const REGISTER_SIZE: usize = 500;
+    const DETAIL_REGISTER_TYPE: char = '2';
+    /// Asset types of the securities and of the shares of collective
+    /// investment institutions.
+    const ASSET_TYPES_SECURITIES: [char; 2] = ['V', 'I'];
+    /// Acquisition type of the positions disposed of during the year.
+    const DISPOSED_ACQUISITION_TYPE: &'static str = "C";
+    /// Value of the dates not declared.
+    const EMPTY_DATE: &'static str = "00000000";
+
+    // Field positions as in reports::aeat_720, 1-based and inclusive.
+    const YEAR_FIELD: (usize, usize) = (5, 8);
+    const ASSET_TYPE_FIELD: (usize, usize) = (102, 102);
+    const COUNTRY_CODE_FIELD: (usize, usize) = (129, 130);
+    const STOCK_ID_FIELD: (usize, usize) = (132, 143);
+    const ACCOUNT_CODE_FIELD: (usize, usize) = (156, 189);
+    const ENTITY_NAME_FIELD: (usize, usize) = (190, 230);
+    const FIRST_ACQUISITION_DATE_FIELD: (usize, usize) = (415, 422);
+    const ACQUISITION_TYPE_FIELD: (usize, usize) = (423, 423);
+    const EXTINCTION_DATE_FIELD: (usize, usize) = (424, 431);
+    const STOCK_QUANTITY_INT_FIELD: (usize, usize) = (463, 472);
+    const STOCK_QUANTITY_FRACTION_FIELD: (usize, usize) = (473, 474);
+
+    fn field(register: &[char], field: (usize, usize)) -> String {
+        register[field.0 - 1..field.1]
+            .iter()
+            .collect::<String>()
+            .trim()
+            .to_string()
+    }
+
+    fn parse_declaration(register: &[char]) -> Result<Aeat720Declaration> {
+        let year = Self::field(register, Self::YEAR_FIELD).parse::<usize>()?;
+        let first_tx_date = NaiveDate::parse_from_str(
+            &Self::field(register, Self::FIRST_ACQUISITION_DATE_FIELD),
+            "%Y%m%d",
+        )?;
+        let extinction_date = match Self::field(register, Self::EXTINCTION_DATE_FIELD).as_str() {
+            "" | Self::EMPTY_DATE => None,
+            date => Some(NaiveDate::parse_from_str(date, "%Y%m%d")?),
+        };
+        let quantity = Decimal::from_str(&format!(
+            "{}.{}",
+            Self::field(register, Self::STOCK_QUANTITY_INT_FIELD),
+            Self::field(register, Self::STOCK_QUANTITY_FRACTION_FIELD)
+        ))?;
+
+        Ok(Aeat720Declaration {
+            year,
+            company: CompanyInfo {
+                name: Self::field(register, Self::ENTITY_NAME_FIELD),
+                isin: Self::field(register, Self::STOCK_ID_FIELD),
+            },
+            country_code: Self::field(register, Self::COUNTRY_CODE_FIELD),
+            account: Self::field(register, Self::ACCOUNT_CODE_FIELD),
+            first_tx_date,
+            closed: Self::field(register, Self::ACQUISITION_TYPE_FIELD)
+                == Self::DISPOSED_ACQUISITION_TYPE,
+            extinction_date,
+            quantity: Some(quantity),
+        })
+    }
+
+    /// Positions declared in the form, with their first acquisition dates.
+    pub fn parse_declarations(&self) -> Result<Aeat720Declarations> {
+        let mut result = vec![];
+
+        if !self.content.starts_with(AEAT_720_FILE_BEGIN) {
+            bail!("Not found AEAT 720 summary register");
+        }
+
+        for line in self.content.lines() {
+            let register: Vec<char> = line.chars().collect();
+            if register.len() < Self::REGISTER_SIZE {
+                log::debug!("Skipping short line in AEAT 720 form: {}", line);
+                continue;
+            }
+            if register[0] != Self::DETAIL_REGISTER_TYPE
+                || !Self::ASSET_TYPES_SECURITIES.contains(&register[Self::ASSET_TYPE_FIELD.0 - 1])
+            {
+                continue;
+            }
+            result.push(Self::parse_declaration(&register)?);
+        }
+
+        Ok(result)
+    }
+
+    /// Parser of the text of an AEAT 720 form.
+    pub fn new(content: String) -> Self {
+        Self { content }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_declarations() {
+        let content =
+            std::fs::read_to_string("tests/data/fichero-720_2019.txt").expect("720 test file");
+        let parser = Aeat720Parser::new(content);
+        let declarations = parser.parse_declarations().unwrap();
+
+        assert_eq!(declarations.len(), 17);
+        assert_eq!(
+            declarations[0],
+            Aeat720Declaration {
+                year: 2019,
+                company: CompanyInfo {
+                    name: String::from("ANGI HOMESERVICES INC- A"),
+                    isin: String::from("US00183L1026"),
+                },
+                country_code: String::from("NL"),
+                account: String::new(),
+                first_tx_date: NaiveDate::from_ymd_opt(2019, 12, 17).unwrap(),
+                closed: false,
+                extinction_date: None,
+                quantity: Some(Decimal::new(300, 0)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_disposed_declaration() {
+        let content =
+            std::fs::read_to_string("tests/data/fichero-720_2019.txt").expect("720 test file");
+        let register = content
+            .lines()
+            .find(|line| line.starts_with('2'))
+            .expect("detail register");
+        let mut register: Vec<char> = register.chars().collect();
+        register.splice(422..431, "C20190601".chars());
+
+        let declaration = Aeat720Parser::parse_declaration(&register).unwrap();
+        assert!(declaration.closed);
+        assert_eq!(
+            declaration.extinction_date,
+            NaiveDate::from_ymd_opt(2019, 6, 1)
+        );
+    }
+
+    #[test]
+    fn test_not_aeat_720_content() {
+        let parser = Aeat720Parser::new(String::from("Producto,Symbol/ISIN"));
+        assert!(parser.parse_declarations().is_err());
+    }
+}
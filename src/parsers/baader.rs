@@ -0,0 +1,254 @@
+use std::sync::Arc;
+
+use crate::data::{BalanceNote, BalanceNotes, BrokerInformation};
+use crate::error::ImportError;
+use crate::parsers::{degiro::DegiroParser, util};
+
+use anyhow::{Result, bail};
+use nom::character::complete::digit1;
+use nom::combinator::{eof, value};
+use nom::error::ErrorKind;
+use nom::multi::{many_till, many0};
+use nom::sequence::preceded;
+use nom::{
+    IResult, Parser,
+    branch::alt,
+    bytes::complete::{tag, take},
+    error::context,
+};
+
+type Res<T, U> = IResult<T, U, (T, ErrorKind)>;
+
+/// Text found in every document issued by Baader Bank, custodian of
+/// Scalable Capital and other German brokers.
+pub(crate) const BAADER_MARK: &str = "Baader Bank";
+
+const BAADER_BALANCE_HEADER_BEGIN: &str =
+    "Nominale Wertpapierbezeichnung ISIN Währung Kurs Kurswert in EUR\n";
+
+const BAADER_BALANCE_HEADER_END: &str = "Summe Kurswerte";
+
+/// Parser of the year-end securities statement ("Depotauszug") issued by
+/// Baader Bank.
+#[derive(Debug)]
+pub struct BaaderParser {
+    content: String,
+    broker: Arc<BrokerInformation>,
+}
+
+impl BaaderParser {
+    fn balance_note<'a>(
+        input: &'a str,
+        broker: &Arc<BrokerInformation>,
+    ) -> Res<&'a str, BalanceNote> {
+        log::trace!("balance note: -{}-", input);
+        context(
+            "balance note",
+            (
+                tag("STK "),
+                DegiroParser::decimal_value, // quantity
+                tag(" "),
+                DegiroParser::company_info, // company info
+                tag(" "),
+                take(3usize), // currency
+                tag(" "),
+                DegiroParser::decimal_value, // price
+                tag(" "),
+                DegiroParser::decimal_value, // value in euro
+                tag("\n"),
+            ),
+        )
+        .parse(input)
+        .map(|(next_input, res)| {
+            let (_, quantity, _, company, _, currency, _, price, _, value_in_euro, _) = res;
+            let category = util::name_asset_category(&company.name);
+            let mut note = BalanceNote::new(
+                company,
+                String::new(),
+                quantity,
+                currency.to_string(),
+                price,
+                value_in_euro,
+                broker,
+            );
+            note.category = category;
+
+            (next_input, note)
+        })
+    }
+
+    /// Page footer ("Seite 1 von 2") ending the rows of a page.
+    fn page_footer(input: &str) -> Res<&str, ()> {
+        value((), (tag("Seite "), digit1, tag(" von "), digit1, tag("\n"))).parse(input)
+    }
+
+    /// Empty lines, page footers and the table header repeated on every
+    /// page between the rows.
+    fn row_separator(input: &str) -> Res<&str, ()> {
+        value(
+            (),
+            many0(alt((
+                value((), tag("\n")),
+                BaaderParser::page_footer,
+                value((), tag(BAADER_BALANCE_HEADER_BEGIN)),
+            ))),
+        )
+        .parse(input)
+    }
+
+    fn balance_notes<'a>(
+        input: &'a str,
+        broker: &Arc<BrokerInformation>,
+    ) -> Res<&'a str, BalanceNotes> {
+        context(
+            "balance notes",
+            many_till(
+                preceded(BaaderParser::row_separator, |x| {
+                    BaaderParser::balance_note(x, broker)
+                }),
+                preceded(BaaderParser::row_separator, eof),
+            ),
+        )
+        .parse(input)
+        .map(|(next_input, (notes, _))| (next_input, notes))
+    }
+
+    fn parse_balance_notes(&self, notes: &str) -> Result<BalanceNotes> {
+        log::debug!("balance notes:-{}-", notes);
+        match BaaderParser::balance_notes(notes, &self.broker) {
+            Ok((_, notes)) => {
+                log::debug!("Ok parsing {} balance notes", notes.len());
+                Ok(notes)
+            }
+            Err(err) => {
+                bail!(util::text_row_error(&self.content, "Posiciones", err));
+            }
+        }
+    }
+
+    /// Parser of the text extracted from a Baader Bank statement.
+    pub fn new(content: String, broker: &Arc<BrokerInformation>) -> Self {
+        Self {
+            content,
+            broker: Arc::clone(broker),
+        }
+    }
+
+    /// The positions table can span several pages, each of them ending
+    /// with the page footer and beginning with the table header. Rows that
+    /// can not be read fail the import instead of ending the table.
+    pub fn parse_pdf_content(&self) -> Result<BalanceNotes> {
+        let end = self
+            .content
+            .find(BAADER_BALANCE_HEADER_END)
+            .unwrap_or(self.content.len());
+        let result = match self.content[..end].find(BAADER_BALANCE_HEADER_BEGIN) {
+            Some(begin) => self.parse_balance_notes(&self.content[begin..end])?,
+            None => vec![],
+        };
+
+        if result.is_empty() {
+            bail!(ImportError::MissingSection(String::from("Posiciones")));
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{AssetCategory, CompanyInfo};
+    use crate::error::{Location, RowIssue};
+    use rust_decimal::Decimal;
+
+    const INPUT: &str = r#"Baader Bank AG · Weihenstephaner Str. 4 · 85716 Unterschleißheim
+Depotauszug per 31.12.2023
+Nominale Wertpapierbezeichnung ISIN Währung Kurs Kurswert in EUR
+STK 12,5 iShsIII-Core MSCI World U.ETF
+Registered Shs USD (Acc) o.N. IE00B4L5Y983 EUR 80,55 1.006,88
+STK 4 Siemens AG Namens-Aktien o.N. DE0007236101 EUR 162,20 648,80
+Seite 1 von 2
+Nominale Wertpapierbezeichnung ISIN Währung Kurs Kurswert in EUR
+STK 3 Apple Inc. Registered Shares o.N. US0378331005 USD 192,53 522,88
+Summe Kurswerte 2.178,56
+"#;
+
+    #[test]
+    fn parse_pdf_content_test() {
+        let broker = Arc::new(BrokerInformation::new(
+            String::from("Baader Bank"),
+            String::from("DE"),
+        ));
+        let parser = BaaderParser::new(INPUT.to_string(), &broker);
+
+        let mut expected = vec![
+            BalanceNote::new(
+                CompanyInfo {
+                    name: String::from(
+                        "iShsIII-Core MSCI World U.ETF Registered Shs USD (Acc) o.N.",
+                    ),
+                    isin: String::from("IE00B4L5Y983"),
+                },
+                String::new(),
+                Decimal::new(125, 1),
+                String::from("EUR"),
+                Decimal::new(80_55, 2),
+                Decimal::new(1_006_88, 2),
+                &broker,
+            ),
+            BalanceNote::new(
+                CompanyInfo {
+                    name: String::from("Siemens AG Namens-Aktien o.N."),
+                    isin: String::from("DE0007236101"),
+                },
+                String::new(),
+                Decimal::new(4, 0),
+                String::from("EUR"),
+                Decimal::new(162_20, 2),
+                Decimal::new(648_80, 2),
+                &broker,
+            ),
+            BalanceNote::new(
+                CompanyInfo {
+                    name: String::from("Apple Inc. Registered Shares o.N."),
+                    isin: String::from("US0378331005"),
+                },
+                String::new(),
+                Decimal::new(3, 0),
+                String::from("USD"),
+                Decimal::new(192_53, 2),
+                Decimal::new(522_88, 2),
+                &broker,
+            ),
+        ];
+        expected[0].category = AssetCategory::Fund;
+        assert_eq!(parser.parse_pdf_content().unwrap(), expected);
+    }
+
+    #[test]
+    fn parse_malformed_row_test() {
+        let broker = Arc::new(BrokerInformation::new(
+            String::from("Baader Bank"),
+            String::from("DE"),
+        ));
+        let input = INPUT.replace("EUR 162,20 648,80", "EUR 162,2O 648,80");
+        let parser = BaaderParser::new(input, &broker);
+        let err = ImportError::from(parser.parse_pdf_content().unwrap_err());
+
+        assert_eq!(
+            err,
+            ImportError::MalformedRow {
+                location: Location::line(6).in_section("Posiciones"),
+                raw: String::from(
+                    "STK 4 Siemens AG Namens-Aktien o.N. DE0007236101 EUR 162,2O 648,80"
+                ),
+                issue: RowIssue::UnexpectedLayout,
+            }
+        );
+
+        let input = INPUT.replace("Seite 1 von 2", "Seite 1 of 2");
+        let parser = BaaderParser::new(input, &broker);
+        assert!(parser.parse_pdf_content().is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::{str::FromStr, sync::Arc};
+
+use crate::{
+    data::{BrokerInformation, CryptoNote, CryptoNotes},
+    error::{ImportError, RowContext},
+    parsers::util::is_fiat_currency,
+};
+
+/// First columns of the Binance transaction history, quoted or not depending
+/// on the export.
+pub(crate) const BINANCE_HEADER_BEGIN: &str = "User_ID,UTC_Time,Account,Operation,Coin,Change";
+
+const TIME_FIELD: &str = "UTC_Time";
+const OPERATION_FIELD: &str = "Operation";
+const COIN_FIELD: &str = "Coin";
+const CHANGE_FIELD: &str = "Change";
+
+/// Movements between the spot wallet and the earn products, the units are
+/// still held by Binance so they are not balance changes.
+const EARN_TRANSFER_OPERATIONS: [&str; 6] = [
+    "Simple Earn Flexible Subscription",
+    "Simple Earn Flexible Redemption",
+    "Simple Earn Locked Subscription",
+    "Simple Earn Locked Redemption",
+    "Staking Purchase",
+    "Staking Redemption",
+];
+
+/// Parser of the transaction history CSV exported by Binance.
+#[derive(Debug)]
+pub struct BinanceParser {
+    content: String,
+    custodian: Arc<BrokerInformation>,
+}
+
+impl BinanceParser {
+    pub(crate) fn is_binance_history(content: &str) -> bool {
+        content
+            .lines()
+            .next()
+            .is_some_and(|header| header.replace('"', "").starts_with(BINANCE_HEADER_BEGIN))
+    }
+
+    fn field(record: &csv::StringRecord, index: usize) -> &str {
+        record.get(index).unwrap_or("").trim()
+    }
+
+    fn parse_decimal(value: &str) -> Result<Decimal> {
+        Decimal::from_str(value)
+            .or_else(|_| Decimal::from_scientific(value))
+            .with_context(|| format!("Invalid change {}", value))
+    }
+
+    /// Older exports write the year with two digits.
+    fn parse_date(value: &str) -> Result<NaiveDate> {
+        let date = value.split_whitespace().next().unwrap_or(value);
+        let format = if date.len() == 8 {
+            "%y-%m-%d"
+        } else {
+            "%Y-%m-%d"
+        };
+        NaiveDate::parse_from_str(date, format).with_context(|| format!("Invalid date {}", value))
+    }
+
+    /// Balance changes of the virtual currencies held in Binance, already
+    /// signed by the export.
+    pub fn parse_crypto_notes(&self) -> Result<CryptoNotes> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(self.content.as_bytes());
+        let headers = rdr.headers()?.clone();
+        let column = |field: &str| {
+            headers
+                .iter()
+                .position(|header| header == field)
+                .ok_or_else(|| anyhow!(ImportError::MissingColumn(field.to_string())))
+        };
+        let time = column(TIME_FIELD)?;
+        let operation = column(OPERATION_FIELD)?;
+        let coin = column(COIN_FIELD)?;
+        let change = column(CHANGE_FIELD)?;
+
+        let mut crypto_notes = vec![];
+        for result in rdr.records() {
+            let record = result?;
+            log::debug!("{:?}", record);
+            let currency = BinanceParser::field(&record, coin);
+            if is_fiat_currency(currency)
+                || EARN_TRANSFER_OPERATIONS.contains(&BinanceParser::field(&record, operation))
+            {
+                continue;
+            }
+
+            crypto_notes.push(CryptoNote::new(
+                BinanceParser::parse_date(BinanceParser::field(&record, time))
+                    .record_context(&record)?,
+                currency.to_string(),
+                BinanceParser::parse_decimal(BinanceParser::field(&record, change))
+                    .record_context(&record)?,
+                &self.custodian,
+            ));
+        }
+
+        Ok(crypto_notes)
+    }
+
+    /// Parser of a Binance export held by `custodian`.
+    pub fn new(content: String, custodian: &Arc<BrokerInformation>) -> Self {
+        Self {
+            content,
+            custodian: Arc::clone(custodian),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = r#""User_ID","UTC_Time","Account","Operation","Coin","Change","Remark"
+"123456789","2023-01-12 10:00:00","Spot","Deposit","EUR","500.00000000",""
+"123456789","2023-01-12 10:01:00","Spot","Transaction Buy","BTC","0.02000000",""
+"123456789","2023-01-12 10:01:00","Spot","Transaction Spend","EUR","-320.00000000",""
+"123456789","2023-01-12 10:01:00","Spot","Transaction Fee","BTC","-0.00002000",""
+"123456789","2023-02-01 00:00:00","Spot","Simple Earn Flexible Subscription","BTC","-0.01000000",""
+"123456789","2023-02-15 00:00:00","Earn","Simple Earn Flexible Interest","BTC","0.00001000",""
+"123456789","23-03-01 12:00:00","Spot","Withdraw","BTC","-0.00500000","Withdraw fee is included"
+"#;
+
+    #[test]
+    fn parse_crypto_notes_test() {
+        let custodian = Arc::new(BrokerInformation::new(
+            String::from("Binance"),
+            String::from("LT"),
+        ));
+        assert!(BinanceParser::is_binance_history(INPUT));
+        let parser = BinanceParser::new(INPUT.to_string(), &custodian);
+        let note = |(year, month, day), quantity| {
+            CryptoNote::new(
+                NaiveDate::from_ymd_opt(year, month, day).unwrap(),
+                String::from("BTC"),
+                quantity,
+                &custodian,
+            )
+        };
+
+        assert_eq!(
+            parser.parse_crypto_notes().unwrap(),
+            vec![
+                note((2023, 1, 12), Decimal::new(2, 2)),
+                note((2023, 1, 12), Decimal::new(-2, 5)),
+                note((2023, 2, 15), Decimal::new(1, 5)),
+                note((2023, 3, 1), Decimal::new(-5, 3)),
+            ]
+        );
+    }
+}
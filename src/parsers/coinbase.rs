@@ -0,0 +1,235 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::{str::FromStr, sync::Arc};
+
+use crate::{
+    data::{BrokerInformation, CryptoNote, CryptoNotes},
+    error::{ImportError, ImportWarning, RowContext},
+    parsers::util::is_fiat_currency,
+};
+
+/// Columns of the Coinbase transaction history, newer exports prepend an
+/// `ID` column and a few lines with the user details.
+pub(crate) const COINBASE_HEADER_FIELDS: &str =
+    "Timestamp,Transaction Type,Asset,Quantity Transacted";
+
+const TIMESTAMP_FIELD: &str = "Timestamp";
+const TYPE_FIELD: &str = "Transaction Type";
+const ASSET_FIELD: &str = "Asset";
+const QUANTITY_FIELD: &str = "Quantity Transacted";
+const NOTES_FIELD: &str = "Notes";
+
+/// Transactions adding units to the Coinbase wallet.
+const INFLOW_TYPES: [&str; 9] = [
+    "Buy",
+    "Receive",
+    "Rewards Income",
+    "Staking Income",
+    "Coinbase Earn",
+    "Learning Reward",
+    "Inflation Reward",
+    "Incentives Rewards Payout",
+    "Advanced Trade Buy",
+];
+/// Transactions removing units from the Coinbase wallet.
+const OUTFLOW_TYPES: [&str; 4] = ["Sell", "Send", "Advanced Trade Sell", "Convert"];
+/// Transfers between the wallets of the Coinbase account, keeping the
+/// balance.
+const INTERNAL_TYPES: [&str; 2] = ["Retail Staking Transfer", "Retail Unstaking Transfer"];
+const CONVERT_TYPE: &str = "Convert";
+
+/// Parser of the transaction history CSV exported by Coinbase.
+#[derive(Debug)]
+pub struct CoinbaseParser {
+    content: String,
+    custodian: Arc<BrokerInformation>,
+    warnings: Vec<ImportWarning>,
+}
+
+impl CoinbaseParser {
+    fn field(record: &csv::StringRecord, index: usize) -> &str {
+        record.get(index).unwrap_or("").trim()
+    }
+
+    fn parse_quantity(value: &str) -> Result<Decimal> {
+        let value = value.replace(',', "");
+        Decimal::from_str(&value)
+            .or_else(|_| Decimal::from_scientific(&value))
+            .with_context(|| format!("Invalid quantity {}", value))
+    }
+
+    fn parse_date(value: &str) -> Result<NaiveDate> {
+        NaiveDate::parse_from_str(value.get(..10).unwrap_or(value), "%Y-%m-%d")
+            .with_context(|| format!("Invalid date {}", value))
+    }
+
+    /// Currency and quantity received in a conversion, as described by its
+    /// notes: `Converted 0.01 BTC to 0.15 ETH`.
+    fn converted_to(notes: &str) -> Option<(String, Decimal)> {
+        let (_, to) = notes.split_once(" to ")?;
+        let mut words = to.split_whitespace();
+        let quantity = CoinbaseParser::parse_quantity(words.next()?).ok()?;
+        let currency = words.next()?;
+
+        Some((currency.to_string(), quantity))
+    }
+
+    /// Balance changes of the virtual currencies held in Coinbase, fiat
+    /// deposits and withdrawals are left out. Transactions of unknown type
+    /// and conversions without the currency received are warned about.
+    pub fn parse_crypto_notes(&mut self) -> Result<CryptoNotes> {
+        let header_start = self
+            .content
+            .lines()
+            .position(|line| line.contains(COINBASE_HEADER_FIELDS))
+            .ok_or_else(|| ImportError::MissingSection(String::from("Transactions")))?;
+        let content: Vec<&str> = self.content.lines().skip(header_start).collect();
+        let content = content.join("\n");
+
+        let mut rdr = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(content.as_bytes());
+        let headers = rdr.headers()?.clone();
+        let column = |field: &str| {
+            headers
+                .iter()
+                .position(|header| header.trim() == field)
+                .ok_or_else(|| anyhow!(ImportError::MissingColumn(field.to_string())))
+        };
+        let timestamp = column(TIMESTAMP_FIELD)?;
+        let kind = column(TYPE_FIELD)?;
+        let asset = column(ASSET_FIELD)?;
+        let quantity = column(QUANTITY_FIELD)?;
+        let notes = column(NOTES_FIELD).ok();
+
+        let mut crypto_notes = vec![];
+        for result in rdr.records() {
+            let record = result?;
+            log::debug!("{:?}", record);
+            let kind = CoinbaseParser::field(&record, kind);
+            let currency = CoinbaseParser::field(&record, asset);
+            if is_fiat_currency(currency) {
+                continue;
+            }
+            let units = CoinbaseParser::parse_quantity(CoinbaseParser::field(&record, quantity))
+                .record_context(&record)?
+                .abs();
+            let units = if INFLOW_TYPES.contains(&kind) {
+                units
+            } else if OUTFLOW_TYPES.contains(&kind) {
+                -units
+            } else {
+                if !INTERNAL_TYPES.contains(&kind) {
+                    self.warnings
+                        .push(ImportWarning::unread_record(&record, header_start));
+                }
+                continue;
+            };
+            let date = CoinbaseParser::parse_date(CoinbaseParser::field(&record, timestamp))
+                .record_context(&record)?;
+
+            crypto_notes.push(CryptoNote::new(
+                date,
+                currency.to_string(),
+                units,
+                &self.custodian,
+            ));
+
+            if kind == CONVERT_TYPE {
+                match notes.and_then(|notes| {
+                    CoinbaseParser::converted_to(CoinbaseParser::field(&record, notes))
+                }) {
+                    Some((currency, _)) if is_fiat_currency(&currency) => (),
+                    Some((currency, units)) => {
+                        crypto_notes.push(CryptoNote::new(date, currency, units, &self.custodian))
+                    }
+                    None => self
+                        .warnings
+                        .push(ImportWarning::unread_record(&record, header_start)),
+                }
+            }
+        }
+
+        Ok(crypto_notes)
+    }
+
+    /// Parser of a Coinbase export held by `custodian`.
+    pub fn new(content: String, custodian: &Arc<BrokerInformation>) -> Self {
+        Self {
+            content,
+            custodian: Arc::clone(custodian),
+            warnings: vec![],
+        }
+    }
+
+    /// Rows left out or imported only in part by the last parse.
+    pub fn warnings(&self) -> &[ImportWarning] {
+        &self.warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Location;
+
+    const INPUT: &str = r#"Transactions
+User,Juan Español,1a2b3c
+ID,Timestamp,Transaction Type,Asset,Quantity Transacted,Price Currency,Price at Transaction,Subtotal,Total (inclusive of fees and/or spread),Fees and/or Spread,Notes
+a1,2023-01-12 10:00:00 UTC,Deposit,EUR,500,EUR,€1.00,€500.00,€500.00,€0.00,
+a2,2023-01-12 10:05:00 UTC,Buy,BTC,0.02,EUR,"€16,000.00",€320.00,€325.00,€5.00,Bought 0.02 BTC for 325 EUR
+a3,2023-02-01 09:00:00 UTC,Staking Income,ETH,0.001,EUR,"€1,500.00",€1.50,€1.50,€0.00,
+a4,2023-03-15 18:30:00 UTC,Convert,BTC,-0.005,EUR,"€22,000.00",€110.00,€110.00,€0.00,Converted 0.005 BTC to 0.06 ETH
+a5,2023-04-01 12:00:00 UTC,Retail Staking Transfer,ETH,0.05,EUR,"€1,700.00",€85.00,€85.00,€0.00,
+a6,2023-05-10 12:00:00 UTC,Send,BTC,0.001,EUR,"€25,000.00",€25.00,€25.00,€0.00,Sent to wallet
+a7,2023-06-01 12:00:00 UTC,Vault Withdrawal,BTC,0.002,EUR,"€25,000.00",€50.00,€50.00,€0.00,
+a8,2023-07-01 12:00:00 UTC,Convert,ETH,-0.01,EUR,"€1,800.00",€18.00,€18.00,€0.00,
+"#;
+
+    #[test]
+    fn parse_crypto_notes_test() {
+        let custodian = Arc::new(BrokerInformation::new(
+            String::from("Coinbase"),
+            String::from("IE"),
+        ));
+        let mut parser = CoinbaseParser::new(INPUT.to_string(), &custodian);
+        let note = |(year, month, day), currency: &str, quantity| {
+            CryptoNote::new(
+                NaiveDate::from_ymd_opt(year, month, day).unwrap(),
+                currency.to_string(),
+                quantity,
+                &custodian,
+            )
+        };
+
+        assert_eq!(
+            parser.parse_crypto_notes().unwrap(),
+            vec![
+                note((2023, 1, 12), "BTC", Decimal::new(2, 2)),
+                note((2023, 2, 1), "ETH", Decimal::new(1, 3)),
+                note((2023, 3, 15), "BTC", Decimal::new(-5, 3)),
+                note((2023, 3, 15), "ETH", Decimal::new(6, 2)),
+                note((2023, 5, 10), "BTC", Decimal::new(-1, 3)),
+                note((2023, 7, 1), "ETH", Decimal::new(-1, 2)),
+            ]
+        );
+        assert_eq!(
+            parser.warnings(),
+            [
+                ImportWarning::UnreadTransaction {
+                    location: Location::line(10),
+                    raw: String::from(
+                        "a7,2023-06-01 12:00:00 UTC,Vault Withdrawal,BTC,0.002,EUR,€25,000.00,€50.00,€50.00,€0.00,"
+                    ),
+                },
+                ImportWarning::UnreadTransaction {
+                    location: Location::line(11),
+                    raw: String::from(
+                        "a8,2023-07-01 12:00:00 UTC,Convert,ETH,-0.01,EUR,€1,800.00,€18.00,€18.00,€0.00,"
+                    ),
+                },
+            ]
+        );
+    }
+}
@@ -1,13 +1,15 @@
 use std::sync::Arc;
 
 use crate::data::{
-    AccountNote, AccountNotes, BalanceNote, BalanceNotes, BrokerInformation, BrokerOperation,
-    CompanyInfo,
+    AccountNote, AccountNotes, AssetCategory, BalanceNote, BalanceNotes, BrokerInformation,
+    BrokerOperation, CompanyInfo,
 };
 
+use crate::error::ImportError;
+use crate::parsers::util;
 use crate::utils::decimal;
 
-use anyhow::{Context, Result, bail};
+use anyhow::{Result, bail};
 use chrono::NaiveDate;
 use nom::character::complete::anychar;
 use nom::error::ErrorKind;
@@ -33,6 +35,8 @@ use rust_decimal::prelude::*;
 
 type Res<T, U> = IResult<T, U, (T, ErrorKind)>;
 
+/// Parser of the text extracted from the Degiro annual PDF report.
+#[derive(Debug)]
 pub struct DegiroParser {
     content: String,
     broker: Arc<BrokerInformation>,
@@ -56,7 +60,10 @@ pérdidas
 "#;
 
 impl DegiroParser {
-    fn n_to_m_digits<'b>(n: usize, m: usize) -> impl FnMut(&'b str) -> Res<&'b str, String> {
+    pub(crate) fn n_to_m_digits<'b>(
+        n: usize,
+        m: usize,
+    ) -> impl FnMut(&'b str) -> Res<&'b str, String> {
         move |input| {
             many_m_n(n, m, one_of("0123456789"))
                 .parse(input)
@@ -64,7 +71,7 @@ impl DegiroParser {
         }
     }
 
-    fn decimal_value(input: &str) -> Res<&str, Decimal> {
+    pub(crate) fn decimal_value(input: &str) -> Res<&str, Decimal> {
         context(
             "decimal value",
             map_res(
@@ -151,7 +158,7 @@ impl DegiroParser {
         .map(|(next_input, res)| (next_input, res.into()))
     }
 
-    fn isin(input: &str) -> Res<&str, String> {
+    pub(crate) fn isin(input: &str) -> Res<&str, String> {
         context(
             "isin",
             (
@@ -170,7 +177,7 @@ impl DegiroParser {
         })
     }
 
-    fn company_info(input: &str) -> Res<&str, CompanyInfo> {
+    pub(crate) fn company_info(input: &str) -> Res<&str, CompanyInfo> {
         context("company info", many_till(anychar, DegiroParser::isin))
             .parse(input)
             .map(|(next_input, res)| {
@@ -269,20 +276,21 @@ impl DegiroParser {
         )
         .parse(input)
         .map(|(next_input, res)| {
-            let (_, value_in_euro, price, currency, quantity, market, _product_type, company) = res;
+            let (_, value_in_euro, price, currency, quantity, market, product_type, company) = res;
+            let mut note = BalanceNote::new(
+                company,
+                market.to_string(),
+                quantity,
+                currency.to_string(),
+                price,
+                value_in_euro,
+                broker,
+            );
+            if product_type == "ETF" {
+                note.category = AssetCategory::Fund;
+            }
 
-            (
-                next_input,
-                BalanceNote::new(
-                    company,
-                    market.to_string(),
-                    quantity,
-                    currency.to_string(),
-                    price,
-                    value_in_euro,
-                    broker,
-                ),
-            )
+            (next_input, note)
         })
     }
 
@@ -318,7 +326,7 @@ impl DegiroParser {
                 notes
             }
             Err(err) => {
-                bail!("Unable to parse account notes: {}", err);
+                bail!(util::text_row_error(&self.content, "Operaciones", err));
             }
         };
 
@@ -334,7 +342,7 @@ impl DegiroParser {
             }
             Err(err) => {
                 log::debug!("Unable to parse balance notes:-{}-", err);
-                bail!("Unable to parse balance notes: {}", err);
+                bail!(util::text_row_error(&self.content, "Posiciones", err));
             }
         };
 
@@ -347,7 +355,7 @@ impl DegiroParser {
         let header_begin = self
             .content
             .find(DEGIRO_NOTES_HEADER_BEGIN)
-            .context("No account notes section found")?;
+            .ok_or_else(|| ImportError::MissingSection(String::from("Operaciones")))?;
 
         let header_end = match self.content.rfind(DEGIRO_NOTES_HEADER_END) {
             Some(end) => end - 1,
@@ -391,6 +399,7 @@ impl DegiroParser {
         Ok(result)
     }
 
+    /// Parser of the text extracted from a Degiro report.
     pub fn new(content: String, broker: &Arc<BrokerInformation>) -> Self {
         Self {
             content,
@@ -398,6 +407,7 @@ impl DegiroParser {
         }
     }
 
+    /// Positions at the end of the year and trades of the report.
     pub fn parse_pdf_content(&self) -> Result<(BalanceNotes, AccountNotes)> {
         let account_notes = self.parse_pdf_account_notes()?;
         let balance_notes = self.parse_pdf_balance_notes()?;
@@ -578,6 +588,12 @@ STOCK WHEN-ISSUED US36262G1013 "#;
                 )
             ))
         );
+        const ETF_NOTE: &str = r#"
+ 800,0080,0000EUR10XETETFISHARES CORE MSCI WORLD UCITS ETF IE00B4L5Y983"#;
+
+        let (_, note) = DegiroParser::balance_note(ETF_NOTE, &degiro_broker).unwrap();
+        assert_eq!(note.company.isin, "IE00B4L5Y983");
+        assert_eq!(note.category, AssetCategory::Fund);
     }
 
     #[test]
@@ -4,16 +4,22 @@ use std::{str::FromStr, sync::Arc};
 
 use crate::{
     data::{BalanceNote, BalanceNotes, BrokerInformation, CompanyInfo},
+    error::ImportWarning,
     utils::decimal,
 };
 
+/// Parser of the portfolio CSV exported by Degiro.
+#[derive(Debug)]
 pub struct DegiroCSVParser {
     content: String,
     broker: Arc<BrokerInformation>,
+    warnings: Vec<ImportWarning>,
 }
 
 impl DegiroCSVParser {
-    pub fn parse_csv(&self) -> Result<BalanceNotes> {
+    /// Positions of the portfolio, rows without ISIN like the cash are left
+    /// out.
+    pub fn parse_csv(&mut self) -> Result<BalanceNotes> {
         let mut rdr = csv::Reader::from_reader(self.content.as_bytes());
         let mut balance_notes = vec![];
 
@@ -21,6 +27,7 @@ impl DegiroCSVParser {
             let record = result?;
             log::debug!("{:?}", record);
             if record.get(1) == Some("") {
+                self.warnings.push(ImportWarning::skipped_record(&record));
                 continue;
             }
             let currency_price = record
@@ -66,17 +73,25 @@ impl DegiroCSVParser {
         Ok(balance_notes)
     }
 
+    /// Parser of a Degiro portfolio CSV.
     pub fn new(content: String, broker: &Arc<BrokerInformation>) -> Self {
         Self {
             content,
             broker: Arc::clone(broker),
+            warnings: vec![],
         }
     }
+
+    /// Rows left out while parsing.
+    pub fn warnings(&self) -> &[ImportWarning] {
+        &self.warnings
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::Location;
 
     fn compare_vectors_by_item<T>(vec1: &[T], vec2: &[T])
     where
@@ -102,7 +117,7 @@ mod tests {
             String::from("Degiro"),
             String::from("NL"),
         ));
-        let parser = DegiroCSVParser::new(INPUT_2019.to_string(), &degiro_broker);
+        let mut parser = DegiroCSVParser::new(INPUT_2019.to_string(), &degiro_broker);
         let balance_notes = parser.parse_csv().unwrap();
         let bal_notes = vec![
             BalanceNote::new(
@@ -228,6 +243,13 @@ mod tests {
         ];
 
         compare_vectors_by_item(&bal_notes, &balance_notes);
+        assert_eq!(
+            parser.warnings(),
+            [ImportWarning::SkippedRow {
+                location: Location::line(2),
+                raw: String::from("CASH & CASH FUND & FTX CASH (EUR),,,,EUR 564.19,564,19"),
+            }]
+        );
     }
 
     const INPUT_2019: &str = r#"Producto,Symbol/ISIN,Cantidad,Precio de,Valor local,Valor en EUR
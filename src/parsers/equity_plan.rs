@@ -0,0 +1,934 @@
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::{str::FromStr, sync::Arc};
+
+use crate::{
+    data::{
+        AccountNote, AccountNotes, BalanceNote, BalanceNotes, BrokerInformation, BrokerOperation,
+        CompanyInfo,
+    },
+    error::{ImportError, Location, RowContext, RowIssue},
+    parsers::util,
+};
+
+/// First columns of the Morgan Stanley StockPlan Connect releases and
+/// withdrawals reports.
+const MORGAN_STANLEY_RELEASES_HEADER_BEGIN: &str = "Vest Date,Order Number,Plan,Type";
+const MORGAN_STANLEY_WITHDRAWALS_HEADER_BEGIN: &str = "Execution Date,Order Number,Plan,Type";
+/// First columns of the Charles Schwab Equity Awards transactions export.
+const SCHWAB_HEADER_BEGIN: &str = r#""Date","Action","Symbol","Description","Quantity""#;
+/// First columns of the Fidelity NetBenefits stock plan activity export.
+const FIDELITY_HEADER_BEGIN: &str = "Run Date,Action,Symbol";
+
+/// First columns of the holdings exports, with the shares held at the date
+/// they are downloaded.
+const MORGAN_STANLEY_HOLDINGS_HEADER_BEGIN: &str = "As Of Date,Plan,Symbol,CUSIP";
+const SCHWAB_HOLDINGS_HEADER_BEGIN: &str = r#""Symbol","Description","CUSIP","Quantity""#;
+const FIDELITY_HOLDINGS_HEADER_BEGIN: &str =
+    "Account Number,Account Name,Symbol,Description,Quantity";
+const HOLDINGS_HEADERS_BEGIN: [&str; 3] = [
+    MORGAN_STANLEY_HOLDINGS_HEADER_BEGIN,
+    SCHWAB_HOLDINGS_HEADER_BEGIN,
+    FIDELITY_HOLDINGS_HEADER_BEGIN,
+];
+
+/// Text found in the year-end statements of each administrator.
+const MORGAN_STANLEY_MARK: &str = "Morgan Stanley at Work";
+const SCHWAB_MARK: &str = "Schwab Equity Award";
+const FIDELITY_MARK: &str = "Fidelity Stock Plan Services";
+
+/// Columns of the holdings table of the year-end statements, ended by its
+/// total row.
+const STATEMENT_HOLDINGS_COLUMNS: [&str; 3] = ["CUSIP", "Quantity", "Market Value"];
+const STATEMENT_HOLDINGS_END: &str = "Total";
+const STATEMENT_PAGE_FOOTER_BEGIN: &str = "Page ";
+const HOLDINGS_SECTION: &str = "Holdings";
+
+const USD_CURRENCY: &str = "USD";
+
+const DATE_FORMATS: [&str; 3] = ["%m/%d/%Y", "%d-%b-%Y", "%Y-%m-%d"];
+
+/// US stock plan administrator holding the shares vested or purchased
+/// through an employee equity plan.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EquityPlanProvider {
+    /// Morgan Stanley at Work (Shareworks).
+    MorganStanley,
+    /// Schwab Equity Award Center.
+    Schwab,
+    /// Fidelity NetBenefits.
+    Fidelity,
+}
+
+impl EquityPlanProvider {
+    /// Recognizes the provider from the header row of its CSV export.
+    pub fn detect(content: &str) -> Option<Self> {
+        let content = content.trim_start_matches('\u{feff}');
+        if content.starts_with(MORGAN_STANLEY_RELEASES_HEADER_BEGIN)
+            || content.starts_with(MORGAN_STANLEY_WITHDRAWALS_HEADER_BEGIN)
+            || content.starts_with(MORGAN_STANLEY_HOLDINGS_HEADER_BEGIN)
+        {
+            Some(EquityPlanProvider::MorganStanley)
+        } else if content.starts_with(SCHWAB_HEADER_BEGIN)
+            || content.starts_with(SCHWAB_HOLDINGS_HEADER_BEGIN)
+        {
+            Some(EquityPlanProvider::Schwab)
+        } else if content.starts_with(FIDELITY_HEADER_BEGIN)
+            || content.starts_with(FIDELITY_HOLDINGS_HEADER_BEGIN)
+        {
+            Some(EquityPlanProvider::Fidelity)
+        } else {
+            None
+        }
+    }
+
+    /// Recognizes the provider of a year-end statement from the text
+    /// extracted from its PDF.
+    pub fn detect_statement(content: &str) -> Option<Self> {
+        if !EquityPlanParser::has_statement_holdings(content) {
+            None
+        } else if content.contains(MORGAN_STANLEY_MARK) {
+            Some(EquityPlanProvider::MorganStanley)
+        } else if content.contains(SCHWAB_MARK) {
+            Some(EquityPlanProvider::Schwab)
+        } else if content.contains(FIDELITY_MARK) {
+            Some(EquityPlanProvider::Fidelity)
+        } else {
+            None
+        }
+    }
+}
+
+/// Parser of the CSV exports and year-end PDF statements of equity plan
+/// administrators. Vested and purchased shares are acquisitions at the fair
+/// market value of the vest or purchase date, only the shares deposited in
+/// the account are counted.
+#[derive(Debug)]
+pub struct EquityPlanParser {
+    content: String,
+    provider: EquityPlanProvider,
+    broker: Arc<BrokerInformation>,
+}
+
+struct Columns<'a> {
+    headers: &'a csv::StringRecord,
+}
+
+impl Columns<'_> {
+    fn find(&self, field: &str) -> Option<usize> {
+        self.headers
+            .iter()
+            .position(|header| header.trim() == field)
+    }
+
+    fn required(&self, field: &str) -> Result<usize> {
+        self.find(field)
+            .ok_or_else(|| anyhow!(ImportError::MissingColumn(field.to_string())))
+    }
+}
+
+impl EquityPlanParser {
+    fn field(record: &csv::StringRecord, index: usize) -> &str {
+        record.get(index).unwrap_or("").trim()
+    }
+
+    fn parse_decimal(value: &str) -> Result<Decimal> {
+        let value: String = value
+            .chars()
+            .filter(|c| !matches!(c, '$' | ',' | ' ' | '(' | ')'))
+            .collect();
+        if value.is_empty() {
+            return Ok(Decimal::ZERO);
+        }
+        Decimal::from_str(&value)
+            .map(|value| value.abs())
+            .with_context(|| format!("Invalid number {}", value))
+    }
+
+    fn parse_date(value: &str) -> Result<NaiveDate> {
+        DATE_FORMATS
+            .iter()
+            .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+            .with_context(|| format!("Invalid date {}", value))
+    }
+
+    /// The activity exports only give the symbol of the shares, or the plan
+    /// name in the Morgan Stanley reports, the ISIN comes from the CUSIP of
+    /// the holdings.
+    fn company(symbol: &str) -> CompanyInfo {
+        CompanyInfo {
+            name: symbol.to_string(),
+            isin: String::new(),
+        }
+    }
+
+    /// CUSIP of the US securities, or CINS of the foreign ones.
+    fn is_cusip(value: &str) -> bool {
+        value.len() == 9
+            && value.chars().all(|c| c.is_ascii_alphanumeric())
+            && value.chars().any(|c| c.is_ascii_digit())
+    }
+
+    /// Shares named `name` held at the end of the period, valued in dollars
+    /// at `price`. Their value in euros is left to the year-end rate.
+    fn balance_note(
+        &self,
+        name: &str,
+        cusip: &str,
+        quantity: &str,
+        price: &str,
+    ) -> Result<BalanceNote> {
+        let mut company = EquityPlanParser::company(name);
+        company.isin = util::cusip_to_isin(cusip).unwrap_or_default();
+        Ok(BalanceNote::new(
+            company,
+            String::new(),
+            EquityPlanParser::parse_decimal(quantity)?,
+            USD_CURRENCY.to_string(),
+            EquityPlanParser::parse_decimal(price)?,
+            Decimal::ZERO,
+            &self.broker,
+        ))
+    }
+
+    fn is_holdings_export(&self) -> bool {
+        HOLDINGS_HEADERS_BEGIN
+            .iter()
+            .any(|header| self.content.starts_with(header))
+    }
+
+    fn is_statement_holdings_header(line: &str) -> bool {
+        STATEMENT_HOLDINGS_COLUMNS
+            .iter()
+            .all(|column| line.contains(column))
+    }
+
+    fn has_statement_holdings(content: &str) -> bool {
+        content
+            .lines()
+            .any(EquityPlanParser::is_statement_holdings_header)
+    }
+
+    fn account_note(
+        &self,
+        date: NaiveDate,
+        symbol: &str,
+        operation: BrokerOperation,
+        quantity: Decimal,
+        price: Decimal,
+        commision: Decimal,
+    ) -> AccountNote {
+        AccountNote::new(
+            date,
+            EquityPlanParser::company(symbol),
+            operation,
+            quantity,
+            price,
+            (quantity * price).round_dp(2),
+            commision,
+            &self.broker,
+        )
+    }
+
+    /// Releases and withdrawals reports share their layout, being the price
+    /// the vest date FMV for releases and the sale price for withdrawals.
+    fn parse_morgan_stanley(&self) -> Result<AccountNotes> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(self.content.as_bytes());
+        let headers = rdr.headers()?.clone();
+        let columns = Columns { headers: &headers };
+        let date = columns
+            .find("Vest Date")
+            .or_else(|| columns.find("Execution Date"))
+            .ok_or_else(|| ImportError::MissingColumn(String::from("Vest Date")))?;
+        let plan = columns.required("Plan")?;
+        let kind = columns.required("Type")?;
+        let price = columns.required("Price")?;
+        let quantity = columns.required("Quantity")?;
+        let net_shares = columns.find("Net Share Proceeds");
+
+        let mut account_notes = vec![];
+        for result in rdr.records() {
+            let record = result?;
+            log::debug!("{:?}", record);
+            let kind = EquityPlanParser::field(&record, kind).to_lowercase();
+            let (operation, quantity) = if kind.contains("release") || kind.contains("purchase") {
+                let net = match net_shares {
+                    Some(index) => EquityPlanParser::field(&record, index),
+                    None => "",
+                };
+                let net = if net.is_empty() {
+                    EquityPlanParser::field(&record, quantity)
+                } else {
+                    net
+                };
+                (BrokerOperation::Buy, EquityPlanParser::parse_decimal(net)?)
+            } else if kind.contains("sale") {
+                (
+                    BrokerOperation::Sell,
+                    EquityPlanParser::parse_decimal(EquityPlanParser::field(&record, quantity))?,
+                )
+            } else {
+                continue;
+            };
+
+            if quantity.is_zero() {
+                continue;
+            }
+            account_notes.push(self.account_note(
+                EquityPlanParser::parse_date(EquityPlanParser::field(&record, date))?,
+                EquityPlanParser::field(&record, plan),
+                operation,
+                quantity,
+                EquityPlanParser::parse_decimal(EquityPlanParser::field(&record, price))?,
+                Decimal::ZERO,
+            ));
+        }
+
+        Ok(account_notes)
+    }
+
+    /// Lapses and deposits are followed by a detail row holding the award
+    /// FMV and the net shares deposited after the shares withheld for taxes.
+    fn parse_schwab(&self) -> Result<AccountNotes> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(self.content.as_bytes());
+        let headers = rdr.headers()?.clone();
+        let columns = Columns { headers: &headers };
+        let date = columns.required("Date")?;
+        let action = columns.required("Action")?;
+        let symbol = columns.required("Symbol")?;
+        let quantity = columns.required("Quantity")?;
+        let fees = columns.find("Fees & Commissions");
+        let fmv = columns.find("FairMarketValuePrice");
+        let purchase_fmv = columns.find("PurchaseFairMarketValue");
+        let sale_price = columns.find("SalePrice");
+        let net_shares = columns.find("NetSharesDeposited");
+        let amount = columns.find("Amount");
+
+        let records = rdr.records().collect::<Result<Vec<_>, _>>()?;
+        let mut account_notes = vec![];
+        for (index, record) in records.iter().enumerate() {
+            let action = EquityPlanParser::field(record, action);
+            if action.is_empty() {
+                continue;
+            }
+            log::debug!("{:?}", record);
+            let detail = records
+                .get(index + 1)
+                .filter(|next| EquityPlanParser::field(next, date).is_empty());
+            let detail_field = |column: Option<usize>| match (detail, column) {
+                (Some(detail), Some(column)) => EquityPlanParser::field(detail, column),
+                _ => "",
+            };
+
+            let gross = EquityPlanParser::parse_decimal(EquityPlanParser::field(record, quantity))?;
+            let (operation, quantity, price) = match action {
+                "Lapse" | "Deposit" => {
+                    let net = detail_field(net_shares);
+                    let quantity = if net.is_empty() {
+                        gross
+                    } else {
+                        EquityPlanParser::parse_decimal(net)?
+                    };
+                    let price = match detail_field(fmv) {
+                        "" => detail_field(purchase_fmv),
+                        value => value,
+                    };
+                    (
+                        BrokerOperation::Buy,
+                        quantity,
+                        EquityPlanParser::parse_decimal(price)?,
+                    )
+                }
+                "Sale" => {
+                    let price = match detail_field(sale_price) {
+                        "" if !gross.is_zero() => {
+                            let amount = match amount {
+                                Some(amount) => EquityPlanParser::field(record, amount),
+                                None => "",
+                            };
+                            EquityPlanParser::parse_decimal(amount)? / gross
+                        }
+                        value => EquityPlanParser::parse_decimal(value)?,
+                    };
+                    (BrokerOperation::Sell, gross, price.round_dp(4))
+                }
+                _ => continue,
+            };
+
+            if quantity.is_zero() {
+                continue;
+            }
+            let commision = match fees {
+                Some(fees) => {
+                    EquityPlanParser::parse_decimal(EquityPlanParser::field(record, fees))?
+                }
+                None => Decimal::ZERO,
+            };
+            account_notes.push(self.account_note(
+                EquityPlanParser::parse_date(EquityPlanParser::field(record, date))?,
+                EquityPlanParser::field(record, symbol),
+                operation,
+                quantity,
+                price,
+                commision,
+            ));
+        }
+
+        Ok(account_notes)
+    }
+
+    /// Activity rows whose action tells a vest, an ESPP purchase, a buy or a
+    /// sale, anything else (dividends, cash movements) is skipped.
+    fn parse_fidelity(&self) -> Result<AccountNotes> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(self.content.as_bytes());
+        let headers = rdr.headers()?.clone();
+        let columns = Columns { headers: &headers };
+        let date = columns.required("Run Date")?;
+        let action = columns.required("Action")?;
+        let symbol = columns.required("Symbol")?;
+        let quantity = columns.required("Quantity")?;
+        let price = columns.required("Price ($)")?;
+        let commision = columns.find("Commission ($)");
+
+        let mut account_notes = vec![];
+        for result in rdr.records() {
+            let record = result?;
+            log::debug!("{:?}", record);
+            let action = EquityPlanParser::field(&record, action).to_uppercase();
+            let operation = if action.contains("SOLD") || action.contains("SALE") {
+                BrokerOperation::Sell
+            } else if ["VEST", "RELEASE", "ESPP", "PURCHASE", "BOUGHT"]
+                .iter()
+                .any(|kind| action.contains(kind))
+            {
+                BrokerOperation::Buy
+            } else {
+                continue;
+            };
+
+            let quantity =
+                EquityPlanParser::parse_decimal(EquityPlanParser::field(&record, quantity))?;
+            if quantity.is_zero() {
+                continue;
+            }
+            let commision = match commision {
+                Some(commision) => {
+                    EquityPlanParser::parse_decimal(EquityPlanParser::field(&record, commision))?
+                }
+                None => Decimal::ZERO,
+            };
+            account_notes.push(self.account_note(
+                EquityPlanParser::parse_date(EquityPlanParser::field(&record, date))?,
+                EquityPlanParser::field(&record, symbol),
+                operation,
+                quantity,
+                EquityPlanParser::parse_decimal(EquityPlanParser::field(&record, price))?,
+                commision,
+            ));
+        }
+
+        Ok(account_notes)
+    }
+
+    /// Acquisitions and sales of the plan shares, with their prices in
+    /// dollars. The holdings exports give none.
+    pub fn parse_account_notes(&self) -> Result<AccountNotes> {
+        if self.is_holdings_export() {
+            return Ok(vec![]);
+        }
+        match self.provider {
+            EquityPlanProvider::MorganStanley => self.parse_morgan_stanley(),
+            EquityPlanProvider::Schwab => self.parse_schwab(),
+            EquityPlanProvider::Fidelity => self.parse_fidelity(),
+        }
+    }
+
+    /// Shares held according to the holdings exports, valued at the price of
+    /// the date they are downloaded, the end of the year when declaring it.
+    /// Rows without shares, like totals, cash or disclaimers, are skipped.
+    /// The activity exports give none.
+    pub fn parse_balance_notes(&self) -> Result<BalanceNotes> {
+        if !self.is_holdings_export() {
+            return Ok(vec![]);
+        }
+        let mut rdr = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(self.content.as_bytes());
+        let headers = rdr.headers()?.clone();
+        let columns = Columns { headers: &headers };
+        // Morgan Stanley activity names the shares after the plan
+        let name = match columns.find("Plan") {
+            Some(plan) => plan,
+            None => columns.required("Symbol")?,
+        };
+        let cusip = columns.find("CUSIP");
+        let quantity = match columns.find("Available Quantity") {
+            Some(quantity) => quantity,
+            None => columns.required("Quantity")?,
+        };
+        let price = ["Market Price", "Last Price", "Price"]
+            .iter()
+            .find_map(|field| columns.find(field))
+            .ok_or_else(|| ImportError::MissingColumn(String::from("Price")))?;
+
+        let mut balance_notes = vec![];
+        for result in rdr.records() {
+            let record = result?;
+            log::debug!("{:?}", record);
+            let name = EquityPlanParser::field(&record, name);
+            let quantity = EquityPlanParser::field(&record, quantity);
+            if name.is_empty() || quantity.is_empty() {
+                continue;
+            }
+            let cusip = match cusip {
+                Some(cusip) => EquityPlanParser::field(&record, cusip),
+                None => "",
+            };
+            balance_notes.push(
+                self.balance_note(
+                    name,
+                    cusip,
+                    quantity,
+                    EquityPlanParser::field(&record, price),
+                )
+                .record_context(&record)?,
+            );
+        }
+
+        Ok(balance_notes)
+    }
+
+    /// Row of the holdings table of a statement, the name of the shares
+    /// followed by their symbol, CUSIP, quantity, price and market value.
+    fn statement_balance_note(&self, row: &str) -> Result<BalanceNote> {
+        let words = row.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            [_, .., symbol, cusip, quantity, price, _] if EquityPlanParser::is_cusip(cusip) => {
+                self.balance_note(symbol, cusip, quantity, price)
+            }
+            _ => bail!(RowIssue::UnexpectedLayout),
+        }
+    }
+
+    /// Shares held at the end of the year according to the holdings table of
+    /// the year-end statement, valued in dollars. The table can span several
+    /// pages, each of them repeating its header.
+    pub fn parse_pdf_content(&self) -> Result<BalanceNotes> {
+        let mut lines = self.content.lines().enumerate();
+        if !lines
+            .by_ref()
+            .any(|(_, line)| EquityPlanParser::is_statement_holdings_header(line))
+        {
+            bail!(ImportError::MissingSection(String::from(HOLDINGS_SECTION)));
+        }
+
+        let mut balance_notes = vec![];
+        for (index, line) in lines {
+            let line = line.trim();
+            if line.starts_with(STATEMENT_HOLDINGS_END) {
+                break;
+            }
+            if line.is_empty()
+                || line.starts_with(STATEMENT_PAGE_FOOTER_BEGIN)
+                || EquityPlanParser::is_statement_holdings_header(line)
+            {
+                continue;
+            }
+            log::debug!("{}", line);
+            balance_notes.push(
+                self.statement_balance_note(line)
+                    .row_context(Location::line(index + 1).in_section(HOLDINGS_SECTION), line)?,
+            );
+        }
+
+        if balance_notes.is_empty() {
+            bail!(ImportError::MissingSection(String::from(HOLDINGS_SECTION)));
+        }
+        Ok(balance_notes)
+    }
+
+    /// Parser of the export of `provider`, holding the shares at `broker`.
+    pub fn new(
+        content: String,
+        provider: EquityPlanProvider,
+        broker: &Arc<BrokerInformation>,
+    ) -> Self {
+        Self {
+            content: content.trim_start_matches('\u{feff}').to_string(),
+            provider,
+            broker: Arc::clone(broker),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ImportWarning;
+
+    const INPUT_MORGAN_STANLEY_RELEASES: &str = r#"Vest Date,Order Number,Plan,Type,Status,Price,Quantity,Net Cash Proceeds,Net Share Proceeds,Tax Payment Method
+15-Mar-2023,N/A,GSU Class C,Release,Complete,$95.50,20.000,$0.00,12.000,Withholding
+15-Jun-2023,N/A,GSU Class C,Release,Staged,$120.25,20.000,$0.00,0.000,Withholding
+"#;
+
+    const INPUT_MORGAN_STANLEY_WITHDRAWALS: &str = r#"Execution Date,Order Number,Plan,Type,Order Status,Price,Quantity,Net Amount,Net Share Proceeds,Tax Payment Method
+20-Sep-2023,WRC123,GSU Class C,Sale,Complete,$130.00,-5.000,$649.90,0,N/A
+"#;
+
+    const INPUT_SCHWAB: &str = r#""Date","Action","Symbol","Description","Quantity","Fees & Commissions","Disbursement Election","Amount","AwardDate","AwardId","FairMarketValuePrice","SalePrice","SharesSoldWithheldForTaxes","NetSharesDeposited","Taxes","PurchaseDate","PurchasePrice","PurchaseFairMarketValue"
+"03/15/2023","Lapse","GOOG","Restricted Stock Lapse","20","","","","","","","","","","","","",""
+"","","","","","","","","03/15/2021","123456","$95.50","","8","12","$700.00","","",""
+"06/30/2023","Deposit","GOOG","ESPP","10","","","","","","","","","","","","",""
+"","","","","","","","","","","","","","","","06/30/2023","$100.00","$118.00"
+"09/20/2023","Sale","GOOG","Share Sale","5","$0.10","","$649.90","","","","","","","","","",""
+"","","","","","","","","","","","$130.00","","","","","",""
+"10/01/2023","Wire Transfer","","Cash Disbursement","","","","-$649.90","","","","","","","","","",""
+"#;
+
+    const INPUT_FIDELITY: &str = r#"Run Date,Action,Symbol,Security Description,Security Type,Quantity,Price ($),Commission ($),Fees ($),Accrued Interest ($),Amount ($),Settlement Date
+03/15/2023,RSU VEST MSFT CORP (MSFT) (Stock Plan),MSFT,MSFT CORP,Cash,12,265.44,,,,0,
+06/30/2023,DIVIDEND RECEIVED MSFT CORP (MSFT) (Cash),MSFT,MSFT CORP,Cash,0,,,,,8.16,
+09/20/2023,YOU SOLD MSFT CORP (MSFT) (Cash),MSFT,MSFT CORP,Cash,-5,315.00,0.05,,,1574.95,09/22/2023
+"#;
+
+    const INPUT_MORGAN_STANLEY_HOLDINGS: &str = r#"As Of Date,Plan,Symbol,CUSIP,Available Quantity,Market Price,Market Value
+31-Dec-2023,GSU Class C,GOOG,02079K107,7.000,$140.93,$986.51
+"#;
+
+    const INPUT_FIDELITY_HOLDINGS: &str = r#"Account Number,Account Name,Symbol,Description,Quantity,Last Price,Last Price Change,Current Value
+Z12345678,STOCK PLAN,MSFT,MICROSOFT CORP,7,$376.04,+$0.00,$2632.28
+Z12345678,STOCK PLAN,Pending Activity,,,,,$0.00
+
+"The data and information in this spreadsheet is provided to you solely for your use."
+"#;
+
+    const INPUT_SCHWAB_STATEMENT: &str = "Schwab Equity Award Center
+Year-End Summary December 31, 2023
+Holdings
+Description Symbol CUSIP Quantity Price Market Value
+ALPHABET INC CLASS C GOOG 02079K107 17 $140.93 $2,395.81
+Page 1 of 2
+Description Symbol CUSIP Quantity Price Market Value
+SPOTIFY TECHNOLOGY SA SPOT L8681T102 2 $187.91 $375.82
+Total Holdings $2,771.63
+";
+
+    const GSU_CLASS_C: (&str, &str) = ("GSU Class C", "");
+    const GOOG: (&str, &str) = ("GOOG", "");
+    const MSFT: (&str, &str) = ("MSFT", "");
+
+    fn us_broker(name: &str) -> Arc<BrokerInformation> {
+        Arc::new(BrokerInformation::new(
+            String::from(name),
+            String::from("US"),
+        ))
+    }
+
+    fn note(
+        date: (i32, u32, u32),
+        company: (&str, &str),
+        operation: BrokerOperation,
+        quantity: Decimal,
+        price: Decimal,
+        commision: Decimal,
+        broker: &Arc<BrokerInformation>,
+    ) -> AccountNote {
+        AccountNote::new(
+            NaiveDate::from_ymd_opt(date.0, date.1, date.2).unwrap(),
+            CompanyInfo {
+                name: String::from(company.0),
+                isin: String::from(company.1),
+            },
+            operation,
+            quantity,
+            price,
+            (quantity * price).round_dp(2),
+            commision,
+            broker,
+        )
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            EquityPlanProvider::detect(INPUT_MORGAN_STANLEY_RELEASES),
+            Some(EquityPlanProvider::MorganStanley)
+        );
+        assert_eq!(
+            EquityPlanProvider::detect(INPUT_MORGAN_STANLEY_WITHDRAWALS),
+            Some(EquityPlanProvider::MorganStanley)
+        );
+        assert_eq!(
+            EquityPlanProvider::detect(INPUT_SCHWAB),
+            Some(EquityPlanProvider::Schwab)
+        );
+        assert_eq!(
+            EquityPlanProvider::detect(INPUT_FIDELITY),
+            Some(EquityPlanProvider::Fidelity)
+        );
+        assert_eq!(EquityPlanProvider::detect("Producto,ISIN"), None);
+    }
+
+    #[test]
+    fn test_import_holdings() {
+        let result = crate::importer::import(INPUT_FIDELITY.as_bytes(), 2023).unwrap();
+
+        // Holdings come from the trades, without ISIN nor value at the end of
+        // the year
+        assert_eq!(result.records.len(), 1);
+        assert_eq!(result.records[0].company.isin, MSFT.1);
+        assert_eq!(result.records[0].quantity, Decimal::new(7, 0));
+        assert!(result.records[0].value_in_euro.is_zero());
+        assert!(
+            result
+                .warnings
+                .contains(&ImportWarning::UnvaluedPositions(vec![String::from(
+                    MSFT.0
+                )]))
+        );
+        assert!(
+            result
+                .warnings
+                .contains(&ImportWarning::MissingIsin(vec![String::from(MSFT.0)]))
+        );
+    }
+
+    #[test]
+    fn test_import_activity_and_holdings() {
+        let mut importer = crate::importer::Importer::new(2023);
+        importer
+            .import(INPUT_MORGAN_STANLEY_RELEASES.as_bytes())
+            .unwrap();
+        let result = importer
+            .import(INPUT_MORGAN_STANLEY_HOLDINGS.as_bytes())
+            .unwrap();
+
+        // The holdings give the ISIN of the shares vested
+        assert_eq!(result.records.len(), 1);
+        assert_eq!(result.records[0].company.isin, "US02079K1079");
+        assert_eq!(result.records[0].quantity, Decimal::new(7, 0));
+        assert_eq!(result.records[0].first_tx_date, 20230315);
+        assert_eq!(result.records[0].value_in_euro, Decimal::new(892_77, 2));
+    }
+
+    #[test]
+    fn test_parse_holdings() {
+        let broker = us_broker("Morgan Stanley");
+        let parser = EquityPlanParser::new(
+            INPUT_MORGAN_STANLEY_HOLDINGS.to_string(),
+            EquityPlanProvider::MorganStanley,
+            &broker,
+        );
+        assert!(parser.parse_account_notes().unwrap().is_empty());
+        assert_eq!(
+            parser.parse_balance_notes().unwrap(),
+            vec![BalanceNote::new(
+                CompanyInfo {
+                    name: String::from(GSU_CLASS_C.0),
+                    isin: String::from("US02079K1079"),
+                },
+                String::new(),
+                Decimal::new(7, 0),
+                String::from("USD"),
+                Decimal::new(140_93, 2),
+                Decimal::ZERO,
+                &broker,
+            )]
+        );
+
+        let broker = us_broker("Fidelity");
+        let parser = EquityPlanParser::new(
+            INPUT_FIDELITY_HOLDINGS.to_string(),
+            EquityPlanProvider::Fidelity,
+            &broker,
+        );
+        assert_eq!(
+            EquityPlanProvider::detect(INPUT_FIDELITY_HOLDINGS),
+            Some(EquityPlanProvider::Fidelity)
+        );
+        assert_eq!(
+            parser.parse_balance_notes().unwrap(),
+            vec![BalanceNote::new(
+                EquityPlanParser::company(MSFT.0),
+                String::new(),
+                Decimal::new(7, 0),
+                String::from("USD"),
+                Decimal::new(376_04, 2),
+                Decimal::ZERO,
+                &broker,
+            )]
+        );
+    }
+
+    #[test]
+    fn test_parse_pdf_content() {
+        let broker = us_broker("Charles Schwab");
+        assert_eq!(
+            EquityPlanProvider::detect_statement(INPUT_SCHWAB_STATEMENT),
+            Some(EquityPlanProvider::Schwab)
+        );
+        let parser = EquityPlanParser::new(
+            INPUT_SCHWAB_STATEMENT.to_string(),
+            EquityPlanProvider::Schwab,
+            &broker,
+        );
+
+        let notes = parser.parse_pdf_content().unwrap();
+        assert_eq!(notes.len(), 2);
+        assert_eq!(
+            notes[0],
+            BalanceNote::new(
+                CompanyInfo {
+                    name: String::from(GOOG.0),
+                    isin: String::from("US02079K1079"),
+                },
+                String::new(),
+                Decimal::new(17, 0),
+                String::from("USD"),
+                Decimal::new(140_93, 2),
+                Decimal::ZERO,
+                &broker,
+            )
+        );
+        // Foreign securities have a CINS, whose ISIN is left to the user
+        assert_eq!(notes[1].company, EquityPlanParser::company("SPOT"));
+
+        let parser = EquityPlanParser::new(
+            INPUT_SCHWAB_STATEMENT.replace(" 02079K107", ""),
+            EquityPlanProvider::Schwab,
+            &broker,
+        );
+        assert_eq!(
+            ImportError::from(parser.parse_pdf_content().unwrap_err()),
+            ImportError::MalformedRow {
+                location: Location::line(5).in_section(HOLDINGS_SECTION),
+                raw: String::from("ALPHABET INC CLASS C GOOG 17 $140.93 $2,395.81"),
+                issue: RowIssue::UnexpectedLayout,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_morgan_stanley() {
+        let broker = us_broker("Morgan Stanley");
+        let releases = EquityPlanParser::new(
+            INPUT_MORGAN_STANLEY_RELEASES.to_string(),
+            EquityPlanProvider::MorganStanley,
+            &broker,
+        );
+        assert_eq!(
+            releases.parse_account_notes().unwrap(),
+            vec![note(
+                (2023, 3, 15),
+                GSU_CLASS_C,
+                BrokerOperation::Buy,
+                Decimal::new(12, 0),
+                Decimal::new(95_50, 2),
+                Decimal::ZERO,
+                &broker
+            )]
+        );
+
+        let withdrawals = EquityPlanParser::new(
+            INPUT_MORGAN_STANLEY_WITHDRAWALS.to_string(),
+            EquityPlanProvider::MorganStanley,
+            &broker,
+        );
+        assert_eq!(
+            withdrawals.parse_account_notes().unwrap(),
+            vec![note(
+                (2023, 9, 20),
+                GSU_CLASS_C,
+                BrokerOperation::Sell,
+                Decimal::new(5, 0),
+                Decimal::new(130, 0),
+                Decimal::ZERO,
+                &broker
+            )]
+        );
+    }
+
+    #[test]
+    fn test_parse_schwab() {
+        let broker = us_broker("Charles Schwab");
+        let parser = EquityPlanParser::new(
+            INPUT_SCHWAB.to_string(),
+            EquityPlanProvider::Schwab,
+            &broker,
+        );
+
+        assert_eq!(
+            parser.parse_account_notes().unwrap(),
+            vec![
+                note(
+                    (2023, 3, 15),
+                    GOOG,
+                    BrokerOperation::Buy,
+                    Decimal::new(12, 0),
+                    Decimal::new(95_50, 2),
+                    Decimal::ZERO,
+                    &broker
+                ),
+                note(
+                    (2023, 6, 30),
+                    GOOG,
+                    BrokerOperation::Buy,
+                    Decimal::new(10, 0),
+                    Decimal::new(118, 0),
+                    Decimal::ZERO,
+                    &broker
+                ),
+                note(
+                    (2023, 9, 20),
+                    GOOG,
+                    BrokerOperation::Sell,
+                    Decimal::new(5, 0),
+                    Decimal::new(130, 0),
+                    Decimal::new(10, 2),
+                    &broker
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_fidelity() {
+        let broker = us_broker("Fidelity");
+        let parser = EquityPlanParser::new(
+            INPUT_FIDELITY.to_string(),
+            EquityPlanProvider::Fidelity,
+            &broker,
+        );
+
+        assert_eq!(
+            parser.parse_account_notes().unwrap(),
+            vec![
+                note(
+                    (2023, 3, 15),
+                    MSFT,
+                    BrokerOperation::Buy,
+                    Decimal::new(12, 0),
+                    Decimal::new(265_44, 2),
+                    Decimal::ZERO,
+                    &broker
+                ),
+                note(
+                    (2023, 9, 20),
+                    MSFT,
+                    BrokerOperation::Sell,
+                    Decimal::new(5, 0),
+                    Decimal::new(315, 0),
+                    Decimal::new(5, 2),
+                    &broker
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,405 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+
+use crate::{
+    data::{
+        AccountNote, AccountNotes, AssetCategory, BalanceNote, BalanceNotes, BrokerInformation,
+        BrokerOperation, CompanyInfo,
+    },
+    error::ImportError,
+    utils::xlsx::{Sheet, serial_date},
+};
+
+const OPEN_POSITIONS_SHEET: &str = "Open Positions";
+const CLOSED_POSITIONS_SHEET: &str = "Closed Positions";
+const ACCOUNT_ACTIVITY_SHEET: &str = "Account Activity";
+
+const POSITION_ID_FIELD: &str = "Position ID";
+const ACTION_FIELD: &str = "Action";
+const ISIN_FIELD: &str = "ISIN";
+const DATE_FIELD: &str = "Date";
+const TYPE_FIELD: &str = "Type";
+const DETAILS_FIELD: &str = "Details";
+const AMOUNT_FIELD: &str = "Amount";
+const UNITS_FIELD: &str = "Units";
+const ASSET_TYPE_FIELD: &str = "Asset type";
+const CURRENT_RATE_FIELD: &str = "Current Rate";
+
+const OPEN_POSITION_TYPE: &str = "Open Position";
+const CLOSE_POSITION_TYPE: &str = "Position closed";
+
+/// Asset types held as real securities, everything else (CFDs, crypto,
+/// copy portfolios) is not a security held in custody.
+const SECURITY_ASSET_TYPES: [&str; 2] = ["Stocks", "ETF"];
+const ETF_ASSET_TYPE: &str = "ETF";
+
+/// Currency of the rates and amounts of the statement.
+const USD_CURRENCY: &str = "USD";
+
+/// Parser of the eToro account statement workbook.
+#[derive(Debug)]
+pub struct EToroParser {
+    sheets: Vec<Sheet>,
+    broker: Arc<BrokerInformation>,
+}
+
+struct SheetColumns<'a> {
+    headers: &'a [String],
+}
+
+impl SheetColumns<'_> {
+    fn find(&self, field: &str) -> Option<usize> {
+        self.headers
+            .iter()
+            .position(|header| header.trim() == field)
+    }
+
+    fn required(&self, field: &str) -> Result<usize> {
+        self.find(field)
+            .ok_or_else(|| anyhow!(ImportError::MissingColumn(field.to_string())))
+    }
+}
+
+impl EToroParser {
+    fn sheet(&self, name: &str) -> Option<&Sheet> {
+        self.sheets.iter().find(|sheet| sheet.name == name)
+    }
+
+    fn field(row: &[String], index: usize) -> &str {
+        row.get(index).map(|value| value.trim()).unwrap_or("")
+    }
+
+    fn parse_decimal(value: &str) -> Result<Decimal> {
+        if value.is_empty() {
+            return Ok(Decimal::ZERO);
+        }
+        Decimal::from_str(value)
+            .or_else(|_| Decimal::from_scientific(value))
+            .with_context(|| format!("Invalid number {}", value))
+    }
+
+    /// Dates are written as `dd/mm/yyyy hh:mm:ss` text or as spreadsheet
+    /// serial numbers, depending on the statement version.
+    fn parse_date(value: &str) -> Result<NaiveDate> {
+        if let Ok(date) = NaiveDate::parse_from_str(value.get(..10).unwrap_or(value), "%d/%m/%Y") {
+            return Ok(date);
+        }
+
+        serial_date(value).with_context(|| format!("Invalid date {}", value))
+    }
+
+    /// Instrument of the `Buy Apple` like actions of the positions sheets.
+    fn company(action: &str, isin: &str) -> CompanyInfo {
+        CompanyInfo {
+            name: action
+                .split_once(' ')
+                .map(|(_, name)| name)
+                .unwrap_or(action)
+                .to_string(),
+            isin: isin.to_string(),
+        }
+    }
+
+    /// The open and closed positions sheets are the only places where eToro
+    /// gives the ISIN, indexed here by position id along with the
+    /// instrument name.
+    fn positions_companies(&self) -> Result<HashMap<String, CompanyInfo>> {
+        let mut companies = HashMap::new();
+        for sheet in [OPEN_POSITIONS_SHEET, CLOSED_POSITIONS_SHEET] {
+            let Some(sheet) = self.sheet(sheet) else {
+                continue;
+            };
+            let Some((headers, rows)) = sheet.rows.split_first() else {
+                continue;
+            };
+
+            let columns = SheetColumns { headers };
+            let position_id = columns.required(POSITION_ID_FIELD)?;
+            let action = columns.required(ACTION_FIELD)?;
+            let Some(isin) = columns.find(ISIN_FIELD) else {
+                continue;
+            };
+
+            for row in rows {
+                let isin = EToroParser::field(row, isin);
+                if isin.is_empty() {
+                    continue;
+                }
+                companies.insert(
+                    EToroParser::field(row, position_id).to_string(),
+                    EToroParser::company(EToroParser::field(row, action), isin),
+                );
+            }
+        }
+
+        Ok(companies)
+    }
+
+    /// Positions on real stocks and ETFs open at the date of the statement,
+    /// valued in dollars at their current rate. Each purchase is a position
+    /// of its own, so the same instrument may be listed several times.
+    pub fn parse_balance_notes(&self) -> Result<BalanceNotes> {
+        let Some(sheet) = self.sheet(OPEN_POSITIONS_SHEET) else {
+            return Ok(vec![]);
+        };
+        let Some((headers, rows)) = sheet.rows.split_first() else {
+            return Ok(vec![]);
+        };
+
+        let columns = SheetColumns { headers };
+        let action = columns.required(ACTION_FIELD)?;
+        let units = columns.required(UNITS_FIELD)?;
+        let current_rate = columns.required(CURRENT_RATE_FIELD)?;
+        let asset_type = columns.required(TYPE_FIELD)?;
+        let isin = columns.required(ISIN_FIELD)?;
+
+        let mut balance_notes: BalanceNotes = vec![];
+        for row in rows {
+            let kind = EToroParser::field(row, asset_type);
+            let company = EToroParser::company(
+                EToroParser::field(row, action),
+                EToroParser::field(row, isin),
+            );
+            if !SECURITY_ASSET_TYPES.contains(&kind) {
+                log::info!("Skipping {} position on {}", kind, company.name);
+                continue;
+            }
+            let quantity = EToroParser::parse_decimal(EToroParser::field(row, units))?;
+            let price = EToroParser::parse_decimal(EToroParser::field(row, current_rate))?;
+
+            if let Some(note) = balance_notes
+                .iter_mut()
+                .find(|note| note.company == company)
+            {
+                note.quantity += quantity;
+                continue;
+            }
+            let mut note = BalanceNote::new(
+                company,
+                String::new(),
+                quantity,
+                String::from(USD_CURRENCY),
+                price,
+                Decimal::ZERO,
+                &self.broker,
+            );
+            if kind == ETF_ASSET_TYPE {
+                note.category = AssetCategory::Fund;
+            }
+            balance_notes.push(note);
+        }
+
+        Ok(balance_notes)
+    }
+
+    /// Opening and closing of positions on real stocks and ETFs, CFD and
+    /// crypto positions are left out as they are not securities.
+    pub fn parse_account_notes(&self) -> Result<AccountNotes> {
+        let sheet = self
+            .sheet(ACCOUNT_ACTIVITY_SHEET)
+            .ok_or_else(|| ImportError::MissingSection(ACCOUNT_ACTIVITY_SHEET.to_string()))?;
+        let Some((headers, rows)) = sheet.rows.split_first() else {
+            return Ok(vec![]);
+        };
+
+        let columns = SheetColumns { headers };
+        let date = columns.required(DATE_FIELD)?;
+        let kind = columns.required(TYPE_FIELD)?;
+        let details = columns.required(DETAILS_FIELD)?;
+        let amount = columns.required(AMOUNT_FIELD)?;
+        let units = columns.required(UNITS_FIELD)?;
+        let position_id = columns.required(POSITION_ID_FIELD)?;
+        let asset_type = columns.required(ASSET_TYPE_FIELD)?;
+
+        let companies = self.positions_companies()?;
+        let mut account_notes = vec![];
+
+        for row in rows {
+            let operation = match EToroParser::field(row, kind) {
+                OPEN_POSITION_TYPE => BrokerOperation::Buy,
+                CLOSE_POSITION_TYPE => BrokerOperation::Sell,
+                _ => continue,
+            };
+            let instrument = EToroParser::field(row, details);
+            if !SECURITY_ASSET_TYPES.contains(&EToroParser::field(row, asset_type)) {
+                log::info!(
+                    "Skipping {} position on {}",
+                    EToroParser::field(row, asset_type),
+                    instrument
+                );
+                continue;
+            }
+
+            let company = companies
+                .get(EToroParser::field(row, position_id))
+                .cloned()
+                .unwrap_or_else(|| CompanyInfo {
+                    name: instrument
+                        .split_once('/')
+                        .map(|(ticker, _)| ticker)
+                        .unwrap_or(instrument)
+                        .to_string(),
+                    isin: String::new(),
+                });
+            let quantity = EToroParser::parse_decimal(EToroParser::field(row, units))?;
+            let value = EToroParser::parse_decimal(EToroParser::field(row, amount))?;
+            let price = if quantity.is_zero() {
+                Decimal::ZERO
+            } else {
+                (value / quantity).round_dp(4)
+            };
+
+            account_notes.push(AccountNote::new(
+                EToroParser::parse_date(EToroParser::field(row, date))?,
+                company,
+                operation,
+                quantity,
+                price,
+                value,
+                Decimal::ZERO,
+                &self.broker,
+            ));
+        }
+
+        Ok(account_notes)
+    }
+
+    /// Parser of the sheets of an eToro account statement.
+    pub fn new(sheets: Vec<Sheet>, broker: &Arc<BrokerInformation>) -> Self {
+        Self {
+            sheets,
+            broker: Arc::clone(broker),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data::{Aeat720Information, PersonalInformation},
+        importer,
+        reports::aeat_720::Aeat720Report,
+        utils::xlsx::read_xlsx,
+    };
+
+    const ETORO_TEST: &[u8] = include_bytes!("testdata/etoro_test.xlsx");
+
+    fn etoro_broker() -> Arc<BrokerInformation> {
+        Arc::new(BrokerInformation::new(
+            String::from("eToro"),
+            String::from("CY"),
+        ))
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(
+            EToroParser::parse_date("10/01/2023 15:30:00").unwrap(),
+            NaiveDate::from_ymd_opt(2023, 1, 10).unwrap()
+        );
+        assert_eq!(
+            EToroParser::parse_date("44936.6458333333").unwrap(),
+            NaiveDate::from_ymd_opt(2023, 1, 10).unwrap()
+        );
+        assert!(EToroParser::parse_date("2023-01-10").is_err());
+    }
+
+    #[test]
+    fn test_parse_balance_notes() {
+        let broker = etoro_broker();
+        let parser = EToroParser::new(read_xlsx(ETORO_TEST).unwrap(), &broker);
+        let mut etf = BalanceNote::new(
+            CompanyInfo {
+                name: String::from("iShares Core MSCI World UCITS ETF"),
+                isin: String::from("IE00B4L5Y983"),
+            },
+            String::new(),
+            Decimal::new(5, 0),
+            String::from("USD"),
+            Decimal::new(96_40, 2),
+            Decimal::ZERO,
+            &broker,
+        );
+        etf.category = AssetCategory::Fund;
+
+        assert_eq!(parser.parse_balance_notes().unwrap(), vec![etf]);
+    }
+
+    #[test]
+    fn test_generate_report() {
+        let result = importer::import(ETORO_TEST, 2023).unwrap();
+        assert!(result.warnings.is_empty());
+        assert_eq!(result.records.len(), 1);
+        let record = &result.records[0];
+        assert_eq!(record.company.isin, "IE00B4L5Y983");
+        assert_eq!(record.category, AssetCategory::Fund);
+        // 5 units at 96.40 USD, at 1.1050 USD per euro
+        assert_eq!(record.value_in_euro, Decimal::new(436_20, 2));
+
+        let report = Aeat720Report::new(&Aeat720Information {
+            records: result.records,
+            personal_info: PersonalInformation {
+                name: String::from("Juan"),
+                surname: String::from("Español Español"),
+                nif: String::from("12345678Z"),
+                year: 2023,
+                phone: String::from("600000000"),
+            },
+        })
+        .unwrap();
+        assert!(report.generate().is_ok());
+    }
+
+    #[test]
+    fn test_parse_account_notes() {
+        let broker = etoro_broker();
+        assert_eq!(infer::get(ETORO_TEST).unwrap().extension(), "xlsx");
+        let parser = EToroParser::new(read_xlsx(ETORO_TEST).unwrap(), &broker);
+        let apple = CompanyInfo {
+            name: String::from("Apple"),
+            isin: String::from("US0378331005"),
+        };
+
+        assert_eq!(
+            parser.parse_account_notes().unwrap(),
+            vec![
+                AccountNote::new(
+                    NaiveDate::from_ymd_opt(2023, 1, 10).unwrap(),
+                    apple.clone(),
+                    BrokerOperation::Buy,
+                    Decimal::new(2, 0),
+                    Decimal::new(150, 0),
+                    Decimal::new(300, 0),
+                    Decimal::ZERO,
+                    &broker,
+                ),
+                AccountNote::new(
+                    NaiveDate::from_ymd_opt(2023, 3, 1).unwrap(),
+                    CompanyInfo {
+                        name: String::from("iShares Core MSCI World UCITS ETF"),
+                        isin: String::from("IE00B4L5Y983"),
+                    },
+                    BrokerOperation::Buy,
+                    Decimal::new(5, 0),
+                    Decimal::new(90, 0),
+                    Decimal::new(450, 0),
+                    Decimal::ZERO,
+                    &broker,
+                ),
+                AccountNote::new(
+                    NaiveDate::from_ymd_opt(2023, 6, 20).unwrap(),
+                    apple,
+                    BrokerOperation::Sell,
+                    Decimal::new(2, 0),
+                    Decimal::new(162_50, 2),
+                    Decimal::new(325, 0),
+                    Decimal::ZERO,
+                    &broker,
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,493 @@
+use anyhow::{Context, Result, bail};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, str::FromStr, sync::Arc};
+
+use crate::{
+    data::{
+        AccountNote, AccountNotes, BalanceNote, BalanceNotes, BrokerInformation, BrokerOperation,
+        CompanyInfo,
+    },
+    error::{ImportError, RowContext},
+    utils::decimal::{normalize_str, transform_i18n_es_str},
+};
+
+const DEFAULT_CURRENCY: &str = "EUR";
+
+/// Words of the operation column telling sells from buys, sells are checked
+/// first as `verkauf` contains `kauf`.
+const SELL_WORDS: [&str; 6] = ["sell", "venta", "sale", "verkauf", "vente", "vendita"];
+const BUY_WORDS: [&str; 6] = ["buy", "compra", "purchase", "kauf", "achat", "acquisto"];
+
+/// Date formats offered to read the date column.
+pub const DATE_FORMATS: [(&str, &str); 5] = [
+    ("%d/%m/%Y", "dd/mm/aaaa"),
+    ("%Y-%m-%d", "aaaa-mm-dd"),
+    ("%m/%d/%Y", "mm/dd/aaaa"),
+    ("%d-%m-%Y", "dd-mm-aaaa"),
+    ("%d.%m.%Y", "dd.mm.aaaa"),
+];
+
+/// Data that can be read from a column of the CSV.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Deserialize, Serialize)]
+pub enum CsvField {
+    /// ISIN of the security.
+    Isin,
+    /// Name of the security.
+    Name,
+    /// Number of shares, negative for sells when there is no operation column.
+    Quantity,
+    /// Value in euros of the position or trade.
+    ValueInEuro,
+    /// Currency of the security.
+    Currency,
+    /// Trade date, the rows are positions without it.
+    Date,
+    /// Buy or sell.
+    Operation,
+}
+
+impl CsvField {
+    /// Fields in the order shown to the user.
+    pub const ALL: [CsvField; 7] = [
+        CsvField::Isin,
+        CsvField::Name,
+        CsvField::Quantity,
+        CsvField::ValueInEuro,
+        CsvField::Currency,
+        CsvField::Date,
+        CsvField::Operation,
+    ];
+
+    /// Name of the field shown to the user.
+    pub fn label(&self) -> &'static str {
+        match self {
+            CsvField::Isin => "ISIN",
+            CsvField::Name => "Nombre",
+            CsvField::Quantity => "Cantidad",
+            CsvField::ValueInEuro => "Valor (€)",
+            CsvField::Currency => "Moneda",
+            CsvField::Date => "Fecha",
+            CsvField::Operation => "Operación",
+        }
+    }
+}
+
+/// Decimal separator of the numbers of the CSV.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub enum DecimalSeparator {
+    /// `1,234.56`
+    #[default]
+    Point,
+    /// `1.234,56`
+    Comma,
+}
+
+impl DecimalSeparator {
+    fn normalize(&self, value: &str) -> String {
+        let value: String = value
+            .chars()
+            .filter(|c| c.is_ascii_digit() || matches!(c, '.' | ',' | '-'))
+            .collect();
+        match self {
+            DecimalSeparator::Point => normalize_str(&value),
+            DecimalSeparator::Comma => transform_i18n_es_str(&value),
+        }
+    }
+}
+
+/// Columns of the CSV holding each field, by header name so the mapping is
+/// still valid when the columns are reordered.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct CsvMapping {
+    /// Header of the column of each field.
+    pub columns: BTreeMap<CsvField, String>,
+    /// Decimal separator of the numbers.
+    pub decimal_separator: DecimalSeparator,
+    /// `chrono` format of the dates.
+    pub date_format: String,
+    /// Name of the broker of the file.
+    pub broker_name: String,
+    /// ISO 3166 code of the country of the broker.
+    pub broker_country: String,
+}
+
+impl Default for CsvMapping {
+    fn default() -> Self {
+        Self {
+            columns: BTreeMap::new(),
+            decimal_separator: DecimalSeparator::default(),
+            date_format: DATE_FORMATS[0].0.to_string(),
+            broker_name: String::new(),
+            broker_country: String::new(),
+        }
+    }
+}
+
+impl CsvMapping {
+    /// Rows are trades when a date column is mapped, otherwise they are the
+    /// positions at the end of the year.
+    pub fn has_trades(&self) -> bool {
+        self.columns.contains_key(&CsvField::Date)
+    }
+
+    /// Broker of the records of the file.
+    pub fn broker(&self) -> Arc<BrokerInformation> {
+        Arc::new(BrokerInformation::new(
+            self.broker_name.trim().to_string(),
+            self.broker_country.trim().to_uppercase(),
+        ))
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !self.columns.contains_key(&CsvField::Isin)
+            && !self.columns.contains_key(&CsvField::Name)
+        {
+            bail!("Selecciona la columna del ISIN o del nombre");
+        }
+        if !self.columns.contains_key(&CsvField::Quantity) {
+            bail!("Selecciona la columna de la cantidad");
+        }
+        if self.broker_name.trim().is_empty() || self.broker_country.trim().len() != 2 {
+            bail!("Indica el nombre del broker y el código de dos letras de su país");
+        }
+
+        Ok(())
+    }
+}
+
+/// Parser of the CSV files of brokers without a specific parser, reading the
+/// columns chosen by the user.
+#[derive(Debug)]
+pub struct GenericCSVParser {
+    content: String,
+    mapping: CsvMapping,
+    broker: Arc<BrokerInformation>,
+}
+
+impl GenericCSVParser {
+    fn delimiter(content: &str) -> u8 {
+        let header = content.lines().next().unwrap_or_default();
+        [b';', b'\t', b',']
+            .into_iter()
+            .max_by_key(|delimiter| header.matches(*delimiter as char).count())
+            .unwrap_or(b',')
+    }
+
+    fn reader(content: &str) -> csv::Reader<&[u8]> {
+        csv::ReaderBuilder::new()
+            .delimiter(GenericCSVParser::delimiter(content))
+            .flexible(true)
+            .from_reader(content.as_bytes())
+    }
+
+    /// Names of the columns, from the first line of the file.
+    pub fn headers(content: &str) -> Result<Vec<String>> {
+        let headers: Vec<String> = GenericCSVParser::reader(content)
+            .headers()?
+            .iter()
+            .map(|header| header.trim().to_string())
+            .filter(|header| !header.is_empty())
+            .collect();
+        if headers.is_empty() {
+            bail!("No se han encontrado columnas en el fichero");
+        }
+
+        Ok(headers)
+    }
+
+    fn parse_decimal(&self, value: &str) -> Result<Decimal> {
+        let number = self.mapping.decimal_separator.normalize(value);
+        if number.is_empty() {
+            return Ok(Decimal::ZERO);
+        }
+        Decimal::from_str(&number).with_context(|| format!("Número no válido: {}", value))
+    }
+
+    /// Dates may be followed by the time.
+    fn parse_date(&self, value: &str) -> Result<NaiveDate> {
+        NaiveDate::parse_and_remainder(value, &self.mapping.date_format)
+            .map(|(date, _)| date)
+            .with_context(|| format!("Fecha no válida: {}", value))
+    }
+
+    fn parse_operation(value: &str) -> Option<BrokerOperation> {
+        let value = value.to_lowercase();
+        if SELL_WORDS.iter().any(|word| value.contains(word)) {
+            Some(BrokerOperation::Sell)
+        } else if BUY_WORDS.iter().any(|word| value.contains(word)) {
+            Some(BrokerOperation::Buy)
+        } else {
+            None
+        }
+    }
+
+    /// Values of the mapped fields for each row, with the record they were
+    /// read from.
+    fn rows(&self) -> Result<Vec<(csv::StringRecord, BTreeMap<CsvField, String>)>> {
+        let mut rdr = GenericCSVParser::reader(&self.content);
+        let headers = rdr.headers()?.clone();
+        let mut columns = vec![];
+        for (field, name) in self.mapping.columns.iter() {
+            let index = headers
+                .iter()
+                .position(|header| header.trim() == name)
+                .ok_or_else(|| ImportError::MissingColumn(name.clone()))?;
+            columns.push((*field, index));
+        }
+
+        let mut rows = vec![];
+        for result in rdr.records() {
+            let record = result?;
+            log::debug!("{:?}", record);
+            let row: BTreeMap<CsvField, String> = columns
+                .iter()
+                .map(|(field, index)| (*field, record.get(*index).unwrap_or("").trim().to_string()))
+                .collect();
+            if row.values().all(|value| value.is_empty()) {
+                continue;
+            }
+            rows.push((record, row));
+        }
+
+        Ok(rows)
+    }
+
+    fn company(row: &BTreeMap<CsvField, String>) -> CompanyInfo {
+        let isin = row.get(&CsvField::Isin).cloned().unwrap_or_default();
+        let name = row
+            .get(&CsvField::Name)
+            .filter(|name| !name.is_empty())
+            .cloned()
+            .unwrap_or_else(|| isin.clone());
+        CompanyInfo { name, isin }
+    }
+
+    fn field(row: &BTreeMap<CsvField, String>, field: CsvField) -> &str {
+        row.get(&field).map(String::as_str).unwrap_or_default()
+    }
+
+    fn price(quantity: Decimal, value: Decimal) -> Decimal {
+        if quantity.is_zero() {
+            Decimal::ZERO
+        } else {
+            (value / quantity).round_dp(4)
+        }
+    }
+
+    /// Positions of the file, when no date column is mapped.
+    pub fn parse_balance_notes(&self) -> Result<BalanceNotes> {
+        let mut balance_notes = vec![];
+        for (record, row) in self.rows()? {
+            let quantity = self
+                .parse_decimal(GenericCSVParser::field(&row, CsvField::Quantity))
+                .record_context(&record)?;
+            if quantity.is_zero() {
+                continue;
+            }
+            let value = self
+                .parse_decimal(GenericCSVParser::field(&row, CsvField::ValueInEuro))
+                .record_context(&record)?;
+            let currency = match GenericCSVParser::field(&row, CsvField::Currency) {
+                "" => DEFAULT_CURRENCY,
+                currency => currency,
+            };
+            balance_notes.push(BalanceNote::new(
+                GenericCSVParser::company(&row),
+                String::new(),
+                quantity,
+                currency.to_string(),
+                GenericCSVParser::price(quantity, value),
+                value,
+                &self.broker,
+            ));
+        }
+
+        Ok(balance_notes)
+    }
+
+    /// Trades of the file, the operation column tells buys from sells and,
+    /// when it is not mapped, the sign of the quantity.
+    pub fn parse_account_notes(&self) -> Result<AccountNotes> {
+        let mut account_notes = vec![];
+        for (record, row) in self.rows()? {
+            let quantity = self
+                .parse_decimal(GenericCSVParser::field(&row, CsvField::Quantity))
+                .record_context(&record)?;
+            let operation = if self.mapping.columns.contains_key(&CsvField::Operation) {
+                GenericCSVParser::parse_operation(GenericCSVParser::field(
+                    &row,
+                    CsvField::Operation,
+                ))
+            } else if quantity.is_sign_negative() {
+                Some(BrokerOperation::Sell)
+            } else {
+                Some(BrokerOperation::Buy)
+            };
+            let Some(operation) = operation else {
+                continue;
+            };
+            let quantity = quantity.abs();
+            let value = self
+                .parse_decimal(GenericCSVParser::field(&row, CsvField::ValueInEuro))
+                .record_context(&record)?
+                .abs();
+
+            account_notes.push(AccountNote::new(
+                self.parse_date(GenericCSVParser::field(&row, CsvField::Date))
+                    .record_context(&record)?,
+                GenericCSVParser::company(&row),
+                operation,
+                quantity,
+                GenericCSVParser::price(quantity, value),
+                value,
+                Decimal::ZERO,
+                &self.broker,
+            ));
+        }
+
+        Ok(account_notes)
+    }
+
+    /// Parser of `content` with the columns of `mapping`, failing when the
+    /// mapping lacks the security, the quantity or the broker.
+    pub fn new(content: String, mapping: &CsvMapping) -> Result<Self> {
+        mapping.validate()?;
+        Ok(Self {
+            content,
+            mapping: mapping.clone(),
+            broker: mapping.broker(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POSITIONS_INPUT: &str = "Valor;Código ISIN;Títulos;Importe EUR\n\
+        APPLE INC;US0378331005;10;\"1.925,30\"\n\
+        ;;;\n\
+        ISHARES CORE MSCI WORLD;IE00B4L5Y983;20;1.610,00\n";
+
+    const TRADES_INPUT: &str = "Date,Security,ISIN,Action,Shares,Amount (EUR)\n\
+        2023-03-15 10:00,Apple,US0378331005,Buy,10,\"1,502.50\"\n\
+        2023-05-15 00:00,Apple,US0378331005,Dividend,,2.40\n\
+        2023-09-20 12:30,Apple,US0378331005,Sell,-4,720.00\n";
+
+    fn mapping(columns: &[(CsvField, &str)]) -> CsvMapping {
+        CsvMapping {
+            columns: columns
+                .iter()
+                .map(|(field, name)| (*field, name.to_string()))
+                .collect(),
+            broker_name: String::from("Mi broker"),
+            broker_country: String::from("lu"),
+            ..CsvMapping::default()
+        }
+    }
+
+    #[test]
+    fn headers_test() {
+        assert_eq!(
+            GenericCSVParser::headers(POSITIONS_INPUT).unwrap(),
+            vec!["Valor", "Código ISIN", "Títulos", "Importe EUR"]
+        );
+        assert_eq!(GenericCSVParser::headers(TRADES_INPUT).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn parse_balance_notes_test() {
+        let mut mapping = mapping(&[
+            (CsvField::Name, "Valor"),
+            (CsvField::Isin, "Código ISIN"),
+            (CsvField::Quantity, "Títulos"),
+            (CsvField::ValueInEuro, "Importe EUR"),
+        ]);
+        mapping.decimal_separator = DecimalSeparator::Comma;
+        let broker = Arc::new(BrokerInformation::new(
+            String::from("Mi broker"),
+            String::from("LU"),
+        ));
+
+        let parser = GenericCSVParser::new(POSITIONS_INPUT.to_string(), &mapping).unwrap();
+        assert_eq!(
+            parser.parse_balance_notes().unwrap(),
+            vec![
+                BalanceNote::new(
+                    CompanyInfo {
+                        name: String::from("APPLE INC"),
+                        isin: String::from("US0378331005"),
+                    },
+                    String::new(),
+                    Decimal::new(10, 0),
+                    String::from("EUR"),
+                    Decimal::new(192_53, 2),
+                    Decimal::new(1925_30, 2),
+                    &broker,
+                ),
+                BalanceNote::new(
+                    CompanyInfo {
+                        name: String::from("ISHARES CORE MSCI WORLD"),
+                        isin: String::from("IE00B4L5Y983"),
+                    },
+                    String::new(),
+                    Decimal::new(20, 0),
+                    String::from("EUR"),
+                    Decimal::new(80_50, 2),
+                    Decimal::new(1610_00, 2),
+                    &broker,
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_account_notes_test() {
+        let mut mapping = mapping(&[
+            (CsvField::Date, "Date"),
+            (CsvField::Name, "Security"),
+            (CsvField::Isin, "ISIN"),
+            (CsvField::Operation, "Action"),
+            (CsvField::Quantity, "Shares"),
+            (CsvField::ValueInEuro, "Amount (EUR)"),
+        ]);
+        mapping.date_format = String::from("%Y-%m-%d");
+        let broker = mapping.broker();
+        let apple = CompanyInfo {
+            name: String::from("Apple"),
+            isin: String::from("US0378331005"),
+        };
+
+        let parser = GenericCSVParser::new(TRADES_INPUT.to_string(), &mapping).unwrap();
+        assert_eq!(
+            parser.parse_account_notes().unwrap(),
+            vec![
+                AccountNote::new(
+                    NaiveDate::from_ymd_opt(2023, 3, 15).unwrap(),
+                    apple.clone(),
+                    BrokerOperation::Buy,
+                    Decimal::new(10, 0),
+                    Decimal::new(150_25, 2),
+                    Decimal::new(1502_50, 2),
+                    Decimal::ZERO,
+                    &broker,
+                ),
+                AccountNote::new(
+                    NaiveDate::from_ymd_opt(2023, 9, 20).unwrap(),
+                    apple,
+                    BrokerOperation::Sell,
+                    Decimal::new(4, 0),
+                    Decimal::new(180, 0),
+                    Decimal::new(720, 0),
+                    Decimal::ZERO,
+                    &broker,
+                ),
+            ]
+        );
+
+        mapping.columns.remove(&CsvField::Quantity);
+        assert!(GenericCSVParser::new(TRADES_INPUT.to_string(), &mapping).is_err());
+    }
+}
@@ -1,14 +1,15 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::HashMap,
     str::FromStr,
     sync::{Arc, LazyLock},
 };
 
 use crate::{
     data::{
-        AccountNote, AccountNotes, BalanceNote, BalanceNotes, BrokerInformation, BrokerOperation,
-        CompanyInfo,
+        AccountNote, AccountNotes, AssetCategory, BalanceNote, BalanceNotes, BrokerInformation,
+        BrokerOperation, CompanyInfo,
     },
+    error::ImportWarning,
     parsers::util,
     utils::decimal,
 };
@@ -42,18 +43,20 @@ enum NoteState {
     Total,
 }
 
+/// Parser of the Interactive Brokers HTML activity statement.
+#[derive(Debug)]
 pub struct IBParser {
     dom: Html,
     broker: Arc<BrokerInformation>,
     companies_info: HashMap<String, CompanyInfo>,
+    warnings: Vec<ImportWarning>,
 }
 
-static STOCKS_STRS: LazyLock<HashSet<Option<&'static str>>> =
-    LazyLock::new(|| HashSet::from([Some("Stocks"), Some("Acciones")]));
-
 impl IBParser {
     const EUR_CURRENCY_STR: &'static str = "EUR";
 
+    /// Parser of an activity statement, failing when it has no financial
+    /// instrument information.
     pub fn new(data: &str, broker: &Arc<BrokerInformation>) -> Result<Self> {
         let dom = Html::parse_document(data);
         let companies_info = IBParser::parse_companies_info(&dom)?;
@@ -62,9 +65,15 @@ impl IBParser {
             dom,
             broker: Arc::clone(broker),
             companies_info,
+            warnings: vec![],
         })
     }
 
+    /// Sections rescaled while parsing the open positions.
+    pub fn warnings(&self) -> &[ImportWarning] {
+        &self.warnings
+    }
+
     fn parse_account_note(
         &self,
         row: &ElementRef<'_>,
@@ -114,7 +123,7 @@ impl IBParser {
             }
         };
 
-        Ok(AccountNote::new(
+        let mut note = AccountNote::new(
             NaiveDate::parse_from_str(date, "%Y-%m-%d, %H:%M:%S")?,
             company_info,
             operation,
@@ -123,9 +132,17 @@ impl IBParser {
             Decimal::from_str(&decimal::normalize_str(value))?.abs(),
             Decimal::from_str(&decimal::normalize_str(commision))?.abs(),
             &self.broker,
-        ))
+        );
+        if with_account_field {
+            note.account = field_values
+                .first()
+                .map_or(String::new(), |x| x.to_string());
+        }
+
+        Ok(note)
     }
 
+    /// Trades of the statement.
     pub fn parse_account_notes(&self) -> Result<AccountNotes> {
         let mut result = Vec::new();
         log::debug!("parsing account notes");
@@ -133,11 +150,12 @@ impl IBParser {
         if let Some(transactions) = self.dom.select(&TRANSACTIONS_SELECTOR).next() {
             let mut state = NoteState::Invalid;
             let mut with_account_field = false;
+            let mut category = None;
 
             for table_row in transactions.select(&THEAD_TH_TR_SELECTOR) {
                 let row_values = table_row.text().filter(|x| *x != "\n").collect::<Vec<_>>();
                 log::debug!("Processing header in account notes:-{:?}-", row_values);
-                if row_values[0] == "Account" {
+                if row_values[0] == "Account" || row_values[0] == "Cuenta" {
                     with_account_field = true;
                 }
             }
@@ -146,7 +164,19 @@ impl IBParser {
                 match state {
                     NoteState::Invalid => {
                         log::debug!("Invalid state");
-                        if STOCKS_STRS.contains(&table_row.text().next()) {
+                        // Only the headers of the asset categories start a section
+                        let has_asset_class = |x: &Element| {
+                            x.has_class("header-asset", CaseSensitivity::AsciiCaseInsensitive)
+                        };
+                        if table_row
+                            .first_child()
+                            .and_then(|x| x.value().as_element().map(has_asset_class))
+                            != Some(true)
+                        {
+                            continue;
+                        }
+                        category = table_row.text().next().and_then(util::ib_asset_category);
+                        if category.is_some_and(|x| x != AssetCategory::Unknown) {
                             state = NoteState::Stocks;
                         }
                     }
@@ -195,7 +225,12 @@ impl IBParser {
                             .map(has_currency_class)
                             != Some(true)
                         {
-                            result.push(self.parse_account_note(&table_row, with_account_field)?);
+                            if category.is_some_and(|x| x.is_declarable()) {
+                                result
+                                    .push(self.parse_account_note(&table_row, with_account_field)?);
+                            } else {
+                                log::debug!("Skipping {:?} trade", category);
+                            }
                         }
                     }
                     NoteState::Total => {
@@ -220,7 +255,12 @@ impl IBParser {
 
                 if let Some(element) = table_row.first_child().unwrap().value().as_element() {
                     if element.has_class("header-asset", CaseSensitivity::AsciiCaseInsensitive) {
-                        start_parsing_symbols = STOCKS_STRS.contains(&table_row.text().next());
+                        // Derivatives have no security id
+                        start_parsing_symbols = table_row
+                            .text()
+                            .next()
+                            .and_then(util::ib_asset_category)
+                            .is_some_and(|x| x.is_declarable());
                         continue;
                     }
                 }
@@ -259,6 +299,7 @@ impl IBParser {
         &self,
         row: &ElementRef<'_>,
         currency: Option<&str>,
+        category: AssetCategory,
     ) -> Result<BalanceNote> {
         let field_values = row.text().filter(|x| *x != "\n").collect::<Vec<_>>();
         log::debug!(
@@ -294,7 +335,7 @@ impl IBParser {
             })
             .unwrap();
 
-        Ok(BalanceNote::new(
+        let mut note = BalanceNote::new(
             company_info,
             String::from(""),
             Decimal::from_str(&decimal::normalize_str(quantity))?
@@ -303,10 +344,14 @@ impl IBParser {
             Decimal::from_str(&decimal::normalize_str(price))?,
             Decimal::from_str(&decimal::normalize_str(value_in_euro))?,
             &self.broker,
-        ))
+        );
+        note.category = category;
+
+        Ok(note)
     }
 
-    pub fn parse_balance_notes(&self) -> Result<BalanceNotes> {
+    /// Open positions of the statement.
+    pub fn parse_balance_notes(&mut self) -> Result<BalanceNotes> {
         log::debug!("parsing balance notes");
         let mut result = Vec::new();
 
@@ -314,12 +359,27 @@ impl IBParser {
             let mut state = NoteState::Invalid;
             let mut current_notes: BalanceNotes = Vec::new();
             let mut currency = None;
+            let mut category = AssetCategory::default();
 
             for table_row in table_open_positions.select(&TBODY_TR_SELECTOR) {
                 match state {
                     NoteState::Invalid => {
                         log::debug!("Invalid state");
-                        if STOCKS_STRS.contains(&table_row.text().next()) {
+                        // Only the headers of the asset categories start a section
+                        let has_asset_class = |x: &Element| {
+                            x.has_class("header-asset", CaseSensitivity::AsciiCaseInsensitive)
+                        };
+                        if table_row
+                            .first_child()
+                            .and_then(|x| x.value().as_element().map(has_asset_class))
+                            != Some(true)
+                        {
+                            continue;
+                        }
+                        if let Some(row_category) =
+                            table_row.text().next().and_then(util::ib_asset_category)
+                        {
+                            category = row_category;
                             state = NoteState::Stocks;
                         }
                     }
@@ -358,7 +418,8 @@ impl IBParser {
                                 state = NoteState::Total;
                             }
                         } else {
-                            let balance_note_result = self.parse_balance_note(&table_row, currency);
+                            let balance_note_result =
+                                self.parse_balance_note(&table_row, currency, category);
                             match balance_note_result {
                                 Ok(balance_note) => current_notes.push(balance_note),
                                 Err(msg) => {
@@ -384,6 +445,9 @@ impl IBParser {
                                 Decimal::from_str(&decimal::normalize_str(total_in_euro_str))?;
                             log::debug!("total in eur: {:?}", total_in_euro);
                             util::recalculate_balance_notes(&mut current_notes, &total_in_euro)?;
+                            self.warnings.push(ImportWarning::RescaledSection(
+                                currency.unwrap_or_default().to_string(),
+                            ));
                         } else {
                             state = NoteState::Invalid;
                         }
@@ -483,7 +547,7 @@ mod tests {
             String::from("Interactive Brokers"),
             String::from("IE"),
         ));
-        let ibparser = IBParser::new(DEFAULT_HTML_TEST, &ib_broker).unwrap();
+        let mut ibparser = IBParser::new(DEFAULT_HTML_TEST, &ib_broker).unwrap();
         let notes = ibparser.parse_balance_notes().unwrap();
 
         let bal_notes = vec![
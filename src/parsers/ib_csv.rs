@@ -1,130 +1,200 @@
-use anyhow::{Result, anyhow};
+use anyhow::{Result, anyhow, bail};
 use chrono::NaiveDate;
 use rust_decimal::Decimal;
-use std::{collections::HashMap, str::FromStr, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    sync::Arc,
+};
 
 use crate::{
     data::{
-        AccountNote, AccountNotes, BalanceNote, BalanceNotes, BrokerInformation, BrokerOperation,
-        CompanyInfo,
+        AccountNote, AccountNotes, AssetCategory, BalanceNote, BalanceNotes, BrokerInformation,
+        BrokerOperation, CompanyInfo, CorporateActionKind, CorporateActionNote,
+        CorporateActionNotes,
     },
+    error::{ImportError, ImportWarning, Location, RowContext, RowIssue},
     parsers::util,
     utils::decimal,
 };
 
-enum NoteState {
-    Invalid,
-    Stocks,
-    Total,
-}
+/// Securities and quantities of the rows of a corporate action.
+type CorporateActionRows = Vec<(CompanyInfo, Decimal)>;
+
+/// Parser of the Interactive Brokers CSV activity statement, in English or
+/// Spanish.
+#[derive(Debug)]
 pub struct IBCSVParser {
     content: String,
     locale: &'static [&'static str],
     broker: Arc<BrokerInformation>,
     companies_info: HashMap<String, CompanyInfo>,
+    /// Symbols of the ETFs, listed by IB as stocks.
+    etfs: HashSet<String>,
+    warnings: Vec<ImportWarning>,
 }
 
 impl IBCSVParser {
     const EUR_CURRENCY_STR: &'static str = "EUR";
 
-    const STOCK_COMPANY_INFO_SECTOR_START_STR: usize = 0;
-    const STOCK_COMPANY_INFO_SECTOR_END_STR: usize = 1;
-    const OPEN_POSITIONS_BEGIN_STR: usize = 2;
-    const OPEN_POSITIONS_END_STR: usize = 3;
-    const OPEN_POSITIONS_STOCK_STR: usize = 4;
-    const OPEN_POSITIONS_TOTAL_STR: usize = 5;
-    const TRADE_BEGIN_STR: usize = 6;
-    const TRADE_BEGIN__NO_ACCOUNT_STR: usize = 7;
-    const TRADE_END_STR: usize = 8;
-    const TRADE_STOCK_STR: usize = 9;
-    const STOCK_COMPANY_INFO_SECTOR_START_OLD_STR: usize = 10;
+    const COMPANY_INFO_HEADER_STR: usize = 0;
+    const COMPANY_INFO_DATA_STR: usize = 1;
+    const OPEN_POSITIONS_DATA_STR: usize = 2;
+    const OPEN_POSITIONS_TOTAL_STR: usize = 3;
+    const TRADE_BEGIN_STR: usize = 4;
+    const TRADE_BEGIN__NO_ACCOUNT_STR: usize = 5;
+    const TRADE_END_STR: usize = 6;
+    const TRADE_DATA_STR: usize = 7;
+    const CORPORATE_ACTIONS_STR: usize = 8;
+    const CORPORATE_ACTIONS_ACCOUNT_HEADER_STR: usize = 9;
+    const OPEN_POSITIONS_HEADER_STR: usize = 10;
+
+    const SYMBOL_FIELD_STRS: &'static [&'static str] = &["Symbol", "Símbolo"];
+    const DESCRIPTION_FIELD_STRS: &'static [&'static str] = &["Description", "Descripción"];
+    const ACCOUNT_FIELD_STRS: &'static [&'static str] = &["Account", "Cuenta"];
+    const SECURITY_ID_FIELD_STRS: &'static [&'static str] = &["Security ID", "Id. de seguridad"];
+    const TYPE_FIELD_STRS: &'static [&'static str] = &["Type", "Tipo"];
+    const ETF_TYPE_STR: &'static str = "ETF";
+
+    const SPLIT_STRS: &'static [&'static str] = &[" Split ", " División "];
+    const ISIN_CHANGE_STRS: &'static [&'static str] =
+        &[" CUSIP/ISIN Change ", " CUSIP/ISIN Cambia "];
+    const MERGER_STRS: &'static [&'static str] =
+        &[" Merged(", " Fusionado(", " Tendered to ", " Ofrecido a "];
+    const SPINOFF_STRS: &'static [&'static str] = &[" Spinoff ", " Spin-off ", " Escisión "];
+    const RATIO_STRS: &'static [&'static str] = &["for", "FOR", "para", "PARA"];
 
     const ES_HEADER_CONTENT: &str = "Statement,Header,Nombre del campo,Valor del campo";
 
     const EN_MSGS: &'static [&'static str] = &[
-        "Financial Instrument Information,Header,Asset Category,Symbol,Description,Conid,Security ID,Underlying,Listing Exch,Multiplier,Type,Code", // STOCK_COMPANY_INFO_SECTOR_START_STR
-        "Financial Instrument Information,Data,Stocks,", // STOCK_COMPANY_INFO_SECTOR_END_STR
-        "Open Positions,Header,DataDiscriminator,Asset Category,Currency,Symbol,Quantity,Mult,Cost Price,Cost Basis,Close Price,Value,Unrealized P/L,Code", // OPEN_POSITIONS_BEGIN_STR
-        "Open Positions,Total,,Stocks,EUR,", // OPEN_POSITIONS_END_STR
-        "Open Positions,Data,Summary,Stocks,", // OPEN_POSITIONS_STOCK_STR
-        "Open Positions,Total,,Stocks,",     // OPEN_POSITIONS_TOTAL_STR
+        "Financial Instrument Information,Header,", // COMPANY_INFO_HEADER_STR
+        "Financial Instrument Information,Data,",   // COMPANY_INFO_DATA_STR
+        "Open Positions,Data,Summary,",             // OPEN_POSITIONS_DATA_STR
+        "Open Positions,Total,,",                   // OPEN_POSITIONS_TOTAL_STR
         "Trades,Header,DataDiscriminator,Asset Category,Currency,Account,Symbol,Date/Time,Quantity,T. Price,C. Price,Proceeds,Comm/Fee,Basis,Realized P/L,MTM P/L,Code", // TRADE_BEGIN_STR
         "Trades,Header,DataDiscriminator,Asset Category,Currency,Symbol,Date/Time,Quantity,T. Price,C. Price,Proceeds,Comm/Fee,Basis,Realized P/L,MTM P/L,Code", // TRADE_BEGIN_NO_ACCOUNT_STR
-        "Trades,Total,",             // TRADE_END_STR
-        "Trades,Data,Order,Stocks,", // TRADE_STOCK_STR
-        "Financial Instrument Information,Header,Asset Category,Symbol,Description,Conid,Security ID,Listing Exch,Multiplier,Type,Code", // STOCK_COMPANY_INFO_SECTOR_START_OLD_STR
+        "Trades,Total,",                                             // TRADE_END_STR
+        "Trades,Data,Order,",                                        // TRADE_DATA_STR
+        "Corporate Actions,Data,",                                   // CORPORATE_ACTIONS_STR
+        "Corporate Actions,Header,Asset Category,Currency,Account,", // CORPORATE_ACTIONS_ACCOUNT_HEADER_STR
+        "Open Positions,Header,",                                    // OPEN_POSITIONS_HEADER_STR
     ];
 
     const ES_MSGS: &'static [&'static str] = &[
-        "Información de instrumento financiero,Header,Categoría de activo,Símbolo,Descripción,Conid,Id. de seguridad,Underlying,Merc. de cotización,Multiplicador,Tipo,Código", // STOCK_COMPANY_INFO_SECTOR_START_STR
-        "Información de instrumento financiero,Data,Acciones,", // STOCK_COMPANY_INFO_SECTOR_END_STR
-        "Posiciones abiertas,Header,DataDiscriminator,Categoría de activo,Divisa,Símbolo,Cantidad,Mult.,Precio de coste,Base de coste,Precio de cierre,Valor,PyG no realizadas,Código", // OPEN_POSITIONS_BEGIN_STR
-        "Posiciones abiertas,Total,,Acciones,EUR,", // OPEN_POSITIONS_END_STR
-        "Posiciones abiertas,Data,Summary,Acciones,", // OPEN_POSITIONS_STOCK_STR
-        "Posiciones abiertas,Total,,Acciones,",     // OPEN_POSITIONS_TOTAL_STR
+        "Información de instrumento financiero,Header,", // COMPANY_INFO_HEADER_STR
+        "Información de instrumento financiero,Data,",   // COMPANY_INFO_DATA_STR
+        "Posiciones abiertas,Data,Summary,",             // OPEN_POSITIONS_DATA_STR
+        "Posiciones abiertas,Total,,",                   // OPEN_POSITIONS_TOTAL_STR
         "Operaciones,Header,DataDiscriminator,Categoría de activo,Divisa,Cuenta,Símbolo,Fecha/Hora,Cantidad,Precio trans.,Precio de cier.,Productos,Tarifa/com.,Básico,PyG realizadas,MTM P/G,Código", // TRADE_BEGIN_STR
         "Operaciones,Header,DataDiscriminator,Categoría de activo,Divisa,Símbolo,Fecha/Hora,Cantidad,Precio trans.,Precio de cier.,Productos,Tarifa/com.,Básico,PyG realizadas,MTM P/G,Código", // TRADE_BEGIN_NO_ACCOUNT_STR
-        "Operaciones,Total,",               // TRADE_END_STR
-        "Operaciones,Data,Order,Acciones,", // TRADE_STOCK_STR
-        "Información de instrumento financiero,Header,Categoría de activo,Símbolo,Descripción,Conid,Id. de seguridad,Merc. de cotización,Multiplicador,Tipo,Código", // STOCK_COMPANY_INFO_SECTOR_START_OLD_STR
+        "Operaciones,Total,",          // TRADE_END_STR
+        "Operaciones,Data,Order,",     // TRADE_DATA_STR
+        "Acciones corporativas,Data,", // CORPORATE_ACTIONS_STR
+        "Acciones corporativas,Header,Categoría de activo,Divisa,Cuenta,", // CORPORATE_ACTIONS_ACCOUNT_HEADER_STR
+        "Posiciones abiertas,Header,", // OPEN_POSITIONS_HEADER_STR
     ];
 
+    /// Error of a statement without the section of the `index` message,
+    /// named as in the statement.
+    fn missing_section(locale: &[&str], index: usize) -> ImportError {
+        let section = locale[index].split(',').next().unwrap_or_default();
+        ImportError::MissingSection(section.to_string())
+    }
+
+    /// Companies of the financial instrument information by symbol, and the
+    /// symbols of the ETFs.
     fn parse_companies_info(
         content: &str,
         locale: &[&'static str],
-    ) -> Result<HashMap<String, CompanyInfo>> {
+    ) -> Result<(HashMap<String, CompanyInfo>, HashSet<String>)> {
         log::debug!("parse companies info");
         let mut result: HashMap<String, CompanyInfo> = HashMap::new();
+        let mut etfs: HashSet<String> = HashSet::new();
+        // Each asset category has its own header with different fields.
+        let mut header: Option<csv::StringRecord> = None;
+        let field_index = |header: &csv::StringRecord, names: &[&str]| {
+            header.iter().position(|field| names.contains(&field))
+        };
 
-        let start = content
-            .find(locale[IBCSVParser::STOCK_COMPANY_INFO_SECTOR_START_STR])
-            .or_else(|| content.find(locale[IBCSVParser::STOCK_COMPANY_INFO_SECTOR_START_OLD_STR]))
-            .ok_or_else(|| anyhow!("Not found beginning of companies info section"))?;
-
-        let end_left = content
-            .rfind(locale[IBCSVParser::STOCK_COMPANY_INFO_SECTOR_END_STR])
-            .ok_or_else(|| anyhow!("Not found end of companies info section"))?;
-
-        let end = content[end_left..]
-            .find('\n')
-            .ok_or_else(|| anyhow!("Not found end of companies info section"))?;
-
-        let mut rdr = csv::Reader::from_reader(&content.as_bytes()[start..end_left + end]);
-
-        for record_result in rdr.records() {
-            let record = record_result?;
+        for line in content.lines() {
+            let is_header = line.starts_with(locale[IBCSVParser::COMPANY_INFO_HEADER_STR]);
+            if !is_header && !line.starts_with(locale[IBCSVParser::COMPANY_INFO_DATA_STR]) {
+                continue;
+            }
+            let mut rdr = csv::ReaderBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .from_reader(line.as_bytes());
+            let Some(record) = rdr.records().next().transpose()? else {
+                continue;
+            };
+            if is_header {
+                header = Some(record);
+                continue;
+            }
+            let header = header.as_ref().ok_or_else(|| {
+                IBCSVParser::missing_section(locale, IBCSVParser::COMPANY_INFO_HEADER_STR)
+            })?;
+            let symbol = field_index(header, IBCSVParser::SYMBOL_FIELD_STRS)
+                .and_then(|index| record.get(index))
+                .ok_or_else(|| anyhow!("Unknown ticker"))?;
+            let name = field_index(header, IBCSVParser::DESCRIPTION_FIELD_STRS)
+                .and_then(|index| record.get(index))
+                .ok_or_else(|| anyhow!("Unknown company name"))?;
+            // Derivatives have no security id
+            let isin = field_index(header, IBCSVParser::SECURITY_ID_FIELD_STRS)
+                .and_then(|index| record.get(index))
+                .unwrap_or("");
+            if field_index(header, IBCSVParser::TYPE_FIELD_STRS)
+                .and_then(|index| record.get(index))
+                .is_some_and(|kind| kind == IBCSVParser::ETF_TYPE_STR)
+            {
+                etfs.insert(String::from(symbol));
+            }
             result.insert(
-                String::from(record.get(3).ok_or_else(|| anyhow!("Unknown ticker"))?),
+                String::from(symbol),
                 CompanyInfo {
-                    name: String::from(
-                        record
-                            .get(4)
-                            .ok_or_else(|| anyhow!("Unknown company name"))?,
-                    ),
-                    isin: String::from(record.get(6).ok_or_else(|| anyhow!("Unknown isin"))?),
+                    name: String::from(name),
+                    isin: String::from(isin),
                 },
             );
         }
 
-        Ok(result)
+        if result.is_empty() {
+            bail!(IBCSVParser::missing_section(
+                locale,
+                IBCSVParser::COMPANY_INFO_HEADER_STR
+            ));
+        }
+
+        Ok((result, etfs))
+    }
+
+    /// Field `index` of a row, rows shorter than the layout of the section
+    /// are malformed.
+    fn field<'a>(fields: &[&'a str], index: usize) -> Result<&'a str> {
+        fields
+            .get(index)
+            .copied()
+            .ok_or_else(|| RowIssue::UnexpectedLayout.into())
     }
 
     fn parse_account_note(&self, fields: &[&str]) -> Result<AccountNote> {
         log::debug!("account note fields {:?}", fields);
         let offset = if fields.len() == 16 { 0 } else { 1 };
-        let symbol = fields[5 + offset];
-        let date = fields[6 + offset];
-        let quantity_str = fields[7 + offset];
+        let symbol = IBCSVParser::field(fields, 5 + offset)?;
+        let date = IBCSVParser::field(fields, 6 + offset)?;
+        let quantity_str = IBCSVParser::field(fields, 7 + offset)?;
         let quantity = Decimal::from_str(&decimal::normalize_str(quantity_str))?;
         let operation = if quantity.is_sign_negative() {
             BrokerOperation::Sell
         } else {
             BrokerOperation::Buy
         };
-        let price = fields[8 + offset];
-        let value = fields[10 + offset];
-        let commision = fields[11 + offset];
-        let _earnings = fields[13 + offset];
+        let price = IBCSVParser::field(fields, 8 + offset)?;
+        let value = IBCSVParser::field(fields, 10 + offset)?;
+        let commision = IBCSVParser::field(fields, 11 + offset)?;
+        let _earnings = IBCSVParser::field(fields, 13 + offset)?;
         let company_info = if let Some(company) = self.companies_info.get(symbol) {
             company.clone()
         } else {
@@ -135,7 +205,7 @@ impl IBCSVParser {
             }
         };
 
-        Ok(AccountNote::new(
+        let mut note = AccountNote::new(
             NaiveDate::parse_from_str(date, "%Y-%m-%d %H:%M:%S")?,
             company_info,
             operation,
@@ -144,9 +214,15 @@ impl IBCSVParser {
             Decimal::from_str(&decimal::normalize_str(value))?.abs(),
             Decimal::from_str(&decimal::normalize_str(commision))?.abs(),
             &self.broker,
-        ))
+        );
+        if offset == 1 {
+            note.account = fields[5].to_string();
+        }
+
+        Ok(note)
     }
 
+    /// Trades of the statement.
     pub fn parse_account_notes(&self) -> Result<AccountNotes> {
         let mut result = Vec::new();
         let start = self
@@ -156,33 +232,53 @@ impl IBCSVParser {
                 self.content
                     .find(self.locale[IBCSVParser::TRADE_BEGIN__NO_ACCOUNT_STR])
             })
-            .ok_or_else(|| anyhow!("Not found beginning of trades section"))?;
+            .ok_or_else(|| {
+                IBCSVParser::missing_section(self.locale, IBCSVParser::TRADE_BEGIN_STR)
+            })?;
 
         let end = self
             .content
             .rfind(self.locale[IBCSVParser::TRADE_END_STR])
-            .ok_or_else(|| anyhow!("Not found end of trades section"))?;
+            .ok_or_else(|| IBCSVParser::missing_section(self.locale, IBCSVParser::TRADE_END_STR))?;
 
         let lines: Vec<&str> = (self.content[start..end - 1]).split('\n').collect();
+        let first_line = self.content[..start].matches('\n').count() + 1;
 
-        for line in lines.iter() {
-            if line.starts_with(self.locale[IBCSVParser::TRADE_STOCK_STR]) {
+        for (index, line) in lines.iter().enumerate() {
+            if line.starts_with(self.locale[IBCSVParser::TRADE_DATA_STR]) {
                 let replaced_line = util::replace_escaped_fields(line);
                 let fields: Vec<&str> = replaced_line.split(',').collect();
-                let account_note = self.parse_account_note(&fields)?;
-                result.push(account_note);
+                let location = Location::line(first_line + index).in_section(fields[0]);
+                let asset_category =
+                    IBCSVParser::field(&fields, 3).row_context(location.clone(), line)?;
+                match util::ib_asset_category(asset_category) {
+                    Some(category) if category.is_declarable() => {
+                        let account_note = self
+                            .parse_account_note(&fields)
+                            .row_context(location, line)?;
+                        result.push(account_note);
+                    }
+                    _ => log::debug!("Skipping {} trade: {}", asset_category, line),
+                }
             }
         }
 
         Ok(result)
     }
 
-    fn parse_balance_note(&self, fields: &[&str], currency: &Option<&str>) -> Result<BalanceNote> {
-        let symbol = fields[5];
-        let quantity = fields[6];
-        let mult = fields[7];
-        let price = fields[10];
-        let value_in_euro = fields[11];
+    fn parse_balance_note(
+        &self,
+        fields: &[&str],
+        category: AssetCategory,
+        with_account_field: bool,
+    ) -> Result<BalanceNote> {
+        let offset = if with_account_field { 1 } else { 0 };
+        let currency = IBCSVParser::field(fields, 4)?;
+        let symbol = IBCSVParser::field(fields, 5 + offset)?;
+        let quantity = IBCSVParser::field(fields, 6 + offset)?;
+        let mult = IBCSVParser::field(fields, 7 + offset)?;
+        let price = IBCSVParser::field(fields, 10 + offset)?;
+        let value_in_euro = IBCSVParser::field(fields, 11 + offset)?;
         let company_info = self
             .companies_info
             .get(symbol)
@@ -198,75 +294,90 @@ impl IBCSVParser {
             })
             .unwrap();
 
-        Ok(BalanceNote::new(
+        let mut note = BalanceNote::new(
             company_info,
             String::from(""),
             Decimal::from_str(&decimal::normalize_str(quantity))?
                 * Decimal::from_str(&decimal::normalize_str(mult))?,
-            String::from(currency.unwrap_or(IBCSVParser::EUR_CURRENCY_STR)),
+            String::from(currency),
             Decimal::from_str(&decimal::normalize_str(price))?,
             Decimal::from_str(&decimal::normalize_str(value_in_euro))?,
             &self.broker,
-        ))
+        );
+        note.category = if category == AssetCategory::Stock && self.etfs.contains(symbol) {
+            AssetCategory::Fund
+        } else {
+            category
+        };
+        if with_account_field {
+            note.account = fields[5].to_string();
+        }
+
+        Ok(note)
     }
 
-    pub fn parse_balance_notes(&self) -> Result<BalanceNotes> {
+    /// Open positions are grouped by asset category and currency. Each
+    /// currency group ends with its total, followed by the total in euros
+    /// when the currency is not the euro, which is used to convert the
+    /// values of the group.
+    pub fn parse_balance_notes(&mut self) -> Result<BalanceNotes> {
         let mut balance_notes = vec![];
 
-        let start = self
+        if !self
             .content
-            .find(self.locale[IBCSVParser::OPEN_POSITIONS_BEGIN_STR])
-            .ok_or_else(|| anyhow!("Not found beginning of open position section"))?;
-
-        let end = self
-            .content
-            .rfind(self.locale[IBCSVParser::OPEN_POSITIONS_END_STR])
-            .ok_or_else(|| anyhow!("Not found end of open position section"))?;
-
-        let lines: Vec<&str> = (self.content[start..end - 1]).split('\n').collect();
+            .contains(self.locale[IBCSVParser::OPEN_POSITIONS_DATA_STR])
+        {
+            bail!(IBCSVParser::missing_section(
+                self.locale,
+                IBCSVParser::OPEN_POSITIONS_DATA_STR
+            ));
+        }
 
-        let mut state = NoteState::Invalid;
         let mut current_notes: BalanceNotes = Vec::new();
-        let mut currency = None;
-
-        for line in lines.iter() {
-            match state {
-                NoteState::Invalid => {
-                    log::debug!("Invalid state");
-                    if line.starts_with(self.locale[IBCSVParser::OPEN_POSITIONS_STOCK_STR]) {
-                        state = NoteState::Stocks;
-                        let fields: Vec<&str> = line.split(',').collect();
-                        currency = Some(fields[4]);
-                        let balance_note = self.parse_balance_note(&fields, &currency)?;
-                        current_notes.push(balance_note);
-                    }
-                }
-                NoteState::Stocks => {
-                    log::debug!("Stocks state");
-                    if line.starts_with(self.locale[IBCSVParser::OPEN_POSITIONS_STOCK_STR]) {
-                        let fields: Vec<&str> = line.split(',').collect();
-                        currency = Some(fields[4]);
-                        let balance_note = self.parse_balance_note(&fields, &currency)?;
-                        current_notes.push(balance_note);
-                    } else if line.starts_with(self.locale[IBCSVParser::OPEN_POSITIONS_TOTAL_STR]) {
-                        state = NoteState::Total;
-                        if currency == Some(IBCSVParser::EUR_CURRENCY_STR) {
-                            state = NoteState::Stocks;
-                            balance_notes.append(&mut current_notes);
-                        }
-                    }
+        let mut currency = String::new();
+        let mut pending_conversion = false;
+        // Consolidated statements of several accounts have an account column
+        let mut with_account_field = false;
+
+        for (index, line) in self.content.lines().enumerate() {
+            let replaced_line = util::replace_escaped_fields(line);
+            let fields: Vec<&str> = replaced_line.split(',').collect();
+            if line.starts_with(self.locale[IBCSVParser::OPEN_POSITIONS_HEADER_STR]) {
+                with_account_field = fields
+                    .get(5)
+                    .is_some_and(|field| IBCSVParser::ACCOUNT_FIELD_STRS.contains(field));
+            } else if line.starts_with(self.locale[IBCSVParser::OPEN_POSITIONS_DATA_STR]) {
+                let location = Location::line(index + 1).in_section(fields[0]);
+                let asset_category =
+                    IBCSVParser::field(&fields, 3).row_context(location.clone(), line)?;
+                let Some(category) = util::ib_asset_category(asset_category) else {
+                    continue;
+                };
+                currency = fields.get(4).unwrap_or(&"").to_string();
+                pending_conversion = false;
+                current_notes.push(
+                    self.parse_balance_note(&fields, category, with_account_field)
+                        .row_context(location, line)?,
+                );
+            } else if line.starts_with(self.locale[IBCSVParser::OPEN_POSITIONS_TOTAL_STR]) {
+                if current_notes.is_empty() {
+                    continue;
                 }
-                NoteState::Total => {
-                    log::debug!("Total state");
-
-                    state = NoteState::Stocks;
-                    let fields: Vec<&str> = line.split(',').collect();
-                    let total_in_euro_str = fields[11];
-                    let total_in_euro =
-                        Decimal::from_str(&decimal::normalize_str(total_in_euro_str))?;
+                if pending_conversion {
+                    let offset = if with_account_field { 1 } else { 0 };
+                    let total_in_euro = IBCSVParser::field(&fields, 11 + offset)
+                        .and_then(|total| Ok(Decimal::from_str(&decimal::normalize_str(total))?))
+                        .row_context(Location::line(index + 1).in_section(fields[0]), line)?;
                     log::debug!("total in eur: {:?}", total_in_euro);
                     util::recalculate_balance_notes(&mut current_notes, &total_in_euro)?;
+                    self.warnings
+                        .push(ImportWarning::RescaledSection(currency.clone()));
                     balance_notes.append(&mut current_notes);
+                    pending_conversion = false;
+                } else if currency == IBCSVParser::EUR_CURRENCY_STR {
+                    balance_notes.append(&mut current_notes);
+                } else {
+                    pending_conversion = true;
                 }
             }
         }
@@ -274,27 +385,227 @@ impl IBCSVParser {
         Ok(balance_notes)
     }
 
+    /// Security the row refers to, described at the end of the description
+    /// as `(SYMBOL, NAME, ISIN)`.
+    fn corporate_action_security(description: &str) -> Option<CompanyInfo> {
+        let begin = description.rfind('(')?;
+        let end = description.rfind(')')?;
+        let fields: Vec<&str> = description.get(begin + 1..end)?.split(", ").collect();
+        if fields.len() < 3 {
+            return None;
+        }
+
+        Some(CompanyInfo {
+            name: fields[1..fields.len() - 1].join(", "),
+            isin: fields[fields.len() - 1].to_string(),
+        })
+    }
+
+    /// Security subject of the corporate action, `SYMBOL(ISIN)` at the
+    /// beginning of the description.
+    fn corporate_action_subject(description: &str) -> Option<String> {
+        let begin = description.find('(')?;
+        let end = description[begin..].find(')')? + begin;
+        Some(description[begin + 1..end].to_string())
+    }
+
+    /// New shares received for each share held, `N for M` in the description.
+    fn corporate_action_ratio(description: &str) -> Option<Decimal> {
+        let tokens: Vec<&str> = description.split_whitespace().collect();
+        let index = tokens
+            .iter()
+            .position(|token| IBCSVParser::RATIO_STRS.contains(token))?;
+        let new_shares = Decimal::from_str(tokens.get(index.checked_sub(1)?)?).ok()?;
+        let old_shares = Decimal::from_str(tokens.get(index + 1)?).ok()?;
+        if old_shares.is_zero() {
+            return None;
+        }
+
+        Some(new_shares / old_shares)
+    }
+
+    fn parse_corporate_action(
+        &self,
+        date: NaiveDate,
+        account: &str,
+        description: &str,
+        rows: &[(CompanyInfo, Decimal)],
+    ) -> Option<CorporateActionNote> {
+        let contains = |strs: &[&str]| strs.iter().any(|x| description.contains(x));
+        let subject_isin = IBCSVParser::corporate_action_subject(description)?;
+        let subject = rows
+            .iter()
+            .map(|(company, _)| company)
+            .find(|company| company.isin == subject_isin)
+            .cloned()
+            .or_else(|| {
+                self.companies_info
+                    .values()
+                    .find(|company| company.isin == subject_isin)
+                    .cloned()
+            })
+            .unwrap_or_else(|| CompanyInfo {
+                name: subject_isin.clone(),
+                isin: subject_isin.clone(),
+            });
+        let removed = rows
+            .iter()
+            .filter(|(company, quantity)| {
+                company.isin == subject_isin && quantity.is_sign_negative()
+            })
+            .fold(Decimal::ZERO, |acc, (_, quantity)| acc + quantity.abs());
+        let received = rows.iter().find(|(company, quantity)| {
+            company.isin != subject_isin && quantity.is_sign_positive()
+        });
+        let ratio = match received {
+            Some((_, quantity)) if !removed.is_zero() => Some(quantity / removed),
+            _ => IBCSVParser::corporate_action_ratio(description),
+        };
+
+        let kind = if contains(IBCSVParser::SPLIT_STRS) {
+            match received {
+                Some((new_company, _)) => CorporateActionKind::IsinChange {
+                    new_company: new_company.clone(),
+                    ratio: ratio?,
+                },
+                None => CorporateActionKind::Split {
+                    ratio: IBCSVParser::corporate_action_ratio(description)?,
+                },
+            }
+        } else if contains(IBCSVParser::ISIN_CHANGE_STRS) {
+            CorporateActionKind::IsinChange {
+                new_company: received?.0.clone(),
+                ratio: ratio?,
+            }
+        } else if contains(IBCSVParser::MERGER_STRS) {
+            CorporateActionKind::Merger {
+                new_company: received?.0.clone(),
+                ratio: ratio?,
+            }
+        } else if contains(IBCSVParser::SPINOFF_STRS) {
+            CorporateActionKind::SpinOff {
+                new_company: received?.0.clone(),
+                ratio: IBCSVParser::corporate_action_ratio(description)?,
+            }
+        } else {
+            log::debug!("Unsupported corporate action: {}", description);
+            return None;
+        };
+
+        let mut note = CorporateActionNote::new(date, subject, kind, &self.broker);
+        note.account = account.to_string();
+
+        Some(note)
+    }
+
+    /// Corporate actions of the statement.
+    pub fn parse_corporate_action_notes(&self) -> Result<CorporateActionNotes> {
+        let offset = if self
+            .content
+            .contains(self.locale[IBCSVParser::CORPORATE_ACTIONS_ACCOUNT_HEADER_STR])
+        {
+            1
+        } else {
+            0
+        };
+        // Rows of the same corporate action share date, account and
+        // description except for the security they refer to.
+        let mut actions: Vec<(NaiveDate, String, String, CorporateActionRows)> = vec![];
+
+        for line in self.content.lines() {
+            if !line.starts_with(self.locale[IBCSVParser::CORPORATE_ACTIONS_STR]) {
+                continue;
+            }
+            let mut rdr = csv::ReaderBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .from_reader(line.as_bytes());
+            let Some(record) = rdr.records().next().transpose()? else {
+                continue;
+            };
+            if record.get(2).is_none_or(|x| x.starts_with("Total")) {
+                continue;
+            }
+            log::debug!("corporate action fields {:?}", record);
+            let account = if offset == 1 {
+                record.get(4).unwrap_or("").to_string()
+            } else {
+                String::new()
+            };
+            let date_str = record
+                .get(5 + offset)
+                .ok_or_else(|| anyhow!("No corporate action date found"))?;
+            let date =
+                NaiveDate::parse_from_str(date_str.get(0..10).unwrap_or(date_str), "%Y-%m-%d")?;
+            let description = record
+                .get(6 + offset)
+                .ok_or_else(|| anyhow!("No corporate action description found"))?;
+            let quantity = Decimal::from_str(&decimal::normalize_str(
+                record
+                    .get(7 + offset)
+                    .ok_or_else(|| anyhow!("No corporate action quantity found"))?,
+            ))?;
+            let Some(company) = IBCSVParser::corporate_action_security(description) else {
+                log::error!("Not security found in corporate action: {}", description);
+                continue;
+            };
+            let prefix = description
+                .rfind(" (")
+                .map_or(description, |index| &description[..index])
+                .to_string();
+
+            match actions
+                .iter_mut()
+                .find(|(action_date, action_account, action_prefix, _)| {
+                    *action_date == date && *action_account == account && *action_prefix == prefix
+                }) {
+                Some((_, _, _, rows)) => rows.push((company, quantity)),
+                None => actions.push((date, account, prefix, vec![(company, quantity)])),
+            }
+        }
+
+        let mut result: CorporateActionNotes = actions
+            .iter()
+            .filter_map(|(date, account, description, rows)| {
+                self.parse_corporate_action(*date, account, description, rows)
+            })
+            .collect();
+        result.sort_by_key(|action| action.date);
+
+        Ok(result)
+    }
+
+    /// Parser of an activity statement, failing when it has no financial
+    /// instrument information.
     pub fn new(content: String, broker: &Arc<BrokerInformation>) -> Result<Self> {
         let locale = if content.contains(IBCSVParser::ES_HEADER_CONTENT) {
             IBCSVParser::ES_MSGS
         } else {
             IBCSVParser::EN_MSGS
         };
-        let companies_info = IBCSVParser::parse_companies_info(&content, locale)?;
+        let (companies_info, etfs) = IBCSVParser::parse_companies_info(&content, locale)?;
 
         Ok(Self {
             content,
             locale,
             broker: Arc::clone(broker),
             companies_info,
+            etfs,
+            warnings: vec![],
         })
     }
+
+    /// Sections rescaled while parsing the open positions.
+    pub fn warnings(&self) -> &[ImportWarning] {
+        &self.warnings
+    }
 }
 
 #[cfg(test)]
 #[allow(clippy::zero_prefixed_literal)]
 mod tests {
     use super::*;
+    use crate::error::RowIssue;
 
     #[ctor::ctor]
     fn init() {
@@ -353,7 +664,7 @@ mod tests {
             String::from("IE"),
         ));
 
-        let parser = IBCSVParser::new(INPUT_2021.to_string(), &ib_broker).unwrap();
+        let mut parser = IBCSVParser::new(INPUT_2021.to_string(), &ib_broker).unwrap();
         let balance_notes = parser.parse_balance_notes().unwrap();
         let bal_notes = get_test_balance_notes(&ib_broker);
 
@@ -367,11 +678,18 @@ mod tests {
             String::from("IE"),
         ));
 
-        let parser = IBCSVParser::new(INPUT_2021_ES.to_string(), &ib_broker).unwrap();
+        let mut parser = IBCSVParser::new(INPUT_2021_ES.to_string(), &ib_broker).unwrap();
         let balance_notes = parser.parse_balance_notes().unwrap();
         let bal_notes = get_test_balance_notes(&ib_broker);
 
         assert_eq!(&bal_notes, &balance_notes);
+        let rescaled = |currency: &str| {
+            parser
+                .warnings()
+                .contains(&ImportWarning::RescaledSection(currency.to_string()))
+        };
+        assert!(rescaled("AUD") && rescaled("USD"));
+        assert!(!rescaled("EUR"));
     }
 
     #[test]
@@ -383,7 +701,10 @@ mod tests {
         let ibparser = IBCSVParser::new(INPUT_2021.to_string(), &ib_broker).unwrap();
         let notes = ibparser.parse_account_notes().unwrap();
 
-        let acc_notes = get_test_account_notes(&ib_broker);
+        let mut acc_notes = get_test_account_notes(&ib_broker);
+        acc_notes
+            .iter_mut()
+            .for_each(|note| note.account = String::from(TEST_ACCOUNT));
 
         compare_vectors_by_item(&acc_notes, &notes);
         assert_eq!(acc_notes, notes);
@@ -398,18 +719,267 @@ mod tests {
         let ibparser = IBCSVParser::new(INPUT_2021_ES.to_string(), &ib_broker).unwrap();
         let notes = ibparser.parse_account_notes().unwrap();
 
-        let acc_notes = get_test_account_notes(&ib_broker);
+        let mut acc_notes = get_test_account_notes(&ib_broker);
+        acc_notes
+            .iter_mut()
+            .for_each(|note| note.account = String::from(TEST_ACCOUNT));
 
         compare_vectors_by_item(&acc_notes, &notes);
         assert_eq!(acc_notes, notes);
     }
 
+    #[test]
+    fn test_parse_account_notes_malformed_row() {
+        let ib_broker: Arc<BrokerInformation> = Arc::new(BrokerInformation::new(
+            String::from("Interactive Brokers"),
+            String::from("IE"),
+        ));
+        let input = INPUT_2021_ES.replace("2021-01-13, 17:59:58", "2021-13-13, 17:59:58");
+        let ibparser = IBCSVParser::new(input, &ib_broker).unwrap();
+        let err = ImportError::from(ibparser.parse_account_notes().unwrap_err());
+
+        assert_eq!(
+            err,
+            ImportError::MalformedRow {
+                location: Location::line(235).in_section("Operaciones"),
+                raw: String::from(
+                    "Operaciones,Data,Order,Acciones,AUD,U1111111,ANO,\"2021-13-13, 17:59:58\",950,4.33,4.14,-4113.5,-6,4119.5,0,-180.5,O;P"
+                ),
+                issue: RowIssue::InvalidDate,
+            }
+        );
+        assert!(
+            err.localized()
+                .starts_with("línea 235 de la sección Operaciones: fecha inválida")
+        );
+
+        let input = INPUT_2021_ES.replace(
+            "Operaciones,Data,Order,Acciones,AUD,U1111111,ANO,\"2021-01-13, 17:59:58\",950,4.33,4.14,-4113.5,-6,4119.5,0,-180.5,O;P",
+            "Operaciones,Data,Order,Acciones,AUD,U1111111,ANO,\"2021-01-13, 17:59:58\",950",
+        );
+        let ibparser = IBCSVParser::new(input, &ib_broker).unwrap();
+        let err = ImportError::from(ibparser.parse_account_notes().unwrap_err());
+
+        assert_eq!(
+            err,
+            ImportError::MalformedRow {
+                location: Location::line(235).in_section("Operaciones"),
+                raw: String::from(
+                    "Operaciones,Data,Order,Acciones,AUD,U1111111,ANO,\"2021-01-13, 17:59:58\",950"
+                ),
+                issue: RowIssue::UnexpectedLayout,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_corporate_action_notes() {
+        let ib_broker: Arc<BrokerInformation> = Arc::new(BrokerInformation::new(
+            String::from("Interactive Brokers"),
+            String::from("IE"),
+        ));
+        let mut actions = get_test_corporate_action_notes(&ib_broker);
+        actions
+            .iter_mut()
+            .for_each(|action| action.account = String::from(TEST_ACCOUNT));
+
+        for input in [INPUT_2021, INPUT_2021_ES] {
+            let ibparser = IBCSVParser::new(input.to_string(), &ib_broker).unwrap();
+            let notes = ibparser.parse_corporate_action_notes().unwrap();
+
+            compare_vectors_by_item(&actions, &notes);
+            assert_eq!(actions, notes);
+        }
+    }
+
+    fn get_test_corporate_action_notes(
+        broker: &Arc<BrokerInformation>,
+    ) -> Vec<CorporateActionNote> {
+        let company = |name: &str, isin: &str| CompanyInfo {
+            name: String::from(name),
+            isin: String::from(isin),
+        };
+        let date = |month, day| NaiveDate::from_ymd_opt(2021, month, day).unwrap();
+
+        vec![
+            CorporateActionNote::new(
+                date(2, 4),
+                company("ILOOKABOUT CORP", "CA45236R1010"),
+                CorporateActionKind::IsinChange {
+                    new_company: company("VOXTUR ANALYTICS CORP", "CA9290821052"),
+                    ratio: Decimal::ONE,
+                },
+                broker,
+            ),
+            CorporateActionNote::new(
+                date(6, 21),
+                company("LVOX 07MAR24 11.5 C", "US22564L1136"),
+                CorporateActionKind::Merger {
+                    new_company: company("LVOXW 18JUN26 11.5 C", "US53838L1180"),
+                    ratio: Decimal::ONE,
+                },
+                broker,
+            ),
+            CorporateActionNote::new(
+                date(7, 1),
+                company("ADVANCE NANOTEK LTD - RIGHTS SUBSCRIPTION", "AU00ANO7SUB6"),
+                CorporateActionKind::Merger {
+                    new_company: company("ADVANCE NANOTEK LTD", "AU000000ANO7"),
+                    ratio: Decimal::ONE,
+                },
+                broker,
+            ),
+            CorporateActionNote::new(
+                date(11, 11),
+                company("JD.COM INC-ADR", "US47215P1066"),
+                CorporateActionKind::Merger {
+                    new_company: company("JD.COM INC-ADR - TENDER", "US47215PCNV0"),
+                    ratio: Decimal::ONE,
+                },
+                broker,
+            ),
+            CorporateActionNote::new(
+                date(11, 17),
+                company("JD.COM INC-ADR - TENDER", "US47215PCNV0"),
+                CorporateActionKind::Merger {
+                    new_company: company("JD.COM INC - CL A", "KYG8208B1014"),
+                    ratio: Decimal::new(2, 0),
+                },
+                broker,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_parse_non_stock_instruments() {
+        let ib_broker: Arc<BrokerInformation> = Arc::new(BrokerInformation::new(
+            String::from("Interactive Brokers"),
+            String::from("IE"),
+        ));
+        let mut parser = IBCSVParser::new(INPUT_NON_STOCKS.to_string(), &ib_broker).unwrap();
+        let categories: Vec<(String, AssetCategory)> = parser
+            .parse_balance_notes()
+            .unwrap()
+            .into_iter()
+            .map(|note| (note.company.isin, note.category))
+            .collect();
+
+        assert_eq!(
+            categories,
+            vec![
+                (String::from("US912828YV68"), AssetCategory::Bond),
+                (String::from(""), AssetCategory::Option),
+                (String::from("IE00B4L5Y983"), AssetCategory::Fund),
+                (String::from("IE00B3XXRP09"), AssetCategory::Fund),
+                (String::from("NL0012969182"), AssetCategory::Stock),
+            ]
+        );
+
+        let notes = parser.parse_account_notes().unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].company.isin, "US912828YV68");
+    }
+
+    const INPUT_NON_STOCKS: &str = r#"Statement,Header,Field Name,Field Value
+Open Positions,Header,DataDiscriminator,Asset Category,Currency,Symbol,Quantity,Mult,Cost Price,Cost Basis,Close Price,Value,Unrealized P/L,Code
+Open Positions,Data,Summary,Bonds,EUR,T 1 1/2 11/30/24,1000,1,0.99,990,0.98,980,-10,
+Open Positions,Total,,Bonds,EUR,,,,,990,,980,-10,
+Open Positions,Header,DataDiscriminator,Asset Category,Currency,Symbol,Quantity,Mult,Cost Price,Cost Basis,Close Price,Value,Unrealized P/L,Code
+Open Positions,Data,Summary,Equity and Index Options,USD,AAPL 17JAN25 200 C,1,100,5,500,4,400,-100,
+Open Positions,Total,,Equity and Index Options,USD,,,,,500,,400,-100,
+Open Positions,Total,,Equity and Index Options,EUR,,,,,450,,360,-90,
+Open Positions,Header,DataDiscriminator,Asset Category,Currency,Symbol,Quantity,Mult,Cost Price,Cost Basis,Close Price,Value,Unrealized P/L,Code
+Open Positions,Data,Summary,Funds,EUR,IWDA,10,1,70,700,80,800,100,
+Open Positions,Total,,Funds,EUR,,,,,700,,800,100,
+Open Positions,Header,DataDiscriminator,Asset Category,Currency,Symbol,Quantity,Mult,Cost Price,Cost Basis,Close Price,Value,Unrealized P/L,Code
+Open Positions,Data,Summary,Stocks,EUR,VUSA,10,1,70,700,90,900,200,
+Open Positions,Data,Summary,Stocks,EUR,ADYEN,1,1,1500,1500,1200,1200,-300,
+Open Positions,Total,,Stocks,EUR,,,,,2200,,2100,-100,
+Trades,Header,DataDiscriminator,Asset Category,Currency,Account,Symbol,Date/Time,Quantity,T. Price,C. Price,Proceeds,Comm/Fee,Basis,Realized P/L,MTM P/L,Code
+Trades,Data,Order,Bonds,EUR,U1111111,T 1 1/2 11/30/24,"2021-03-01, 10:00:00",1000,0.99,0.99,-990,-1,991,0,0,O
+Trades,Data,Order,Equity and Index Options,USD,U1111111,AAPL 17JAN25 200 C,"2021-03-02, 10:00:00",1,5,5,-500,-1,501,0,0,O
+Trades,Total,,,,,,,,,,,,,,,
+Financial Instrument Information,Header,Asset Category,Symbol,Description,Conid,Security ID,Listing Exch,Multiplier,Type,Code
+Financial Instrument Information,Data,Bonds,T 1 1/2 11/30/24,T 1 1/2 11/30/24,123,US912828YV68,,1,BOND,
+Financial Instrument Information,Data,Funds,IWDA,ISHARES CORE MSCI WORLD,456,IE00B4L5Y983,AEB,1,ETF,
+Financial Instrument Information,Data,Stocks,VUSA,VANGUARD S&P 500 UCITS ETF,654,IE00B3XXRP09,AEB,1,ETF,
+Financial Instrument Information,Data,Stocks,ADYEN,ADYEN NV,321202935,NL0012969182,AEB,1,COMMON,
+Financial Instrument Information,Header,Asset Category,Symbol,Description,Conid,Underlying,Listing Exch,Multiplier,Expiry,Delivery Month,Type,Strike,Code
+Financial Instrument Information,Data,Equity and Index Options,AAPL 17JAN25 200 C,AAPL 17JAN25 200 C,789,AAPL,CBOE,100,2025-01-17,2025-01,C,200,
+"#;
+
+    #[test]
+    fn test_parse_consolidated_balance_notes() {
+        let ib_broker: Arc<BrokerInformation> = Arc::new(BrokerInformation::new(
+            String::from("Interactive Brokers"),
+            String::from("IE"),
+        ));
+        let mut parser = IBCSVParser::new(INPUT_CONSOLIDATED.to_string(), &ib_broker).unwrap();
+        let accounts: Vec<(String, Decimal, Decimal)> = parser
+            .parse_balance_notes()
+            .unwrap()
+            .into_iter()
+            .map(|note| (note.account, note.quantity, note.value_in_euro))
+            .collect();
+
+        assert_eq!(
+            accounts,
+            vec![
+                (
+                    String::from("U1111111"),
+                    Decimal::new(10, 0),
+                    Decimal::new(1800, 0)
+                ),
+                (
+                    String::from("U1111111"),
+                    Decimal::new(10, 0),
+                    Decimal::new(2000, 0)
+                ),
+                (
+                    String::from("U2111111"),
+                    Decimal::new(5, 0),
+                    Decimal::new(1000, 0)
+                ),
+            ]
+        );
+    }
+
+    const INPUT_CONSOLIDATED: &str = r#"Statement,Header,Field Name,Field Value
+Open Positions,Header,DataDiscriminator,Asset Category,Currency,Account,Symbol,Quantity,Mult,Cost Price,Cost Basis,Close Price,Value,Unrealized P/L,Code
+Open Positions,Data,Summary,Stocks,USD,U1111111,AAPL,10,1,150,1500,200,2000,500,
+Open Positions,Total,,Stocks,USD,,,,,,1500,,2000,500,
+Open Positions,Total,,Stocks,EUR,,,,,,1350,,1800,450,
+Open Positions,Header,DataDiscriminator,Asset Category,Currency,Account,Symbol,Quantity,Mult,Cost Price,Cost Basis,Close Price,Value,Unrealized P/L,Code
+Open Positions,Data,Summary,Stocks,EUR,U1111111,ADYEN,10,1,150,1500,200,2000,500,
+Open Positions,Data,Summary,Stocks,EUR,U2111111,ADYEN,5,1,150,750,200,1000,250,
+Open Positions,Total,,Stocks,EUR,,,,,,2250,,3000,750,
+Financial Instrument Information,Header,Asset Category,Symbol,Description,Conid,Security ID,Listing Exch,Multiplier,Type,Code
+Financial Instrument Information,Data,Stocks,ADYEN,ADYEN NV,321202935,NL0012969182,AEB,1,COMMON,
+Financial Instrument Information,Data,Stocks,AAPL,APPLE INC,265598,US0378331005,NASDAQ,1,COMMON,
+"#;
+
+    const TEST_ACCOUNT: &str = "U1111111";
     const INPUT_2021: &str = include_str!("testdata/ib_test.csv");
     const INPUT_2021_ES: &str = include_str!("testdata/ib_test_es.csv");
 
     #[allow(clippy::mistyped_literal_suffixes)]
     fn get_test_balance_notes(broker: &Arc<BrokerInformation>) -> Vec<BalanceNote> {
+        let mut warrant = BalanceNote::new(
+            CompanyInfo {
+                name: String::from("LVOXW 18JUN26 11.5 C"),
+                isin: String::from("US53838L1180"),
+            },
+            String::from(""),
+            Decimal::new(4400, 0),
+            String::from("USD"),
+            Decimal::new(0_6082, 4),
+            Decimal::new(2353_16, 2),
+            broker,
+        );
+        warrant.category = AssetCategory::Warrant;
+
         vec![
+            warrant,
             BalanceNote::new(
                 CompanyInfo {
                     name: String::from("ADVANCE ZINCTEK LTD"),
@@ -584,6 +1154,32 @@ mod tests {
     #[allow(clippy::mistyped_literal_suffixes)]
     fn get_test_account_notes(broker: &Arc<BrokerInformation>) -> Vec<AccountNote> {
         vec![
+            AccountNote::new(
+                NaiveDate::from_ymd_opt(2021, 04, 01).unwrap(),
+                CompanyInfo {
+                    name: String::from("LVOX 07MAR24 11.5 C"),
+                    isin: String::from("US22564L1136"),
+                },
+                BrokerOperation::Buy,
+                Decimal::new(3100, 0),
+                Decimal::new(1_12, 2),
+                Decimal::new(3472, 0),
+                Decimal::new(15_5, 1),
+                broker,
+            ),
+            AccountNote::new(
+                NaiveDate::from_ymd_opt(2021, 04, 28).unwrap(),
+                CompanyInfo {
+                    name: String::from("LVOX 07MAR24 11.5 C"),
+                    isin: String::from("US22564L1136"),
+                },
+                BrokerOperation::Buy,
+                Decimal::new(1300, 0),
+                Decimal::new(1, 0),
+                Decimal::new(1300, 0),
+                Decimal::new(6_5, 1),
+                broker,
+            ),
             AccountNote::new(
                 NaiveDate::from_ymd_opt(2021, 01, 13).unwrap(),
                 CompanyInfo {
@@ -901,6 +1497,20 @@ mod tests {
 
     fn companies_info() -> HashMap<String, CompanyInfo> {
         HashMap::from([
+            (
+                "CRSAW".to_string(),
+                CompanyInfo {
+                    name: "LVOX 07MAR24 11.5 C".to_string(),
+                    isin: "US22564L1136".to_string(),
+                },
+            ),
+            (
+                "LVOXW".to_string(),
+                CompanyInfo {
+                    name: "LVOXW 18JUN26 11.5 C".to_string(),
+                    isin: "US53838L1180".to_string(),
+                },
+            ),
             (
                 "3328.T".to_string(),
                 CompanyInfo {
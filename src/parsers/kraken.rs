@@ -0,0 +1,179 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::{str::FromStr, sync::Arc};
+
+use crate::{
+    data::{BrokerInformation, CryptoNote, CryptoNotes},
+    error::{ImportError, RowContext},
+    parsers::util::is_fiat_currency,
+};
+
+/// First columns of the Kraken ledgers export, used to detect it.
+pub(crate) const KRAKEN_HEADER_BEGIN: &str = "\"txid\",\"refid\",\"time\",\"type\"";
+
+const TXID_FIELD: &str = "txid";
+const TIME_FIELD: &str = "time";
+const ASSET_FIELD: &str = "asset";
+const AMOUNT_FIELD: &str = "amount";
+const FEE_FIELD: &str = "fee";
+
+/// Assets listed by Kraken before 2018, named with an `X` (crypto) or `Z`
+/// (fiat) prefix.
+const LEGACY_ASSETS: [&str; 17] = [
+    "XETC", "XETH", "XLTC", "XMLN", "XREP", "XXBT", "XXDG", "XXLM", "XXMR", "XXRP", "XZEC", "ZAUD",
+    "ZCAD", "ZEUR", "ZGBP", "ZJPY", "ZUSD",
+];
+
+/// Parser of the ledgers CSV exported by Kraken, every movement of the
+/// account balances with the amount and the fee charged.
+#[derive(Debug)]
+pub struct KrakenParser {
+    content: String,
+    custodian: Arc<BrokerInformation>,
+}
+
+impl KrakenParser {
+    fn field(record: &csv::StringRecord, index: usize) -> &str {
+        record.get(index).unwrap_or("").trim()
+    }
+
+    fn parse_decimal(value: &str) -> Result<Decimal> {
+        if value.is_empty() {
+            return Ok(Decimal::ZERO);
+        }
+        Decimal::from_str(value)
+            .or_else(|_| Decimal::from_scientific(value))
+            .with_context(|| format!("Invalid amount {}", value))
+    }
+
+    fn parse_date(value: &str) -> Result<NaiveDate> {
+        NaiveDate::parse_from_str(value.get(..10).unwrap_or(value), "%Y-%m-%d")
+            .with_context(|| format!("Invalid date {}", value))
+    }
+
+    /// Kraken suffixes the staked and earning balances (`ETH2.S`, `DOT.S`,
+    /// `USDC.M`), so staking transfers net out once the asset is normalized.
+    fn normalize_asset(asset: &str) -> String {
+        let asset = asset.split('.').next().unwrap_or(asset);
+        let asset = match asset {
+            "ETH2" => "ETH",
+            _ if LEGACY_ASSETS.contains(&asset) => &asset[1..],
+            _ => asset,
+        };
+
+        match asset {
+            "XBT" => String::from("BTC"),
+            "XDG" => String::from("DOGE"),
+            _ => asset.to_string(),
+        }
+    }
+
+    /// Balance changes of the virtual currencies held in Kraken, the fee is
+    /// discounted from the amount as the ledger gives them apart.
+    pub fn parse_crypto_notes(&self) -> Result<CryptoNotes> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(self.content.as_bytes());
+        let headers = rdr.headers()?.clone();
+        let column = |field: &str| {
+            headers
+                .iter()
+                .position(|header| header == field)
+                .ok_or_else(|| anyhow!(ImportError::MissingColumn(field.to_string())))
+        };
+        let txid = column(TXID_FIELD)?;
+        let time = column(TIME_FIELD)?;
+        let asset = column(ASSET_FIELD)?;
+        let amount = column(AMOUNT_FIELD)?;
+        let fee = column(FEE_FIELD)?;
+
+        let mut crypto_notes = vec![];
+        for result in rdr.records() {
+            let record = result?;
+            log::debug!("{:?}", record);
+            // Entries without transaction id are pending movements, repeated
+            // once they are settled.
+            if KrakenParser::field(&record, txid).is_empty() {
+                continue;
+            }
+            let currency = KrakenParser::normalize_asset(KrakenParser::field(&record, asset));
+            if is_fiat_currency(&currency) {
+                continue;
+            }
+            let quantity = KrakenParser::parse_decimal(KrakenParser::field(&record, amount))
+                .record_context(&record)?
+                - KrakenParser::parse_decimal(KrakenParser::field(&record, fee))
+                    .record_context(&record)?;
+
+            crypto_notes.push(CryptoNote::new(
+                KrakenParser::parse_date(KrakenParser::field(&record, time))
+                    .record_context(&record)?,
+                currency,
+                quantity,
+                &self.custodian,
+            ));
+        }
+
+        Ok(crypto_notes)
+    }
+
+    /// Parser of a Kraken ledger held by `custodian`.
+    pub fn new(content: String, custodian: &Arc<BrokerInformation>) -> Self {
+        Self {
+            content,
+            custodian: Arc::clone(custodian),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = r#""txid","refid","time","type","subtype","aclass","asset","amount","fee","balance"
+"","QCCBSNH-3XG5MW-ABCDEF","2023-01-10 09:00:00","deposit","","currency","ZEUR",1000.0000,0.0000,""
+"L4UESK-KG3EQ-UFO4T5","QCCBSNH-3XG5MW-ABCDEF","2023-01-10 09:05:00","deposit","","currency","ZEUR",1000.0000,0.0000,1000.0000
+"LKZL2M-ABCDE-FGHIJK","TJKLXX-5YCJF-ABCDEF","2023-01-12 10:00:00","trade","","currency","ZEUR",-330.0000,0.8600,669.1400
+"LBQRN4-ABCDE-FGHIJK","TJKLXX-5YCJF-ABCDEF","2023-01-12 10:00:00","trade","","currency","XXBT",0.0200000000,0.0000000000,0.0200000000
+"LSTK01-ABCDE-FGHIJK","RUSTK-ABCDE-FGHIJK","2023-02-01 08:00:00","transfer","spottostaking","currency","XETH",-0.5000000000,0.0000000000,0.0000000000
+"LSTK02-ABCDE-FGHIJK","RUSTK-ABCDE-FGHIJK","2023-02-01 08:00:00","transfer","stakingfromspot","currency","ETH2.S",0.5000000000,0.0000000000,0.5000000000
+"LWD001-ABCDE-FGHIJK","AGBJ5Q-ABCDE-FGHIJK","2023-03-01 11:00:00","withdrawal","","currency","XXBT",-0.0050000000,0.0001500000,0.0148500000
+"#;
+
+    #[test]
+    fn parse_crypto_notes_test() {
+        let custodian = Arc::new(BrokerInformation::new(
+            String::from("Kraken"),
+            String::from("IE"),
+        ));
+        let parser = KrakenParser::new(INPUT.to_string(), &custodian);
+        let note = |(year, month, day), currency: &str, quantity| {
+            CryptoNote::new(
+                NaiveDate::from_ymd_opt(year, month, day).unwrap(),
+                currency.to_string(),
+                quantity,
+                &custodian,
+            )
+        };
+
+        assert_eq!(
+            parser.parse_crypto_notes().unwrap(),
+            vec![
+                note((2023, 1, 12), "BTC", Decimal::new(2, 2)),
+                note((2023, 2, 1), "ETH", Decimal::new(-5, 1)),
+                note((2023, 2, 1), "ETH", Decimal::new(5, 1)),
+                note((2023, 3, 1), "BTC", Decimal::new(-515, 5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn normalize_asset_test() {
+        assert_eq!(KrakenParser::normalize_asset("XXBT"), "BTC");
+        assert_eq!(KrakenParser::normalize_asset("XXDG"), "DOGE");
+        assert_eq!(KrakenParser::normalize_asset("ZEUR"), "EUR");
+        assert_eq!(KrakenParser::normalize_asset("DOT.S"), "DOT");
+        assert_eq!(KrakenParser::normalize_asset("SOL"), "SOL");
+    }
+}
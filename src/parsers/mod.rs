@@ -1,6 +1,45 @@
+//! Readers of the broker reports, crypto exchange exports and previous
+//! AEAT forms.
+
+/// Previous AEAT 720 forms.
+pub mod aeat_720;
+/// Baader Bank PDF statements of Scalable Capital accounts.
+pub mod baader;
+/// Binance transaction history exports.
+pub mod binance;
+/// Coinbase transaction history exports.
+pub mod coinbase;
+/// Degiro annual PDF reports.
 pub mod degiro;
+/// Degiro portfolio CSV exports.
 pub mod degiro_csv;
+/// Equity plan CSV exports and year-end statements of Morgan Stanley, Schwab
+/// and Fidelity.
+pub mod equity_plan;
+/// eToro account statements.
+pub mod etoro;
+/// CSV files of other brokers, with the columns chosen by the user.
+pub mod generic_csv;
+/// Interactive Brokers HTML activity statements.
 pub mod ib;
+/// Interactive Brokers CSV activity statements.
 pub mod ib_csv;
+/// Kraken ledger exports.
+pub mod kraken;
+/// OFX/QFX investment statements.
+pub mod ofx;
+/// Text extraction of PDF reports.
 pub mod pdf;
-mod util;
+/// Fourth quarter average prices of the negotiated securities.
+pub mod q4_prices;
+/// Revolut trading account statements.
+pub mod revolut;
+/// Scalable Capital CSV exports.
+pub mod scalable_csv;
+/// Trade Republic PDF reports.
+pub mod trade_republic;
+/// Trading 212 CSV exports.
+pub mod trading212;
+pub(crate) mod util;
+/// XTB account statements.
+pub mod xtb;
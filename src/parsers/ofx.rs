@@ -0,0 +1,640 @@
+use anyhow::{Context, Result, bail};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+
+use crate::data::{
+    AccountNote, AccountNotes, AssetCategory, BalanceNote, BalanceNotes, BrokerInformation,
+    BrokerOperation, CompanyInfo,
+};
+use crate::parsers::util;
+
+/*
+   OFX investment statements (https://www.financialdata.exchange/ofx).
+   Version 1 files are SGML, leaf elements are not closed, while version 2
+   files are XML. Both are read as a tree of aggregates:
+
+   INVSTMTRS
+     CURDEF                    default currency of the amounts
+     INVACCTFROM/BROKERID      broker domain, ACCTID account number
+     INVTRANLIST               BUYSTOCK/INVBUY, SELLMF/INVSELL, REINVEST...
+     INVPOSLIST                POSSTOCK/INVPOS, POSMF/INVPOS...
+   SECLIST                     STOCKINFO/SECINFO, MFINFO/SECINFO...
+*/
+
+const OFX_MARK: &str = "OFXHEADER";
+const OFX_BEGIN: &str = "<OFX>";
+const EUR_CURRENCY: &str = "EUR";
+const ISIN_ID_TYPE: &str = "ISIN";
+const CUSIP_ID_TYPE: &str = "CUSIP";
+
+/// ISO 3166 alpha-3 codes of the countries of the usual brokers, as given
+/// in the addresses of the OFX files, with their alpha-2 code.
+const COUNTRY_CODES: [(&str, &str); 15] = [
+    ("USA", "US"),
+    ("CAN", "CA"),
+    ("GBR", "GB"),
+    ("IRL", "IE"),
+    ("DEU", "DE"),
+    ("NLD", "NL"),
+    ("LUX", "LU"),
+    ("CHE", "CH"),
+    ("FRA", "FR"),
+    ("BEL", "BE"),
+    ("ESP", "ES"),
+    ("ITA", "IT"),
+    ("AUT", "AT"),
+    ("AUS", "AU"),
+    ("HKG", "HK"),
+];
+
+/// Element of an OFX document, aggregates hold their children and leaf
+/// elements their value.
+#[derive(Debug, Default)]
+struct OfxElement {
+    name: String,
+    value: String,
+    children: Vec<OfxElement>,
+}
+
+impl OfxElement {
+    fn new(name: &str, value: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            value: value
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&amp;", "&"),
+            children: vec![],
+        }
+    }
+
+    /// Reads the elements after the headers, closing the aggregates left
+    /// open and ignoring the closing tags of the XML leaf elements.
+    fn parse(content: &str) -> Self {
+        let mut stack = vec![OfxElement::default()];
+        let mut rest = content;
+
+        while let Some(start) = rest.find('<') {
+            let Some(end) = rest[start..].find('>') else {
+                break;
+            };
+            let tag = rest[start + 1..start + end].trim();
+            rest = &rest[start + end + 1..];
+            let text = rest[..rest.find('<').unwrap_or(rest.len())].trim();
+
+            if tag.starts_with('?') || tag.starts_with('!') {
+                continue;
+            } else if let Some(name) = tag.strip_prefix('/') {
+                if let Some(position) = stack.iter().rposition(|element| element.name == name)
+                    && position > 0
+                {
+                    while stack.len() > position {
+                        OfxElement::close(&mut stack);
+                    }
+                }
+            } else if let Some(name) = tag.strip_suffix('/') {
+                OfxElement::push(&mut stack, OfxElement::new(name.trim(), ""));
+            } else if !text.is_empty() {
+                OfxElement::push(&mut stack, OfxElement::new(tag, text));
+            } else {
+                stack.push(OfxElement::new(tag, ""));
+            }
+        }
+
+        while stack.len() > 1 {
+            OfxElement::close(&mut stack);
+        }
+        stack.pop().unwrap_or_default()
+    }
+
+    fn push(stack: &mut [OfxElement], element: OfxElement) {
+        if let Some(parent) = stack.last_mut() {
+            parent.children.push(element);
+        }
+    }
+
+    fn close(stack: &mut Vec<OfxElement>) {
+        if let Some(element) = stack.pop() {
+            OfxElement::push(stack, element);
+        }
+    }
+
+    /// Descendant following a path of names like `SECID/UNIQUEID`.
+    fn get(&self, path: &str) -> Option<&OfxElement> {
+        path.split('/').try_fold(self, |element, name| {
+            element.children.iter().find(|child| child.name == name)
+        })
+    }
+
+    fn value(&self, path: &str) -> Option<&str> {
+        self.get(path)
+            .map(|element| element.value.as_str())
+            .filter(|value| !value.is_empty())
+    }
+
+    fn find_all<'a>(&'a self, name: &str, result: &mut Vec<&'a OfxElement>) {
+        for child in &self.children {
+            if child.name == name {
+                result.push(child);
+            } else {
+                child.find_all(name, result);
+            }
+        }
+    }
+
+    fn descendants(&self, name: &str) -> Vec<&OfxElement> {
+        let mut result = vec![];
+        self.find_all(name, &mut result);
+        result
+    }
+}
+
+/// Parser of the OFX/QFX investment statements exported by brokers like
+/// Schwab, Fidelity or Vanguard. Positions not valued in euros are left
+/// without value, to be converted at the year end rate or reported as
+/// unvalued when importing them.
+#[derive(Debug)]
+pub struct OfxParser {
+    root: OfxElement,
+    broker: Arc<BrokerInformation>,
+}
+
+impl OfxParser {
+    /// Whether `content` starts like an OFX document.
+    pub fn is_ofx(content: &[u8]) -> bool {
+        let head = String::from_utf8_lossy(&content[..content.len().min(1024)]);
+        head.contains(OFX_MARK)
+    }
+
+    fn parse_decimal(value: Option<&str>) -> Result<Decimal> {
+        match value {
+            Some(value) => Decimal::from_str(value)
+                .or_else(|_| Decimal::from_scientific(value))
+                .with_context(|| format!("Invalid amount {}", value)),
+            None => Ok(Decimal::ZERO),
+        }
+    }
+
+    /// Dates are `YYYYMMDD` optionally followed by the time and time zone.
+    fn parse_date(value: &str) -> Result<NaiveDate> {
+        NaiveDate::parse_from_str(value.get(..8).unwrap_or(value), "%Y%m%d")
+            .with_context(|| format!("Invalid date {}", value))
+    }
+
+    /// Category of the aggregate of a security, position or transaction,
+    /// like `STOCKINFO`, `POSMF` or `BUYDEBT`.
+    fn category(aggregate: &str) -> AssetCategory {
+        if aggregate.contains("STOCK") {
+            AssetCategory::Stock
+        } else if aggregate.contains("MF") {
+            AssetCategory::Fund
+        } else if aggregate.contains("DEBT") {
+            AssetCategory::Bond
+        } else if aggregate.contains("OPT") {
+            AssetCategory::Option
+        } else {
+            AssetCategory::Other
+        }
+    }
+
+    /// Securities of the statement by their unique id, the ISIN is known
+    /// when it is the type of the id or derived from the CUSIP of a US
+    /// security. Canadian securities, quoted in CAD, also have CUSIPs but
+    /// a CA prefix, so their ISIN is left for the user to fill.
+    fn securities(&self) -> HashMap<String, (CompanyInfo, AssetCategory)> {
+        let mut securities = HashMap::new();
+        for seclist in self.root.descendants("SECLIST") {
+            for info in &seclist.children {
+                let Some(secinfo) = info.get("SECINFO") else {
+                    continue;
+                };
+                let Some(id) = secinfo.value("SECID/UNIQUEID") else {
+                    continue;
+                };
+                let isin = match secinfo.value("SECID/UNIQUEIDTYPE") {
+                    Some(ISIN_ID_TYPE) => id.to_string(),
+                    Some(CUSIP_ID_TYPE) if secinfo.value("CURRENCY/CURSYM") != Some("CAD") => {
+                        util::cusip_to_isin(id).unwrap_or_default()
+                    }
+                    _ => String::new(),
+                };
+                let name = secinfo
+                    .value("SECNAME")
+                    .or(secinfo.value("TICKER"))
+                    .unwrap_or(id);
+                // ETFs come as stocks, only their names tell them apart
+                let category = match OfxParser::category(&info.name) {
+                    AssetCategory::Stock => util::name_asset_category(name),
+                    category => category,
+                };
+                securities.insert(
+                    id.to_string(),
+                    (
+                        CompanyInfo {
+                            name: name.to_string(),
+                            isin,
+                        },
+                        category,
+                    ),
+                );
+            }
+        }
+        securities
+    }
+
+    fn security(
+        securities: &HashMap<String, (CompanyInfo, AssetCategory)>,
+        element: &OfxElement,
+        aggregate: &str,
+    ) -> (CompanyInfo, AssetCategory) {
+        let id = element.value("SECID/UNIQUEID").unwrap_or_default();
+        securities.get(id).cloned().unwrap_or_else(|| {
+            (
+                CompanyInfo {
+                    name: id.to_string(),
+                    isin: String::new(),
+                },
+                OfxParser::category(aggregate),
+            )
+        })
+    }
+
+    /// Account of the statement, only set when the file holds several
+    /// accounts.
+    fn account(&self, statement: &OfxElement) -> String {
+        if self.root.descendants("INVSTMTRS").len() > 1 {
+            statement
+                .value("INVACCTFROM/ACCTID")
+                .unwrap_or_default()
+                .to_string()
+        } else {
+            String::new()
+        }
+    }
+
+    /// Positions of the statements.
+    pub fn parse_balance_notes(&self) -> Result<BalanceNotes> {
+        let securities = self.securities();
+        let mut balance_notes = vec![];
+
+        for statement in self.root.descendants("INVSTMTRS") {
+            let default_currency = statement.value("CURDEF").unwrap_or(EUR_CURRENCY);
+            let account = self.account(statement);
+            let Some(positions) = statement.get("INVPOSLIST") else {
+                continue;
+            };
+            for position in &positions.children {
+                let Some(invpos) = position.get("INVPOS") else {
+                    continue;
+                };
+                log::debug!("{:?}", invpos);
+                let (company, category) = OfxParser::security(&securities, invpos, &position.name);
+                let currency = invpos
+                    .value("CURRENCY/CURSYM")
+                    .or(invpos.value("ORIGCURRENCY/CURSYM"))
+                    .unwrap_or(default_currency);
+                let rate = OfxParser::parse_decimal(
+                    invpos
+                        .value("CURRENCY/CURRATE")
+                        .or(invpos.value("ORIGCURRENCY/CURRATE")),
+                )?;
+                let market_value = OfxParser::parse_decimal(invpos.value("MKTVAL"))?;
+                let value_in_euro = if currency == EUR_CURRENCY {
+                    market_value
+                } else if default_currency == EUR_CURRENCY && !rate.is_zero() {
+                    (market_value * rate).round_dp(2)
+                } else {
+                    Decimal::ZERO
+                };
+
+                let mut note = BalanceNote::new(
+                    company,
+                    String::new(),
+                    OfxParser::parse_decimal(invpos.value("UNITS"))?,
+                    currency.to_string(),
+                    OfxParser::parse_decimal(invpos.value("UNITPRICE"))?,
+                    value_in_euro,
+                    &self.broker,
+                );
+                note.category = category;
+                note.account = account.clone();
+                balance_notes.push(note);
+            }
+        }
+
+        Ok(balance_notes)
+    }
+
+    /// Buys, sells and reinvestments of the statements.
+    pub fn parse_account_notes(&self) -> Result<AccountNotes> {
+        let securities = self.securities();
+        let mut account_notes = vec![];
+
+        for statement in self.root.descendants("INVSTMTRS") {
+            let account = self.account(statement);
+            let Some(transactions) = statement.get("INVTRANLIST") else {
+                continue;
+            };
+            for transaction in &transactions.children {
+                let (operation, detail) = if transaction.name.starts_with("BUY") {
+                    (BrokerOperation::Buy, transaction.get("INVBUY"))
+                } else if transaction.name.starts_with("SELL") {
+                    (BrokerOperation::Sell, transaction.get("INVSELL"))
+                } else if transaction.name == "REINVEST" {
+                    (BrokerOperation::Buy, Some(transaction))
+                } else {
+                    continue;
+                };
+                let Some(detail) = detail else {
+                    continue;
+                };
+                log::debug!("{:?}", detail);
+                let Some(date) = detail.value("INVTRAN/DTTRADE") else {
+                    bail!("Trade date not found in OFX transaction");
+                };
+
+                let (company, _) = OfxParser::security(&securities, detail, &transaction.name);
+                let quantity = OfxParser::parse_decimal(detail.value("UNITS"))?.abs();
+                let price = OfxParser::parse_decimal(detail.value("UNITPRICE"))?;
+                let mut note = AccountNote::new(
+                    OfxParser::parse_date(date)?,
+                    company,
+                    operation,
+                    quantity,
+                    price,
+                    quantity * price,
+                    OfxParser::parse_decimal(detail.value("COMMISSION"))?.abs(),
+                    &self.broker,
+                );
+                note.account = account.clone();
+                account_notes.push(note);
+            }
+        }
+
+        Ok(account_notes)
+    }
+
+    /// Alpha-2 code of the country of an OFX address, given as alpha-2 or
+    /// alpha-3 code.
+    fn country_code(country: &str) -> Option<String> {
+        let country = country.trim().to_uppercase();
+        match country.len() {
+            2 if country.chars().all(|c| c.is_ascii_alphabetic()) => Some(country),
+            3 => COUNTRY_CODES
+                .iter()
+                .find(|(alpha3, _)| *alpha3 == country)
+                .map(|(_, alpha2)| alpha2.to_string()),
+            _ => None,
+        }
+    }
+
+    /// The broker is named after the financial institution of the file,
+    /// located in the country of the address of the file, if any, or the
+    /// one of `broker` otherwise.
+    pub fn new(content: &str, broker: &Arc<BrokerInformation>) -> Result<Self> {
+        let Some(begin) = content.find(OFX_BEGIN) else {
+            bail!("OFX document not found");
+        };
+        let root = OfxElement::parse(&content[begin..]);
+
+        let name = root
+            .descendants("FI")
+            .first()
+            .and_then(|fi| fi.value("ORG"))
+            .or_else(|| {
+                root.descendants("INVACCTFROM")
+                    .first()
+                    .and_then(|account| account.value("BROKERID"))
+            })
+            .map(str::to_string);
+        let country_code = root
+            .descendants("COUNTRY")
+            .iter()
+            .find_map(|country| OfxParser::country_code(&country.value))
+            .unwrap_or_else(|| broker.country_code.clone());
+        let broker = match name {
+            Some(name) => Arc::new(BrokerInformation::new(name, country_code)),
+            None => Arc::new(BrokerInformation::new(broker.name.clone(), country_code)),
+        };
+
+        Ok(Self { root, broker })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SGML_INPUT: &str = r#"OFXHEADER:100
+DATA:OFXSGML
+VERSION:102
+SECURITY:NONE
+ENCODING:USASCII
+CHARSET:1252
+
+<OFX>
+<SIGNONMSGSRSV1><SONRS>
+<STATUS><CODE>0<SEVERITY>INFO</STATUS>
+<DTSERVER>20240105120000.000[-5:EST]
+<LANGUAGE>ENG
+<FI><ORG>Charles Schwab &amp; Co.<FID>5104</FI>
+</SONRS></SIGNONMSGSRSV1>
+<INVSTMTMSGSRSV1><INVSTMTTRNRS>
+<TRNUID>1
+<STATUS><CODE>0<SEVERITY>INFO</STATUS>
+<INVSTMTRS>
+<DTASOF>20231231
+<CURDEF>USD
+<INVACCTFROM><BROKERID>schwab.com<ACCTID>12345678</INVACCTFROM>
+<INVTRANLIST>
+<DTSTART>20230101<DTEND>20231231
+<BUYSTOCK><INVBUY>
+<INVTRAN><FITID>T1<DTTRADE>20230315<DTSETTLE>20230317</INVTRAN>
+<SECID><UNIQUEID>US0378331005<UNIQUEIDTYPE>ISIN</SECID>
+<UNITS>10<UNITPRICE>150.25<COMMISSION>1.00<TOTAL>-1503.50
+<SUBACCTSEC>CASH<SUBACCTFUND>CASH
+</INVBUY><BUYTYPE>BUY</BUYSTOCK>
+<INCOME>
+<INVTRAN><FITID>T2<DTTRADE>20230515</INVTRAN>
+<SECID><UNIQUEID>US0378331005<UNIQUEIDTYPE>ISIN</SECID>
+<INCOMETYPE>DIV<TOTAL>2.40<SUBACCTSEC>CASH<SUBACCTFUND>CASH
+</INCOME>
+<SELLMF><INVSELL>
+<INVTRAN><FITID>T3<DTTRADE>20230920123000</INVTRAN>
+<SECID><UNIQUEID>922908363<UNIQUEIDTYPE>CUSIP</SECID>
+<UNITS>-2.5<UNITPRICE>400<COMMISSION>0<TOTAL>1000
+<SUBACCTSEC>CASH<SUBACCTFUND>CASH
+</INVSELL><SELLTYPE>SELL</SELLMF>
+</INVTRANLIST>
+<INVPOSLIST>
+<POSSTOCK><INVPOS>
+<SECID><UNIQUEID>US0378331005<UNIQUEIDTYPE>ISIN</SECID>
+<HELDINACCT>CASH<POSTYPE>LONG<UNITS>10<UNITPRICE>192.53<MKTVAL>1925.30<DTPRICEASOF>20231229
+</INVPOS></POSSTOCK>
+</INVPOSLIST>
+</INVSTMTRS>
+</INVSTMTTRNRS></INVSTMTMSGSRSV1>
+<SECLISTMSGSRSV1><SECLIST>
+<STOCKINFO><SECINFO>
+<SECID><UNIQUEID>US0378331005<UNIQUEIDTYPE>ISIN</SECID>
+<SECNAME>APPLE INC<TICKER>AAPL
+</SECINFO></STOCKINFO>
+<MFINFO><SECINFO>
+<SECID><UNIQUEID>922908363<UNIQUEIDTYPE>CUSIP</SECID>
+<SECNAME>VANGUARD S&amp;P 500 ETF<TICKER>VOO
+</SECINFO></MFINFO>
+</SECLIST></SECLISTMSGSRSV1>
+</OFX>
+"#;
+
+    const XML_INPUT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<?OFX OFXHEADER="200" VERSION="220" SECURITY="NONE"?>
+<OFX>
+  <PROFMSGSRSV1>
+    <PROFTRNRS>
+      <PROFRS>
+        <FINAME>Bank Example</FINAME>
+        <ADDR1>Calle Mayor 1</ADDR1>
+        <CITY>Madrid</CITY>
+        <POSTALCODE>28013</POSTALCODE>
+        <COUNTRY>ESP</COUNTRY>
+      </PROFRS>
+    </PROFTRNRS>
+  </PROFMSGSRSV1>
+  <INVSTMTMSGSRSV1>
+    <INVSTMTTRNRS>
+      <INVSTMTRS>
+        <DTASOF>20231231</DTASOF>
+        <CURDEF>EUR</CURDEF>
+        <INVACCTFROM><BROKERID>bank.example</BROKERID><ACCTID>ES01</ACCTID></INVACCTFROM>
+        <INVPOSLIST>
+          <POSMF>
+            <INVPOS>
+              <SECID><UNIQUEID>IE00B4L5Y983</UNIQUEID><UNIQUEIDTYPE>ISIN</UNIQUEIDTYPE></SECID>
+              <HELDINACCT>CASH</HELDINACCT>
+              <POSTYPE>LONG</POSTYPE>
+              <UNITS>20</UNITS>
+              <UNITPRICE>80.50</UNITPRICE>
+              <MKTVAL>1610.00</MKTVAL>
+              <MEMO></MEMO>
+            </INVPOS>
+          </POSMF>
+        </INVPOSLIST>
+      </INVSTMTRS>
+    </INVSTMTTRNRS>
+  </INVSTMTMSGSRSV1>
+  <SECLISTMSGSRSV1>
+    <SECLIST>
+      <MFINFO>
+        <SECINFO>
+          <SECID><UNIQUEID>IE00B4L5Y983</UNIQUEID><UNIQUEIDTYPE>ISIN</UNIQUEIDTYPE></SECID>
+          <SECNAME>ISHARES CORE MSCI WORLD</SECNAME>
+        </SECINFO>
+      </MFINFO>
+    </SECLIST>
+  </SECLISTMSGSRSV1>
+</OFX>
+"#;
+
+    #[test]
+    fn country_code_test() {
+        assert_eq!(OfxParser::country_code("USA"), Some(String::from("US")));
+        assert_eq!(OfxParser::country_code("ie"), Some(String::from("IE")));
+        assert_eq!(OfxParser::country_code("XYZ"), None);
+        assert_eq!(OfxParser::country_code(""), None);
+    }
+
+    #[test]
+    fn is_ofx_test() {
+        assert!(OfxParser::is_ofx(SGML_INPUT.as_bytes()));
+        assert!(OfxParser::is_ofx(XML_INPUT.as_bytes()));
+        assert!(!OfxParser::is_ofx(b"Date,Ticker,Type,Quantity"));
+    }
+
+    #[test]
+    fn parse_sgml_test() {
+        let broker = Arc::new(BrokerInformation::new(
+            String::from("Desconocido"),
+            String::new(),
+        ));
+        let parser = OfxParser::new(SGML_INPUT, &broker).unwrap();
+        let schwab = Arc::new(BrokerInformation::new(
+            String::from("Charles Schwab & Co."),
+            String::new(),
+        ));
+        let apple = CompanyInfo {
+            name: String::from("APPLE INC"),
+            isin: String::from("US0378331005"),
+        };
+
+        let mut balance_note = BalanceNote::new(
+            apple.clone(),
+            String::new(),
+            Decimal::new(10, 0),
+            String::from("USD"),
+            Decimal::new(192_53, 2),
+            Decimal::ZERO,
+            &schwab,
+        );
+        balance_note.category = AssetCategory::Stock;
+        assert_eq!(parser.parse_balance_notes().unwrap(), vec![balance_note]);
+
+        assert_eq!(
+            parser.parse_account_notes().unwrap(),
+            vec![
+                AccountNote::new(
+                    NaiveDate::from_ymd_opt(2023, 3, 15).unwrap(),
+                    apple,
+                    BrokerOperation::Buy,
+                    Decimal::new(10, 0),
+                    Decimal::new(150_25, 2),
+                    Decimal::new(1502_50, 2),
+                    Decimal::new(1, 0),
+                    &schwab,
+                ),
+                AccountNote::new(
+                    NaiveDate::from_ymd_opt(2023, 9, 20).unwrap(),
+                    CompanyInfo {
+                        name: String::from("VANGUARD S&P 500 ETF"),
+                        isin: String::from("US9229083632"),
+                    },
+                    BrokerOperation::Sell,
+                    Decimal::new(25, 1),
+                    Decimal::new(400, 0),
+                    Decimal::new(1000, 0),
+                    Decimal::ZERO,
+                    &schwab,
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_xml_test() {
+        let broker = Arc::new(BrokerInformation::new(
+            String::from("Desconocido"),
+            String::new(),
+        ));
+        let parser = OfxParser::new(XML_INPUT, &broker).unwrap();
+
+        let mut balance_note = BalanceNote::new(
+            CompanyInfo {
+                name: String::from("ISHARES CORE MSCI WORLD"),
+                isin: String::from("IE00B4L5Y983"),
+            },
+            String::new(),
+            Decimal::new(20, 0),
+            String::from("EUR"),
+            Decimal::new(80_50, 2),
+            Decimal::new(1610, 0),
+            &Arc::new(BrokerInformation::new(
+                String::from("bank.example"),
+                String::from("ES"),
+            )),
+        );
+        balance_note.category = AssetCategory::Fund;
+        assert_eq!(parser.parse_balance_notes().unwrap(), vec![balance_note]);
+        assert_eq!(parser.parse_account_notes().unwrap(), vec![]);
+    }
+}
@@ -14,6 +14,7 @@ fn remove_repeated_section(mut input: String, section: &str) -> String {
 
     input
 }
+/// Text of a PDF document.
 pub fn read_pdf(data: &[u8]) -> Result<String, OutputError> {
     let out = pdf_extract::extract_text_from_mem(data)?;
     let out = remove_repeated_section(out, DEGIRO_NOTES_HEADER_BEGIN);
@@ -21,6 +22,20 @@ pub fn read_pdf(data: &[u8]) -> Result<String, OutputError> {
     Ok(out)
 }
 
+/// Text of each page of a PDF document.
+pub fn read_pdf_pages(data: &[u8]) -> Result<Vec<String>, OutputError> {
+    pdf_extract::extract_text_from_mem_by_pages(data)
+}
+
+/// Page of a PDF document with the text `line`, starting at 1.
+pub fn find_page(data: &[u8], line: &str) -> Option<usize> {
+    let pages = read_pdf_pages(data).ok()?;
+    pages
+        .iter()
+        .position(|page| page.contains(line))
+        .map(|index| index + 1)
+}
+
 mod tests {
     #[test]
     #[ignore]
@@ -0,0 +1,143 @@
+use anyhow::{Result, bail};
+use rust_decimal::Decimal;
+use std::{collections::HashMap, str::FromStr};
+
+use crate::{
+    error::ImportError,
+    parsers::generic_csv::DecimalSeparator,
+    utils::decimal::{normalize_str, transform_i18n_es_str},
+};
+
+/// Parser of a list of fourth quarter average prices, one security per line
+/// with its ISIN and the price in euros, like the list of negotiated
+/// securities published for the wealth tax or a spreadsheet exported as CSV.
+#[derive(Debug)]
+pub struct Q4PricesParser {
+    content: String,
+    decimal_separator: DecimalSeparator,
+}
+
+impl Q4PricesParser {
+    fn delimiter(&self) -> u8 {
+        if self.content.contains(';') {
+            b';'
+        } else if self.content.contains('\t') {
+            b'\t'
+        } else {
+            b','
+        }
+    }
+
+    fn parse_price(&self, value: &str) -> Option<Decimal> {
+        let value = value.trim_matches(|c: char| c.is_whitespace() || c == '€');
+        let value = match self.decimal_separator {
+            DecimalSeparator::Point => normalize_str(value),
+            DecimalSeparator::Comma => transform_i18n_es_str(value),
+        };
+
+        Decimal::from_str(&value).ok()
+    }
+
+    /// Prices by ISIN, the last number following it in each row, rows
+    /// without ISIN like headers are skipped. Files without any are not
+    /// prices lists.
+    pub fn parse_prices(&self) -> Result<HashMap<String, Decimal>> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .delimiter(self.delimiter())
+            .from_reader(self.content.as_bytes());
+        let mut prices = HashMap::new();
+        for result in rdr.records() {
+            let record = result?;
+            let fields: Vec<&str> = record.iter().map(str::trim).collect();
+            let Some(isin_index) = fields.iter().position(|field| isin::parse(field).is_ok())
+            else {
+                continue;
+            };
+            let price = fields[isin_index + 1..]
+                .iter()
+                .rev()
+                .find_map(|field| self.parse_price(field));
+            if let Some(price) = price {
+                prices.insert(fields[isin_index].to_string(), price);
+            }
+        }
+
+        if prices.is_empty() {
+            bail!(ImportError::UnrecognizedFormat(None));
+        }
+
+        Ok(prices)
+    }
+
+    /// Parser of the text of a prices list whose numbers use
+    /// `decimal_separator`.
+    pub fn new(content: String, decimal_separator: DecimalSeparator) -> Self {
+        Self {
+            content,
+            decimal_separator,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_prices_test() {
+        const INPUT: &str = "ISIN;Denominación;Cotización media 4T\n\
+            US0378331005;APPLE INC;175,25\n\
+            IE00B4L5Y983;ISHARES CORE MSCI WORLD;\"1.234,50\"\n\
+            ;Sin ISIN;12,00\n";
+
+        let prices = Q4PricesParser::new(INPUT.to_string(), DecimalSeparator::Comma)
+            .parse_prices()
+            .unwrap();
+        assert_eq!(
+            prices,
+            HashMap::from([
+                (String::from("US0378331005"), Decimal::new(17525, 2)),
+                (String::from("IE00B4L5Y983"), Decimal::new(1234_50, 2)),
+            ])
+        );
+
+        let prices = Q4PricesParser::new(
+            String::from(
+                "US5949181045,MICROSOFT,365.1\nIE00B4L5Y983,\"ISHARES, MSCI WORLD\",\"1,234.50\"\n",
+            ),
+            DecimalSeparator::Point,
+        )
+        .parse_prices()
+        .unwrap();
+        assert_eq!(
+            prices,
+            HashMap::from([
+                (String::from("US5949181045"), Decimal::new(3651, 1)),
+                (String::from("IE00B4L5Y983"), Decimal::new(1234_50, 2)),
+            ])
+        );
+
+        // The separator is not guessed from the values.
+        let prices = Q4PricesParser::new(
+            String::from("US5949181045;MICROSOFT;365.100\n"),
+            DecimalSeparator::Comma,
+        )
+        .parse_prices()
+        .unwrap();
+        assert_eq!(
+            prices,
+            HashMap::from([(String::from("US5949181045"), Decimal::new(365100, 0))])
+        );
+
+        assert_eq!(
+            Q4PricesParser::new(String::from("a;b\n"), DecimalSeparator::Comma)
+                .parse_prices()
+                .unwrap_err()
+                .downcast::<ImportError>()
+                .unwrap(),
+            ImportError::UnrecognizedFormat(None)
+        );
+    }
+}
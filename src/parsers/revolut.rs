@@ -0,0 +1,252 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::{str::FromStr, sync::Arc};
+
+use crate::{
+    data::{AccountNote, AccountNotes, BrokerInformation, BrokerOperation, CompanyInfo},
+    error::{ImportError, RowContext},
+};
+
+/// First columns of the Revolut trading account statement, used to detect it.
+pub(crate) const REVOLUT_HEADER_BEGIN: &str = "Date,Ticker,Type,Quantity";
+
+const DATE_FIELD: &str = "Date";
+const TICKER_FIELD: &str = "Ticker";
+const TYPE_FIELD: &str = "Type";
+const QUANTITY_FIELD: &str = "Quantity";
+const PRICE_FIELD: &str = "Price per share";
+const TOTAL_FIELD: &str = "Total Amount";
+const CURRENCY_FIELD: &str = "Currency";
+const FX_RATE_FIELD: &str = "FX Rate";
+
+const EUR_CURRENCY: &str = "EUR";
+
+const BUY_TYPE: &str = "BUY";
+const SELL_TYPE: &str = "SELL";
+/// Split rows give the shares received or removed, without price.
+const SPLIT_TYPE: &str = "STOCK SPLIT";
+
+/// Parser of the trading account statement CSV exported by Revolut.
+#[derive(Debug)]
+pub struct RevolutParser {
+    content: String,
+    broker: Arc<BrokerInformation>,
+}
+
+impl RevolutParser {
+    fn field(record: &csv::StringRecord, index: usize) -> &str {
+        record.get(index).unwrap_or("").trim()
+    }
+
+    /// Amounts carry the currency as prefix, either its code (`USD 130.25`)
+    /// or its symbol (`$130.25`).
+    fn parse_amount(value: &str) -> Result<Decimal> {
+        let number: String = value
+            .chars()
+            .filter(|c| c.is_ascii_digit() || *c == '.' || *c == '-')
+            .collect();
+        if number.is_empty() {
+            return Ok(Decimal::ZERO);
+        }
+        Decimal::from_str(&number).with_context(|| format!("Invalid amount {}", value))
+    }
+
+    fn parse_date(value: &str) -> Result<NaiveDate> {
+        NaiveDate::parse_from_str(value.get(..10).unwrap_or(value), "%Y-%m-%d")
+            .with_context(|| format!("Invalid date {}", value))
+    }
+
+    /// Units of the currency of the row per euro, as given by the FX rate
+    /// column, or one when the row is already in euros.
+    fn euro_rate(record: &csv::StringRecord, currency: usize, fx_rate: usize) -> Result<Decimal> {
+        if RevolutParser::field(record, currency) == EUR_CURRENCY {
+            return Ok(Decimal::ONE);
+        }
+        let rate = RevolutParser::parse_amount(RevolutParser::field(record, fx_rate))?;
+        if rate.is_zero() {
+            return Err(anyhow!(
+                "No FX rate for {}",
+                RevolutParser::field(record, currency)
+            ));
+        }
+
+        Ok(rate)
+    }
+
+    /// Buys, sells and splits of the statement, with their prices and
+    /// amounts converted to euros at the FX rate of each row. The statement
+    /// only gives the ticker, so the ISIN has to be filled in the table.
+    pub fn parse_account_notes(&self) -> Result<AccountNotes> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(self.content.as_bytes());
+        let headers = rdr.headers()?.clone();
+        let column = |field: &str| {
+            headers
+                .iter()
+                .position(|header| header == field)
+                .ok_or_else(|| anyhow!(ImportError::MissingColumn(field.to_string())))
+        };
+        let date = column(DATE_FIELD)?;
+        let ticker = column(TICKER_FIELD)?;
+        let kind = column(TYPE_FIELD)?;
+        let quantity = column(QUANTITY_FIELD)?;
+        let price = column(PRICE_FIELD)?;
+        let total = column(TOTAL_FIELD)?;
+        let currency = column(CURRENCY_FIELD)?;
+        let fx_rate = column(FX_RATE_FIELD)?;
+
+        let mut account_notes = vec![];
+        for result in rdr.records() {
+            let record = result?;
+            log::debug!("{:?}", record);
+            let kind = RevolutParser::field(&record, kind);
+            let shares = RevolutParser::parse_amount(RevolutParser::field(&record, quantity))
+                .record_context(&record)?;
+            let operation = if kind.starts_with(BUY_TYPE) {
+                BrokerOperation::Buy
+            } else if kind.starts_with(SELL_TYPE)
+                || (kind == SPLIT_TYPE && shares.is_sign_negative())
+            {
+                BrokerOperation::Sell
+            } else if kind == SPLIT_TYPE {
+                BrokerOperation::Buy
+            } else {
+                continue;
+            };
+
+            let (price, value) = if kind == SPLIT_TYPE {
+                (Decimal::ZERO, Decimal::ZERO)
+            } else {
+                let rate =
+                    RevolutParser::euro_rate(&record, currency, fx_rate).record_context(&record)?;
+                (
+                    (RevolutParser::parse_amount(RevolutParser::field(&record, price))
+                        .record_context(&record)?
+                        / rate)
+                        .round_dp(4),
+                    (RevolutParser::parse_amount(RevolutParser::field(&record, total))
+                        .record_context(&record)?
+                        .abs()
+                        / rate)
+                        .round_dp(2),
+                )
+            };
+
+            let ticker = RevolutParser::field(&record, ticker);
+            account_notes.push(AccountNote::new(
+                RevolutParser::parse_date(RevolutParser::field(&record, date))
+                    .record_context(&record)?,
+                CompanyInfo {
+                    name: ticker.to_string(),
+                    isin: String::new(),
+                },
+                operation,
+                shares.abs(),
+                price,
+                value,
+                Decimal::ZERO,
+                &self.broker,
+            ));
+        }
+
+        Ok(account_notes)
+    }
+
+    /// Parser of a Revolut trading account statement.
+    pub fn new(content: String, broker: &Arc<BrokerInformation>) -> Self {
+        Self {
+            content,
+            broker: Arc::clone(broker),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = r#"Date,Ticker,Type,Quantity,Price per share,Total Amount,Currency,FX Rate
+2023-01-05T09:01:02.123Z,,CASH TOP-UP,,,USD 1000,USD,1.0612
+2023-01-12T15:30:05.456Z,AAPL,BUY - MARKET,2.5,USD 130.00,USD 325,USD,1.0800
+2023-03-01T10:00:00.000Z,NVDA,BUY - LIMIT,1,$230.10,$230.10,USD,1.0700
+2023-05-18T12:00:00Z,AAPL,DIVIDEND,,,USD 0.55,USD,1.0900
+2023-06-10T00:00:00Z,NVDA,STOCK SPLIT,9,,USD 0,USD,1.0800
+2023-06-20T14:00:00.000Z,AAPL,SELL - MARKET,1,USD 185.00,USD 185,USD,1.0900
+2023-07-03T09:00:00.000Z,ASML,BUY - MARKET,1,EUR 600.00,EUR 600,EUR,1.0000
+"#;
+
+    #[test]
+    fn parse_account_notes_test() {
+        let broker = Arc::new(BrokerInformation::new(
+            String::from("Revolut"),
+            String::from("LT"),
+        ));
+        let parser = RevolutParser::new(INPUT.to_string(), &broker);
+        let company = |name: &str| CompanyInfo {
+            name: String::from(name),
+            isin: String::new(),
+        };
+
+        assert_eq!(
+            parser.parse_account_notes().unwrap(),
+            vec![
+                AccountNote::new(
+                    NaiveDate::from_ymd_opt(2023, 1, 12).unwrap(),
+                    company("AAPL"),
+                    BrokerOperation::Buy,
+                    Decimal::new(25, 1),
+                    Decimal::new(120_3704, 4),
+                    Decimal::new(300_93, 2),
+                    Decimal::ZERO,
+                    &broker,
+                ),
+                AccountNote::new(
+                    NaiveDate::from_ymd_opt(2023, 3, 1).unwrap(),
+                    company("NVDA"),
+                    BrokerOperation::Buy,
+                    Decimal::new(1, 0),
+                    Decimal::new(215_0467, 4),
+                    Decimal::new(215_05, 2),
+                    Decimal::ZERO,
+                    &broker,
+                ),
+                AccountNote::new(
+                    NaiveDate::from_ymd_opt(2023, 6, 10).unwrap(),
+                    company("NVDA"),
+                    BrokerOperation::Buy,
+                    Decimal::new(9, 0),
+                    Decimal::ZERO,
+                    Decimal::ZERO,
+                    Decimal::ZERO,
+                    &broker,
+                ),
+                AccountNote::new(
+                    NaiveDate::from_ymd_opt(2023, 6, 20).unwrap(),
+                    company("AAPL"),
+                    BrokerOperation::Sell,
+                    Decimal::new(1, 0),
+                    Decimal::new(169_7248, 4),
+                    Decimal::new(169_72, 2),
+                    Decimal::ZERO,
+                    &broker,
+                ),
+                AccountNote::new(
+                    NaiveDate::from_ymd_opt(2023, 7, 3).unwrap(),
+                    company("ASML"),
+                    BrokerOperation::Buy,
+                    Decimal::new(1, 0),
+                    Decimal::new(600, 0),
+                    Decimal::new(600, 0),
+                    Decimal::ZERO,
+                    &broker,
+                ),
+            ]
+        );
+
+        let input = INPUT.replace("USD 325,USD,1.0800", "USD 325,USD,");
+        let parser = RevolutParser::new(input, &broker);
+        assert!(parser.parse_account_notes().is_err());
+    }
+}
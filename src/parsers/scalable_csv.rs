@@ -0,0 +1,187 @@
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::{str::FromStr, sync::Arc};
+
+use crate::{
+    data::{AccountNote, AccountNotes, BrokerInformation, BrokerOperation, CompanyInfo},
+    error::{ImportError, RowContext},
+    utils::decimal,
+};
+
+/// First columns of the Scalable Capital transactions export, used to detect it.
+pub(crate) const SCALABLE_HEADER_BEGIN: &str = "date;time;status;reference";
+
+const DATE_FIELD: &str = "date";
+const STATUS_FIELD: &str = "status";
+const DESCRIPTION_FIELD: &str = "description";
+const TYPE_FIELD: &str = "type";
+const ISIN_FIELD: &str = "isin";
+const SHARES_FIELD: &str = "shares";
+const PRICE_FIELD: &str = "price";
+const AMOUNT_FIELD: &str = "amount";
+const FEE_FIELD: &str = "fee";
+const CURRENCY_FIELD: &str = "currency";
+
+const EXECUTED_STATUS: &str = "Executed";
+const BUY_TYPES: [&str; 2] = ["Buy", "Savings plan"];
+const SELL_TYPES: [&str; 1] = ["Sell"];
+
+/// Parser of the transactions CSV exported by Scalable Capital, whose
+/// securities are held in custody by Baader Bank.
+#[derive(Debug)]
+pub struct ScalableCSVParser {
+    content: String,
+    broker: Arc<BrokerInformation>,
+}
+
+impl ScalableCSVParser {
+    fn parse_decimal(value: &str) -> Result<Decimal> {
+        if value.is_empty() {
+            return Ok(Decimal::ZERO);
+        }
+        Decimal::from_str(&decimal::transform_i18n_es_str(value))
+            .with_context(|| format!("Invalid number {}", value))
+    }
+
+    fn broker_operation(kind: &str) -> Option<BrokerOperation> {
+        if BUY_TYPES.contains(&kind) {
+            Some(BrokerOperation::Buy)
+        } else if SELL_TYPES.contains(&kind) {
+            Some(BrokerOperation::Sell)
+        } else {
+            None
+        }
+    }
+
+    /// Executed trades found in the export, cash movements, distributions
+    /// and cancelled orders are ignored.
+    pub fn parse_account_notes(&self) -> Result<AccountNotes> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .delimiter(b';')
+            .flexible(true)
+            .from_reader(self.content.as_bytes());
+        let headers = rdr.headers()?.clone();
+        let column = |field: &str| {
+            headers
+                .iter()
+                .position(|header| header == field)
+                .ok_or_else(|| anyhow!(ImportError::MissingColumn(field.to_string())))
+        };
+        let date = column(DATE_FIELD)?;
+        let status = column(STATUS_FIELD)?;
+        let description = column(DESCRIPTION_FIELD)?;
+        let kind = column(TYPE_FIELD)?;
+        let isin = column(ISIN_FIELD)?;
+        let shares = column(SHARES_FIELD)?;
+        let price = column(PRICE_FIELD)?;
+        let amount = column(AMOUNT_FIELD)?;
+        let fee = column(FEE_FIELD)?;
+        let currency = column(CURRENCY_FIELD)?;
+
+        let mut account_notes = vec![];
+        for result in rdr.records() {
+            let record = result?;
+            log::debug!("{:?}", record);
+            let field = |index: usize| record.get(index).unwrap_or("").trim();
+
+            if field(status) != EXECUTED_STATUS {
+                continue;
+            }
+            let Some(operation) = ScalableCSVParser::broker_operation(field(kind)) else {
+                continue;
+            };
+            if field(currency) != "EUR" {
+                bail!(ImportError::UnsupportedAsset(format!(
+                    "{} ({})",
+                    field(isin),
+                    field(currency)
+                )));
+            }
+
+            account_notes.push(AccountNote::new(
+                NaiveDate::parse_from_str(field(date), "%Y-%m-%d")
+                    .with_context(|| format!("Invalid date {}", field(date)))
+                    .record_context(&record)?,
+                CompanyInfo {
+                    name: field(description).to_string(),
+                    isin: field(isin).to_string(),
+                },
+                operation,
+                ScalableCSVParser::parse_decimal(field(shares)).record_context(&record)?,
+                ScalableCSVParser::parse_decimal(field(price)).record_context(&record)?,
+                ScalableCSVParser::parse_decimal(field(amount))
+                    .record_context(&record)?
+                    .abs(),
+                ScalableCSVParser::parse_decimal(field(fee))
+                    .record_context(&record)?
+                    .abs(),
+                &self.broker,
+            ));
+        }
+
+        Ok(account_notes)
+    }
+
+    /// Parser of a Scalable Capital CSV export.
+    pub fn new(content: String, broker: &Arc<BrokerInformation>) -> Self {
+        Self {
+            content,
+            broker: Arc::clone(broker),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = r#"date;time;status;reference;description;assetType;type;isin;shares;price;amount;fee;tax;currency
+2023-01-02;08:00:00;Executed;"SCALsin78vS5CYz";"Deposit";Cash;Deposit;;;;1.000,00;;;EUR
+2023-01-16;10:15:02;Executed;"SCALsin1";"iShares Core MSCI World";Security;Savings plan;IE00B4L5Y983;1,234;76,50;-94,40;0,00;0,00;EUR
+2023-03-01;12:30:45;Cancelled;"SCALsin2";"Apple";Security;Buy;US0378331005;5;140,00;-700,00;0,99;0,00;EUR
+2023-06-20;09:01:10;Executed;"SCALsin3";"Siemens";Security;Sell;DE0007236101;2;165,10;330,20;0,99;0,00;EUR
+2023-07-01;09:00:00;Executed;"SCALsin4";"iShares Core MSCI World";Security;Distribution;IE00B4L5Y983;;;2,10;;;EUR
+"#;
+
+    #[test]
+    fn parse_account_notes_test() {
+        let broker = Arc::new(BrokerInformation::new(
+            String::from("Baader Bank"),
+            String::from("DE"),
+        ));
+        let parser = ScalableCSVParser::new(INPUT.to_string(), &broker);
+
+        assert_eq!(
+            parser.parse_account_notes().unwrap(),
+            vec![
+                AccountNote::new(
+                    NaiveDate::from_ymd_opt(2023, 1, 16).unwrap(),
+                    CompanyInfo {
+                        name: String::from("iShares Core MSCI World"),
+                        isin: String::from("IE00B4L5Y983"),
+                    },
+                    BrokerOperation::Buy,
+                    Decimal::new(1_234, 3),
+                    Decimal::new(76_50, 2),
+                    Decimal::new(94_40, 2),
+                    Decimal::ZERO,
+                    &broker,
+                ),
+                AccountNote::new(
+                    NaiveDate::from_ymd_opt(2023, 6, 20).unwrap(),
+                    CompanyInfo {
+                        name: String::from("Siemens"),
+                        isin: String::from("DE0007236101"),
+                    },
+                    BrokerOperation::Sell,
+                    Decimal::new(2, 0),
+                    Decimal::new(165_10, 2),
+                    Decimal::new(330_20, 2),
+                    Decimal::new(99, 2),
+                    &broker,
+                ),
+            ]
+        );
+    }
+}
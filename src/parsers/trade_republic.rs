@@ -0,0 +1,518 @@
+use std::sync::Arc;
+
+use crate::data::{
+    AccountNote, AccountNotes, BalanceNote, BalanceNotes, BrokerInformation, BrokerOperation,
+    CompanyInfo,
+};
+use crate::error::ImportError;
+use crate::parsers::{degiro::DegiroParser, util};
+
+use anyhow::{Result, bail};
+use chrono::NaiveDate;
+use nom::character::complete::anychar;
+use nom::combinator::{eof, value};
+use nom::error::ErrorKind;
+use nom::multi::{many_till, many0};
+use nom::sequence::preceded;
+use nom::{IResult, Parser, branch::alt, bytes::complete::tag, error::context};
+
+type Res<T, U> = IResult<T, U, (T, ErrorKind)>;
+
+/// Text found in every document issued by Trade Republic.
+pub(crate) const TRADE_REPUBLIC_MARK: &str = "Trade Republic Bank GmbH";
+
+/// Header of the positions table of the securities account statement
+/// (German and Spanish editions).
+const TRADE_REPUBLIC_BALANCE_HEADER_BEGIN: [&str; 2] = [
+    "STK. / NOMINALE WERTPAPIERBEZEICHNUNG KURS PRO STÜCK KURSWERT IN EUR\n",
+    "CANT. / NOMINAL DESCRIPCIÓN DEL VALOR PRECIO POR UNIDAD VALOR DE MERCADO EN EUR\n",
+];
+
+const TRADE_REPUBLIC_BALANCE_HEADER_END: [&str; 2] = ["ANZAHL POSITIONEN", "NÚMERO DE POSICIONES"];
+
+/// Header of the transactions table of the annual tax report (German and
+/// Spanish editions).
+const TRADE_REPUBLIC_NOTES_HEADER_BEGIN: [&str; 2] = [
+    "DATUM GESCHÄFT WERTPAPIER STÜCK KURS BETRAG GEBÜHR\n",
+    "FECHA OPERACIÓN VALOR CANTIDAD PRECIO IMPORTE COMISIÓN\n",
+];
+
+/// Section following the transactions in the annual tax report.
+const TRADE_REPUBLIC_NOTES_HEADER_END: [&str; 2] = ["GEWINNE UND VERLUSTE", "GANANCIAS Y PÉRDIDAS"];
+
+/// Parser of the text extracted from the Trade Republic PDF reports.
+#[derive(Debug)]
+pub struct TradeRepublicParser {
+    content: String,
+    broker: Arc<BrokerInformation>,
+}
+
+impl TradeRepublicParser {
+    fn date_concept(input: &str) -> Res<&str, NaiveDate> {
+        context(
+            "date concept",
+            (
+                DegiroParser::n_to_m_digits(2, 2),
+                tag("."),
+                DegiroParser::n_to_m_digits(2, 2),
+                tag("."),
+                DegiroParser::n_to_m_digits(4, 4),
+            ),
+        )
+        .parse(input)
+        .and_then(|(next_input, res)| {
+            let (day, _, month, _, year) = res;
+            match NaiveDate::from_ymd_opt(
+                year.parse::<i32>().unwrap(),
+                month.parse::<u32>().unwrap(),
+                day.parse::<u32>().unwrap(),
+            ) {
+                Some(date) => Ok((next_input, date)),
+                None => Err(nom::Err::Error((input, ErrorKind::Verify))),
+            }
+        })
+    }
+
+    fn broker_operation(input: &str) -> Res<&str, BrokerOperation> {
+        context(
+            "broker operation",
+            alt((
+                value(BrokerOperation::Buy, tag("Kauf")),
+                value(BrokerOperation::Sell, tag("Verkauf")),
+                value(BrokerOperation::Buy, tag("Compra")),
+                value(BrokerOperation::Sell, tag("Venta")),
+            )),
+        )
+        .parse(input)
+    }
+
+    fn quantity_unit(input: &str) -> Res<&str, &str> {
+        context("quantity unit", alt((tag("Stk."), tag("uds.")))).parse(input)
+    }
+
+    fn company_info(input: &str) -> Res<&str, CompanyInfo> {
+        context(
+            "company info",
+            many_till(anychar, preceded(tag("ISIN: "), DegiroParser::isin)),
+        )
+        .parse(input)
+        .map(|(next_input, res)| {
+            let (company_name, isin) = res;
+            let company_name: String = company_name.into_iter().collect();
+            let company_name = company_name.replace('\n', " ").trim().to_string();
+
+            (
+                next_input,
+                CompanyInfo {
+                    name: company_name,
+                    isin,
+                },
+            )
+        })
+    }
+
+    fn account_note<'a>(
+        input: &'a str,
+        broker: &Arc<BrokerInformation>,
+    ) -> Res<&'a str, AccountNote> {
+        context(
+            "account note",
+            (
+                TradeRepublicParser::date_concept,
+                tag(" "),
+                TradeRepublicParser::broker_operation,
+                tag(" "),
+                TradeRepublicParser::company_info,
+                tag(" "),
+                DegiroParser::decimal_value, // quantity
+                tag(" "),
+                DegiroParser::decimal_value, // price
+                tag(" "),
+                DegiroParser::decimal_value, // value in euro
+                tag(" "),
+                DegiroParser::decimal_value, // commision
+                tag("\n"),
+            ),
+        )
+        .parse(input)
+        .map(|(next_input, res)| {
+            let (date, _, operation, _, company, _, quantity, _, price, _, value, _, commision, _) =
+                res;
+
+            (
+                next_input,
+                AccountNote::new(
+                    date, company, operation, quantity, price, value, commision, broker,
+                ),
+            )
+        })
+    }
+
+    fn balance_note<'a>(
+        input: &'a str,
+        broker: &Arc<BrokerInformation>,
+    ) -> Res<&'a str, BalanceNote> {
+        log::trace!("balance note: -{}-", input);
+        context(
+            "balance note",
+            (
+                DegiroParser::decimal_value, // quantity
+                tag(" "),
+                TradeRepublicParser::quantity_unit,
+                tag(" "),
+                TradeRepublicParser::company_info,
+                tag("\n"),
+                DegiroParser::decimal_value, // price
+                tag("\n"),
+                TradeRepublicParser::date_concept, // price date
+                tag("\n"),
+                DegiroParser::decimal_value, // value in euro
+                tag("\n"),
+            ),
+        )
+        .parse(input)
+        .map(|(next_input, res)| {
+            let (quantity, _, _, _, company, _, price, _, _, _, value_in_euro, _) = res;
+            let category = util::name_asset_category(&company.name);
+            let mut note = BalanceNote::new(
+                company,
+                String::new(),
+                quantity,
+                String::from("EUR"),
+                price,
+                value_in_euro,
+                broker,
+            );
+            note.category = category;
+
+            (next_input, note)
+        })
+    }
+
+    /// Lines between the rows of a table, blank lines and the header
+    /// repeated at the top of each page.
+    fn row_separator(input: &str) -> Res<&str, ()> {
+        value(
+            (),
+            many0(alt((
+                tag("\n"),
+                tag(TRADE_REPUBLIC_NOTES_HEADER_BEGIN[0]),
+                tag(TRADE_REPUBLIC_NOTES_HEADER_BEGIN[1]),
+                tag(TRADE_REPUBLIC_BALANCE_HEADER_BEGIN[0]),
+                tag(TRADE_REPUBLIC_BALANCE_HEADER_BEGIN[1]),
+            ))),
+        )
+        .parse(input)
+    }
+
+    /// Every row of a section, which must end right after the last one.
+    fn account_notes<'a>(
+        input: &'a str,
+        broker: &Arc<BrokerInformation>,
+    ) -> Res<&'a str, AccountNotes> {
+        context(
+            "account notes",
+            many_till(
+                preceded(TradeRepublicParser::row_separator, |x| {
+                    TradeRepublicParser::account_note(x, broker)
+                }),
+                preceded(TradeRepublicParser::row_separator, eof),
+            ),
+        )
+        .parse(input)
+        .map(|(next_input, (notes, _))| (next_input, notes))
+    }
+
+    fn balance_notes<'a>(
+        input: &'a str,
+        broker: &Arc<BrokerInformation>,
+    ) -> Res<&'a str, BalanceNotes> {
+        context(
+            "balance notes",
+            many_till(
+                preceded(TradeRepublicParser::row_separator, |x| {
+                    TradeRepublicParser::balance_note(x, broker)
+                }),
+                preceded(TradeRepublicParser::row_separator, eof),
+            ),
+        )
+        .parse(input)
+        .map(|(next_input, (notes, _))| (next_input, notes))
+    }
+
+    fn find_any(content: &str, patterns: &[&str]) -> Option<(usize, usize)> {
+        patterns
+            .iter()
+            .find_map(|pattern| content.find(pattern).map(|pos| (pos, pattern.len())))
+    }
+
+    fn parse_pdf_account_notes(&self) -> Result<AccountNotes> {
+        let Some((begin, len)) =
+            TradeRepublicParser::find_any(&self.content, &TRADE_REPUBLIC_NOTES_HEADER_BEGIN)
+        else {
+            return Ok(vec![]);
+        };
+
+        let notes = &self.content[begin + len..];
+        let notes = match TradeRepublicParser::find_any(notes, &TRADE_REPUBLIC_NOTES_HEADER_END) {
+            Some((end, _)) => &notes[..end],
+            None => notes,
+        };
+        log::debug!("account notes:-{}-", notes);
+        match TradeRepublicParser::account_notes(notes, &self.broker) {
+            Ok((_, notes)) => {
+                log::debug!("Ok parsing {} account notes", notes.len());
+                Ok(notes)
+            }
+            Err(err) => {
+                bail!(util::text_row_error(&self.content, "Operaciones", err));
+            }
+        }
+    }
+
+    fn parse_pdf_balance_notes(&self) -> Result<BalanceNotes> {
+        let Some((begin, len)) =
+            TradeRepublicParser::find_any(&self.content, &TRADE_REPUBLIC_BALANCE_HEADER_BEGIN)
+        else {
+            return Ok(vec![]);
+        };
+
+        let notes = &self.content[begin + len..];
+        let notes = match TradeRepublicParser::find_any(notes, &TRADE_REPUBLIC_BALANCE_HEADER_END) {
+            Some((end, _)) => &notes[..end],
+            None => notes,
+        };
+
+        log::debug!("balance notes:-{}-", notes);
+        match TradeRepublicParser::balance_notes(notes, &self.broker) {
+            Ok((_, notes)) => {
+                log::debug!("Ok parsing {} balance notes", notes.len());
+                Ok(notes)
+            }
+            Err(err) => {
+                bail!(util::text_row_error(&self.content, "Posiciones", err));
+            }
+        }
+    }
+
+    /// Parser of the text extracted from a Trade Republic report.
+    pub fn new(content: String, broker: &Arc<BrokerInformation>) -> Self {
+        Self {
+            content,
+            broker: Arc::clone(broker),
+        }
+    }
+
+    /// Parses either a securities account statement, which gives the open
+    /// positions, or an annual tax report, which gives the year transactions.
+    pub fn parse_pdf_content(&self) -> Result<(BalanceNotes, AccountNotes)> {
+        let account_notes = self.parse_pdf_account_notes()?;
+        let balance_notes = self.parse_pdf_balance_notes()?;
+
+        if account_notes.is_empty() && balance_notes.is_empty() {
+            bail!(ImportError::MissingSection(String::from(
+                "Posiciones u Operaciones"
+            )));
+        }
+
+        Ok((balance_notes, account_notes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::AssetCategory;
+    use crate::error::{Location, RowIssue};
+    use rust_decimal::Decimal;
+
+    const INPUT_STATEMENT: &str = r#"Trade Republic Bank GmbH
+DEPOTAUSZUG
+zum 31.12.2023
+STK. / NOMINALE WERTPAPIERBEZEICHNUNG KURS PRO STÜCK KURSWERT IN EUR
+3,000000 Stk. Apple Inc.
+Registered Shares o.N.
+ISIN: US0378331005
+174,79
+29.12.2023
+524,37
+12,534100 Stk. iShs Core MSCI World UCITS ETF
+ISIN: IE00B4L5Y983
+1.000,50
+29.12.2023
+12.540,63
+ANZAHL POSITIONEN: 2 KURSWERT IN EUR: 13.065,00
+"#;
+
+    const INPUT_TAX_REPORT: &str = r#"Trade Republic Bank GmbH
+INFORME FISCAL 2023
+FECHA OPERACIÓN VALOR CANTIDAD PRECIO IMPORTE COMISIÓN
+15.03.2023 Compra Apple Inc. ISIN: US0378331005 3,000000 145,10 435,30 1,00
+02.11.2023 Venta Siemens AG ISIN: DE0007236101 1,500000 120,00 180,00 1,00
+GANANCIAS Y PÉRDIDAS
+"#;
+
+    /// Tax report as `read_pdf` extracts it, with the blank lines and the
+    /// header repeated where the table continues on the next page.
+    const INPUT_TAX_REPORT_PAGES: &str = r#"
+
+Trade Republic Bank GmbH
+INFORME FISCAL 2023
+FECHA OPERACIÓN VALOR CANTIDAD PRECIO IMPORTE COMISIÓN
+15.03.2023 Compra Apple Inc. ISIN: US0378331005 3,000000 145,10 435,30 1,00
+
+
+FECHA OPERACIÓN VALOR CANTIDAD PRECIO IMPORTE COMISIÓN
+02.11.2023 Venta Siemens AG ISIN: DE0007236101 1,500000 120,00 180,00 1,00
+
+GANANCIAS Y PÉRDIDAS
+"#;
+
+    fn trade_republic_broker() -> Arc<BrokerInformation> {
+        Arc::new(BrokerInformation::new(
+            String::from("Trade Republic"),
+            String::from("DE"),
+        ))
+    }
+
+    #[test]
+    fn date_concept_test() {
+        assert_eq!(
+            TradeRepublicParser::date_concept("29.12.2023\n"),
+            Ok(("\n", NaiveDate::from_ymd_opt(2023, 12, 29).unwrap()))
+        );
+        assert_eq!(
+            TradeRepublicParser::date_concept("32.12.2023\n"),
+            Err(nom::Err::Error(("32.12.2023\n", ErrorKind::Verify)))
+        );
+    }
+
+    #[test]
+    fn broker_operation_test() {
+        assert_eq!(
+            TradeRepublicParser::broker_operation("Kauf "),
+            Ok((" ", BrokerOperation::Buy))
+        );
+        assert_eq!(
+            TradeRepublicParser::broker_operation("Venta "),
+            Ok((" ", BrokerOperation::Sell))
+        );
+    }
+
+    #[test]
+    fn parse_statement_test() {
+        let broker = trade_republic_broker();
+        let parser = TradeRepublicParser::new(INPUT_STATEMENT.to_string(), &broker);
+        let (balance_notes, account_notes) = parser.parse_pdf_content().unwrap();
+
+        assert!(account_notes.is_empty());
+        let mut expected = vec![
+            BalanceNote::new(
+                CompanyInfo {
+                    name: String::from("Apple Inc. Registered Shares o.N."),
+                    isin: String::from("US0378331005"),
+                },
+                String::new(),
+                Decimal::new(3_000000, 6),
+                String::from("EUR"),
+                Decimal::new(174_79, 2),
+                Decimal::new(524_37, 2),
+                &broker,
+            ),
+            BalanceNote::new(
+                CompanyInfo {
+                    name: String::from("iShs Core MSCI World UCITS ETF"),
+                    isin: String::from("IE00B4L5Y983"),
+                },
+                String::new(),
+                Decimal::new(12_534100, 6),
+                String::from("EUR"),
+                Decimal::new(1_000_50, 2),
+                Decimal::new(12_540_63, 2),
+                &broker,
+            ),
+        ];
+        expected[1].category = AssetCategory::Fund;
+        assert_eq!(balance_notes, expected);
+    }
+
+    #[test]
+    fn parse_tax_report_test() {
+        let broker = trade_republic_broker();
+        let parser = TradeRepublicParser::new(INPUT_TAX_REPORT.to_string(), &broker);
+        let (balance_notes, account_notes) = parser.parse_pdf_content().unwrap();
+
+        assert!(balance_notes.is_empty());
+        assert_eq!(
+            account_notes,
+            vec![
+                AccountNote::new(
+                    NaiveDate::from_ymd_opt(2023, 3, 15).unwrap(),
+                    CompanyInfo {
+                        name: String::from("Apple Inc."),
+                        isin: String::from("US0378331005"),
+                    },
+                    BrokerOperation::Buy,
+                    Decimal::new(3_000000, 6),
+                    Decimal::new(145_10, 2),
+                    Decimal::new(435_30, 2),
+                    Decimal::new(1_00, 2),
+                    &broker,
+                ),
+                AccountNote::new(
+                    NaiveDate::from_ymd_opt(2023, 11, 2).unwrap(),
+                    CompanyInfo {
+                        name: String::from("Siemens AG"),
+                        isin: String::from("DE0007236101"),
+                    },
+                    BrokerOperation::Sell,
+                    Decimal::new(1_500000, 6),
+                    Decimal::new(120_00, 2),
+                    Decimal::new(180_00, 2),
+                    Decimal::new(1_00, 2),
+                    &broker,
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_tax_report_pages_test() {
+        let broker = trade_republic_broker();
+        let parser = TradeRepublicParser::new(INPUT_TAX_REPORT_PAGES.to_string(), &broker);
+        let (_, account_notes) = parser.parse_pdf_content().unwrap();
+
+        assert_eq!(account_notes.len(), 2);
+        assert_eq!(account_notes[1].company.isin, "DE0007236101");
+    }
+
+    #[test]
+    fn parse_malformed_row_test() {
+        let broker = trade_republic_broker();
+        let input = INPUT_TAX_REPORT_PAGES.replace("1,500000 120,00", "1,500000 12O,00");
+        let parser = TradeRepublicParser::new(input, &broker);
+        let err = ImportError::from(parser.parse_pdf_content().unwrap_err());
+
+        assert_eq!(
+            err,
+            ImportError::MalformedRow {
+                location: Location::line(10).in_section("Operaciones"),
+                raw: String::from(
+                    "02.11.2023 Venta Siemens AG ISIN: DE0007236101 1,500000 12O,00 180,00 1,00"
+                ),
+                issue: RowIssue::UnexpectedLayout,
+            }
+        );
+
+        let input = INPUT_STATEMENT.replace("524,37\n", "524,37\nPágina 1 de 2\n");
+        let parser = TradeRepublicParser::new(input, &broker);
+        assert!(parser.parse_pdf_content().is_err());
+    }
+
+    #[test]
+    fn parse_unknown_document_test() {
+        let broker = trade_republic_broker();
+        let parser = TradeRepublicParser::new(TRADE_REPUBLIC_MARK.to_string(), &broker);
+        assert!(parser.parse_pdf_content().is_err());
+    }
+}
@@ -0,0 +1,367 @@
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::Decimal;
+use std::{str::FromStr, sync::Arc};
+
+use crate::{
+    data::{AccountNote, AccountNotes, BrokerInformation, BrokerOperation, CompanyInfo},
+    error::{ImportError, ImportWarning, RowContext},
+    utils::fx,
+};
+
+/// First columns of the Trading 212 history export, used to detect it.
+pub(crate) const TRADING212_HEADER_BEGIN: &str = "Action,Time,ISIN";
+
+const ACTION_FIELD: &str = "Action";
+const TIME_FIELD: &str = "Time";
+const ISIN_FIELD: &str = "ISIN";
+const TICKER_FIELD: &str = "Ticker";
+const NAME_FIELD: &str = "Name";
+const QUANTITY_FIELD: &str = "No. of shares";
+const PRICE_FIELD: &str = "Price / share";
+const CURRENCY_FIELD: &str = "Currency (Price / share)";
+const EXCHANGE_RATE_FIELD: &str = "Exchange rate";
+const TOTAL_FIELD: &str = "Total";
+const TOTAL_CURRENCY_FIELD: &str = "Currency (Total)";
+const EUR_CURRENCY: &str = "EUR";
+
+/// Charges listed in their own column, all of them in the account currency.
+const FEE_FIELDS: [&str; 4] = [
+    "Currency conversion fee",
+    "Stamp duty reserve tax",
+    "Finra fee",
+    "French transaction tax",
+];
+
+/// Parser of the history CSV exported by Trading 212.
+#[derive(Debug)]
+pub struct Trading212Parser {
+    content: String,
+    broker: Arc<BrokerInformation>,
+    warnings: Vec<ImportWarning>,
+}
+
+struct Columns {
+    action: usize,
+    time: usize,
+    isin: usize,
+    ticker: Option<usize>,
+    name: Option<usize>,
+    quantity: usize,
+    price: usize,
+    currency: Option<usize>,
+    exchange_rate: usize,
+    total: usize,
+    total_currency: Option<usize>,
+    fees: Vec<usize>,
+}
+
+impl Columns {
+    fn from_headers(headers: &csv::StringRecord) -> Result<Self> {
+        let position = |field: &str| headers.iter().position(|header| header == field);
+        let required = |field: &str| {
+            position(field).ok_or_else(|| anyhow!(ImportError::MissingColumn(field.to_string())))
+        };
+
+        Ok(Self {
+            action: required(ACTION_FIELD)?,
+            time: required(TIME_FIELD)?,
+            isin: required(ISIN_FIELD)?,
+            ticker: position(TICKER_FIELD),
+            name: position(NAME_FIELD),
+            quantity: required(QUANTITY_FIELD)?,
+            price: required(PRICE_FIELD)?,
+            currency: position(CURRENCY_FIELD),
+            exchange_rate: required(EXCHANGE_RATE_FIELD)?,
+            total: required(TOTAL_FIELD)?,
+            total_currency: position(TOTAL_CURRENCY_FIELD),
+            fees: FEE_FIELDS
+                .iter()
+                .filter_map(|field| position(field))
+                .collect(),
+        })
+    }
+}
+
+impl Trading212Parser {
+    fn broker_operation(action: &str) -> Option<BrokerOperation> {
+        let action = action.to_lowercase();
+        if action.ends_with(" buy") {
+            Some(BrokerOperation::Buy)
+        } else if action.ends_with(" sell") {
+            Some(BrokerOperation::Sell)
+        } else {
+            None
+        }
+    }
+
+    fn parse_date(time: &str) -> Result<NaiveDate> {
+        let date = time.get(..10).unwrap_or(time);
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .with_context(|| format!("Invalid date {} in Trading 212 CSV", time))
+    }
+
+    fn parse_decimal(value: &str) -> Result<Decimal> {
+        if value.is_empty() {
+            return Ok(Decimal::ZERO);
+        }
+        Decimal::from_str(value).with_context(|| format!("Invalid number {}", value))
+    }
+
+    fn field(record: &csv::StringRecord, index: usize) -> &str {
+        record.get(index).unwrap_or("").trim()
+    }
+
+    fn parse_account_note(
+        &self,
+        record: &csv::StringRecord,
+        columns: &Columns,
+        operation: BrokerOperation,
+        warnings: &mut Vec<ImportWarning>,
+    ) -> Result<AccountNote> {
+        let isin = Trading212Parser::field(record, columns.isin);
+        if isin.is_empty() {
+            bail!("Trade without ISIN in Trading 212 CSV");
+        }
+        let name = columns
+            .name
+            .or(columns.ticker)
+            .map(|index| Trading212Parser::field(record, index))
+            .filter(|name| !name.is_empty())
+            .unwrap_or(isin);
+
+        let quantity =
+            Trading212Parser::parse_decimal(Trading212Parser::field(record, columns.quantity))?;
+        let price =
+            Trading212Parser::parse_decimal(Trading212Parser::field(record, columns.price))?;
+        let currency = columns
+            .currency
+            .map(|index| Trading212Parser::field(record, index))
+            .unwrap_or(EUR_CURRENCY);
+        let account_currency = columns
+            .total_currency
+            .map(|index| Trading212Parser::field(record, index))
+            .filter(|currency| !currency.is_empty())
+            .unwrap_or(EUR_CURRENCY);
+        // Exchange rate is given as units of the price currency per unit of
+        // the account currency.
+        let exchange_rate =
+            match Decimal::from_str(Trading212Parser::field(record, columns.exchange_rate)) {
+                Ok(rate) if !rate.is_zero() => rate,
+                _ if currency == account_currency => Decimal::ONE,
+                _ => bail!("Unknown exchange rate for {} in Trading 212 CSV", isin),
+            };
+        let price = price / exchange_rate;
+        let date = Trading212Parser::parse_date(Trading212Parser::field(record, columns.time))?;
+        let year = date.year() as usize;
+        // Amounts of accounts in other currencies are converted to euros at
+        // the reference rate of the end of the year of the trade.
+        let euro_rate = fx::year_end_rate(account_currency, year).unwrap_or_else(|| {
+            let warning = ImportWarning::MissingExchangeRate {
+                currency: account_currency.to_string(),
+                year,
+            };
+            if !warnings.contains(&warning) {
+                warnings.push(warning);
+            }
+            Decimal::ZERO
+        });
+        let to_euro = |amount: Decimal| {
+            if euro_rate.is_zero() {
+                Decimal::ZERO
+            } else {
+                amount / euro_rate
+            }
+        };
+
+        let mut commision = Decimal::ZERO;
+        for index in columns.fees.iter() {
+            commision += Trading212Parser::parse_decimal(Trading212Parser::field(record, *index))?;
+        }
+
+        // Total is the cash movement in the account currency, fees included.
+        let total =
+            Trading212Parser::parse_decimal(Trading212Parser::field(record, columns.total))?;
+        let value = match operation {
+            BrokerOperation::Buy => total - commision,
+            BrokerOperation::Sell => total + commision,
+        };
+
+        Ok(AccountNote::new(
+            date,
+            CompanyInfo {
+                name: name.to_string(),
+                isin: isin.to_string(),
+            },
+            operation,
+            quantity,
+            to_euro(price).round_dp(4),
+            to_euro(value).round_dp(2),
+            to_euro(commision).round_dp(2),
+            &self.broker,
+        ))
+    }
+
+    /// Trades found in the history, deposits, dividends and any other
+    /// cash movements are ignored.
+    pub fn parse_account_notes(&mut self) -> Result<AccountNotes> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(self.content.as_bytes());
+        let columns = Columns::from_headers(rdr.headers()?)?;
+        let mut account_notes = vec![];
+        let mut warnings = vec![];
+
+        for result in rdr.records() {
+            let record = result?;
+            log::debug!("{:?}", record);
+            let Some(operation) = Trading212Parser::broker_operation(Trading212Parser::field(
+                &record,
+                columns.action,
+            )) else {
+                continue;
+            };
+
+            account_notes.push(
+                self.parse_account_note(&record, &columns, operation, &mut warnings)
+                    .record_context(&record)?,
+            );
+        }
+        self.warnings.extend(warnings);
+
+        Ok(account_notes)
+    }
+
+    /// Parser of a Trading 212 history export.
+    pub fn new(content: String, broker: &Arc<BrokerInformation>) -> Self {
+        Self {
+            content,
+            broker: Arc::clone(broker),
+            warnings: vec![],
+        }
+    }
+
+    /// Account currencies of the trades left without value in euros.
+    pub fn warnings(&self) -> &[ImportWarning] {
+        &self.warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = r#"Action,Time,ISIN,Ticker,Name,No. of shares,Price / share,Currency (Price / share),Exchange rate,Result,Currency (Result),Total,Currency (Total),Currency conversion fee,Currency (Currency conversion fee)
+Deposit,2023-01-10 09:12:01,,,,,,,,,,1000.00,EUR,,
+Market buy,2023-01-12 15:30:05,US0378331005,AAPL,Apple,2.5000000000,130.00,USD,1.0800000000,,,301.24,EUR,0.45,EUR
+Limit buy,2023-02-01 10:01:22.123,IE00B4L5Y983,SWDA,iShares Core MSCI World,3,75.20,EUR,1.00,,,225.60,EUR,,
+Dividend (Dividend),2023-05-18 12:00:00,US0378331005,AAPL,Apple,2.5000000000,0.22,USD,Not available,,,0.51,EUR,,
+Market sell,2023-06-20 14:00:00,US0378331005,AAPL,Apple,1.0000000000,185.00,USD,1.0900000000,15.40,EUR,169.57,EUR,0.25,EUR
+"#;
+
+    #[test]
+    fn parse_account_notes_test() {
+        let broker = Arc::new(BrokerInformation::new(
+            String::from("Trading 212"),
+            String::from("CY"),
+        ));
+        let mut parser = Trading212Parser::new(INPUT.to_string(), &broker);
+        let apple = CompanyInfo {
+            name: String::from("Apple"),
+            isin: String::from("US0378331005"),
+        };
+
+        assert_eq!(
+            parser.parse_account_notes().unwrap(),
+            vec![
+                AccountNote::new(
+                    NaiveDate::from_ymd_opt(2023, 1, 12).unwrap(),
+                    apple.clone(),
+                    BrokerOperation::Buy,
+                    Decimal::new(2_5000000000, 10),
+                    Decimal::new(120_3704, 4),
+                    Decimal::new(300_79, 2),
+                    Decimal::new(45, 2),
+                    &broker,
+                ),
+                AccountNote::new(
+                    NaiveDate::from_ymd_opt(2023, 2, 1).unwrap(),
+                    CompanyInfo {
+                        name: String::from("iShares Core MSCI World"),
+                        isin: String::from("IE00B4L5Y983"),
+                    },
+                    BrokerOperation::Buy,
+                    Decimal::new(3, 0),
+                    Decimal::new(75_2000, 4),
+                    Decimal::new(225_60, 2),
+                    Decimal::ZERO,
+                    &broker,
+                ),
+                AccountNote::new(
+                    NaiveDate::from_ymd_opt(2023, 6, 20).unwrap(),
+                    apple,
+                    BrokerOperation::Sell,
+                    Decimal::new(1_0000000000, 10),
+                    Decimal::new(169_7248, 4),
+                    Decimal::new(169_82, 2),
+                    Decimal::new(25, 2),
+                    &broker,
+                ),
+            ]
+        );
+    }
+
+    const INPUT_GBP: &str = r#"Action,Time,ISIN,Ticker,Name,No. of shares,Price / share,Currency (Price / share),Exchange rate,Result,Currency (Result),Total,Currency (Total),Currency conversion fee,Currency (Currency conversion fee)
+Market buy,2018-11-05 09:00:00,GB00BH4HKS39,VOD,Vodafone,10,1.60,GBP,1.00,,,16.00,GBP,,
+Market buy,2023-03-01 10:00:00,US0378331005,AAPL,Apple,2,145.00,USD,1.2000000000,,,242.05,GBP,0.37,GBP
+"#;
+
+    #[test]
+    fn parse_account_notes_gbp_account_test() {
+        let broker = Arc::new(BrokerInformation::new(
+            String::from("Trading 212"),
+            String::from("CY"),
+        ));
+        let mut parser = Trading212Parser::new(INPUT_GBP.to_string(), &broker);
+        let notes = parser.parse_account_notes().unwrap();
+
+        // Amounts in pounds converted at the reference rate of the year end
+        assert_eq!(
+            notes[1],
+            AccountNote::new(
+                NaiveDate::from_ymd_opt(2023, 3, 1).unwrap(),
+                CompanyInfo {
+                    name: String::from("Apple"),
+                    isin: String::from("US0378331005"),
+                },
+                BrokerOperation::Buy,
+                Decimal::new(2, 0),
+                Decimal::new(139_0407, 4),
+                Decimal::new(278_10, 2),
+                Decimal::new(43, 2),
+                &broker,
+            )
+        );
+        // No reference rate before 2019
+        assert_eq!(notes[0].quantity, Decimal::new(10, 0));
+        assert_eq!(notes[0].price, Decimal::ZERO);
+        assert_eq!(notes[0].value, Decimal::ZERO);
+        assert_eq!(
+            parser.warnings(),
+            [ImportWarning::MissingExchangeRate {
+                currency: String::from("GBP"),
+                year: 2018,
+            }]
+        );
+    }
+
+    #[test]
+    fn missing_columns_test() {
+        let broker = Arc::new(BrokerInformation::new(
+            String::from("Trading 212"),
+            String::from("CY"),
+        ));
+        let mut parser = Trading212Parser::new(String::from("Action,Time,ISIN\n"), &broker);
+        assert!(parser.parse_account_notes().is_err());
+    }
+}
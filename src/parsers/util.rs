@@ -1,5 +1,12 @@
-use crate::data::BalanceNotes;
+use crate::{
+    data::{
+        AccountNotes, AssetCategory, BalanceNotes, BrokerOperation, CorporateActionKind,
+        CorporateActionNote, CorporateActionNotes,
+    },
+    error::{ImportError, Location, RowIssue},
+};
 use anyhow::Result;
+use nom::error::ErrorKind;
 use rust_decimal::Decimal;
 
 pub(crate) fn recalculate_balance_notes(
@@ -16,6 +23,35 @@ pub(crate) fn recalculate_balance_notes(
     Ok(())
 }
 
+/// Line of the text `content` where a nom parser of the `section` stopped,
+/// the input left in the error being a slice of `content`.
+pub(crate) fn text_row_error(
+    content: &str,
+    section: &str,
+    err: nom::Err<(&str, ErrorKind)>,
+) -> ImportError {
+    let rest = match err {
+        nom::Err::Error((rest, _)) | nom::Err::Failure((rest, _)) => Some(rest),
+        nom::Err::Incomplete(_) => None,
+    };
+    let offset = rest
+        .and_then(|rest| (rest.as_ptr() as usize).checked_sub(content.as_ptr() as usize))
+        .filter(|offset| *offset <= content.len())
+        .unwrap_or(content.len());
+    let begin = content[..offset].rfind('\n').map_or(0, |index| index + 1);
+
+    ImportError::MalformedRow {
+        location: Location::line(content[..offset].matches('\n').count() + 1).in_section(section),
+        raw: content[begin..]
+            .lines()
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_string(),
+        issue: RowIssue::UnexpectedLayout,
+    }
+}
+
 pub(crate) fn replace_escaped_fields(original_str: &str) -> String {
     let mut fields_str = String::new();
     let mut in_quoted_field = false;
@@ -35,3 +71,309 @@ pub(crate) fn replace_escaped_fields(original_str: &str) -> String {
 
     fields_str
 }
+
+/// Fiat currencies found in the crypto exchanges exports, their balances are
+/// cash and not virtual currencies.
+const FIAT_CURRENCIES: [&str; 8] = ["EUR", "USD", "GBP", "CHF", "CAD", "JPY", "AUD", "TRY"];
+
+pub(crate) fn is_fiat_currency(currency: &str) -> bool {
+    FIAT_CURRENCIES.contains(&currency.trim().to_uppercase().as_str())
+}
+
+/// Classifies the asset category of Interactive Brokers reports, both in
+/// English and Spanish. Currency conversions are not instruments, so `None`
+/// is returned for them as well as for empty categories. Categories not
+/// recognized are `Unknown`, left out of the form with a warning.
+pub(crate) fn ib_asset_category(category: &str) -> Option<AssetCategory> {
+    let category = category.trim().to_lowercase();
+    if category.is_empty()
+        || category.contains("forex")
+        || category.contains("fórex")
+        || category.contains("divisa")
+    {
+        None
+    } else if category.contains("option") || category.contains("opcion") {
+        Some(AssetCategory::Option)
+    } else if category.contains("future") || category.contains("futuro") {
+        Some(AssetCategory::Future)
+    } else if category.contains("stock") || category == "acciones" {
+        Some(AssetCategory::Stock)
+    } else if category.contains("bond") || category.contains("bono") {
+        Some(AssetCategory::Bond)
+    } else if category.contains("fund") || category.contains("fondo") {
+        Some(AssetCategory::Fund)
+    } else if category.contains("warrant") {
+        Some(AssetCategory::Warrant)
+    } else if category.contains("structured") || category.contains("estructurado") {
+        Some(AssetCategory::StructuredProduct)
+    } else {
+        log::warn!("Unknown Interactive Brokers asset category {}", category);
+        Some(AssetCategory::Unknown)
+    }
+}
+
+/// ISIN of a US security identified by its CUSIP, the US prefix followed
+/// by the CUSIP and the check digit. CINS codes, beginning with a letter,
+/// belong to securities of other countries whose prefix is unknown, so
+/// `None` is returned for them as well as for anything else than a CUSIP.
+pub(crate) fn cusip_to_isin(cusip: &str) -> Option<String> {
+    let cusip = cusip.trim().to_uppercase();
+    if !cusip.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    isin::build_from_parts("US", &cusip)
+        .ok()
+        .map(|isin| isin.to_string())
+}
+
+/// Category of a security from its name, for the statements without
+/// instrument type. Funds and ETFs carry markers like "UCITS" or "ETF" in
+/// their names, the rest are taken as stocks.
+pub(crate) fn name_asset_category(name: &str) -> AssetCategory {
+    let is_fund = name
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| matches!(word.to_uppercase().as_str(), "ETF" | "UCITS" | "SICAV"));
+    if is_fund {
+        AssetCategory::Fund
+    } else {
+        AssetCategory::Stock
+    }
+}
+
+/// Degiro reports ISIN changes as a sell of the old security and a buy of the
+/// new one on the same date, for the same value and without commissions.
+/// Switches between funds without commissions, like the ones of the core
+/// selection, look the same, so the pair must also keep the product name or
+/// the quantity. Those pairs are taken out of `notes` and returned as
+/// corporate actions.
+pub(crate) fn extract_isin_changes(notes: &mut AccountNotes) -> CorporateActionNotes {
+    let mut result = vec![];
+    let mut paired = vec![];
+
+    for (sell_index, sell) in notes.iter().enumerate() {
+        if sell.operation != BrokerOperation::Sell || !sell.commision.is_zero() {
+            continue;
+        }
+        let buy = notes.iter().enumerate().find(|(buy_index, buy)| {
+            buy.operation == BrokerOperation::Buy
+                && buy.commision.is_zero()
+                && buy.date == sell.date
+                && buy.broker == sell.broker
+                && buy.company.isin != sell.company.isin
+                && buy.value == sell.value
+                && (same_product_name(&buy.company.name, &sell.company.name)
+                    || buy.quantity == sell.quantity)
+                && !paired.contains(buy_index)
+        });
+        if let Some((buy_index, buy)) = buy
+            && !sell.quantity.is_zero()
+        {
+            paired.push(sell_index);
+            paired.push(buy_index);
+            result.push(CorporateActionNote::new(
+                sell.date,
+                sell.company.clone(),
+                CorporateActionKind::IsinChange {
+                    new_company: buy.company.clone(),
+                    ratio: buy.quantity / sell.quantity,
+                },
+                &sell.broker,
+            ));
+        }
+    }
+
+    let mut index = 0;
+    notes.retain(|_| {
+        index += 1;
+        !paired.contains(&(index - 1))
+    });
+
+    result
+}
+
+/// Whether both names are the same product, as Degiro writes them
+/// differently on each side of some operations.
+fn same_product_name(name: &str, other: &str) -> bool {
+    let normalize = |name: &str| {
+        name.split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_uppercase()
+    };
+    normalize(name) == normalize(other)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::data::{AccountNote, BrokerInformation, CompanyInfo};
+
+    #[test]
+    fn test_ib_asset_category() {
+        assert_eq!(ib_asset_category("Stocks"), Some(AssetCategory::Stock));
+        assert_eq!(ib_asset_category("Acciones"), Some(AssetCategory::Stock));
+        assert_eq!(
+            ib_asset_category("Equity and Index Options"),
+            Some(AssetCategory::Option)
+        );
+        assert_eq!(ib_asset_category("Fórex"), None);
+        assert_eq!(ib_asset_category(""), None);
+        assert_eq!(ib_asset_category("CFDs"), Some(AssetCategory::Unknown));
+        assert!(!AssetCategory::Unknown.is_declarable());
+    }
+
+    #[test]
+    fn test_cusip_to_isin() {
+        assert_eq!(
+            cusip_to_isin("922908363"),
+            Some(String::from("US9229083632"))
+        );
+        assert_eq!(
+            cusip_to_isin("037833100"),
+            Some(String::from("US0378331005"))
+        );
+        assert_eq!(cusip_to_isin("03783310"), None);
+        assert_eq!(cusip_to_isin("G0403H108"), None);
+    }
+
+    #[test]
+    fn test_name_asset_category() {
+        assert_eq!(
+            name_asset_category("iShs Core MSCI World UCITS ETF"),
+            AssetCategory::Fund
+        );
+        assert_eq!(
+            name_asset_category("iShsIII-Core MSCI World U.ETF Registered Shs USD (Acc) o.N."),
+            AssetCategory::Fund
+        );
+        assert_eq!(
+            name_asset_category("Apple Inc. Registered Shares o.N."),
+            AssetCategory::Stock
+        );
+        assert_eq!(name_asset_category("NETFLIX INC."), AssetCategory::Stock);
+    }
+
+    #[test]
+    fn test_extract_isin_changes() {
+        let broker = Arc::new(BrokerInformation::new(
+            String::from("Degiro"),
+            String::from("NL"),
+        ));
+        let date = NaiveDate::from_ymd_opt(2021, 2, 4).unwrap();
+        let old_company = CompanyInfo {
+            name: String::from("ILLUMINA"),
+            isin: String::from("CA45236R1010"),
+        };
+        let new_company = CompanyInfo {
+            name: String::from("VOXTUR ANALYTICS"),
+            isin: String::from("CA9290821052"),
+        };
+        let note = |company: &CompanyInfo, operation, commision| {
+            AccountNote::new(
+                date,
+                company.clone(),
+                operation,
+                Decimal::new(100, 0),
+                Decimal::new(2, 0),
+                Decimal::new(200, 0),
+                commision,
+                &broker,
+            )
+        };
+        let mut notes = vec![
+            note(&old_company, BrokerOperation::Sell, Decimal::ZERO),
+            note(&new_company, BrokerOperation::Buy, Decimal::ZERO),
+            note(&old_company, BrokerOperation::Buy, Decimal::new(2, 0)),
+        ];
+
+        let actions = extract_isin_changes(&mut notes);
+
+        assert_eq!(
+            actions,
+            vec![CorporateActionNote::new(
+                date,
+                old_company.clone(),
+                CorporateActionKind::IsinChange {
+                    new_company,
+                    ratio: Decimal::ONE,
+                },
+                &broker,
+            )]
+        );
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].commision, Decimal::new(2, 0));
+    }
+
+    #[test]
+    fn test_extract_isin_changes_fund_switch() {
+        let broker = Arc::new(BrokerInformation::new(
+            String::from("Degiro"),
+            String::from("NL"),
+        ));
+        let date = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+        let note = |name: &str, isin: &str, operation, quantity, price| {
+            AccountNote::new(
+                date,
+                CompanyInfo {
+                    name: String::from(name),
+                    isin: String::from(isin),
+                },
+                operation,
+                Decimal::new(quantity, 0),
+                Decimal::new(price, 0),
+                Decimal::new(quantity * price, 0),
+                Decimal::ZERO,
+                &broker,
+            )
+        };
+
+        // Core selection ETFs switched without commissions
+        let mut notes = vec![
+            note(
+                "ISHARES CORE MSCI WORLD UCITS ETF",
+                "IE00B4L5Y983",
+                BrokerOperation::Sell,
+                30,
+                80,
+            ),
+            note(
+                "VANGUARD FTSE ALL-WORLD UCITS ETF",
+                "IE00BK5BQT80",
+                BrokerOperation::Buy,
+                24,
+                100,
+            ),
+        ];
+        assert!(extract_isin_changes(&mut notes).is_empty());
+        assert_eq!(notes.len(), 2);
+
+        // Same product with a new ISIN after a reverse split
+        let mut notes = vec![
+            note(
+                "LVOX 07MAR24",
+                "US22564L1136",
+                BrokerOperation::Sell,
+                30,
+                80,
+            ),
+            note("LVOX 07MAR24", "US53838L1180", BrokerOperation::Buy, 3, 800),
+        ];
+        let actions = extract_isin_changes(&mut notes);
+        assert_eq!(actions.len(), 1);
+        assert_eq!(
+            actions[0].kind,
+            CorporateActionKind::IsinChange {
+                new_company: CompanyInfo {
+                    name: String::from("LVOX 07MAR24"),
+                    isin: String::from("US53838L1180"),
+                },
+                ratio: Decimal::new(1, 1),
+            }
+        );
+        assert!(notes.is_empty());
+    }
+}
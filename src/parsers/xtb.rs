@@ -0,0 +1,567 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::Decimal;
+use std::{str::FromStr, sync::Arc};
+
+use crate::{
+    data::{
+        AccountNote, AccountNotes, BalanceNote, BalanceNotes, BrokerInformation, BrokerOperation,
+        CompanyInfo,
+    },
+    error::{ImportError, ImportWarning},
+    utils::{
+        fx,
+        xlsx::{Sheet, serial_date},
+    },
+};
+
+/// Sheet name prefixes of the XTB account statement, the open positions one
+/// ends with the statement date.
+const OPEN_POSITIONS_SHEET: &str = "OPEN POSITION";
+const CLOSED_POSITIONS_SHEET: &str = "CLOSED POSITION HISTORY";
+const STATEMENT_DATE_FORMAT: &str = "%d%m%Y";
+
+const CURRENCY_FIELD: &str = "Currency";
+const ACCOUNT_TYPE_FIELD: &str = "Account type";
+/// Account types of the CFD accounts, whose positions are contracts and not
+/// shares held.
+const CFD_ACCOUNT_TYPE: &str = "CFD";
+const EUR_CURRENCY: &str = "EUR";
+
+const POSITION_FIELD: &str = "Position";
+const SYMBOL_FIELD: &str = "Symbol";
+const TYPE_FIELD: &str = "Type";
+const VOLUME_FIELD: &str = "Volume";
+const OPEN_TIME_FIELD: &str = "Open time";
+const OPEN_PRICE_FIELD: &str = "Open price";
+const CLOSE_TIME_FIELD: &str = "Close time";
+const CLOSE_PRICE_FIELD: &str = "Close price";
+const MARKET_PRICE_FIELD: &str = "Market price";
+const PURCHASE_VALUE_FIELD: &str = "Purchase value";
+const SALE_VALUE_FIELD: &str = "Sale value";
+const COMMISSION_FIELD: &str = "Commission";
+const GROSS_PROFIT_FIELD: &str = "Gross P/L";
+
+/// Short positions can only be CFDs, even in the stocks and ETFs accounts.
+const LONG_POSITION_TYPE: &str = "BUY";
+
+/// Parser of the account statement workbook exported by XTB.
+#[derive(Debug)]
+pub struct XtbParser {
+    sheets: Vec<Sheet>,
+    broker: Arc<BrokerInformation>,
+    warnings: Vec<ImportWarning>,
+}
+
+/// Positions table of a sheet, found after the account information rows.
+struct PositionsTable<'a> {
+    headers: &'a [String],
+    rows: &'a [Vec<String>],
+    /// Currency of the values of the account, euros when the sheet does
+    /// not tell it.
+    currency: &'a str,
+    /// Whether the positions are from a CFD account.
+    cfd: bool,
+}
+
+impl<'a> PositionsTable<'a> {
+    fn from_sheet(sheet: &'a Sheet) -> Option<Self> {
+        let header = sheet.rows.iter().position(|row| {
+            row.iter().any(|cell| cell.trim() == POSITION_FIELD)
+                && row.iter().any(|cell| cell.trim() == SYMBOL_FIELD)
+        })?;
+
+        // The account information is a row of field names followed by the
+        // row of their values.
+        let account_info = |field: &str| {
+            sheet.rows[..header]
+                .windows(2)
+                .find_map(|rows| {
+                    let column = rows[0].iter().position(|cell| cell.trim() == field)?;
+                    Some(XtbParser::field(&rows[1], column))
+                })
+                .filter(|value| !value.is_empty())
+        };
+
+        Some(Self {
+            headers: &sheet.rows[header],
+            rows: &sheet.rows[header + 1..],
+            currency: account_info(CURRENCY_FIELD).unwrap_or(EUR_CURRENCY),
+            cfd: account_info(ACCOUNT_TYPE_FIELD)
+                .is_some_and(|kind| kind.to_uppercase().contains(CFD_ACCOUNT_TYPE)),
+        })
+    }
+
+    fn column(&self, field: &str) -> Result<usize> {
+        self.headers
+            .iter()
+            .position(|header| header.trim() == field)
+            .ok_or_else(|| anyhow!(ImportError::MissingColumn(field.to_string())))
+    }
+
+    /// Rows of long positions, the table ends at the first row without
+    /// position id, usually the totals one.
+    fn long_positions(&self) -> Result<impl Iterator<Item = &'a Vec<String>> + use<'a>> {
+        let position = self.column(POSITION_FIELD)?;
+        let kind = self.column(TYPE_FIELD)?;
+
+        Ok(self
+            .rows
+            .iter()
+            .take_while(move |row| !XtbParser::field(row, position).is_empty())
+            .filter(move |row| XtbParser::field(row, kind) == LONG_POSITION_TYPE))
+    }
+}
+
+impl XtbParser {
+    /// Recognizes the XTB statement by its sheet names.
+    pub fn is_xtb_statement(sheets: &[Sheet]) -> bool {
+        sheets.iter().any(|sheet| {
+            sheet.name.starts_with(OPEN_POSITIONS_SHEET)
+                || sheet.name.starts_with(CLOSED_POSITIONS_SHEET)
+        })
+    }
+
+    fn field(row: &[String], index: usize) -> &str {
+        row.get(index).map(|value| value.trim()).unwrap_or("")
+    }
+
+    fn parse_decimal(value: &str) -> Result<Decimal> {
+        if value.is_empty() {
+            return Ok(Decimal::ZERO);
+        }
+        Decimal::from_str(value)
+            .or_else(|_| Decimal::from_scientific(value))
+            .with_context(|| format!("Invalid number {}", value))
+    }
+
+    fn parse_date(value: &str) -> Result<NaiveDate> {
+        if let Ok(date) = NaiveDate::parse_from_str(value.get(..10).unwrap_or(value), "%d.%m.%Y") {
+            return Ok(date);
+        }
+
+        serial_date(value).with_context(|| format!("Invalid date {}", value))
+    }
+
+    /// Positions tables of the sheets `name`, but for the CFD accounts.
+    fn tables(&self, name: &str) -> impl Iterator<Item = PositionsTable<'_>> {
+        self.sheets
+            .iter()
+            .filter(move |sheet| sheet.name.starts_with(name))
+            .filter_map(PositionsTable::from_sheet)
+            .filter(|table| !table.cfd)
+    }
+
+    /// Year of the statement date the open positions sheet is named after.
+    fn statement_year(&self) -> Option<usize> {
+        self.sheets.iter().find_map(|sheet| {
+            let date = sheet.name.strip_prefix(OPEN_POSITIONS_SHEET)?.trim();
+            NaiveDate::parse_from_str(date, STATEMENT_DATE_FORMAT)
+                .ok()
+                .and_then(|date| usize::try_from(date.year()).ok())
+        })
+    }
+
+    /// Value in euros of `value` in the account `currency`, at the
+    /// reference rate of the end of the statement year.
+    fn value_in_euro(&self, value: Decimal, currency: &str) -> Option<Decimal> {
+        if currency == EUR_CURRENCY {
+            return Some(value);
+        }
+        self.statement_year()
+            .and_then(|year| fx::to_euro(value, currency, year))
+    }
+
+    fn company(symbol: &str) -> CompanyInfo {
+        CompanyInfo {
+            name: symbol.to_string(),
+            isin: String::new(),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn account_note(
+        &self,
+        row: &[String],
+        symbol: usize,
+        volume: usize,
+        time: usize,
+        price: usize,
+        value: usize,
+        commission: Option<usize>,
+        operation: BrokerOperation,
+    ) -> Result<AccountNote> {
+        let commission = match commission {
+            Some(commission) => XtbParser::parse_decimal(XtbParser::field(row, commission))?.abs(),
+            None => Decimal::ZERO,
+        };
+
+        Ok(AccountNote::new(
+            XtbParser::parse_date(XtbParser::field(row, time))?,
+            XtbParser::company(XtbParser::field(row, symbol)),
+            operation,
+            XtbParser::parse_decimal(XtbParser::field(row, volume))?,
+            XtbParser::parse_decimal(XtbParser::field(row, price))?,
+            XtbParser::parse_decimal(XtbParser::field(row, value))?.abs(),
+            commission,
+            &self.broker,
+        ))
+    }
+
+    /// Opening of the positions still open and opening and closing of the
+    /// closed ones, sorted by date.
+    pub fn parse_account_notes(&self) -> Result<AccountNotes> {
+        let mut account_notes = vec![];
+
+        for table in self.tables(OPEN_POSITIONS_SHEET) {
+            let symbol = table.column(SYMBOL_FIELD)?;
+            let volume = table.column(VOLUME_FIELD)?;
+            let open_time = table.column(OPEN_TIME_FIELD)?;
+            let open_price = table.column(OPEN_PRICE_FIELD)?;
+            let purchase_value = table.column(PURCHASE_VALUE_FIELD)?;
+            for row in table.long_positions()? {
+                account_notes.push(self.account_note(
+                    row,
+                    symbol,
+                    volume,
+                    open_time,
+                    open_price,
+                    purchase_value,
+                    None,
+                    BrokerOperation::Buy,
+                )?);
+            }
+        }
+
+        for table in self.tables(CLOSED_POSITIONS_SHEET) {
+            let symbol = table.column(SYMBOL_FIELD)?;
+            let volume = table.column(VOLUME_FIELD)?;
+            let open_time = table.column(OPEN_TIME_FIELD)?;
+            let open_price = table.column(OPEN_PRICE_FIELD)?;
+            let close_time = table.column(CLOSE_TIME_FIELD)?;
+            let close_price = table.column(CLOSE_PRICE_FIELD)?;
+            let purchase_value = table.column(PURCHASE_VALUE_FIELD)?;
+            let sale_value = table.column(SALE_VALUE_FIELD)?;
+            let commission = table.column(COMMISSION_FIELD).ok();
+            for row in table.long_positions()? {
+                account_notes.push(self.account_note(
+                    row,
+                    symbol,
+                    volume,
+                    open_time,
+                    open_price,
+                    purchase_value,
+                    None,
+                    BrokerOperation::Buy,
+                )?);
+                account_notes.push(self.account_note(
+                    row,
+                    symbol,
+                    volume,
+                    close_time,
+                    close_price,
+                    sale_value,
+                    commission,
+                    BrokerOperation::Sell,
+                )?);
+            }
+        }
+
+        account_notes.sort_by_key(|note| note.date);
+        Ok(account_notes)
+    }
+
+    /// Open positions at the statement date, grouped by symbol. Values are
+    /// the purchase value plus the unrealized profit, in the account
+    /// currency and converted to euros at the end of the statement year.
+    /// Without rate of the account currency the positions are left
+    /// unvalued, for the user to complete them.
+    pub fn parse_balance_notes(&mut self) -> Result<BalanceNotes> {
+        let mut balance_notes: BalanceNotes = vec![];
+        let mut unvalued_currencies: Vec<String> = vec![];
+
+        for table in self.tables(OPEN_POSITIONS_SHEET) {
+            let symbol = table.column(SYMBOL_FIELD)?;
+            let volume = table.column(VOLUME_FIELD)?;
+            let market_price = table.column(MARKET_PRICE_FIELD)?;
+            let purchase_value = table.column(PURCHASE_VALUE_FIELD)?;
+            let gross_profit = table.column(GROSS_PROFIT_FIELD)?;
+            for row in table.long_positions()? {
+                let company = XtbParser::company(XtbParser::field(row, symbol));
+                let quantity = XtbParser::parse_decimal(XtbParser::field(row, volume))?;
+                let value = match self.value_in_euro(
+                    XtbParser::parse_decimal(XtbParser::field(row, purchase_value))?
+                        + XtbParser::parse_decimal(XtbParser::field(row, gross_profit))?,
+                    table.currency,
+                ) {
+                    Some(value) => value,
+                    None => {
+                        let currency = table.currency.to_uppercase();
+                        if !unvalued_currencies.contains(&currency) {
+                            unvalued_currencies.push(currency);
+                        }
+                        Decimal::ZERO
+                    }
+                };
+
+                match balance_notes
+                    .iter_mut()
+                    .find(|note| note.company == company)
+                {
+                    Some(note) => {
+                        note.quantity += quantity;
+                        note.value_in_euro += value;
+                    }
+                    None => balance_notes.push(BalanceNote::new(
+                        company,
+                        String::new(),
+                        quantity,
+                        table.currency.to_string(),
+                        XtbParser::parse_decimal(XtbParser::field(row, market_price))?,
+                        value,
+                        &self.broker,
+                    )),
+                }
+            }
+        }
+
+        if let Some(year) = self.statement_year() {
+            self.warnings.extend(
+                unvalued_currencies
+                    .into_iter()
+                    .map(|currency| ImportWarning::MissingExchangeRate { currency, year }),
+            );
+        }
+
+        Ok(balance_notes)
+    }
+
+    /// Parser of the sheets of an XTB account statement.
+    pub fn new(sheets: Vec<Sheet>, broker: &Arc<BrokerInformation>) -> Self {
+        Self {
+            sheets,
+            broker: Arc::clone(broker),
+            warnings: vec![],
+        }
+    }
+
+    /// Account currencies whose positions were left unvalued.
+    pub fn warnings(&self) -> &[ImportWarning] {
+        &self.warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(cells: &[&str]) -> Vec<String> {
+        cells.iter().map(|cell| cell.to_string()).collect()
+    }
+
+    fn statement() -> Vec<Sheet> {
+        account_statement("Stocks & ETFs", "EUR")
+    }
+
+    fn account_statement(account_type: &str, currency: &str) -> Vec<Sheet> {
+        vec![
+            Sheet {
+                name: String::from("OPEN POSITION 31122023"),
+                rows: vec![
+                    row(&[
+                        "",
+                        "Name and surname",
+                        "Account",
+                        "Currency",
+                        "Account type",
+                    ]),
+                    row(&["", "Test User", "12345678", currency, account_type]),
+                    row(&[]),
+                    row(&[
+                        "",
+                        "Position",
+                        "Symbol",
+                        "Type",
+                        "Volume",
+                        "Open time",
+                        "Open price",
+                        "Market price",
+                        "Purchase value",
+                        "Commission",
+                        "Gross P/L",
+                    ]),
+                    row(&[
+                        "",
+                        "1001",
+                        "AAPL.US",
+                        "BUY",
+                        "2",
+                        "10.01.2023 15:30:00",
+                        "130.5",
+                        "192.53",
+                        "241.67",
+                        "0",
+                        "106.93",
+                    ]),
+                    row(&[
+                        "", "1002", "AAPL.US", "BUY", "1", "44986.5", "150", "192.53", "138.89",
+                        "0", "35.41",
+                    ]),
+                    row(&[
+                        "", "1003", "US500", "SELL", "0.1", "44986.5", "4000", "4769", "400", "0",
+                        "-76.9",
+                    ]),
+                    row(&["", "", "Total", "", "", "", "", "", "780.56", "0", "65.44"]),
+                ],
+            },
+            Sheet {
+                name: String::from("CLOSED POSITION HISTORY"),
+                rows: vec![
+                    row(&[
+                        "",
+                        "Position",
+                        "Symbol",
+                        "Type",
+                        "Volume",
+                        "Open time",
+                        "Open price",
+                        "Close time",
+                        "Close price",
+                        "Purchase value",
+                        "Sale value",
+                        "Commission",
+                    ]),
+                    row(&[
+                        "",
+                        "900",
+                        "SAN.ES",
+                        "BUY",
+                        "100",
+                        "02.02.2023 09:00:00",
+                        "3.5",
+                        "20.06.2023 10:00:00",
+                        "3.8",
+                        "350",
+                        "380",
+                        "-0.5",
+                    ]),
+                ],
+            },
+        ]
+    }
+
+    fn xtb_broker() -> Arc<BrokerInformation> {
+        Arc::new(BrokerInformation::new(
+            String::from("XTB"),
+            String::from("PL"),
+        ))
+    }
+
+    #[test]
+    fn test_parse_balance_notes() {
+        let broker = xtb_broker();
+        let mut parser = XtbParser::new(statement(), &broker);
+        assert!(XtbParser::is_xtb_statement(&statement()));
+
+        assert_eq!(
+            parser.parse_balance_notes().unwrap(),
+            vec![BalanceNote::new(
+                XtbParser::company("AAPL.US"),
+                String::new(),
+                Decimal::new(3, 0),
+                String::from("EUR"),
+                Decimal::new(192_53, 2),
+                Decimal::new(522_90, 2),
+                &broker,
+            )]
+        );
+    }
+
+    #[test]
+    fn test_parse_account_currency() {
+        let broker = xtb_broker();
+        let mut parser = XtbParser::new(account_statement("Stocks & ETFs", "USD"), &broker);
+
+        assert_eq!(
+            parser.parse_balance_notes().unwrap(),
+            vec![BalanceNote::new(
+                XtbParser::company("AAPL.US"),
+                String::new(),
+                Decimal::new(3, 0),
+                String::from("USD"),
+                Decimal::new(192_53, 2),
+                Decimal::new(473_22, 2),
+                &broker,
+            )]
+        );
+
+        let mut parser = XtbParser::new(account_statement("Stocks & ETFs", "PLN"), &broker);
+        assert_eq!(
+            parser.parse_balance_notes().unwrap()[0].value_in_euro,
+            Decimal::new(120_50, 2)
+        );
+
+        let mut parser = XtbParser::new(account_statement("Stocks & ETFs", "ARS"), &broker);
+        assert!(
+            parser.parse_balance_notes().unwrap()[0]
+                .value_in_euro
+                .is_zero()
+        );
+        assert_eq!(
+            parser.warnings(),
+            [ImportWarning::MissingExchangeRate {
+                currency: String::from("ARS"),
+                year: 2023
+            }]
+        );
+    }
+
+    #[test]
+    fn test_skip_cfd_account() {
+        let broker = xtb_broker();
+        let mut sheets = account_statement("CFD", "EUR");
+        // Only the open positions sheet tells the account type
+        sheets.pop();
+        let mut parser = XtbParser::new(sheets, &broker);
+
+        assert!(parser.parse_balance_notes().unwrap().is_empty());
+        assert!(parser.parse_account_notes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_account_notes() {
+        let broker = xtb_broker();
+        let parser = XtbParser::new(statement(), &broker);
+
+        let notes = parser.parse_account_notes().unwrap();
+        assert_eq!(notes.len(), 4);
+        assert_eq!(
+            notes[0],
+            AccountNote::new(
+                NaiveDate::from_ymd_opt(2023, 1, 10).unwrap(),
+                XtbParser::company("AAPL.US"),
+                BrokerOperation::Buy,
+                Decimal::new(2, 0),
+                Decimal::new(1305, 1),
+                Decimal::new(241_67, 2),
+                Decimal::ZERO,
+                &broker,
+            )
+        );
+        assert_eq!(notes[1].date, NaiveDate::from_ymd_opt(2023, 2, 2).unwrap());
+        assert_eq!(notes[2].date, NaiveDate::from_ymd_opt(2023, 3, 1).unwrap());
+        assert_eq!(
+            notes[3],
+            AccountNote::new(
+                NaiveDate::from_ymd_opt(2023, 6, 20).unwrap(),
+                XtbParser::company("SAN.ES"),
+                BrokerOperation::Sell,
+                Decimal::new(100, 0),
+                Decimal::new(38, 1),
+                Decimal::new(380, 0),
+                Decimal::new(5, 1),
+                &broker,
+            )
+        );
+    }
+}
@@ -19,6 +19,11 @@ impl PersonalInfoViewer {
     }
 
     pub fn render(this: &Arc<Self>) -> Dom {
+        let info = this.personal_info.get_cloned();
+        let year = match info.year {
+            0 => DEFAULT_YEAR,
+            year => year,
+        };
         html!("section", {
             .class(&*FLEX_CONTAINER_CLASS)
             .child(html!("span", {
@@ -30,6 +35,7 @@ impl PersonalInfoViewer {
                         .attr("type", "text")
                         .attr("autocomplete", "given-name")
                         .attr("placeholder", "Nombre")
+                        .attr("value", &info.name)
                         .style("height", "24px")
                         .with_node!(element => {
                             .event(clone!(this => move |_: events::Input| {
@@ -48,6 +54,7 @@ impl PersonalInfoViewer {
                         .attr("type", "text")
                         .attr("autocomplete", "family-name")
                         .attr("placeholder", "Apellidos")
+                        .attr("value", &info.surname)
                         .style("height", "24px")
                         .with_node!(element => {
                             .event(clone!(this => move |_: events::Input| {
@@ -66,6 +73,7 @@ impl PersonalInfoViewer {
                         .attr("type", "text")
                         .attr("max-length", "9")
                         .attr("placeholder", "DNI con letra")
+                        .attr("value", &info.nif)
                         .style("height", "24px")
                         .with_node!(element => {
                             .event(clone!(this => move |_: events::Input| {
@@ -84,7 +92,7 @@ impl PersonalInfoViewer {
                         .attr("type", "text")
                         .attr("maxlength", "4")
                         .attr("placeholder", "Año")
-                        .attr("value", &DEFAULT_YEAR.to_string())
+                        .attr("value", &year.to_string())
                         .style("height", "24px")
                         .with_node!(element => {
                             .event(clone!(this => move |_: events::Input| {
@@ -104,6 +112,7 @@ impl PersonalInfoViewer {
                         .attr("autocomplete", "tel")
                         .attr("maxlength", "9")
                         .attr("placeholder", "Teléfono")
+                        .attr("value", &info.phone)
                         .style("height", "24px")
                         .with_node!(element => {
                             .event(clone!(this => move |_: events::Input| {
@@ -0,0 +1,356 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result, anyhow, bail};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chacha20poly1305::{
+    Key, KeyInit, XChaCha20Poly1305, XNonce,
+    aead::{Aead, Payload},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    data::{Aeat720Records, Aeat721Records, BrokerInformation, PersonalInformation},
+    ledger::Ledger,
+};
+
+/// Version of the project files written. It is increased on every change of
+/// the format that older versions are unable to read, files of previous
+/// versions are upgraded when opened.
+pub const PROJECT_VERSION: u64 = 1;
+
+const SALT_LENGTH: usize = 16;
+
+/// Highest Argon2id parameters accepted from a project file, so a crafted
+/// one can not exhaust the memory or the time of the browser: 256 MiB, 10
+/// iterations and 4 lanes.
+const MAX_MEMORY_COST: u32 = 256 * 1024;
+const MAX_TIME_COST: u32 = 10;
+const MAX_PARALLELISM: u32 = 4;
+
+/// Project file encrypted with XChaCha20-Poly1305, the key is derived from
+/// the password with Argon2id and the parameters kept in the file. The
+/// header (version, parameters and salt) is authenticated along with the
+/// content.
+#[derive(Debug, Deserialize, Serialize)]
+struct EncryptedProject {
+    version: u64,
+    /// Argon2id memory size in KiB.
+    memory_cost: u32,
+    /// Argon2id number of iterations.
+    time_cost: u32,
+    /// Argon2id degree of parallelism.
+    parallelism: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl EncryptedProject {
+    fn cipher(&self, password: &str, salt: &[u8]) -> Result<XChaCha20Poly1305> {
+        if self.memory_cost > MAX_MEMORY_COST
+            || self.time_cost > MAX_TIME_COST
+            || self.parallelism > MAX_PARALLELISM
+        {
+            bail!(
+                "Key derivation parameters (m={}, t={}, p={}) above the supported ones",
+                self.memory_cost,
+                self.time_cost,
+                self.parallelism
+            );
+        }
+        let params = Params::new(self.memory_cost, self.time_cost, self.parallelism, Some(32))
+            .map_err(|err| anyhow!("Invalid key derivation parameters: {}", err))?;
+        let mut key = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(password.as_bytes(), salt, &mut key)
+            .map_err(|err| anyhow!("Unable to derive key: {}", err))?;
+        Ok(XChaCha20Poly1305::new(Key::from_slice(&key)))
+    }
+
+    /// Fields of the header authenticated as associated data.
+    fn associated_data(&self) -> Vec<u8> {
+        format!(
+            "{}:{}:{}:{}:{}",
+            self.version, self.memory_cost, self.time_cost, self.parallelism, self.salt
+        )
+        .into_bytes()
+    }
+
+    fn encrypt(content: &[u8], password: &str) -> Result<Self> {
+        let mut salt = [0u8; SALT_LENGTH];
+        getrandom::getrandom(&mut salt).map_err(|err| anyhow!("No random source: {}", err))?;
+        let mut nonce = XNonce::default();
+        getrandom::getrandom(&mut nonce).map_err(|err| anyhow!("No random source: {}", err))?;
+        let mut encrypted = Self {
+            version: PROJECT_VERSION,
+            memory_cost: Params::DEFAULT_M_COST,
+            time_cost: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+            salt: STANDARD.encode(salt),
+            nonce: STANDARD.encode(nonce),
+            ciphertext: String::new(),
+        };
+        let ciphertext = encrypted
+            .cipher(password, &salt)?
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: content,
+                    aad: &encrypted.associated_data(),
+                },
+            )
+            .map_err(|_| anyhow!("Unable to encrypt project"))?;
+        encrypted.ciphertext = STANDARD.encode(ciphertext);
+
+        Ok(encrypted)
+    }
+
+    fn decrypt(&self, password: &str) -> Result<Vec<u8>> {
+        let salt = STANDARD.decode(&self.salt).context("Invalid salt")?;
+        let nonce = STANDARD.decode(&self.nonce).context("Invalid nonce")?;
+        if nonce.len() != XNonce::default().len() {
+            bail!("Invalid nonce length {}", nonce.len());
+        }
+        let ciphertext = STANDARD
+            .decode(&self.ciphertext)
+            .context("Invalid ciphertext")?;
+        self.cipher(password, &salt)?
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: &self.associated_data(),
+                },
+            )
+            .map_err(|_| anyhow!("Wrong password or damaged project file"))
+    }
+}
+
+/// Work of a declared year, saved in a file to be opened in another browser,
+/// like the one of the tax advisor.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Project {
+    version: u64,
+    #[serde(default)]
+    pub personal_info: PersonalInformation,
+    /// Brokers and custodians of the records.
+    #[serde(default)]
+    pub brokers: Vec<Arc<BrokerInformation>>,
+    #[serde(default)]
+    pub records: Aeat720Records,
+    #[serde(default)]
+    pub crypto_records: Aeat721Records,
+    /// Trades, corporate actions and previous declarations read from the
+    /// imported reports.
+    #[serde(default)]
+    pub ledger: Ledger,
+}
+
+impl Project {
+    pub fn new(
+        personal_info: PersonalInformation,
+        records: Aeat720Records,
+        crypto_records: Aeat721Records,
+        ledger: Ledger,
+    ) -> Self {
+        let mut brokers: Vec<Arc<BrokerInformation>> = vec![];
+        let used = records
+            .iter()
+            .map(|r| &r.broker)
+            .chain(crypto_records.iter().map(|r| &r.custodian));
+        for broker in used {
+            if !brokers.iter().any(|b| b == broker) {
+                brokers.push(Arc::clone(broker));
+            }
+        }
+
+        Self {
+            version: PROJECT_VERSION,
+            personal_info,
+            brokers,
+            records,
+            crypto_records,
+            ledger,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec_pretty(self)?)
+    }
+
+    /// Project file encrypted with `password`.
+    pub fn to_encrypted_json(&self, password: &str) -> Result<Vec<u8>> {
+        let encrypted = EncryptedProject::encrypt(&self.to_json()?, password)?;
+        Ok(serde_json::to_vec_pretty(&encrypted)?)
+    }
+
+    /// Whether `content` is an encrypted project file, which needs the
+    /// password to be opened.
+    pub fn is_encrypted(content: &[u8]) -> bool {
+        serde_json::from_slice::<Value>(content)
+            .is_ok_and(|value| value.get("ciphertext").is_some())
+    }
+
+    /// Reads a project file encrypted with `password`.
+    pub fn from_encrypted_json(content: &[u8], password: &str) -> Result<Self> {
+        let encrypted: EncryptedProject =
+            serde_json::from_slice(content).context("Invalid encrypted project file")?;
+        if encrypted.version > PROJECT_VERSION {
+            bail!(
+                "Project file version {} is newer than the supported {}",
+                encrypted.version,
+                PROJECT_VERSION
+            );
+        }
+        Self::from_json(&encrypted.decrypt(password)?)
+    }
+
+    /// Reads a project file of this or a previous version, the records of
+    /// the same broker share its definition.
+    pub fn from_json(content: &[u8]) -> Result<Self> {
+        let value: Value = serde_json::from_slice(content).context("Invalid project file")?;
+        let version = match value.get("version").and_then(Value::as_u64) {
+            Some(version) => version,
+            None => bail!("Project file without version"),
+        };
+        if version > PROJECT_VERSION {
+            bail!(
+                "Project file version {} is newer than the supported {}",
+                version,
+                PROJECT_VERSION
+            );
+        }
+
+        let mut project: Project =
+            serde_json::from_value(Self::upgrade(value, version)?).context("Invalid project")?;
+        project.version = PROJECT_VERSION;
+        for record in project.records.iter_mut() {
+            if let Some(broker) = project.brokers.iter().find(|b| **b == record.broker) {
+                record.broker = Arc::clone(broker);
+            }
+        }
+        for record in project.crypto_records.iter_mut() {
+            if let Some(broker) = project.brokers.iter().find(|b| **b == record.custodian) {
+                record.custodian = Arc::clone(broker);
+            }
+        }
+
+        Ok(project)
+    }
+
+    /// Converts the content of a file of `version` to the current format,
+    /// one version at a time.
+    fn upgrade(value: Value, version: u64) -> Result<Value> {
+        match version {
+            PROJECT_VERSION => Ok(value),
+            _ => bail!("Unsupported project file version {}", version),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::*;
+    use crate::data::{Aeat720Record, AssetCategory, CompanyInfo, FirstTxDateSource, Provenance};
+
+    fn record(broker: &Arc<BrokerInformation>, isin: &str) -> Aeat720Record {
+        Aeat720Record {
+            company: CompanyInfo {
+                name: String::from("ADYEN NV"),
+                isin: String::from(isin),
+            },
+            quantity: Decimal::new(10, 0),
+            value_in_euro: Decimal::new(12000, 0),
+            first_tx_date: 20230201,
+            broker: Arc::clone(broker),
+            percentage: Decimal::new(100, 0),
+            first_tx_date_source: FirstTxDateSource::Report,
+            category: AssetCategory::Stock,
+            account: String::new(),
+            provenance: Provenance::default(),
+        }
+    }
+
+    fn broker() -> Arc<BrokerInformation> {
+        Arc::new(BrokerInformation::new(
+            String::from("Interactive Brokers"),
+            String::from("IE"),
+        ))
+    }
+
+    #[test]
+    fn project_round_trip_test() {
+        let ib = broker();
+        let project = Project::new(
+            PersonalInformation {
+                name: String::from("JUAN"),
+                surname: String::from("ESPAÑOL ESPAÑOL"),
+                nif: String::from("12345678Z"),
+                year: 2023,
+                phone: String::from("600000000"),
+            },
+            vec![
+                record(&ib, "NL0012969182"),
+                record(&broker(), "US0378331005"),
+            ],
+            vec![],
+            Ledger::new(),
+        );
+        assert_eq!(project.brokers.len(), 1);
+
+        let opened = Project::from_json(&project.to_json().unwrap()).unwrap();
+        assert_eq!(opened, project);
+        assert!(Arc::ptr_eq(
+            &opened.records[0].broker,
+            &opened.records[1].broker
+        ));
+    }
+
+    #[test]
+    fn project_version_test() {
+        assert!(Project::from_json(br#"{"version": 1}"#).is_ok());
+        assert!(Project::from_json(br#"{"records": []}"#).is_err());
+        assert!(Project::from_json(br#"{"version": 2}"#).is_err());
+        assert!(Project::from_json(b"not a project").is_err());
+    }
+
+    #[test]
+    fn encrypted_project_test() {
+        let project = Project::new(
+            PersonalInformation::default(),
+            vec![record(&broker(), "NL0012969182")],
+            vec![],
+            Ledger::new(),
+        );
+
+        let content = project.to_encrypted_json("contraseña").unwrap();
+        assert!(Project::is_encrypted(&content));
+        assert!(!Project::is_encrypted(&project.to_json().unwrap()));
+        assert!(!String::from_utf8_lossy(&content).contains("NL0012969182"));
+        assert_eq!(
+            Project::from_encrypted_json(&content, "contraseña").unwrap(),
+            project
+        );
+        assert!(Project::from_encrypted_json(&content, "otra").is_err());
+
+        // The header is authenticated, and its parameters bounded.
+        let tampered = |field: &str, value: u64| {
+            let mut encrypted: Value = serde_json::from_slice(&content).unwrap();
+            encrypted[field] = Value::from(value);
+            serde_json::to_vec(&encrypted).unwrap()
+        };
+        assert!(Project::from_encrypted_json(&tampered("version", 0), "contraseña").is_err());
+        assert!(
+            Project::from_encrypted_json(&tampered("memory_cost", 4 * 1024 * 1024), "contraseña")
+                .unwrap_err()
+                .to_string()
+                .contains("above the supported")
+        );
+        assert!(Project::from_encrypted_json(&tampered("time_cost", 11), "contraseña").is_err());
+        assert!(Project::from_encrypted_json(&tampered("parallelism", 64), "contraseña").is_err());
+    }
+}
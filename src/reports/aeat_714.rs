@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+
+use crate::data::{Aeat720Record, Aeat721Record, AssetCategory, CompanyInfo};
+
+/*
+   aeat 714 model (Impuesto sobre el Patrimonio) valuation rules.
+   https://www.boe.es/buscar/act.php?id=BOE-A-1991-14392 (Ley 19/1991)
+
+   Deposits (art. 12): greatest of the balance at 31 December and the
+   average balance of the fourth quarter.
+   Negotiated debt securities (art. 13) and negotiated shares (art. 15.1):
+   average negotiation price of the fourth quarter, as published yearly by
+   the Ministerio de Hacienda.
+   Collective investment institutions (art. 15.2): liquidation value at
+   31 December.
+   Virtual currencies: market value at 31 December.
+*/
+
+/// Groups of assets declared together in the AEAT 714 form.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Aeat714Category {
+    /// Current or savings accounts.
+    Deposits,
+    /// Negotiated debt securities.
+    DebtSecurities,
+    /// Negotiated shares.
+    Shares,
+    /// Collective investment institutions.
+    Funds,
+    /// Virtual currencies.
+    VirtualCurrencies,
+}
+
+impl Aeat714Category {
+    const ALL: [Aeat714Category; 5] = [
+        Aeat714Category::Deposits,
+        Aeat714Category::DebtSecurities,
+        Aeat714Category::Shares,
+        Aeat714Category::Funds,
+        Aeat714Category::VirtualCurrencies,
+    ];
+
+    /// Category of the securities of an asset category, `None` for the
+    /// ones without a category of their own in the form, like warrants or
+    /// structured products, which the user has to classify.
+    pub fn from_asset_category(category: AssetCategory) -> Option<Self> {
+        match category {
+            AssetCategory::Stock => Some(Aeat714Category::Shares),
+            AssetCategory::Fund => Some(Aeat714Category::Funds),
+            AssetCategory::Bond => Some(Aeat714Category::DebtSecurities),
+            _ => None,
+        }
+    }
+
+    /// Name of the category, with the article of Ley 19/1991 valuing it.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Aeat714Category::Deposits => "Depósitos en cuenta corriente o de ahorro (art. 12)",
+            Aeat714Category::DebtSecurities => {
+                "Valores representativos de la cesión a terceros de capitales propios negociados (art. 13)"
+            }
+            Aeat714Category::Shares => {
+                "Acciones y participaciones negociadas en mercados organizados (art. 15.1)"
+            }
+            Aeat714Category::Funds => {
+                "Acciones y participaciones en instituciones de inversión colectiva (art. 15.2)"
+            }
+            Aeat714Category::VirtualCurrencies => "Monedas virtuales",
+        }
+    }
+
+    /// Whether the assets are valued at the fourth quarter average price
+    /// instead of the value at 31 December.
+    pub fn uses_q4_average_price(&self) -> bool {
+        matches!(
+            self,
+            Aeat714Category::DebtSecurities | Aeat714Category::Shares
+        )
+    }
+}
+
+/// Balances of the accounts held at the brokers, not part of the imported
+/// reports.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Aeat714Deposits {
+    /// Balance at 31 December.
+    pub year_end_balance: Decimal,
+    /// Average balance of the fourth quarter.
+    pub q4_average_balance: Decimal,
+}
+
+/// Total value of the assets of a category.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Aeat714Total {
+    /// Category totaled.
+    pub category: Aeat714Category,
+    /// Number of assets of the category.
+    pub assets: usize,
+    /// Value of the owned part of the assets.
+    pub value: Decimal,
+}
+
+/// Values of the assets for the AEAT 714 form.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Aeat714Report {
+    totals: Vec<Aeat714Total>,
+    missing_prices: Vec<CompanyInfo>,
+    unclassified: Vec<CompanyInfo>,
+}
+
+impl Aeat714Report {
+    /// Value of the owned part of a security, `None` when its fourth quarter
+    /// average price is needed but unknown.
+    pub fn security_value(
+        record: &Aeat720Record,
+        q4_prices: &HashMap<String, Decimal>,
+    ) -> Option<Decimal> {
+        let value = if Aeat714Category::from_asset_category(record.category)
+            .is_some_and(|category| category.uses_q4_average_price())
+        {
+            record.quantity * q4_prices.get(&record.company.isin)?
+        } else {
+            record.value_in_euro
+        };
+
+        Some((value * record.percentage / Decimal::ONE_HUNDRED).round_dp(2))
+    }
+
+    /// Totals the records of the AEAT 720 and 721 tables per category. The
+    /// securities without fourth quarter average price are valued at
+    /// 31 December and listed as missing so the user can fill them, the
+    /// ones of a category not in the form are left out and listed as
+    /// unclassified.
+    pub fn new(
+        records: &[Aeat720Record],
+        crypto_records: &[Aeat721Record],
+        q4_prices: &HashMap<String, Decimal>,
+        deposits: &Aeat714Deposits,
+    ) -> Self {
+        let mut totals: Vec<Aeat714Total> = Aeat714Category::ALL
+            .iter()
+            .map(|category| Aeat714Total {
+                category: *category,
+                assets: 0,
+                value: Decimal::ZERO,
+            })
+            .collect();
+        let mut missing_prices: Vec<CompanyInfo> = vec![];
+        let mut unclassified: Vec<CompanyInfo> = vec![];
+        let mut add = |category: Aeat714Category, value: Decimal| {
+            if let Some(total) = totals.iter_mut().find(|total| total.category == category) {
+                total.assets += 1;
+                total.value += value;
+            }
+        };
+
+        let deposits_value = deposits.year_end_balance.max(deposits.q4_average_balance);
+        if !deposits_value.is_zero() {
+            add(Aeat714Category::Deposits, deposits_value);
+        }
+
+        for record in records {
+            let Some(category) = Aeat714Category::from_asset_category(record.category) else {
+                if !unclassified.contains(&record.company) {
+                    unclassified.push(record.company.clone());
+                }
+                continue;
+            };
+            let value = Self::security_value(record, q4_prices).unwrap_or_else(|| {
+                if !missing_prices.contains(&record.company) {
+                    missing_prices.push(record.company.clone());
+                }
+                (record.value_in_euro * record.percentage / Decimal::ONE_HUNDRED).round_dp(2)
+            });
+            add(category, value);
+        }
+
+        for record in crypto_records {
+            add(
+                Aeat714Category::VirtualCurrencies,
+                (record.value_in_euro * record.percentage / Decimal::ONE_HUNDRED).round_dp(2),
+            );
+        }
+
+        Self {
+            totals,
+            missing_prices,
+            unclassified,
+        }
+    }
+
+    /// Totals of every category, in the order of the form.
+    pub fn totals(&self) -> &[Aeat714Total] {
+        &self.totals
+    }
+
+    /// Securities valued at 31 December for lack of fourth quarter price.
+    pub fn missing_prices(&self) -> &[CompanyInfo] {
+        &self.missing_prices
+    }
+
+    /// Securities left out of the totals for their category, to be
+    /// classified in the table.
+    pub fn unclassified(&self) -> &[CompanyInfo] {
+        &self.unclassified
+    }
+
+    /// Value of all the assets.
+    pub fn total(&self) -> Decimal {
+        self.totals
+            .iter()
+            .fold(Decimal::ZERO, |acc, total| acc + total.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{BrokerInformation, FirstTxDateSource, Provenance};
+    use std::sync::Arc;
+
+    fn record(isin: &str, category: AssetCategory, quantity: i64, value: i64) -> Aeat720Record {
+        Aeat720Record {
+            company: CompanyInfo {
+                name: isin.to_string(),
+                isin: isin.to_string(),
+            },
+            quantity: Decimal::new(quantity, 0),
+            value_in_euro: Decimal::new(value, 0),
+            first_tx_date: 20230101,
+            broker: Arc::new(BrokerInformation::new(
+                String::from("Degiro"),
+                String::from("NL"),
+            )),
+            percentage: Decimal::ONE_HUNDRED,
+            first_tx_date_source: FirstTxDateSource::Default,
+            category,
+            account: String::new(),
+            provenance: Provenance::default(),
+        }
+    }
+
+    #[test]
+    fn test_report_totals() {
+        let mut shared = record("US0378331005", AssetCategory::Stock, 10, 1800);
+        shared.percentage = Decimal::new(50, 0);
+        let records = vec![
+            shared,
+            record("IE00B4L5Y983", AssetCategory::Fund, 20, 1600),
+            record("US5949181045", AssetCategory::Stock, 5, 1700),
+            record("DE000HG8RJJ6", AssetCategory::Warrant, 100, 300),
+            record("XS0000000000", AssetCategory::Unknown, 1, 900),
+        ];
+        let q4_prices = HashMap::from([(String::from("US0378331005"), Decimal::new(17525, 2))]);
+        let deposits = Aeat714Deposits {
+            year_end_balance: Decimal::new(500, 0),
+            q4_average_balance: Decimal::new(650, 0),
+        };
+
+        let report = Aeat714Report::new(&records, &[], &q4_prices, &deposits);
+
+        assert_eq!(
+            report.totals(),
+            &[
+                Aeat714Total {
+                    category: Aeat714Category::Deposits,
+                    assets: 1,
+                    value: Decimal::new(650, 0),
+                },
+                Aeat714Total {
+                    category: Aeat714Category::DebtSecurities,
+                    assets: 0,
+                    value: Decimal::ZERO,
+                },
+                Aeat714Total {
+                    category: Aeat714Category::Shares,
+                    assets: 2,
+                    value: Decimal::new(2576_25, 2),
+                },
+                Aeat714Total {
+                    category: Aeat714Category::Funds,
+                    assets: 1,
+                    value: Decimal::new(1600, 0),
+                },
+                Aeat714Total {
+                    category: Aeat714Category::VirtualCurrencies,
+                    assets: 0,
+                    value: Decimal::ZERO,
+                },
+            ]
+        );
+        assert_eq!(report.missing_prices(), &[records[2].company.clone()]);
+        assert_eq!(
+            report.unclassified(),
+            &[records[3].company.clone(), records[4].company.clone()]
+        );
+        assert_eq!(report.total(), Decimal::new(4826_25, 2));
+    }
+}
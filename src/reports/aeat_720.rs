@@ -2,7 +2,7 @@ use crate::data::{Aeat720Information, Aeat720Record};
 use anyhow::{Result, bail};
 use encoding_rs::ISO_8859_15;
 use rust_decimal::{Decimal, prelude::ToPrimitive};
-use std::io::Write;
+use std::{fmt, io::Write};
 
 /*
    aeat 720 model specification.
@@ -42,7 +42,7 @@ use std::io::Write;
     name: AlphaNumericField,                  // Pos 36-75
     declarationType: NumericField,            // Pos 76 value: '1' if owner
     ownershipType: AlphaNumericField,         // Pos 77-101
-    assetType: StringField,                   // Pos 102 value: 'V' if stocks
+    assetType: StringField,                   // Pos 102 value: 'V' if stocks, 'I' if funds
     assetSubType: NumericField,               // Pos 103 value: '1' usually
     realStateAssetType: AlphaNumericField,    // Pos 104-128
     countryCode: StringField,                 // Pos 129-130
@@ -71,21 +71,25 @@ use std::io::Write;
     OwnPercentageFrac: NumericField, // Pos 479-480
     blank: StringField,      // Pos 481-500
 */
-const AEAT_720_REGISTER_SIZE_BYTES: usize = 500;
+pub(crate) const AEAT_720_REGISTER_SIZE_BYTES: usize = 500;
 const AEAT_720_DOCUMENT_ID: usize = 720;
-const AEAT_720_NEGATIVE_SIGN: &str = "N";
+pub(crate) const AEAT_720_NEGATIVE_SIGN: &str = "N";
 
-type AeatRegisterArray = [u8; AEAT_720_REGISTER_SIZE_BYTES];
+pub(crate) type AeatRegisterArray = [u8; AEAT_720_REGISTER_SIZE_BYTES];
 
 #[derive(Debug, PartialEq)]
-enum Aeat720Field {
+pub(crate) enum Aeat720Field {
     AlphaNumeric(usize, usize),
     Numeric(usize, usize),
     String(usize, usize),
 }
 
 impl Aeat720Field {
-    fn write_field(fields: &mut AeatRegisterArray, field: Aeat720Field, value: &str) -> Result<()> {
+    pub(crate) fn write_field(
+        fields: &mut AeatRegisterArray,
+        field: Aeat720Field,
+        value: &str,
+    ) -> Result<()> {
         match field {
             Aeat720Field::Numeric(_, _) => {
                 let num_value = value.parse::<usize>()?;
@@ -114,7 +118,7 @@ impl Aeat720Field {
         Ok(())
     }
 
-    fn write_numeric_field(
+    pub(crate) fn write_numeric_field(
         fields: &mut AeatRegisterArray,
         field: Aeat720Field,
         value: usize,
@@ -297,6 +301,7 @@ impl DetailRegister {
     const AEAT_720_OWNER_TYPE: usize = 1;
     const AEAT_720_ASSET_TYPE: &'static str = "V";
     const AEAT_720_STOCK_ID_TYPE: usize = 1;
+    const AEAT_720_ACCOUNT_ID_TYPE_OTHER: &'static str = "O";
     const AEAT_720_ASSET_FIRST_ACQUISITION: &'static str = "A";
     // const AEAT_720_ASSET_INCREMENTAL_ACQUISITION: &'static str = "M";
     // const AEAT_720_ASSET_DISPOSAL: &'static str = "C";
@@ -460,8 +465,33 @@ impl Default for DetailRegister {
     }
 }
 
+/// Record whose ISIN is not valid, so the country of the issuer can not be
+/// declared.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvalidIsin {
+    /// Name of the security.
+    pub name: String,
+    /// ISIN given for it.
+    pub isin: String,
+}
+
+impl fmt::Display for InvalidIsin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid ISIN {:?} of {}", self.isin, self.name)
+    }
+}
+
+impl std::error::Error for InvalidIsin {}
+
 impl DetailRegister {
     fn new(record: &Aeat720Record, year: usize, nif: &str, name: &str) -> Result<Self> {
+        // The country of the issuer is taken from the ISIN.
+        if isin::parse(&record.company.isin).is_err() {
+            bail!(InvalidIsin {
+                name: record.company.name.clone(),
+                isin: record.company.isin.clone(),
+            });
+        }
         let mut fields = Self::default().fields;
 
         Aeat720Field::write_numeric_field(&mut fields, Self::YEAR_FIELD, year)?;
@@ -473,7 +503,25 @@ impl DetailRegister {
             Self::COUNTRY_CODE_FIELD,
             &record.broker.country_code,
         )?;
+        Aeat720Field::write_field(
+            &mut fields,
+            Self::ASSET_TYPE_FIELD,
+            record.category.aeat720_asset_type(),
+        )?;
+        Aeat720Field::write_numeric_field(
+            &mut fields,
+            Self::ASSET_SUBTYPE_FIELD,
+            record.category.aeat720_asset_subtype(),
+        )?;
         Aeat720Field::write_field(&mut fields, Self::STOCK_ID_FIELD, &record.company.isin)?;
+        if !record.account.is_empty() {
+            Aeat720Field::write_field(
+                &mut fields,
+                Self::ACCOUNT_ID_TYPE_FIELD,
+                Self::AEAT_720_ACCOUNT_ID_TYPE_OTHER,
+            )?;
+            Aeat720Field::write_field(&mut fields, Self::ACCOUNT_CODE_FIELD, &record.account)?;
+        }
         Aeat720Field::write_field(
             &mut fields,
             Self::ENTITY_NAME_FIELD,
@@ -541,17 +589,34 @@ impl DetailRegister {
         Ok(Self { fields })
     }
 }
+/// AEAT 720 form, securities held abroad.
+#[derive(Debug)]
 pub struct Aeat720Report {
     summary: SummaryRegister,
     details: Vec<DetailRegister>,
 }
 
 impl Aeat720Report {
+    /// Form of `info`, failing when its data does not fit the registers, a
+    /// record lacks a valid ISIN or is valued at zero.
     pub fn new(info: &Aeat720Information) -> Result<Aeat720Report> {
+        Self::build(info, false)
+    }
+
+    /// Form of `info` like [`Aeat720Report::new`], but accepting the
+    /// records valued at zero once the user confirmed them.
+    pub fn new_accepting_unvalued(info: &Aeat720Information) -> Result<Aeat720Report> {
+        Self::build(info, true)
+    }
+
+    fn build(info: &Aeat720Information, accept_unvalued: bool) -> Result<Aeat720Report> {
         let mut details = Vec::new();
         let full_name = info.full_name();
 
         for record in &info.records {
+            if !accept_unvalued && record.value_in_euro.is_zero() {
+                bail!("{} is valued at zero", record.company.name);
+            }
             let detail = DetailRegister::new(
                 record,
                 info.personal_info.year,
@@ -573,6 +638,7 @@ impl Aeat720Report {
         })
     }
 
+    /// Content of the form file to submit to the AEAT.
     pub fn generate(self) -> Result<Vec<u8>> {
         let mut result = Vec::with_capacity(
             AEAT_720_REGISTER_SIZE_BYTES * (self.details.len() + 1) + (self.details.len() + 1),
@@ -592,6 +658,11 @@ impl Aeat720Report {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::data::{
+        AssetCategory, BrokerInformation, CompanyInfo, FirstTxDateSource, PersonalInformation,
+        Provenance,
+    };
+    use std::sync::Arc;
 
     #[test]
     fn test_write_numeric_field() {
@@ -821,6 +892,109 @@ mod tests {
         assert_eq!(DetailRegister::default().fields, DEFAULT_FIELDS);
     }
 
+    #[test]
+    fn test_detail_register_invalid_isin() {
+        let mut record = Aeat720Record {
+            company: CompanyInfo {
+                name: String::from("ISHARES CORE MSCI WORLD"),
+                isin: String::new(),
+            },
+            quantity: Decimal::new(5, 0),
+            value_in_euro: Decimal::new(40000, 2),
+            first_tx_date: 20230105,
+            broker: Arc::new(BrokerInformation::new(
+                String::from("eToro"),
+                String::from("CY"),
+            )),
+            percentage: Decimal::ONE_HUNDRED,
+            first_tx_date_source: FirstTxDateSource::Report,
+            category: AssetCategory::Fund,
+            account: String::new(),
+            provenance: Provenance::default(),
+        };
+        let info = |record: &Aeat720Record| Aeat720Information {
+            records: vec![record.clone()],
+            personal_info: PersonalInformation::default(),
+        };
+
+        assert_eq!(
+            Aeat720Report::new(&info(&record))
+                .unwrap_err()
+                .downcast_ref::<InvalidIsin>(),
+            Some(&InvalidIsin {
+                name: String::from("ISHARES CORE MSCI WORLD"),
+                isin: String::new(),
+            })
+        );
+        record.company.isin = String::from("I");
+        assert!(Aeat720Report::new(&info(&record)).is_err());
+        record.company.isin = String::from("464287200");
+        assert!(Aeat720Report::new(&info(&record)).is_err());
+        record.company.isin = String::from("IE00B4L5Y983");
+        assert!(Aeat720Report::new(&info(&record)).is_ok());
+    }
+
+    #[test]
+    fn test_unvalued_record() {
+        let record = Aeat720Record {
+            company: CompanyInfo {
+                name: String::from("ADYEN NV"),
+                isin: String::from("NL0012969182"),
+            },
+            quantity: Decimal::new(5, 0),
+            value_in_euro: Decimal::ZERO,
+            first_tx_date: 20230105,
+            broker: Arc::new(BrokerInformation::new(
+                String::from("Trading 212"),
+                String::from("CY"),
+            )),
+            percentage: Decimal::ONE_HUNDRED,
+            first_tx_date_source: FirstTxDateSource::Trades,
+            category: AssetCategory::Stock,
+            account: String::new(),
+            provenance: Provenance::default(),
+        };
+        let info = Aeat720Information {
+            records: vec![record],
+            personal_info: PersonalInformation::default(),
+        };
+
+        let err = Aeat720Report::new(&info).unwrap_err();
+        assert!(err.downcast_ref::<InvalidIsin>().is_none());
+        assert!(Aeat720Report::new_accepting_unvalued(&info).is_ok());
+    }
+
+    #[test]
+    fn test_fund_detail_register() {
+        let mut record = Aeat720Record {
+            company: CompanyInfo {
+                name: String::from("ISHARES CORE MSCI WORLD"),
+                isin: String::from("IE00B4L5Y983"),
+            },
+            quantity: Decimal::new(5, 0),
+            value_in_euro: Decimal::new(40000, 2),
+            first_tx_date: 20230105,
+            broker: Arc::new(BrokerInformation::new(
+                String::from("eToro"),
+                String::from("CY"),
+            )),
+            percentage: Decimal::ONE_HUNDRED,
+            first_tx_date_source: FirstTxDateSource::Report,
+            category: AssetCategory::Fund,
+            account: String::new(),
+            provenance: Provenance::default(),
+        };
+
+        let fields = DetailRegister::new(&record, 2023, "", "").unwrap().fields;
+        assert_eq!(fields[101], b'I');
+        assert_eq!(fields[102], b'0');
+
+        record.category = AssetCategory::Bond;
+        let fields = DetailRegister::new(&record, 2023, "", "").unwrap().fields;
+        assert_eq!(fields[101], b'V');
+        assert_eq!(fields[102], b'2');
+    }
+
     #[test]
     fn test_iso_8859_15_encoding() {
         assert_eq!(ISO_8859_15.encode("Ñ").0.to_vec(), vec![209]);
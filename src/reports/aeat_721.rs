@@ -0,0 +1,639 @@
+use crate::{
+    data::{Aeat721Information, Aeat721Record},
+    reports::aeat_720::{
+        AEAT_720_NEGATIVE_SIGN, AEAT_720_REGISTER_SIZE_BYTES, Aeat720Field, AeatRegisterArray,
+    },
+};
+use anyhow::{Result, bail};
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use std::io::Write;
+
+/*
+   aeat 721 model specification, same 500 characters layout as the 720.
+   https://www.boe.es/buscar/act.php?id=BOE-A-2023-17361 (Orden HFP/886/2023)
+
+    Summary Register:
+
+    registerType: NumericField,           // Pos 1   value: '1'
+    model: NumericField,                  // Pos 2-4 value: '721'
+    year: NumericField,                   // Pos 5-8
+    nif: AlphaNumericField,               // Pos 9-17
+    name: AlphaNumericField,              // Pos 18-57 Surname name
+    transmission: StringField,            // Pos 58 value: 'T'
+    telephone: NumericField,              // Pos 59-67
+    contactName: AlphaNumericField,       // Pos 68-107
+    id: NumericField,                     // Pos 108-120
+    complementary: StringField,           // Pos 121 value: 'C' if complementary declaration
+    replacement: StringField,             // Pos 122 value: 'S' if replacement declaration
+    previousDeclarationId: NumericField,  // Pos 123-135
+    totalDetailRegisters: NumericField,   // Pos 136-144
+    valuationSummarySign: StringField,    // Pos 145 value: 'N' if negative
+    valuationSummaryInt: NumericField,    // Pos 146-160
+    valuationSummaryFrac: NumericField,   // Pos 161-162
+    blank: StringField,                   // Pos 163-500
+
+    Detail Register:
+
+    registerType: NumericField,           // Pos 1 value: '2'
+    model: NumericField,                  // Pos 2-4 value: '721'
+    year: NumericField,                   // Pos 5-8
+    nif: AlphaNumericField,               // Pos 9-17
+    nifDeclared: AlphaNumericField,       // Pos 18-26
+    proxyNif: AlphaNumericField,          // Pos 27-35
+    name: AlphaNumericField,              // Pos 36-75
+    declarationType: NumericField,        // Pos 76 value: '1' if owner
+    ownershipType: AlphaNumericField,     // Pos 77-101
+    assetType: StringField,               // Pos 102 value: 'M' virtual currency
+    assetSubType: NumericField,           // Pos 103 value: '1' held by a custodian
+    currencyName: AlphaNumericField,      // Pos 104-165
+    currencyId: AlphaNumericField,        // Pos 166-189
+    entityName: AlphaNumericField,        // Pos 190-230
+    entityNif: AlphaNumericField,         // Pos 231-250
+    entityAddress: AlphaNumericField,     // Pos 251-412
+    entityCountryCode: StringField,       // Pos 413-414
+    buyingDate: NumericField,             // Pos 415-422 value: YYYYMMDD format
+    buyingType: StringField,  // Pos 423 value: 'A' initial 'M' already existed 'C' disposed
+    sellingDate: NumericField, // Pos 424-431 value: YYYYMMDD format
+    valuationSign: StringField, // Pos 432 value: 'N' if negative
+    valuationInt: NumericField, // Pos 433-444
+    valuationFrac: NumericField, // Pos 445-446
+    unitsInt: NumericField,   // Pos 447-458
+    unitsFrac: NumericField,  // Pos 459-468
+    OwnPercentageInt: NumericField, // Pos 469-471
+    OwnPercentageFrac: NumericField, // Pos 472-473
+    blank: StringField,       // Pos 474-500
+*/
+const AEAT_721_DOCUMENT_ID: usize = 721;
+const AEAT_721_UNITS_DECIMALS: u32 = 10;
+
+/// Decimal part of `value` as an integer of `digits` digits.
+fn fraction_digits(value: &Decimal, digits: u32) -> usize {
+    (value.fract().abs() * Decimal::from(10_usize.pow(digits)))
+        .trunc()
+        .to_usize()
+        .unwrap_or(0)
+}
+
+#[derive(Debug)]
+struct SummaryRegister {
+    fields: AeatRegisterArray,
+}
+
+impl SummaryRegister {
+    // Field values
+    const AEAT_721_SUMMARY_REGISTER_TYPE: usize = 1;
+    const AEAT_721_TRANSMISSION_ASSET: &'static str = "T";
+
+    // Field definitions
+    const REGISTER_TYPE_FIELD: Aeat720Field = Aeat720Field::Numeric(1, 1);
+    const DOCUMENT_ID_FIELD: Aeat720Field = Aeat720Field::Numeric(2, 4);
+    const YEAR_FIELD: Aeat720Field = Aeat720Field::Numeric(5, 8);
+    const NIF_FIELD: Aeat720Field = Aeat720Field::AlphaNumeric(9, 17);
+    const NAME_FIELD: Aeat720Field = Aeat720Field::AlphaNumeric(18, 57);
+    const TRANSMISSION_FIELD: Aeat720Field = Aeat720Field::String(58, 58);
+    const TELEPHONE_FIELD: Aeat720Field = Aeat720Field::Numeric(59, 67);
+    const CONTACT_NAME_FIELD: Aeat720Field = Aeat720Field::AlphaNumeric(68, 107);
+    const SECOND_DOCUMENT_ID_FIELD: Aeat720Field = Aeat720Field::Numeric(108, 110);
+    const ID_FIELD: Aeat720Field = Aeat720Field::Numeric(111, 120);
+    const COMPLEMENTARY_FIELD: Aeat720Field = Aeat720Field::String(121, 121);
+    const REPLACEMENT_FIELD: Aeat720Field = Aeat720Field::String(122, 122);
+    const PREVIOUS_DECLARARION_ID_FIELD: Aeat720Field = Aeat720Field::Numeric(123, 135);
+    const TOTAL_DETAIL_REGISTERS_FIELD: Aeat720Field = Aeat720Field::Numeric(136, 144);
+    const VALUATION_SIGN_FIELD: Aeat720Field = Aeat720Field::String(145, 145); // 'N' if negative
+    const VALUATION_INT_FIELD: Aeat720Field = Aeat720Field::Numeric(146, 160);
+    const VALUATION_FRACTION_FIELD: Aeat720Field = Aeat720Field::Numeric(161, 162);
+    const REMAINDER_BLANK_FIELD: Aeat720Field = Aeat720Field::String(163, 500);
+}
+
+impl Default for SummaryRegister {
+    #[allow(unused_must_use)]
+    fn default() -> Self {
+        let mut fields: AeatRegisterArray = [b' '; AEAT_720_REGISTER_SIZE_BYTES];
+
+        Aeat720Field::write_numeric_field(
+            &mut fields,
+            Self::REGISTER_TYPE_FIELD,
+            Self::AEAT_721_SUMMARY_REGISTER_TYPE,
+        );
+        Aeat720Field::write_numeric_field(
+            &mut fields,
+            Self::DOCUMENT_ID_FIELD,
+            AEAT_721_DOCUMENT_ID,
+        );
+        Aeat720Field::write_numeric_field(&mut fields, Self::YEAR_FIELD, 0);
+        Aeat720Field::write_field(&mut fields, Self::NIF_FIELD, "");
+        Aeat720Field::write_field(&mut fields, Self::NAME_FIELD, "");
+        Aeat720Field::write_field(
+            &mut fields,
+            Self::TRANSMISSION_FIELD,
+            Self::AEAT_721_TRANSMISSION_ASSET,
+        );
+        Aeat720Field::write_numeric_field(&mut fields, Self::TELEPHONE_FIELD, 0);
+        Aeat720Field::write_field(&mut fields, Self::CONTACT_NAME_FIELD, "");
+        Aeat720Field::write_numeric_field(
+            &mut fields,
+            Self::SECOND_DOCUMENT_ID_FIELD,
+            AEAT_721_DOCUMENT_ID,
+        );
+        Aeat720Field::write_numeric_field(&mut fields, Self::ID_FIELD, 1);
+        Aeat720Field::write_field(&mut fields, Self::COMPLEMENTARY_FIELD, "");
+        Aeat720Field::write_field(&mut fields, Self::REPLACEMENT_FIELD, "");
+        Aeat720Field::write_numeric_field(&mut fields, Self::PREVIOUS_DECLARARION_ID_FIELD, 0);
+        Aeat720Field::write_numeric_field(&mut fields, Self::TOTAL_DETAIL_REGISTERS_FIELD, 0);
+        Aeat720Field::write_field(&mut fields, Self::VALUATION_SIGN_FIELD, "");
+        Aeat720Field::write_numeric_field(&mut fields, Self::VALUATION_INT_FIELD, 0);
+        Aeat720Field::write_numeric_field(&mut fields, Self::VALUATION_FRACTION_FIELD, 0);
+        Aeat720Field::write_field(&mut fields, Self::REMAINDER_BLANK_FIELD, "");
+
+        Self { fields }
+    }
+}
+
+impl SummaryRegister {
+    fn new(
+        records: &[Aeat721Record],
+        year: usize,
+        nif: &str,
+        name: &str,
+        phone: &str,
+    ) -> Result<Self> {
+        let mut fields = Self::default().fields;
+
+        Aeat720Field::write_field(&mut fields, Self::NIF_FIELD, nif)?;
+        Aeat720Field::write_numeric_field(&mut fields, Self::YEAR_FIELD, year)?;
+        Aeat720Field::write_field(&mut fields, Self::NAME_FIELD, name)?;
+        if !phone.is_empty() {
+            Aeat720Field::write_field(&mut fields, Self::TELEPHONE_FIELD, phone)?;
+        }
+        Aeat720Field::write_field(&mut fields, Self::CONTACT_NAME_FIELD, name)?;
+        Aeat720Field::write_numeric_field(
+            &mut fields,
+            Self::TOTAL_DETAIL_REGISTERS_FIELD,
+            records.len(),
+        )?;
+
+        let total_valuation = records
+            .iter()
+            .fold(Decimal::ZERO, |acc, record| acc + record.value_in_euro);
+        if total_valuation.is_sign_negative() {
+            Aeat720Field::write_field(
+                &mut fields,
+                Self::VALUATION_SIGN_FIELD,
+                AEAT_720_NEGATIVE_SIGN,
+            )?;
+        }
+        Aeat720Field::write_numeric_field(
+            &mut fields,
+            Self::VALUATION_INT_FIELD,
+            total_valuation.trunc().abs().to_usize().unwrap_or(0),
+        )?;
+        Aeat720Field::write_numeric_field(
+            &mut fields,
+            Self::VALUATION_FRACTION_FIELD,
+            fraction_digits(&total_valuation, 2),
+        )?;
+
+        Ok(Self { fields })
+    }
+}
+
+#[derive(Debug)]
+struct DetailRegister {
+    fields: AeatRegisterArray,
+}
+
+impl DetailRegister {
+    // Field values
+    const AEAT_721_DETAIL_REGISTER_TYPE: usize = 2;
+    const AEAT_721_OWNER_TYPE: usize = 1;
+    const AEAT_721_ASSET_TYPE: &'static str = "M";
+    const AEAT_721_CUSTODIED_ASSET_SUBTYPE: usize = 1;
+    const AEAT_721_ASSET_FIRST_ACQUISITION: &'static str = "A";
+    const AEAT_721_ASSET_KEPT: &'static str = "M";
+    const AEAT_721_ASSET_EXTINCTION: &'static str = "C";
+
+    // Field definitions
+    const REGISTER_TYPE_FIELD: Aeat720Field = Aeat720Field::Numeric(1, 1);
+    const DOCUMENT_ID_FIELD: Aeat720Field = Aeat720Field::Numeric(2, 4);
+    const YEAR_FIELD: Aeat720Field = Aeat720Field::Numeric(5, 8);
+    const NIF_FIELD: Aeat720Field = Aeat720Field::AlphaNumeric(9, 17);
+    const DECLARED_NIF_FIELD: Aeat720Field = Aeat720Field::AlphaNumeric(18, 26);
+    const PROXY_NIF_FIELD: Aeat720Field = Aeat720Field::AlphaNumeric(27, 35);
+    const NAME_FIELD: Aeat720Field = Aeat720Field::AlphaNumeric(36, 75);
+    const DECLARATION_TYPE_FIELD: Aeat720Field = Aeat720Field::Numeric(76, 76);
+    const OWNERSHIP_TYPE_FIELD: Aeat720Field = Aeat720Field::AlphaNumeric(77, 101);
+    const ASSET_TYPE_FIELD: Aeat720Field = Aeat720Field::String(102, 102);
+    const ASSET_SUBTYPE_FIELD: Aeat720Field = Aeat720Field::Numeric(103, 103);
+    const CURRENCY_NAME_FIELD: Aeat720Field = Aeat720Field::AlphaNumeric(104, 165);
+    const CURRENCY_ID_FIELD: Aeat720Field = Aeat720Field::AlphaNumeric(166, 189);
+    const ENTITY_NAME_FIELD: Aeat720Field = Aeat720Field::AlphaNumeric(190, 230);
+    const ENTITY_NIF_FIELD: Aeat720Field = Aeat720Field::AlphaNumeric(231, 250);
+    const ENTITY_ADDRESS_FIELD: Aeat720Field = Aeat720Field::AlphaNumeric(251, 412);
+    const ENTITY_COUNTRY_CODE_FIELD: Aeat720Field = Aeat720Field::String(413, 414);
+    const FIRST_ACQUISITION_DATE_FIELD: Aeat720Field = Aeat720Field::Numeric(415, 422);
+    const ACQUISITION_TYPE_FIELD: Aeat720Field = Aeat720Field::String(423, 423);
+    const EXTINCTION_DATE_FIELD: Aeat720Field = Aeat720Field::Numeric(424, 431);
+    const VALUATION_SIGN_FIELD: Aeat720Field = Aeat720Field::String(432, 432); // 'N' if negative
+    const VALUATION_INT_FIELD: Aeat720Field = Aeat720Field::Numeric(433, 444);
+    const VALUATION_FRACTION_FIELD: Aeat720Field = Aeat720Field::Numeric(445, 446);
+    const UNITS_INT_FIELD: Aeat720Field = Aeat720Field::Numeric(447, 458);
+    const UNITS_FRACTION_FIELD: Aeat720Field = Aeat720Field::Numeric(459, 468);
+    const OWNED_PERCENTAGE_INT_FIELD: Aeat720Field = Aeat720Field::Numeric(469, 471);
+    const OWNED_PERCENTAGE_FRACTION_FIELD: Aeat720Field = Aeat720Field::Numeric(472, 473);
+    const REMAINDER_BLANK_FIELD: Aeat720Field = Aeat720Field::String(474, 500);
+}
+
+impl Default for DetailRegister {
+    #[allow(unused_must_use)]
+    fn default() -> Self {
+        let mut fields: AeatRegisterArray = [b' '; AEAT_720_REGISTER_SIZE_BYTES];
+
+        Aeat720Field::write_numeric_field(
+            &mut fields,
+            Self::REGISTER_TYPE_FIELD,
+            Self::AEAT_721_DETAIL_REGISTER_TYPE,
+        );
+        Aeat720Field::write_numeric_field(
+            &mut fields,
+            Self::DOCUMENT_ID_FIELD,
+            AEAT_721_DOCUMENT_ID,
+        );
+        Aeat720Field::write_numeric_field(&mut fields, Self::YEAR_FIELD, 0);
+        Aeat720Field::write_field(&mut fields, Self::NIF_FIELD, "");
+        Aeat720Field::write_field(&mut fields, Self::DECLARED_NIF_FIELD, "");
+        Aeat720Field::write_field(&mut fields, Self::PROXY_NIF_FIELD, "");
+        Aeat720Field::write_field(&mut fields, Self::NAME_FIELD, "");
+        Aeat720Field::write_numeric_field(
+            &mut fields,
+            Self::DECLARATION_TYPE_FIELD,
+            Self::AEAT_721_OWNER_TYPE,
+        );
+        Aeat720Field::write_field(&mut fields, Self::OWNERSHIP_TYPE_FIELD, "");
+        Aeat720Field::write_field(
+            &mut fields,
+            Self::ASSET_TYPE_FIELD,
+            Self::AEAT_721_ASSET_TYPE,
+        );
+        Aeat720Field::write_numeric_field(
+            &mut fields,
+            Self::ASSET_SUBTYPE_FIELD,
+            Self::AEAT_721_CUSTODIED_ASSET_SUBTYPE,
+        );
+        Aeat720Field::write_field(&mut fields, Self::CURRENCY_NAME_FIELD, "");
+        Aeat720Field::write_field(&mut fields, Self::CURRENCY_ID_FIELD, "");
+        Aeat720Field::write_field(&mut fields, Self::ENTITY_NAME_FIELD, "");
+        Aeat720Field::write_field(&mut fields, Self::ENTITY_NIF_FIELD, "");
+        Aeat720Field::write_field(&mut fields, Self::ENTITY_ADDRESS_FIELD, "");
+        Aeat720Field::write_field(&mut fields, Self::ENTITY_COUNTRY_CODE_FIELD, "");
+        Aeat720Field::write_numeric_field(&mut fields, Self::FIRST_ACQUISITION_DATE_FIELD, 0);
+        Aeat720Field::write_field(
+            &mut fields,
+            Self::ACQUISITION_TYPE_FIELD,
+            Self::AEAT_721_ASSET_FIRST_ACQUISITION,
+        );
+        Aeat720Field::write_numeric_field(&mut fields, Self::EXTINCTION_DATE_FIELD, 0);
+        Aeat720Field::write_field(&mut fields, Self::VALUATION_SIGN_FIELD, "");
+        Aeat720Field::write_numeric_field(&mut fields, Self::VALUATION_INT_FIELD, 0);
+        Aeat720Field::write_numeric_field(&mut fields, Self::VALUATION_FRACTION_FIELD, 0);
+        Aeat720Field::write_numeric_field(&mut fields, Self::UNITS_INT_FIELD, 0);
+        Aeat720Field::write_numeric_field(&mut fields, Self::UNITS_FRACTION_FIELD, 0);
+        Aeat720Field::write_numeric_field(&mut fields, Self::OWNED_PERCENTAGE_INT_FIELD, 0);
+        Aeat720Field::write_numeric_field(&mut fields, Self::OWNED_PERCENTAGE_FRACTION_FIELD, 0);
+        Aeat720Field::write_field(&mut fields, Self::REMAINDER_BLANK_FIELD, "");
+
+        Self { fields }
+    }
+}
+
+impl DetailRegister {
+    /// Currencies sold out during `year` are declared as extinguished, the
+    /// ones acquired before it as kept and the rest as acquired in it.
+    fn acquisition_type(record: &Aeat721Record, year: usize) -> &'static str {
+        if record.is_extinct() {
+            Self::AEAT_721_ASSET_EXTINCTION
+        } else if record.first_tx_date < year * 10000 + 101 {
+            Self::AEAT_721_ASSET_KEPT
+        } else {
+            Self::AEAT_721_ASSET_FIRST_ACQUISITION
+        }
+    }
+
+    fn new(record: &Aeat721Record, year: usize, nif: &str, name: &str) -> Result<Self> {
+        let mut fields = Self::default().fields;
+
+        Aeat720Field::write_numeric_field(&mut fields, Self::YEAR_FIELD, year)?;
+        Aeat720Field::write_field(&mut fields, Self::NIF_FIELD, nif)?;
+        Aeat720Field::write_field(&mut fields, Self::DECLARED_NIF_FIELD, nif)?;
+        Aeat720Field::write_field(&mut fields, Self::NAME_FIELD, name)?;
+        Aeat720Field::write_field(
+            &mut fields,
+            Self::CURRENCY_NAME_FIELD,
+            &record.name.to_uppercase(),
+        )?;
+        Aeat720Field::write_field(
+            &mut fields,
+            Self::CURRENCY_ID_FIELD,
+            &record.currency.to_uppercase(),
+        )?;
+        Aeat720Field::write_field(
+            &mut fields,
+            Self::ENTITY_NAME_FIELD,
+            &record.custodian.name.to_uppercase(),
+        )?;
+        Aeat720Field::write_field(
+            &mut fields,
+            Self::ENTITY_COUNTRY_CODE_FIELD,
+            &record.custodian.country_code,
+        )?;
+        Aeat720Field::write_numeric_field(
+            &mut fields,
+            Self::FIRST_ACQUISITION_DATE_FIELD,
+            record.first_tx_date,
+        )?;
+        Aeat720Field::write_field(
+            &mut fields,
+            Self::ACQUISITION_TYPE_FIELD,
+            Self::acquisition_type(record, year),
+        )?;
+        Aeat720Field::write_numeric_field(
+            &mut fields,
+            Self::EXTINCTION_DATE_FIELD,
+            record.extinction_date,
+        )?;
+
+        if record.value_in_euro.is_sign_negative() {
+            Aeat720Field::write_field(
+                &mut fields,
+                Self::VALUATION_SIGN_FIELD,
+                AEAT_720_NEGATIVE_SIGN,
+            )?;
+        }
+        Aeat720Field::write_numeric_field(
+            &mut fields,
+            Self::VALUATION_INT_FIELD,
+            record.value_in_euro.trunc().abs().to_usize().unwrap_or(0),
+        )?;
+        Aeat720Field::write_numeric_field(
+            &mut fields,
+            Self::VALUATION_FRACTION_FIELD,
+            fraction_digits(&record.value_in_euro, 2),
+        )?;
+
+        Aeat720Field::write_numeric_field(
+            &mut fields,
+            Self::UNITS_INT_FIELD,
+            record.quantity.trunc().abs().to_usize().unwrap_or(0),
+        )?;
+        Aeat720Field::write_numeric_field(
+            &mut fields,
+            Self::UNITS_FRACTION_FIELD,
+            fraction_digits(&record.quantity, AEAT_721_UNITS_DECIMALS),
+        )?;
+
+        Aeat720Field::write_numeric_field(
+            &mut fields,
+            Self::OWNED_PERCENTAGE_INT_FIELD,
+            record.percentage.trunc().abs().to_usize().unwrap_or(0),
+        )?;
+        Aeat720Field::write_numeric_field(
+            &mut fields,
+            Self::OWNED_PERCENTAGE_FRACTION_FIELD,
+            fraction_digits(&record.percentage, 2),
+        )?;
+
+        Ok(Self { fields })
+    }
+}
+
+/// AEAT 721 form, virtual currencies held abroad.
+#[derive(Debug)]
+pub struct Aeat721Report {
+    summary: SummaryRegister,
+    details: Vec<DetailRegister>,
+}
+
+impl Aeat721Report {
+    /// Form of `info`, failing when its data does not fit the registers or
+    /// a record still held is valued at zero.
+    pub fn new(info: &Aeat721Information) -> Result<Aeat721Report> {
+        Self::build(info, false)
+    }
+
+    /// Form of `info` like [`Aeat721Report::new`], but accepting the
+    /// records valued at zero once the user confirmed them.
+    pub fn new_accepting_unvalued(info: &Aeat721Information) -> Result<Aeat721Report> {
+        Self::build(info, true)
+    }
+
+    fn build(info: &Aeat721Information, accept_unvalued: bool) -> Result<Aeat721Report> {
+        let full_name = info.full_name();
+        let details = info
+            .records
+            .iter()
+            .map(|record| {
+                if !accept_unvalued && record.is_unvalued() {
+                    bail!("{} is valued at zero", record.name);
+                }
+                DetailRegister::new(
+                    record,
+                    info.personal_info.year,
+                    &info.personal_info.nif,
+                    &full_name,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Aeat721Report {
+            summary: SummaryRegister::new(
+                &info.records,
+                info.personal_info.year,
+                &info.personal_info.nif,
+                &full_name,
+                &info.personal_info.phone,
+            )?,
+            details,
+        })
+    }
+
+    /// Content of the form file to submit to the AEAT.
+    pub fn generate(self) -> Result<Vec<u8>> {
+        let mut result = Vec::with_capacity(
+            AEAT_720_REGISTER_SIZE_BYTES * (self.details.len() + 1) + (self.details.len() + 1),
+        );
+
+        result.write_all(&self.summary.fields)?;
+        result.write_all(b"\n")?;
+        for detail in self.details {
+            result.write_all(&detail.fields)?;
+            result.write_all(b"\n")?;
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{BrokerInformation, PersonalInformation};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_default_summary_register() {
+        let expected = [
+            "1",
+            "721",
+            "0000",
+            &" ".repeat(9),   // nif
+            &" ".repeat(40),  // name
+            "T",              // transmission
+            &"0".repeat(9),   // telephone
+            &" ".repeat(40),  // contact name
+            "721",            // second document id
+            "0000000001",     // id
+            " ",              // complementary
+            " ",              // replacement
+            &"0".repeat(13),  // previous declaration id
+            &"0".repeat(9),   // total registers
+            " ",              // valuation sign
+            &"0".repeat(15),  // valuation int
+            "00",             // valuation fraction
+            &" ".repeat(338), // remainder blank
+        ]
+        .concat();
+
+        assert_eq!(
+            SummaryRegister::default().fields.to_vec(),
+            expected.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_default_detail_register() {
+        let expected = [
+            "2",
+            "721",
+            "0000",
+            &" ".repeat(9),   // nif
+            &" ".repeat(9),   // declared nif
+            &" ".repeat(9),   // proxy nif
+            &" ".repeat(40),  // name
+            "1",              // declaration type
+            &" ".repeat(25),  // ownership type
+            "M",              // asset type
+            "1",              // asset subtype
+            &" ".repeat(62),  // currency name
+            &" ".repeat(24),  // currency id
+            &" ".repeat(41),  // entity name
+            &" ".repeat(20),  // entity nif
+            &" ".repeat(162), // entity address
+            "  ",             // entity country code
+            &"0".repeat(8),   // first acquisition date
+            "A",              // acquisition type
+            &"0".repeat(8),   // extinction date
+            " ",              // valuation sign
+            &"0".repeat(12),  // valuation int
+            "00",             // valuation fraction
+            &"0".repeat(12),  // units int
+            &"0".repeat(10),  // units fraction
+            "000",            // owned percentage int
+            "00",             // owned percentage fraction
+            &" ".repeat(27),  // remainder blank
+        ]
+        .concat();
+
+        assert_eq!(
+            DetailRegister::default().fields.to_vec(),
+            expected.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_generate_report() {
+        let info = Aeat721Information {
+            records: vec![Aeat721Record {
+                name: String::from("Bitcoin"),
+                currency: String::from("BTC"),
+                quantity: Decimal::new(123456789, 8),
+                value_in_euro: Decimal::new(1132085, 2),
+                first_tx_date: 20230112,
+                extinction_date: 0,
+                custodian: Arc::new(BrokerInformation::new(
+                    String::from("Kraken"),
+                    String::from("IE"),
+                )),
+                percentage: Decimal::ONE_HUNDRED,
+            }],
+            personal_info: PersonalInformation {
+                name: String::from("Juan"),
+                surname: String::from("Español"),
+                nif: String::from("12345678Z"),
+                year: 2023,
+                phone: String::new(),
+            },
+        };
+
+        let report = Aeat721Report::new(&info).unwrap().generate().unwrap();
+        assert_eq!(report.len(), 2 * (AEAT_720_REGISTER_SIZE_BYTES + 1));
+
+        let summary = &report[..AEAT_720_REGISTER_SIZE_BYTES];
+        assert_eq!(&summary[135..162], b"000000001 00000000001132085");
+
+        let detail =
+            &report[AEAT_720_REGISTER_SIZE_BYTES + 1..2 * AEAT_720_REGISTER_SIZE_BYTES + 1];
+        assert_eq!(&detail[103..110], b"BITCOIN");
+        assert_eq!(&detail[165..168], b"BTC");
+        assert_eq!(&detail[189..195], b"KRAKEN");
+        assert_eq!(&detail[412..414], b"IE");
+        assert_eq!(&detail[414..423], b"20230112A");
+        assert_eq!(&detail[414..431], b"20230112A00000000");
+        assert_eq!(
+            &detail[431..473],
+            b" 00000001132085000000000001234567890010000"
+        );
+    }
+
+    #[test]
+    fn test_acquisition_type() {
+        let custodian = Arc::new(BrokerInformation::new(
+            String::from("Kraken"),
+            String::from("IE"),
+        ));
+        let record = |first_tx_date, extinction_date, value_in_euro| Aeat721Record {
+            name: String::from("Bitcoin"),
+            currency: String::from("BTC"),
+            quantity: Decimal::ONE,
+            value_in_euro,
+            first_tx_date,
+            extinction_date,
+            custodian: Arc::clone(&custodian),
+            percentage: Decimal::ONE_HUNDRED,
+        };
+        let mut info = Aeat721Information {
+            records: vec![
+                record(20230101, 0, Decimal::TEN),
+                record(20221231, 0, Decimal::TEN),
+                record(20220301, 20230915, Decimal::ZERO),
+            ],
+            personal_info: PersonalInformation {
+                name: String::from("Juan"),
+                surname: String::from("Español"),
+                nif: String::from("12345678Z"),
+                year: 2023,
+                phone: String::new(),
+            },
+        };
+
+        let report = Aeat721Report::new(&info).unwrap().generate().unwrap();
+        let detail = |index: usize| {
+            let start = (index + 1) * (AEAT_720_REGISTER_SIZE_BYTES + 1);
+            report[start + 414..start + 431].to_vec()
+        };
+        assert_eq!(detail(0), b"20230101A00000000");
+        assert_eq!(detail(1), b"20221231M00000000");
+        assert_eq!(detail(2), b"20220301C20230915");
+
+        info.records.push(record(20220301, 0, Decimal::ZERO));
+        assert!(Aeat721Report::new(&info).is_err());
+    }
+}
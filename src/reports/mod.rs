@@ -1 +1,8 @@
+//! Generators of the AEAT forms.
+
+/// Wealth tax (modelo 714) summary.
+pub mod aeat_714;
+/// AEAT 720 form, securities held abroad.
 pub mod aeat_720;
+/// AEAT 721 form, virtual currencies held abroad.
+pub mod aeat_721;
@@ -1,5 +1,6 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
+use anyhow::{Result, bail};
 use chrono::NaiveDate;
 use dominator::{Dom, clone, events, html, with_node};
 use futures_signals::{
@@ -8,25 +9,30 @@ use futures_signals::{
     signal_vec::{MutableVec, SignalVecExt},
 };
 use rust_decimal::Decimal;
-use web_sys::{HtmlElement, HtmlInputElement};
+use web_sys::{HtmlElement, HtmlInputElement, HtmlSelectElement};
 
 use crate::{
-    css::{TABLE_CAPTION, TABLE_HEADER, TABLE_ROW, TABLE_STYLE},
+    css::{
+        CONFLICT_CELL_CLASS, DUPLICATE_CELL_CLASS, TABLE_CAPTION, TABLE_HEADER, TABLE_ROW,
+        TABLE_STYLE,
+    },
     data::{
-        Aeat720Record, BrokerInformation, CompanyInfo, DEFAULT_BROKER, DEFAULT_LOCALE,
-        DEFAULT_NUMBER_OF_DECIMALS, DEFAULT_YEAR,
+        Aeat720Record, AssetCategory, BrokerInformation, CompanyInfo, DEFAULT_BROKER,
+        DEFAULT_LOCALE, DEFAULT_NUMBER_OF_DECIMALS, DEFAULT_YEAR, FirstTxDateSource, Provenance,
     },
+    ledger::Ledger,
     utils::{
         date_to_usize,
         decimal::{decimal_to_str_locale, valid_str_number_with_decimals},
         icons::{render_svg_plus_icon, render_svg_trash_icon},
-        usize_to_date,
+        resolve_first_tx_date, usize_to_date,
     },
 };
 
 const NAME_NOT_VALID_ERR_MSG: &str = "Nombre no válido";
 const ISIN_NOT_VALID_ERR_MSG: &str = "ISIN no válido";
 const VALUE_NOT_VALID_ERR_MSG: &str = "Valor (€) no válido";
+const UNVALUED_ERR_MSG: &str = "Sin valorar, introduce el valor a 31 de diciembre";
 const QUANTITY_NOT_VALID_ERR_MSG: &str = "Nº acciones no válido";
 const PERCENT_NOT_VALID_ERR_MSG: &str = "Porcentaje no válido";
 
@@ -39,6 +45,19 @@ struct Aeat720RecordInfo {
     quantity_err_msg: Mutable<Option<&'static str>>,
     percent_err_msg: Mutable<Option<&'static str>>,
 }
+
+/// Row with the same position as other rows of the table, with the fields
+/// having different values in them.
+#[derive(Debug, Clone, Default)]
+struct Duplicate {
+    rows: Vec<usize>,
+    name: bool,
+    first_tx_date: bool,
+    value: bool,
+    quantity: bool,
+    percentage: bool,
+}
+
 pub struct Table {
     headers: Vec<&'static str>,
     data: MutableVec<Mutable<Aeat720RecordInfo>>,
@@ -50,7 +69,9 @@ impl Table {
             headers: vec![
                 "Nombre compañía",
                 "ISIN",
+                "Tipo",
                 "Cód. país",
+                "Cuenta",
                 "Fecha 1ª adquisición",
                 "Valor (€)",
                 "Nº acciones",
@@ -66,21 +87,80 @@ impl Table {
             .to_signal_map(|x| !x.is_empty())
     }
 
+    fn record_info(record: Aeat720Record) -> Aeat720RecordInfo {
+        // Positions rebuilt from the trades history have no value, so they
+        // start flagged until the user types it.
+        let value_err_msg = if record.value_in_euro.is_zero() {
+            Some(UNVALUED_ERR_MSG)
+        } else {
+            None
+        };
+        Aeat720RecordInfo {
+            record,
+            name_err_msg: Mutable::new(None),
+            isin_err_msg: Mutable::new(None),
+            value_err_msg: Mutable::new(value_err_msg),
+            quantity_err_msg: Mutable::new(None),
+            percent_err_msg: Mutable::new(None),
+        }
+    }
+
     pub fn extend_rows(&self, records: Vec<Aeat720Record>) {
         for record in records.into_iter() {
             self.data
                 .lock_mut()
-                .push_cloned(Mutable::new(Aeat720RecordInfo {
-                    record,
-                    name_err_msg: Mutable::new(None),
-                    isin_err_msg: Mutable::new(None),
-                    value_err_msg: Mutable::new(None),
-                    quantity_err_msg: Mutable::new(None),
-                    percent_err_msg: Mutable::new(None),
-                }));
+                .push_cloned(Mutable::new(Self::record_info(record)));
         }
     }
 
+    /// Duplicated rows by index, see [`duplicated_positions`].
+    fn duplicates(&self) -> BTreeMap<usize, Duplicate> {
+        let records = self.get_records();
+        let mut result = BTreeMap::new();
+        for rows in duplicated_positions(&records) {
+            for &index in &rows {
+                let record = &records[index];
+                let differs = |field: fn(&Aeat720Record, &Aeat720Record) -> bool| {
+                    rows.iter().any(|other| field(record, &records[*other]))
+                };
+                result.insert(
+                    index,
+                    Duplicate {
+                        rows: rows.clone(),
+                        name: differs(|a, b| a.company.name != b.company.name),
+                        first_tx_date: differs(|a, b| a.first_tx_date != b.first_tx_date),
+                        value: differs(|a, b| a.value_in_euro != b.value_in_euro),
+                        quantity: differs(|a, b| a.quantity != b.quantity),
+                        percentage: differs(|a, b| a.percentage != b.percentage),
+                    },
+                );
+            }
+        }
+        result
+    }
+
+    /// Replaces the duplicated `rows` with a single one, see
+    /// [`merge_positions`].
+    fn merge_rows(&self, rows: &[usize]) {
+        let mut data = self.data.lock_mut();
+        let records = rows
+            .iter()
+            .map(|index| data[*index].lock_ref().record.clone())
+            .collect::<Vec<_>>();
+        let mut merged = match merge_positions(&records) {
+            Ok(merged) => merged,
+            Err(err) => {
+                log::error!("Unable to merge rows: {}", err);
+                return;
+            }
+        };
+        merged.provenance.edited = true;
+        for index in rows.iter().skip(1).rev() {
+            data.remove(*index);
+        }
+        data.set_cloned(rows[0], Mutable::new(Self::record_info(merged)));
+    }
+
     fn create_default_record() -> Aeat720RecordInfo {
         let record = Aeat720Record {
             company: CompanyInfo {
@@ -92,6 +172,10 @@ impl Table {
             first_tx_date: date_to_usize(DEFAULT_YEAR as i32, 1, 1),
             broker: DEFAULT_BROKER.clone(),
             percentage: Decimal::ONE_HUNDRED,
+            first_tx_date_source: FirstTxDateSource::Default,
+            category: AssetCategory::Stock,
+            account: String::new(),
+            provenance: Provenance::default(),
         };
         Aeat720RecordInfo {
             record,
@@ -103,11 +187,26 @@ impl Table {
         }
     }
 
+    /// Changes the record of a row, marking it as edited by the user.
+    fn edit_record(record: &Mutable<Aeat720RecordInfo>, edit: impl FnOnce(&mut Aeat720Record)) {
+        let mut row = record.lock_mut();
+        edit(&mut row.record);
+        row.record.provenance.edited = true;
+    }
+
     pub fn add_default(&self) {
         let record = Self::create_default_record();
         self.data.lock_mut().insert_cloned(0, Mutable::new(record));
     }
 
+    /// Records of the rows, sent again on every change of any of them.
+    pub fn records_signal(&self) -> impl Signal<Item = Vec<Aeat720Record>> + use<> {
+        self.data
+            .signal_vec_cloned()
+            .map_signal(|row| row.signal_ref(|info| info.record.clone()))
+            .to_signal_cloned()
+    }
+
     pub fn get_records(&self) -> Vec<Aeat720Record> {
         let mut result = vec![];
         for record in self.data.lock_ref().iter() {
@@ -116,10 +215,43 @@ impl Table {
         result
     }
 
+    /// Resolves again the first acquisition dates not typed by the user, the
+    /// ledger may know older dates after importing more reports.
+    pub fn update_first_tx_dates(&self, ledger: &Ledger, year: usize) {
+        for row in self.data.lock_ref().iter() {
+            let mut row = row.lock_mut();
+            if row.record.first_tx_date_source == FirstTxDateSource::Manual {
+                continue;
+            }
+            let (first_tx_date, source) = resolve_first_tx_date(
+                &row.record.company,
+                &row.record.broker,
+                &row.record.account,
+                &[],
+                ledger,
+                year,
+            );
+            if source != FirstTxDateSource::Default
+                && (first_tx_date, source)
+                    != (row.record.first_tx_date, row.record.first_tx_date_source)
+            {
+                row.record.first_tx_date = first_tx_date;
+                row.record.first_tx_date_source = source;
+            }
+        }
+    }
+
     pub fn clear(&self) {
         self.data.lock_mut().clear();
     }
 
+    /// Removes the rows held at the entity named `name`.
+    pub fn remove_broker_rows(&self, name: &str) {
+        self.data
+            .lock_mut()
+            .retain(|row| row.lock_ref().record.broker.name != name);
+    }
+
     fn render_header_cells(this: &Arc<Self>) -> Vec<Dom> {
         this.headers
             .iter()
@@ -177,10 +309,12 @@ impl Table {
 
     fn company_name_cell(
         record: &Mutable<Aeat720RecordInfo>,
+        conflict: bool,
     ) -> impl Signal<Item = Option<Dom>> + use<> {
         record.signal_ref(clone!(record => move |r| {
             Some(
               html!("td", {
+                .apply_if(conflict, |dom| dom.class(&*CONFLICT_CELL_CLASS))
                 .child(
                   html!("input" => HtmlInputElement, {
                     .style("display", "block")
@@ -206,7 +340,7 @@ impl Table {
                           *record.lock_mut().name_err_msg.lock_mut() = Some(NAME_NOT_VALID_ERR_MSG);
                           let _ = element.focus();
                         }
-                        record.lock_mut().record.company.name = name;
+                        Self::edit_record(&record, |r| r.company.name = name);
                       }))
                     })
                   })
@@ -255,7 +389,7 @@ impl Table {
                           *record.lock_mut().isin_err_msg.lock_mut() = Some(ISIN_NOT_VALID_ERR_MSG);
                           let _ = element.focus();
                         }
-                        record.lock_mut().record.company.isin = isin;
+                        Self::edit_record(&record, |r| r.company.isin = isin);
                       }))
                     })
                 }))
@@ -269,6 +403,39 @@ impl Table {
         }))
     }
 
+    fn category_cell(
+        record: &Mutable<Aeat720RecordInfo>,
+    ) -> impl Signal<Item = Option<Dom>> + use<> {
+        record.signal_ref(clone!(record => move |r| {
+            let selected = r.record.category;
+            Some(
+              html!("td", {
+                .child(html!("select" => HtmlSelectElement, {
+                    .children(AssetCategory::ALL.iter().enumerate().map(|(index, category)| {
+                        html!("option", {
+                            .attr("value", &index.to_string())
+                            .apply_if(*category == selected, |dom| dom.attr("selected", ""))
+                            .text(category.description())
+                        })
+                    }))
+                    .with_node!(element => {
+                      .event(clone!(record => move |_: events::Change| {
+                        if let Some(category) = element
+                            .value()
+                            .parse::<usize>()
+                            .ok()
+                            .and_then(|index| AssetCategory::ALL.get(index))
+                        {
+                            Self::edit_record(&record, |r| r.category = *category);
+                        }
+                      }))
+                    })
+                }))
+              })
+            )
+        }))
+    }
+
     fn broker_country_code_cell(
         record: &Mutable<Aeat720RecordInfo>,
     ) -> impl Signal<Item = Option<Dom>> + use<> {
@@ -287,7 +454,7 @@ impl Table {
                           name: "new unknown".to_string(),
                           country_code: element.value(),
                         });
-                        record.lock_mut().record.broker = broker;
+                        Self::edit_record(&record, |r| r.broker = broker);
                       }))
                     })
                   })
@@ -297,32 +464,73 @@ impl Table {
         }))
     }
 
-    fn date_cell(record: &Mutable<Aeat720RecordInfo>) -> impl Signal<Item = Option<Dom>> + use<> {
+    fn account_cell(
+        record: &Mutable<Aeat720RecordInfo>,
+    ) -> impl Signal<Item = Option<Dom>> + use<> {
+        record.signal_ref(clone!(record => move |r| {
+            Some(
+              html!("td", {
+                .child(
+                  html!("input" => HtmlInputElement, {
+                    .attr("type", "text")
+                    .attr("size", "10")
+                    .attr("maxlength", "34")
+                    .attr("value", &r.record.account)
+                    .with_node!(element => {
+                      .event(clone!(record => move |_: events::Change| {
+                        Self::edit_record(&record, |r| r.account = element.value());
+                      }))
+                    })
+                  })
+                )
+              })
+            )
+        }))
+    }
+
+    fn date_cell(
+        record: &Mutable<Aeat720RecordInfo>,
+        conflict: bool,
+    ) -> impl Signal<Item = Option<Dom>> + use<> {
         record.signal_ref(clone!(record => move |r| {
           let first_tx_date = r.record.first_tx_date;
           let date = usize_to_date(first_tx_date)
               .map_or("".to_string(), |d| d.format("%Y-%m-%d").to_string());
             Some(
               html!("td", {
+                .apply_if(conflict, |dom| dom.class(&*CONFLICT_CELL_CLASS))
                 .child(html!("input" => HtmlInputElement, {
                   .attr("type", "date")
                   .attr("value", &date)
                   .with_node!(element => {
                       .event(clone!(record => move |_: events::Change| {
                         let parsed_date = NaiveDate::parse_from_str(&element.value(), "%Y-%m-%d").unwrap();
-                        record.lock_mut().record.first_tx_date =
+                        let mut record = record.lock_mut();
+                        record.record.first_tx_date =
                           parsed_date.format("%Y%m%d").to_string().parse::<usize>().unwrap_or(first_tx_date);
+                        record.record.first_tx_date_source = FirstTxDateSource::Manual;
+                        record.record.provenance.edited = true;
                       }))
                     })
                 }))
+                .child(html!("span", {
+                    .style("display", "block")
+                    .style("color", "grey")
+                    .style("font-size", "small")
+                    .text(r.record.first_tx_date_source.description())
+                }))
               })
             )
         }))
     }
 
-    fn value_cell(record: &Mutable<Aeat720RecordInfo>) -> impl Signal<Item = Option<Dom>> + use<> {
+    fn value_cell(
+        record: &Mutable<Aeat720RecordInfo>,
+        conflict: bool,
+    ) -> impl Signal<Item = Option<Dom>> + use<> {
         record.signal_ref(clone!(record => move |r| {
             Some(html!("td", {
+              .apply_if(conflict, |dom| dom.class(&*CONFLICT_CELL_CLASS))
               .child(html!("input" => HtmlInputElement, {
                 .style("text-align", "right")
                 .attr("type", "text")
@@ -344,12 +552,12 @@ impl Table {
                     if valid_str_number_with_decimals(&money_str, DEFAULT_NUMBER_OF_DECIMALS, DEFAULT_LOCALE) {
                       if let Ok(money) = money_str.parse::<Decimal>() {
                         *record.lock_mut().value_err_msg.lock_mut() = None;
-                        record.lock_mut().record.value_in_euro = money;
+                        Self::edit_record(&record, |r| r.value_in_euro = money);
                         return
                       }
                     }
                     *record.lock_mut().value_err_msg.lock_mut() = Some(VALUE_NOT_VALID_ERR_MSG);
-                    record.lock_mut().record.value_in_euro = Decimal::ZERO;
+                    Self::edit_record(&record, |r| r.value_in_euro = Decimal::ZERO);
                     let _ = element.focus();
                   }))
                 })
@@ -365,9 +573,11 @@ impl Table {
 
     fn quantity_cell(
         record: &Mutable<Aeat720RecordInfo>,
+        conflict: bool,
     ) -> impl Signal<Item = Option<Dom>> + use<> {
         record.signal_ref(clone!(record => move |r| {
             Some(html!("td", {
+              .apply_if(conflict, |dom| dom.class(&*CONFLICT_CELL_CLASS))
               .child(html!("input" => HtmlInputElement, {
                 .style("text-align", "right")
                 .attr("type", "text")
@@ -389,12 +599,12 @@ impl Table {
                     if valid_str_number_with_decimals(&quantity_str, DEFAULT_NUMBER_OF_DECIMALS, DEFAULT_LOCALE) {
                       if let Ok(quantity) = quantity_str.parse::<Decimal>() {
                         *record.lock_mut().quantity_err_msg.lock_mut() = None;
-                        record.lock_mut().record.quantity = quantity;
+                        Self::edit_record(&record, |r| r.quantity = quantity);
                         return
                       }
                     }
                     *record.lock_mut().quantity_err_msg.lock_mut() = Some(QUANTITY_NOT_VALID_ERR_MSG);
-                    record.lock_mut().record.quantity = Decimal::ONE_HUNDRED;
+                    Self::edit_record(&record, |r| r.quantity = Decimal::ONE_HUNDRED);
                     let _ = element.focus();
                   }))
                 })
@@ -410,9 +620,11 @@ impl Table {
 
     fn percentage_cell(
         record: &Mutable<Aeat720RecordInfo>,
+        conflict: bool,
     ) -> impl Signal<Item = Option<Dom>> + use<> {
         record.signal_ref(clone!(record => move |r| {
             Some(html!("td", {
+              .apply_if(conflict, |dom| dom.class(&*CONFLICT_CELL_CLASS))
               .child(html!("input" => HtmlInputElement, {
                 .style("text-align", "right")
                 .attr("type", "text")
@@ -435,13 +647,13 @@ impl Table {
                       if let Ok(percentage) = percentage_str.parse::<Decimal>() {
                         if percentage.gt(&Decimal::ZERO) && percentage.le(&Decimal::ONE_HUNDRED) {
                           *record.lock_mut().percent_err_msg.lock_mut() = None;
-                          record.lock_mut().record.percentage = percentage;
+                          Self::edit_record(&record, |r| r.percentage = percentage);
                           return;
                         }
                       }
                     }
                     *record.lock_mut().percent_err_msg.lock_mut() = Some(PERCENT_NOT_VALID_ERR_MSG);
-                    record.lock_mut().record.percentage = Decimal::ONE_HUNDRED;
+                    Self::edit_record(&record, |r| r.percentage = Decimal::ONE_HUNDRED);
                     let _ = element.focus();
                   }))
                 })
@@ -460,8 +672,9 @@ impl Table {
         this: &Arc<Self>,
         index: usize,
         record: &Mutable<Aeat720RecordInfo>,
+        duplicate: Option<Duplicate>,
     ) -> impl Signal<Item = Option<Dom>> + use<> {
-        record.signal_ref(clone!(this => move |_r| {
+        record.signal_ref(clone!(this => move |r| {
          let delete_span = html!("span" => HtmlElement, {
            .child(render_svg_trash_icon("red", "24"))
           .with_node!(_element => {
@@ -473,37 +686,75 @@ impl Table {
 
             Some(
               html!("td", {
+                .child(html!("span", {
+                  .style("cursor", "help")
+                  .attr("title", &r.record.provenance.description())
+                  .text("ⓘ ")
+                }))
                 .child(delete_span)
+                .apply_if(duplicate.is_some(), |dom| {
+                  // Rows owned in different percentages can not be summed.
+                  let conflicting = duplicate.as_ref().is_some_and(|duplicate| duplicate.percentage);
+                  dom.child(html!("input" => HtmlInputElement, {
+                    .attr("type", "button")
+                    .attr("value", "Fusionar")
+                    .attr("title", if conflicting {
+                      "Las filas tienen distinto porcentaje de titularidad, corrígelo antes de fusionarlas"
+                    } else {
+                      "Suma las acciones y el valor de las filas duplicadas"
+                    })
+                    .apply_if(conflicting, |dom| dom.attr("disabled", "true"))
+                    .event(clone!(this, duplicate => move |_: events::Click| {
+                      if let Some(duplicate) = &duplicate {
+                        this.merge_rows(&duplicate.rows);
+                      }
+                    }))
+                  }))
+                })
               })
             )
         }))
     }
 
-    fn render_row(this: &Arc<Self>, index: usize, record: &Mutable<Aeat720RecordInfo>) -> Dom {
+    fn render_row(
+        this: &Arc<Self>,
+        index: usize,
+        record: &Mutable<Aeat720RecordInfo>,
+        duplicate: Option<Duplicate>,
+    ) -> Dom {
+        let conflicts = duplicate.clone().unwrap_or_default();
         html!("tr", {
           .class(&*TABLE_ROW)
           .child(
             html!("td", {
               .text(&format!("{}", index + 1))
+              .apply_if(duplicate.is_some(), |dom| {
+                let rows = conflicts.rows.iter().map(|row| (row + 1).to_string()).collect::<Vec<_>>();
+                dom.class(&*DUPLICATE_CELL_CLASS)
+                  .attr("title", &format!("Misma posición (ISIN, broker y cuenta) en las filas {}", rows.join(", ")))
+              })
             })
           )
-          .child_signal(Self::company_name_cell(record))
+          .child_signal(Self::company_name_cell(record, conflicts.name))
           .child_signal(Self::company_isin_cell(record))
+          .child_signal(Self::category_cell(record))
           .child_signal(Self::broker_country_code_cell(record))
-          .child_signal(Self::date_cell(record))
-          .child_signal(Self::value_cell(record))
-          .child_signal(Self::quantity_cell(record))
-          .child_signal(Self::percentage_cell(record))
-          .child_signal(Self::actions_cell(this, index, record))
+          .child_signal(Self::account_cell(record))
+          .child_signal(Self::date_cell(record, conflicts.first_tx_date))
+          .child_signal(Self::value_cell(record, conflicts.value))
+          .child_signal(Self::quantity_cell(record, conflicts.quantity))
+          .child_signal(Self::percentage_cell(record, conflicts.percentage))
+          .child_signal(Self::actions_cell(this, index, record, duplicate))
         })
     }
 
     fn render_body(this: &Arc<Self>) -> Dom {
+        let duplicates = this.duplicates();
         html!("tbody", {
           .children_signal_vec(this.data.signal_vec_cloned()
             .enumerate().map(clone!(this => move |(index, record)| {
               let i = index.get().unwrap_or(usize::MAX);
-              Table::render_row(&this, i, &record)
+              Table::render_row(&this, i, &record, duplicates.get(&i).cloned())
            }))
           )
         })
@@ -525,7 +776,7 @@ impl Table {
          .child(
             html!("caption", {
               .class(&*TABLE_CAPTION)
-              .text("Movimientos importados/creados.")
+              .text("Movimientos importados/creados. Las filas duplicadas tienen el número resaltado y en rojo los datos que difieren entre ellas.")
             })
 
           )
@@ -538,3 +789,119 @@ impl Table {
         })
     }
 }
+
+/// Groups of indexes of `records` with the same position, leaving out the
+/// records without duplicates.
+fn duplicated_positions(records: &[Aeat720Record]) -> Vec<Vec<usize>> {
+    let mut grouped = vec![false; records.len()];
+    let mut result = vec![];
+    for (index, record) in records.iter().enumerate() {
+        if grouped[index] {
+            continue;
+        }
+        let group = (index..records.len())
+            .filter(|other| !grouped[*other] && record.is_same_position(&records[*other]))
+            .collect::<Vec<_>>();
+        if group.len() > 1 {
+            for other in &group {
+                grouped[*other] = true;
+            }
+            result.push(group);
+        }
+    }
+    result
+}
+
+/// Merges duplicated `records` of a position summing their quantity and
+/// value and keeping the earliest first acquisition date, preferring the
+/// dates found over the default ones. The rest of the fields are the ones of
+/// the first record, records owned in different percentages are not merged.
+fn merge_positions(records: &[Aeat720Record]) -> Result<Aeat720Record> {
+    let Some((first, rest)) = records.split_first() else {
+        bail!("No positions to merge");
+    };
+    let mut result = first.clone();
+    for record in rest {
+        if record.percentage != result.percentage {
+            bail!(
+                "{} is owned in different percentages ({} and {})",
+                result.company.name,
+                result.percentage,
+                record.percentage
+            );
+        }
+        result.quantity += record.quantity;
+        result.value_in_euro += record.value_in_euro;
+        let is_default = |source| source == FirstTxDateSource::Default;
+        let earlier = match (
+            is_default(result.first_tx_date_source),
+            is_default(record.first_tx_date_source),
+        ) {
+            (true, false) => true,
+            (false, true) => false,
+            _ => record.first_tx_date < result.first_tx_date,
+        };
+        if earlier {
+            result.first_tx_date = record.first_tx_date;
+            result.first_tx_date_source = record.first_tx_date_source;
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_duplicated_positions() {
+        let broker = Arc::new(BrokerInformation::new(
+            String::from("Degiro"),
+            String::from("NL"),
+        ));
+        let record = |isin: &str, account: &str, quantity, first_tx_date| Aeat720Record {
+            company: CompanyInfo {
+                name: String::from("ACME"),
+                isin: isin.to_string(),
+            },
+            quantity: Decimal::new(quantity, 0),
+            value_in_euro: Decimal::new(quantity * 10, 0),
+            first_tx_date,
+            broker: Arc::clone(&broker),
+            percentage: Decimal::ONE_HUNDRED,
+            first_tx_date_source: FirstTxDateSource::Trades,
+            category: AssetCategory::Stock,
+            account: account.to_string(),
+            provenance: Provenance::default(),
+        };
+        let records = vec![
+            record("US0000000001", "U1", 5, 20200105),
+            record("US0000000002", "U1", 1, 20200101),
+            record("US0000000001", "U2", 2, 20190101),
+            record("US0000000001", "U1", 3, 20180301),
+        ];
+
+        let duplicates = duplicated_positions(&records);
+        assert_eq!(duplicates, vec![vec![0, 3]]);
+        let merged = merge_positions(&[records[0].clone(), records[3].clone()]).unwrap();
+        assert_eq!(merged.quantity, Decimal::new(8, 0));
+        assert_eq!(merged.value_in_euro, Decimal::new(80, 0));
+        assert_eq!(merged.first_tx_date, 20180301);
+        assert_eq!(merged.account, "U1");
+
+        // Default dates do not replace the ones found, however earlier.
+        let mut defaulted = records[3].clone();
+        defaulted.first_tx_date = 20000101;
+        defaulted.first_tx_date_source = FirstTxDateSource::Default;
+        let merged = merge_positions(&[defaulted.clone(), records[0].clone()]).unwrap();
+        assert_eq!(merged.first_tx_date, 20200105);
+        assert_eq!(merged.first_tx_date_source, FirstTxDateSource::Trades);
+        let merged = merge_positions(&[records[0].clone(), defaulted]).unwrap();
+        assert_eq!(merged.first_tx_date, 20200105);
+
+        let mut shared = records[3].clone();
+        shared.percentage = Decimal::new(50, 0);
+        assert!(merge_positions(&[records[0].clone(), shared]).is_err());
+        assert!(merge_positions(&[]).is_err());
+    }
+}
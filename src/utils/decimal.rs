@@ -1,4 +1,6 @@
+#[cfg(feature = "web")]
 use num_format::Locale;
+#[cfg(feature = "web")]
 use rust_decimal::Decimal;
 
 pub fn transform_i18n_es_str(input: &str) -> String {
@@ -9,6 +11,7 @@ pub fn normalize_str(input: &str) -> String {
     str::replace(input, ",", "")
 }
 
+#[cfg(feature = "web")]
 pub fn decimal_to_str_locale(number: &Decimal, locale: &Locale) -> String {
     let mut result = number.to_string();
     if let Some(idx) = result.rfind('.') {
@@ -18,6 +21,7 @@ pub fn decimal_to_str_locale(number: &Decimal, locale: &Locale) -> String {
     result
 }
 
+#[cfg(feature = "web")]
 pub fn valid_str_number_with_decimals(number: &str, decimal_number: u16, locale: &Locale) -> bool {
     let mut state = 0; // 0 integer part, 1 decimal part
     let mut decimals = 0;
@@ -41,7 +45,7 @@ pub fn valid_str_number_with_decimals(number: &str, decimal_number: u16, locale:
     true
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "web"))]
 mod tests {
     use super::*;
 
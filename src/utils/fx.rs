@@ -0,0 +1,241 @@
+//! Euro foreign exchange reference rates of the European Central Bank on the
+//! last business day of each year, the ones the AEAT takes to value in euros
+//! the positions held in other currencies.
+
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+
+const EUR_CURRENCY: &str = "EUR";
+
+/// First year of [`YEAR_END_RATES`].
+const FIRST_YEAR: usize = 2019;
+
+/// Units of each ECB reference currency per euro at the end of each year
+/// since [`FIRST_YEAR`], empty for the years the ECB did not publish it.
+const YEAR_END_RATES: [(&str, [&str; 7]); 32] = [
+    (
+        "AUD",
+        [
+            "1.5995", "1.5896", "1.5615", "1.5693", "1.6263", "1.6772", "1.7597",
+        ],
+    ),
+    (
+        "BGN",
+        [
+            "1.9558", "1.9558", "1.9558", "1.9558", "1.9558", "1.9558", "1.9558",
+        ],
+    ),
+    (
+        "BRL",
+        [
+            "4.5157", "6.3735", "6.3101", "5.6386", "5.3618", "6.4253", "6.4567",
+        ],
+    ),
+    (
+        "CAD",
+        [
+            "1.4598", "1.5633", "1.4393", "1.4440", "1.4642", "1.4948", "1.6102",
+        ],
+    ),
+    (
+        "CHF",
+        [
+            "1.0854", "1.0802", "1.0331", "0.9847", "0.9260", "0.9412", "0.9312",
+        ],
+    ),
+    (
+        "CNY",
+        [
+            "7.8205", "8.0225", "7.1947", "7.3582", "7.8509", "7.5833", "8.2340",
+        ],
+    ),
+    (
+        "CZK",
+        [
+            "25.408", "26.242", "24.858", "24.116", "24.724", "25.185", "24.231",
+        ],
+    ),
+    (
+        "DKK",
+        [
+            "7.4715", "7.4409", "7.4364", "7.4365", "7.4529", "7.4578", "7.4689",
+        ],
+    ),
+    (
+        "GBP",
+        [
+            "0.85080", "0.89903", "0.84028", "0.88693", "0.86905", "0.82918", "0.87260",
+        ],
+    ),
+    (
+        "HKD",
+        [
+            "8.7473", "9.5142", "8.8333", "8.3163", "8.6314", "8.0686", "9.1435",
+        ],
+    ),
+    ("HRK", ["7.4395", "7.5519", "7.5156", "7.5365", "", "", ""]),
+    (
+        "HUF",
+        [
+            "330.53", "363.89", "369.19", "400.87", "382.80", "411.35", "385.08",
+        ],
+    ),
+    (
+        "IDR",
+        [
+            "15595.60", "17240.76", "16100.42", "16519.82", "17079.71", "16820.88", "19613.37",
+        ],
+    ),
+    (
+        "ILS",
+        [
+            "3.8845", "3.9447", "3.5159", "3.7554", "3.9821", "3.7876", "3.7432",
+        ],
+    ),
+    (
+        "INR",
+        [
+            "80.1870", "89.6605", "84.2292", "88.1710", "91.9045", "88.9335", "105.6215",
+        ],
+    ),
+    (
+        "ISK",
+        [
+            "135.80", "156.10", "147.60", "151.50", "150.50", "146.10", "147.60",
+        ],
+    ),
+    (
+        "JPY",
+        [
+            "121.94", "126.49", "130.38", "140.66", "156.33", "163.06", "184.09",
+        ],
+    ),
+    (
+        "KRW",
+        [
+            "1296.28", "1336.00", "1346.38", "1344.09", "1433.66", "1532.15", "1691.95",
+        ],
+    ),
+    (
+        "MXN",
+        [
+            "21.2202", "24.4160", "23.1438", "20.8560", "18.7231", "21.5504", "21.1125",
+        ],
+    ),
+    (
+        "MYR",
+        [
+            "4.5953", "4.9340", "4.7184", "4.6984", "5.0775", "4.6454", "4.7721",
+        ],
+    ),
+    (
+        "NOK",
+        [
+            "9.8638", "10.4703", "9.9888", "10.5138", "11.2405", "11.7950", "11.8400",
+        ],
+    ),
+    (
+        "NZD",
+        [
+            "1.6653", "1.6984", "1.6579", "1.6798", "1.7504", "1.8532", "2.0316",
+        ],
+    ),
+    (
+        "PHP",
+        [
+            "56.900", "59.125", "57.763", "59.320", "61.190", "60.195", "69.126",
+        ],
+    ),
+    (
+        "PLN",
+        [
+            "4.2568", "4.5597", "4.5969", "4.6808", "4.3395", "4.2750", "4.2178",
+        ],
+    ),
+    (
+        "RON",
+        [
+            "4.7830", "4.8683", "4.9490", "4.9495", "4.9756", "4.9743", "5.0985",
+        ],
+    ),
+    ("RUB", ["69.9563", "91.4671", "85.3004", "", "", "", ""]),
+    (
+        "SEK",
+        [
+            "10.4468", "10.0343", "10.2503", "11.1218", "11.0960", "11.4590", "10.8215",
+        ],
+    ),
+    (
+        "SGD",
+        [
+            "1.5111", "1.6218", "1.5279", "1.4300", "1.4591", "1.4164", "1.5107",
+        ],
+    ),
+    (
+        "THB",
+        [
+            "33.415", "36.727", "37.653", "36.835", "37.973", "35.676", "36.967",
+        ],
+    ),
+    (
+        "TRY",
+        [
+            "6.6843", "9.1131", "15.2335", "19.9649", "32.6531", "36.7372", "50.3145",
+        ],
+    ),
+    (
+        "USD",
+        [
+            "1.1234", "1.2271", "1.1326", "1.0666", "1.1050", "1.0389", "1.1750",
+        ],
+    ),
+    (
+        "ZAR",
+        [
+            "15.7773", "18.0219", "18.0625", "18.0986", "20.3477", "19.6188", "19.5155",
+        ],
+    ),
+];
+
+/// Units of `currency` per euro at the end of `year`, `None` for the
+/// currencies and years without rate.
+pub fn year_end_rate(currency: &str, year: usize) -> Option<Decimal> {
+    let currency = currency.trim().to_uppercase();
+    if currency == EUR_CURRENCY {
+        return Some(Decimal::ONE);
+    }
+    let (_, rates) = YEAR_END_RATES.iter().find(|(code, _)| *code == currency)?;
+    let rate = rates.get(year.checked_sub(FIRST_YEAR)?)?;
+    Decimal::from_str(rate).ok()
+}
+
+/// Value in euros of `amount` of `currency` at the end of `year`, rounded to
+/// cents.
+pub fn to_euro(amount: Decimal, currency: &str, year: usize) -> Option<Decimal> {
+    year_end_rate(currency, year).map(|rate| (amount / rate).round_dp(2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_euro() {
+        assert_eq!(
+            to_euro(Decimal::new(482, 0), "USD", 2023),
+            Some(Decimal::new(436_20, 2))
+        );
+        assert_eq!(
+            to_euro(Decimal::new(100, 0), "EUR", 2023),
+            Some(Decimal::new(100, 0))
+        );
+        assert_eq!(
+            to_euro(Decimal::new(100, 0), "gbp", 2023),
+            Some(Decimal::new(115_07, 2))
+        );
+        assert_eq!(to_euro(Decimal::new(100, 0), "USD", 2010), None);
+        assert_eq!(to_euro(Decimal::new(100, 0), "RUB", 2023), None);
+        assert_eq!(to_euro(Decimal::new(100, 0), "XYZ", 2023), None);
+    }
+}
@@ -1,23 +1,52 @@
 use std::sync::{Arc, LazyLock};
 
 use anyhow::{Result, bail};
-use chrono::NaiveDate;
+use chrono::{Datelike, NaiveDate, Utc};
+use encoding_rs::ISO_8859_15;
 use rust_decimal::Decimal;
+use xlsx::read_xlsx;
 use zip::read_zip;
 
 use crate::{
     data::{
-        AccountNotes, Aeat720Record, Aeat720Records, BalanceNotes, BrokerInformation, DEFAULT_YEAR,
+        AccountNote, AccountNotes, Aeat720Record, Aeat720Records, Aeat721Record, Aeat721Records,
+        AssetCategory, BalanceNote, BalanceNotes, BrokerInformation, CompanyInfo,
+        CorporateActionNotes, CryptoNote, CryptoNotes, DEFAULT_YEAR, FirstTxDateSource, Provenance,
     },
+    error::{ImportError, ImportWarning, Location},
+    importer::ImportResult,
+    ledger::{Ledger, LedgerPosition},
     parsers::{
-        degiro::DegiroParser, degiro_csv::DegiroCSVParser, ib::IBParser, ib_csv::IBCSVParser,
-        pdf::read_pdf,
+        aeat_720::{AEAT_720_FILE_BEGIN, Aeat720Parser},
+        baader::{BAADER_MARK, BaaderParser},
+        binance::BinanceParser,
+        coinbase::{COINBASE_HEADER_FIELDS, CoinbaseParser},
+        degiro::DegiroParser,
+        degiro_csv::DegiroCSVParser,
+        equity_plan::{EquityPlanParser, EquityPlanProvider},
+        etoro::EToroParser,
+        generic_csv::{CsvMapping, GenericCSVParser},
+        ib::IBParser,
+        ib_csv::IBCSVParser,
+        kraken::{KRAKEN_HEADER_BEGIN, KrakenParser},
+        ofx::OfxParser,
+        pdf::{find_page, read_pdf, read_pdf_pages},
+        revolut::{REVOLUT_HEADER_BEGIN, RevolutParser},
+        scalable_csv::{SCALABLE_HEADER_BEGIN, ScalableCSVParser},
+        trade_republic::{TRADE_REPUBLIC_MARK, TradeRepublicParser},
+        trading212::{TRADING212_HEADER_BEGIN, Trading212Parser},
+        util::extract_isin_changes,
+        xtb::XtbParser,
     },
 };
 
 pub mod decimal;
+pub mod fx;
+#[cfg(feature = "web")]
 pub mod icons;
+#[cfg(feature = "web")]
 pub mod web;
+pub mod xlsx;
 pub mod zip;
 
 static DEGIRO_BROKER: LazyLock<Arc<BrokerInformation>> = LazyLock::new(|| {
@@ -34,6 +63,119 @@ static IB_BROKER: LazyLock<Arc<BrokerInformation>> = LazyLock::new(|| {
     ))
 });
 
+static TRADE_REPUBLIC_BROKER: LazyLock<Arc<BrokerInformation>> = LazyLock::new(|| {
+    Arc::new(BrokerInformation::new(
+        String::from("Trade Republic"),
+        String::from("DE"),
+    ))
+});
+
+static TRADING212_BROKER: LazyLock<Arc<BrokerInformation>> = LazyLock::new(|| {
+    Arc::new(BrokerInformation::new(
+        String::from("Trading 212"),
+        String::from("CY"),
+    ))
+});
+
+static ETORO_BROKER: LazyLock<Arc<BrokerInformation>> = LazyLock::new(|| {
+    Arc::new(BrokerInformation::new(
+        String::from("eToro"),
+        String::from("CY"),
+    ))
+});
+
+static REVOLUT_BROKER: LazyLock<Arc<BrokerInformation>> = LazyLock::new(|| {
+    Arc::new(BrokerInformation::new(
+        String::from("Revolut Securities"),
+        String::from("LT"),
+    ))
+});
+
+static XTB_BROKER: LazyLock<Arc<BrokerInformation>> = LazyLock::new(|| {
+    Arc::new(BrokerInformation::new(
+        String::from("XTB"),
+        String::from("PL"),
+    ))
+});
+
+static MORGAN_STANLEY_BROKER: LazyLock<Arc<BrokerInformation>> = LazyLock::new(|| {
+    Arc::new(BrokerInformation::new(
+        String::from("Morgan Stanley"),
+        String::from("US"),
+    ))
+});
+
+static SCHWAB_BROKER: LazyLock<Arc<BrokerInformation>> = LazyLock::new(|| {
+    Arc::new(BrokerInformation::new(
+        String::from("Charles Schwab"),
+        String::from("US"),
+    ))
+});
+
+static FIDELITY_BROKER: LazyLock<Arc<BrokerInformation>> = LazyLock::new(|| {
+    Arc::new(BrokerInformation::new(
+        String::from("Fidelity"),
+        String::from("US"),
+    ))
+});
+
+/// Broker of the OFX statements, named after the institution of the file.
+/// Its country is the one of the file, or asked to the user when the file
+/// does not give it.
+static OFX_BROKER: LazyLock<Arc<BrokerInformation>> = LazyLock::new(|| {
+    Arc::new(BrokerInformation::new(
+        String::from("Desconocido"),
+        String::new(),
+    ))
+});
+
+/// Custodian of Scalable Capital accounts, the depository entity required by
+/// the 720 form.
+static BAADER_BROKER: LazyLock<Arc<BrokerInformation>> = LazyLock::new(|| {
+    Arc::new(BrokerInformation::new(
+        String::from("Baader Bank"),
+        String::from("DE"),
+    ))
+});
+
+static COINBASE_CUSTODIAN: LazyLock<Arc<BrokerInformation>> = LazyLock::new(|| {
+    Arc::new(BrokerInformation::new(
+        String::from("Coinbase Europe"),
+        String::from("IE"),
+    ))
+});
+
+static KRAKEN_CUSTODIAN: LazyLock<Arc<BrokerInformation>> = LazyLock::new(|| {
+    Arc::new(BrokerInformation::new(
+        String::from("Payward Ireland (Kraken)"),
+        String::from("IE"),
+    ))
+});
+
+static BINANCE_CUSTODIAN: LazyLock<Arc<BrokerInformation>> = LazyLock::new(|| {
+    Arc::new(BrokerInformation::new(
+        String::from("Binance"),
+        String::from("LT"),
+    ))
+});
+
+/// Denominations of the most common virtual currencies, the rest are named
+/// after their identifier.
+const CRYPTO_CURRENCY_NAMES: [(&str, &str); 12] = [
+    ("BTC", "Bitcoin"),
+    ("ETH", "Ethereum"),
+    ("USDT", "Tether"),
+    ("USDC", "USD Coin"),
+    ("BNB", "BNB"),
+    ("XRP", "XRP"),
+    ("ADA", "Cardano"),
+    ("SOL", "Solana"),
+    ("DOT", "Polkadot"),
+    ("DOGE", "Dogecoin"),
+    ("LTC", "Litecoin"),
+    ("MATIC", "Polygon"),
+];
+
 pub fn usize_to_date(date_int: usize) -> Option<NaiveDate> {
     let mut date = date_int;
     let day = date % 100;
@@ -43,53 +185,241 @@ pub fn usize_to_date(date_int: usize) -> Option<NaiveDate> {
     NaiveDate::from_ymd_opt(date as i32, month as u32, day as u32)
 }
 
-fn read_degiro_pdf(content: Vec<u8>) -> Result<(BalanceNotes, AccountNotes)> {
+fn read_broker_pdf_text(
+    data: String,
+) -> Result<(BalanceNotes, AccountNotes, CorporateActionNotes)> {
+    if data.contains(TRADE_REPUBLIC_MARK) {
+        let parser = TradeRepublicParser::new(data, &TRADE_REPUBLIC_BROKER);
+        let (balance_notes, account_notes) = parser.parse_pdf_content()?;
+        return Ok((balance_notes, account_notes, vec![]));
+    }
+
+    if data.contains(BAADER_MARK) {
+        let parser = BaaderParser::new(data, &BAADER_BROKER);
+        return Ok((parser.parse_pdf_content()?, vec![], vec![]));
+    }
+
+    if let Some(provider) = EquityPlanProvider::detect_statement(&data) {
+        let parser = EquityPlanParser::new(data, provider, equity_plan_broker(provider));
+        return Ok((parser.parse_pdf_content()?, vec![], vec![]));
+    }
+
+    let parser = DegiroParser::new(data, &DEGIRO_BROKER);
+    let (balance_notes, mut account_notes) = parser.parse_pdf_content()?;
+    let corporate_actions = extract_isin_changes(&mut account_notes);
+    Ok((balance_notes, account_notes, corporate_actions))
+}
+
+fn read_broker_pdf(content: Vec<u8>) -> Result<(BalanceNotes, AccountNotes, CorporateActionNotes)> {
     if let Ok(data) = read_pdf(&content) {
-        let parser = DegiroParser::new(data, &DEGIRO_BROKER);
-        parser.parse_pdf_content()
+        // The parsers only see the text of the whole document, so the page
+        // of a wrong row is looked for once it fails.
+        read_broker_pdf_text(data).map_err(|err| match ImportError::from(err) {
+            ImportError::MalformedRow {
+                mut location,
+                raw,
+                issue,
+            } => {
+                location.page = find_page(&content, &raw);
+                ImportError::MalformedRow {
+                    location,
+                    raw,
+                    issue,
+                }
+                .into()
+            }
+            err => err.into(),
+        })
     } else {
-        bail!("Error parseando el pdf de Degiro".to_string());
+        bail!(ImportError::UnrecognizedFormat(Some(String::from("PDF"))));
     }
 }
 
-fn read_ib_html(content: Vec<u8>) -> Result<(BalanceNotes, AccountNotes)> {
+fn read_ib_html(
+    content: Vec<u8>,
+    warnings: &mut Vec<ImportWarning>,
+) -> Result<(BalanceNotes, AccountNotes, CorporateActionNotes)> {
     if let Ok(data) = String::from_utf8(content) {
-        if let Ok(parser) = IBParser::new(&data, &IB_BROKER) {
+        if let Ok(mut parser) = IBParser::new(&data, &IB_BROKER) {
             let account_notes = parser.parse_account_notes()?;
             let balance_notes = parser.parse_balance_notes()?;
-            Ok((balance_notes, account_notes))
+            warnings.extend_from_slice(parser.warnings());
+            Ok((balance_notes, account_notes, vec![]))
         } else {
-            bail!("Unable to parse interactive brokers html");
+            bail!(ImportError::UnrecognizedFormat(Some(String::from("HTML"))));
         }
     } else {
-        bail!("Unable to get string from interactive brokers html content");
+        bail!(ImportError::Encoding(String::from(
+            "Interactive Brokers HTML"
+        )));
     }
 }
 
-fn read_ib_csv(content: Vec<u8>) -> Result<(BalanceNotes, AccountNotes)> {
+fn read_ib_csv(
+    content: Vec<u8>,
+    warnings: &mut Vec<ImportWarning>,
+) -> Result<(BalanceNotes, AccountNotes, CorporateActionNotes)> {
     if let Ok(data) = String::from_utf8(content) {
-        if let Ok(parser) = IBCSVParser::new(data, &IB_BROKER) {
+        if let Ok(mut parser) = IBCSVParser::new(data, &IB_BROKER) {
             let account_notes = parser.parse_account_notes()?;
             let balance_notes = parser.parse_balance_notes()?;
-            Ok((balance_notes, account_notes))
+            warnings.extend_from_slice(parser.warnings());
+            let corporate_actions = parser.parse_corporate_action_notes()?;
+            Ok((balance_notes, account_notes, corporate_actions))
         } else {
-            bail!("Unable to parse interactive brokers CSV");
+            bail!(ImportError::UnrecognizedFormat(None));
         }
     } else {
-        bail!("Unable to get string from interactive brokers csv content");
+        bail!(ImportError::UnrecognizedFormat(None));
     }
 }
 
-fn read_degiro_csv(content: Vec<u8>) -> Result<(BalanceNotes, AccountNotes)> {
+fn read_trading212_csv(
+    content: Vec<u8>,
+    warnings: &mut Vec<ImportWarning>,
+) -> Result<(BalanceNotes, AccountNotes, CorporateActionNotes)> {
     if let Ok(data) = String::from_utf8(content) {
-        let parser = DegiroCSVParser::new(data, &DEGIRO_BROKER);
+        let mut parser = Trading212Parser::new(data, &TRADING212_BROKER);
+        let account_notes = parser.parse_account_notes()?;
+        warnings.extend_from_slice(parser.warnings());
+        Ok((vec![], account_notes, vec![]))
+    } else {
+        bail!(ImportError::Encoding(String::from("Trading 212 CSV")));
+    }
+}
+
+fn read_scalable_csv(
+    content: Vec<u8>,
+) -> Result<(BalanceNotes, AccountNotes, CorporateActionNotes)> {
+    if let Ok(data) = String::from_utf8(content) {
+        let parser = ScalableCSVParser::new(data, &BAADER_BROKER);
+        let account_notes = parser.parse_account_notes()?;
+        Ok((vec![], account_notes, vec![]))
+    } else {
+        bail!(ImportError::Encoding(String::from("Scalable Capital CSV")));
+    }
+}
+
+fn read_xlsx_statement(
+    content: Vec<u8>,
+    warnings: &mut Vec<ImportWarning>,
+) -> Result<(BalanceNotes, AccountNotes, CorporateActionNotes)> {
+    let sheets = read_xlsx(&content)?;
+    if XtbParser::is_xtb_statement(&sheets) {
+        let mut parser = XtbParser::new(sheets, &XTB_BROKER);
+        let balance_notes = parser.parse_balance_notes()?;
+        let account_notes = parser.parse_account_notes()?;
+        warnings.extend_from_slice(parser.warnings());
+        return Ok((balance_notes, account_notes, vec![]));
+    }
+
+    let parser = EToroParser::new(sheets, &ETORO_BROKER);
+    let balance_notes = parser.parse_balance_notes()?;
+    let account_notes = parser.parse_account_notes()?;
+    Ok((balance_notes, account_notes, vec![]))
+}
+
+fn read_revolut_csv(
+    content: Vec<u8>,
+) -> Result<(BalanceNotes, AccountNotes, CorporateActionNotes)> {
+    if let Ok(data) = String::from_utf8(content) {
+        let parser = RevolutParser::new(data, &REVOLUT_BROKER);
+        let account_notes = parser.parse_account_notes()?;
+        Ok((vec![], account_notes, vec![]))
+    } else {
+        bail!(ImportError::Encoding(String::from("Revolut CSV")));
+    }
+}
+
+/// Text of a file, exports not in UTF-8 are usually in latin-1.
+pub(crate) fn decode_text(content: Vec<u8>) -> String {
+    match String::from_utf8(content) {
+        Ok(data) => data,
+        Err(err) => ISO_8859_15.decode(err.as_bytes()).0.into_owned(),
+    }
+}
+
+fn read_ofx_statement(
+    content: Vec<u8>,
+) -> Result<(BalanceNotes, AccountNotes, CorporateActionNotes)> {
+    let parser = OfxParser::new(&decode_text(content), &OFX_BROKER)?;
+    let balance_notes = parser.parse_balance_notes()?;
+    let account_notes = parser.parse_account_notes()?;
+    Ok((balance_notes, account_notes, vec![]))
+}
+
+fn read_generic_csv(
+    content: Vec<u8>,
+    mapping: &CsvMapping,
+) -> Result<(BalanceNotes, AccountNotes, CorporateActionNotes)> {
+    let parser = GenericCSVParser::new(decode_text(content), mapping)?;
+    if mapping.has_trades() {
+        Ok((vec![], parser.parse_account_notes()?, vec![]))
+    } else {
+        Ok((parser.parse_balance_notes()?, vec![], vec![]))
+    }
+}
+
+fn equity_plan_broker(provider: EquityPlanProvider) -> &'static Arc<BrokerInformation> {
+    match provider {
+        EquityPlanProvider::MorganStanley => &MORGAN_STANLEY_BROKER,
+        EquityPlanProvider::Schwab => &SCHWAB_BROKER,
+        EquityPlanProvider::Fidelity => &FIDELITY_BROKER,
+    }
+}
+
+fn read_equity_plan_csv(
+    content: Vec<u8>,
+    provider: EquityPlanProvider,
+) -> Result<(BalanceNotes, AccountNotes, CorporateActionNotes)> {
+    if let Ok(data) = String::from_utf8(content) {
+        let parser = EquityPlanParser::new(data, provider, equity_plan_broker(provider));
+        let balance_notes = parser.parse_balance_notes()?;
+        let account_notes = parser.parse_account_notes()?;
+        Ok((balance_notes, account_notes, vec![]))
+    } else {
+        bail!(ImportError::Encoding(String::from("equity plan CSV")));
+    }
+}
+
+fn read_degiro_csv(
+    content: Vec<u8>,
+    warnings: &mut Vec<ImportWarning>,
+) -> Result<(BalanceNotes, AccountNotes, CorporateActionNotes)> {
+    if let Ok(data) = String::from_utf8(content) {
+        let mut parser = DegiroCSVParser::new(data, &DEGIRO_BROKER);
         let balance_notes = parser.parse_csv()?;
-        Ok((balance_notes, vec![]))
+        warnings.extend_from_slice(parser.warnings());
+        Ok((balance_notes, vec![], vec![]))
     } else {
-        bail!("Unable to parse Degiro CSV");
+        bail!(ImportError::Encoding(String::from("Degiro CSV")));
     }
 }
 
+/// Reads the balance changes of a crypto exchange export and the warnings
+/// about its rows, `None` when the content is not from a supported exchange.
+fn read_crypto_csv(content: &[u8]) -> Option<Result<(CryptoNotes, Vec<ImportWarning>)>> {
+    let data = std::str::from_utf8(content).ok()?;
+    let notes = if data.starts_with(KRAKEN_HEADER_BEGIN) {
+        KrakenParser::new(data.to_string(), &KRAKEN_CUSTODIAN)
+            .parse_crypto_notes()
+            .map(|notes| (notes, vec![]))
+    } else if BinanceParser::is_binance_history(data) {
+        BinanceParser::new(data.to_string(), &BINANCE_CUSTODIAN)
+            .parse_crypto_notes()
+            .map(|notes| (notes, vec![]))
+    } else if data.contains(COINBASE_HEADER_FIELDS) {
+        let mut parser = CoinbaseParser::new(data.to_string(), &COINBASE_CUSTODIAN);
+        parser
+            .parse_crypto_notes()
+            .map(|notes| (notes, parser.warnings().to_vec()))
+    } else {
+        return None;
+    };
+
+    Some(notes)
+}
+
 pub(crate) fn date_to_usize(year: i32, month: u32, day: u32) -> usize {
     let date = NaiveDate::from_ymd_opt(year, month, day)
         .unwrap()
@@ -98,22 +428,352 @@ pub(crate) fn date_to_usize(year: i32, month: u32, day: u32) -> usize {
     date.parse::<usize>().unwrap_or(0)
 }
 
-fn transform_to_aeat720_records(notes: (BalanceNotes, AccountNotes)) -> Result<Aeat720Records> {
-    let mut result = vec![];
+pub(crate) fn year_end(year: usize) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year as i32, 12, 31)
+        .or_else(|| NaiveDate::from_ymd_opt(DEFAULT_YEAR as i32, 12, 31))
+        .unwrap()
+}
+
+/// Resolves the first acquisition date of a position held at the end of
+/// `year` from the ledger, or from the report notes when the ledger knows
+/// nothing about it.
+pub(crate) fn resolve_first_tx_date(
+    company: &CompanyInfo,
+    broker: &BrokerInformation,
+    account: &str,
+    account_notes: &[AccountNote],
+    ledger: &Ledger,
+    year: usize,
+) -> (usize, FirstTxDateSource) {
+    let (date, source) = ledger
+        .first_acquisition(company, broker, account, year_end(year))
+        .or_else(|| {
+            account_notes
+                .iter()
+                .find(|&x| x.company == *company && x.account == account)
+                .map(|x| (x.date, FirstTxDateSource::Report))
+        })
+        .unwrap_or((
+            NaiveDate::from_ymd_opt(DEFAULT_YEAR as i32, 1, 1).unwrap(),
+            FirstTxDateSource::Default,
+        ));
+
+    (date_to_usize(date.year(), date.month(), date.day()), source)
+}
+
+/// Whether `position` is of a security traded in `account_notes`.
+fn traded_position(account_notes: &AccountNotes, position: &LedgerPosition) -> bool {
+    // The ledger may know the ISIN of the notes naming the security
+    account_notes.iter().any(|note| {
+        (note.company == position.company
+            || note.company.isin.is_empty() && note.company.name == position.company.name)
+            && note.broker == position.broker
+            && note.account == position.account
+    })
+}
 
-    for note in notes.0.iter() {
-        let first_tx_date = {
-            let company = notes.1.iter().find(|&x| x.company == note.company);
-            match company {
-                Some(c) => c.date.format("%Y%m%d").to_string(),
-                None => NaiveDate::from_ymd_opt(DEFAULT_YEAR as i32, 1, 1)
-                    .unwrap()
-                    .format("%Y%m%d")
-                    .to_string(),
+fn ledger_to_aeat720_records(
+    account_notes: &AccountNotes,
+    ledger: &Ledger,
+    year: usize,
+) -> Aeat720Records {
+    ledger
+        .positions_at(year_end(year))
+        .into_iter()
+        .filter(|position| traded_position(account_notes, position))
+        .map(|position| {
+            let (first_tx_date, first_tx_date_source) = resolve_first_tx_date(
+                &position.company,
+                &position.broker,
+                &position.account,
+                account_notes,
+                ledger,
+                year,
+            );
+            Aeat720Record {
+                company: position.company,
+                quantity: position.quantity,
+                value_in_euro: Decimal::ZERO,
+                first_tx_date,
+                broker: position.broker,
+                percentage: Decimal::new(100, 0),
+                first_tx_date_source,
+                category: AssetCategory::default(),
+                account: position.account,
+                provenance: Provenance::default(),
             }
-            .parse::<usize>()
-            .unwrap_or(0)
-        };
+        })
+        .collect()
+}
+
+fn crypto_currency_name(currency: &str) -> String {
+    CRYPTO_CURRENCY_NAMES
+        .iter()
+        .find(|(id, _)| *id == currency)
+        .map_or(currency, |(_, name)| name)
+        .to_string()
+}
+
+/// Virtual currencies held at the end of `year` by each custodian, acquired
+/// when the balance last went from zero to positive, and the ones sold out
+/// during `year`, with the date of the sale as extinction date. The
+/// valuation at 31 December is not in the exports, so it is left for the
+/// user to fill.
+fn crypto_notes_to_aeat721_records(notes: &[CryptoNote], year: usize) -> Aeat721Records {
+    let mut notes: Vec<&CryptoNote> = notes
+        .iter()
+        .filter(|note| note.date <= year_end(year))
+        .collect();
+    notes.sort_by_key(|note| note.date);
+
+    let mut holdings: Vec<(&CryptoNote, Decimal, NaiveDate, Option<NaiveDate>)> = vec![];
+    for note in notes {
+        match holdings.iter_mut().find(|(holding, _, _, _)| {
+            holding.currency == note.currency && holding.custodian == note.custodian
+        }) {
+            Some((_, quantity, first_tx_date, extinction_date)) => {
+                if *quantity <= Decimal::ZERO {
+                    *first_tx_date = note.date;
+                    *extinction_date = None;
+                }
+                *quantity += note.quantity;
+                if *quantity <= Decimal::ZERO {
+                    *extinction_date = Some(note.date);
+                }
+            }
+            None => holdings.push((note, note.quantity, note.date, None)),
+        }
+    }
+
+    let to_usize = |date: NaiveDate| date_to_usize(date.year(), date.month(), date.day());
+    holdings
+        .into_iter()
+        .filter_map(|(note, quantity, first_tx_date, extinction_date)| {
+            let held = quantity.is_sign_positive() && !quantity.is_zero();
+            let extinction_date = match extinction_date {
+                _ if held => 0,
+                Some(date) if date.year() as usize == year => to_usize(date),
+                _ => return None,
+            };
+            Some(Aeat721Record {
+                name: crypto_currency_name(&note.currency),
+                currency: note.currency.clone(),
+                quantity: if held {
+                    quantity.normalize()
+                } else {
+                    Decimal::ZERO
+                },
+                value_in_euro: Decimal::ZERO,
+                first_tx_date: to_usize(first_tx_date),
+                extinction_date,
+                custodian: note.custodian.clone(),
+                percentage: Decimal::ONE_HUNDRED,
+            })
+        })
+        .collect()
+}
+
+/// Warning about the open positions left out of the AEAT 720 form, like
+/// derivatives.
+fn excluded_positions_warning(notes: &BalanceNotes) -> Option<ImportWarning> {
+    let excluded: Vec<String> = notes
+        .iter()
+        .filter(|note| !note.category.is_declarable())
+        .map(|note| {
+            format!(
+                "{} ({})",
+                note.company.name,
+                note.category.description().to_lowercase()
+            )
+        })
+        .collect();
+    if excluded.is_empty() {
+        return None;
+    }
+
+    Some(ImportWarning::ExcludedPositions(excluded))
+}
+
+/// Warning about the records imported without ISIN, which have to be
+/// completed by hand.
+fn missing_isin_warning(records: &Aeat720Records) -> Option<ImportWarning> {
+    let names: Vec<String> = records
+        .iter()
+        .filter(|record| record.company.isin.is_empty())
+        .map(|record| record.company.name.clone())
+        .collect();
+    if names.is_empty() {
+        return None;
+    }
+
+    Some(ImportWarning::MissingIsin(names))
+}
+
+/// Warning about the records without value at the end of the year, the
+/// reports with only trades do not give it.
+fn unvalued_positions_warning<'a>(
+    names: impl Iterator<Item = (&'a str, Decimal)>,
+) -> Option<ImportWarning> {
+    let names: Vec<String> = names
+        .filter(|(_, value)| value.is_zero())
+        .map(|(name, _)| name.to_string())
+        .collect();
+    if names.is_empty() {
+        return None;
+    }
+
+    Some(ImportWarning::UnvaluedPositions(names))
+}
+
+/// Warnings about the securities of `account_notes` sold beyond the trades
+/// history until the end of `year`, whose quantity rebuilt from the trades
+/// misses the shares bought before it.
+fn incomplete_history_warnings(
+    account_notes: &AccountNotes,
+    ledger: &Ledger,
+    year: usize,
+) -> Vec<ImportWarning> {
+    ledger
+        .incomplete_positions_at(year_end(year))
+        .into_iter()
+        .filter(|position| traded_position(account_notes, position))
+        .map(|position| ImportWarning::IncompleteHistory {
+            name: position.company.name,
+            quantity: position.unknown_quantity,
+        })
+        .collect()
+}
+
+/// Consolidated statements list together the open positions of every
+/// account, the trades history tells how they are split among accounts.
+fn split_by_account(note: &BalanceNote, positions: &[LedgerPosition]) -> BalanceNotes {
+    if !note.account.is_empty() {
+        return vec![note.clone()];
+    }
+    let same_security = |position: &&LedgerPosition| {
+        position.broker == note.broker
+            && if note.company.isin.is_empty() {
+                position.company.name == note.company.name
+            } else {
+                position.company.isin == note.company.isin
+            }
+    };
+    let accounts: Vec<&LedgerPosition> = positions.iter().filter(same_security).collect();
+    let total = accounts
+        .iter()
+        .fold(Decimal::ZERO, |acc, position| acc + position.quantity);
+
+    match accounts.as_slice() {
+        [position] => {
+            let mut note = note.clone();
+            note.account = position.account.clone();
+            vec![note]
+        }
+        [_, _, ..] if total == note.quantity => {
+            let mut result = vec![];
+            let mut remaining_value = note.value_in_euro;
+            for (index, position) in accounts.iter().enumerate() {
+                let mut split = note.clone();
+                split.account = position.account.clone();
+                split.quantity = position.quantity;
+                split.value_in_euro = if index + 1 == accounts.len() {
+                    remaining_value
+                } else {
+                    (note.value_in_euro * position.quantity / total).round_dp(2)
+                };
+                remaining_value -= split.value_in_euro;
+                result.push(split);
+            }
+            result
+        }
+        _ => vec![note.clone()],
+    }
+}
+
+/// Values in euros the positions of the reports that only give their value
+/// in the currency of the security, at the reference rate of the end of the
+/// year. The currencies without rate are warned about once.
+fn value_in_euro(
+    mut note: BalanceNote,
+    year: usize,
+    warnings: &mut Vec<ImportWarning>,
+) -> BalanceNote {
+    if !note.value_in_euro.is_zero() || note.currency.trim().is_empty() {
+        return note;
+    }
+    match fx::to_euro(note.quantity * note.price, &note.currency, year) {
+        Some(value) => note.value_in_euro = value,
+        None => {
+            let warning = ImportWarning::MissingExchangeRate {
+                currency: note.currency.trim().to_uppercase(),
+                year,
+            };
+            if !warnings.contains(&warning) {
+                warnings.push(warning);
+            }
+        }
+    }
+
+    note
+}
+
+fn transform_to_aeat720_records(
+    notes: (BalanceNotes, AccountNotes, CorporateActionNotes),
+    ledger: &mut Ledger,
+    year: usize,
+    warnings: &mut Vec<ImportWarning>,
+) -> Result<Aeat720Records> {
+    let mut result = vec![];
+    warnings.extend(excluded_positions_warning(&notes.0));
+
+    ledger.add_notes(&notes.1);
+    ledger.add_corporate_actions(&notes.2);
+    for note in notes.0.iter().filter(|note| !note.company.isin.is_empty()) {
+        ledger.assign_isin(&note.company, &note.broker);
+    }
+
+    if notes.0.is_empty() {
+        log::debug!("No open positions found, using trades history");
+        let records = ledger_to_aeat720_records(&notes.1, ledger, year);
+        warnings.extend(incomplete_history_warnings(&notes.1, ledger, year));
+        warnings.extend(missing_isin_warning(&records));
+        warnings.extend(unvalued_positions_warning(
+            records
+                .iter()
+                .map(|r| (r.company.name.as_str(), r.value_in_euro)),
+        ));
+        return Ok(records);
+    }
+
+    let positions = ledger.positions_at(year_end(year));
+    let balance_notes: BalanceNotes = notes
+        .0
+        .iter()
+        .filter(|note| note.category.is_declarable())
+        .flat_map(|note| split_by_account(note, &positions))
+        .map(|note| value_in_euro(note, year, warnings))
+        .collect();
+
+    for note in balance_notes.iter() {
+        let position =
+            ledger.position_at(&note.company, &note.broker, &note.account, year_end(year));
+        if let Some(position) = &position
+            && position.quantity != note.quantity
+        {
+            warnings.push(ImportWarning::QuantityMismatch {
+                name: note.company.name.clone(),
+                report: note.quantity,
+                trades: position.quantity,
+            });
+        }
+        let (first_tx_date, first_tx_date_source) = resolve_first_tx_date(
+            &note.company,
+            &note.broker,
+            &note.account,
+            &notes.1,
+            ledger,
+            year,
+        );
         result.push(Aeat720Record {
             company: note.company.clone(),
             quantity: note.quantity,
@@ -121,30 +781,417 @@ fn transform_to_aeat720_records(notes: (BalanceNotes, AccountNotes)) -> Result<A
             first_tx_date,
             broker: note.broker.clone(),
             percentage: Decimal::new(100, 0),
+            first_tx_date_source,
+            category: note.category,
+            account: note.account.clone(),
+            provenance: Provenance::default(),
         })
     }
+    warnings.extend(missing_isin_warning(&result));
+    warnings.extend(unvalued_positions_warning(
+        result
+            .iter()
+            .map(|r| (r.company.name.as_str(), r.value_in_euro)),
+    ));
 
     Ok(result)
 }
 
-pub(crate) fn file_importer(content: Vec<u8>) -> Result<Aeat720Records> {
+/// Places of `content` where the securities of `records` are first listed,
+/// by their ISIN or, lacking it, by their name. The pages of a PDF and the
+/// lines of the text formats are looked for, `None` for the rest.
+fn locate_records(content: &[u8], format: &str, records: &Aeat720Records) -> Vec<Option<Location>> {
+    let needle = |record: &Aeat720Record| {
+        if record.company.isin.is_empty() {
+            record.company.name.clone()
+        } else {
+            record.company.isin.clone()
+        }
+    };
+    match format {
+        "PDF" => {
+            let pages = read_pdf_pages(content).unwrap_or_default();
+            records
+                .iter()
+                .map(|record| {
+                    let needle = needle(record);
+                    pages
+                        .iter()
+                        .position(|page| page.contains(&needle))
+                        .map(|index| Location {
+                            page: Some(index + 1),
+                            ..Default::default()
+                        })
+                })
+                .collect()
+        }
+        "CSV" | "HTML" | "OFX" => {
+            let text = decode_text(content.to_vec());
+            records
+                .iter()
+                .map(|record| {
+                    let needle = needle(record);
+                    text.lines()
+                        .position(|line| line.contains(&needle))
+                        .map(|index| Location::line(index + 1))
+                })
+                .collect()
+        }
+        _ => vec![None; records.len()],
+    }
+}
+
+/// Sets where `records` come from, read from `content` in the detected
+/// `format`.
+fn set_provenance(records: &mut Aeat720Records, content: &[u8], format: &str) {
+    let imported_at = Utc::now();
+    let locations = locate_records(content, format, records);
+    for (record, location) in records.iter_mut().zip(locations) {
+        record.provenance = Provenance {
+            filename: String::new(),
+            parser: format!("{} {}", record.broker.name, format),
+            location,
+            imported_at: Some(imported_at),
+            edited: false,
+        };
+    }
+}
+
+fn import_aeat720_declarations(content: Vec<u8>, ledger: &mut Ledger) -> Result<Aeat720Records> {
+    let (data, _, had_errors) = ISO_8859_15.decode(&content);
+    if had_errors {
+        bail!(ImportError::Encoding(String::from("AEAT 720")));
+    }
+    let parser = Aeat720Parser::new(data.into_owned());
+    ledger.add_declarations(&parser.parse_declarations()?);
+
+    Ok(vec![])
+}
+
+/// Reads the securities of a broker report or previous AEAT 720 form,
+/// returning them with the name of the detected document format.
+fn securities_importer(
+    content: Vec<u8>,
+    ledger: &mut Ledger,
+    year: usize,
+    warnings: &mut Vec<ImportWarning>,
+) -> Result<(&'static str, Aeat720Records)> {
     let file_type = infer::get(&content);
+    let source = content.clone();
 
-    match file_type {
+    let (format, notes) = match file_type {
         Some(infer_type) => match infer_type.extension() {
-            "zip" => file_importer(read_zip(content)?),
-            "html" => transform_to_aeat720_records(read_ib_html(content)?),
-            "pdf" => transform_to_aeat720_records(read_degiro_pdf(content)?),
-            _ => {
-                bail!("{} Infer types not valid", infer_type);
+            "zip" => return securities_importer(read_zip(content)?, ledger, year, warnings),
+            "html" => ("HTML", read_ib_html(content, warnings)?),
+            "pdf" => ("PDF", read_broker_pdf(content)?),
+            "xlsx" => ("XLSX", read_xlsx_statement(content, warnings)?),
+            "xml" if OfxParser::is_ofx(&content) => ("OFX", read_ofx_statement(content)?),
+            extension => {
+                bail!(ImportError::UnrecognizedFormat(Some(extension.to_string())));
             }
         },
         None => {
-            if content.starts_with("Producto".as_bytes()) {
-                transform_to_aeat720_records(read_degiro_csv(content)?)
+            if content.starts_with(AEAT_720_FILE_BEGIN.as_bytes()) {
+                return Ok(("AEAT 720", import_aeat720_declarations(content, ledger)?));
+            } else if OfxParser::is_ofx(&content) {
+                ("OFX", read_ofx_statement(content)?)
+            } else if let Some(provider) = std::str::from_utf8(&content)
+                .ok()
+                .and_then(EquityPlanProvider::detect)
+            {
+                ("CSV", read_equity_plan_csv(content, provider)?)
+            } else if content.starts_with(REVOLUT_HEADER_BEGIN.as_bytes()) {
+                ("CSV", read_revolut_csv(content)?)
+            } else if content.starts_with(TRADING212_HEADER_BEGIN.as_bytes()) {
+                ("CSV", read_trading212_csv(content, warnings)?)
+            } else if content.starts_with(SCALABLE_HEADER_BEGIN.as_bytes()) {
+                ("CSV", read_scalable_csv(content)?)
+            } else if content.starts_with("Producto".as_bytes()) {
+                ("CSV", read_degiro_csv(content, warnings)?)
             } else {
-                transform_to_aeat720_records(read_ib_csv(content)?)
+                ("CSV", read_ib_csv(content, warnings)?)
             }
         }
+    };
+
+    let mut records = transform_to_aeat720_records(notes, ledger, year, warnings)?;
+    set_provenance(&mut records, &source, format);
+    Ok((format, records))
+}
+
+/// Imports a broker report, a previous AEAT 720 form or a crypto exchange
+/// export, returning the AEAT 720 and 721 records found and the warnings to
+/// show to the user about them.
+pub(crate) fn file_importer(
+    content: Vec<u8>,
+    ledger: &mut Ledger,
+    year: usize,
+) -> Result<ImportResult, ImportError> {
+    if let Some(crypto_notes) = read_crypto_csv(&content) {
+        let (crypto_notes, mut warnings) = crypto_notes?;
+        let crypto_records = crypto_notes_to_aeat721_records(&crypto_notes, year);
+        warnings.extend(unvalued_positions_warning(
+            crypto_records
+                .iter()
+                .filter(|r| !r.is_extinct())
+                .map(|r| (r.name.as_str(), r.value_in_euro)),
+        ));
+        return Ok(ImportResult {
+            format: String::from("CSV"),
+            crypto_records,
+            warnings,
+            ..Default::default()
+        });
+    }
+
+    let mut warnings = vec![];
+    let (format, records) = securities_importer(content, ledger, year, &mut warnings)?;
+    Ok(ImportResult {
+        format: format.to_string(),
+        records,
+        crypto_records: vec![],
+        warnings,
+    })
+}
+
+/// Imports the CSV of a broker without parser, reading the columns chosen
+/// by the user.
+pub(crate) fn generic_csv_importer(
+    content: Vec<u8>,
+    mapping: &CsvMapping,
+    ledger: &mut Ledger,
+    year: usize,
+) -> Result<ImportResult, ImportError> {
+    let mut warnings = vec![];
+    let mut records = transform_to_aeat720_records(
+        read_generic_csv(content.clone(), mapping)?,
+        ledger,
+        year,
+        &mut warnings,
+    )?;
+    set_provenance(&mut records, &content, "CSV");
+    Ok(ImportResult {
+        format: String::from("CSV"),
+        records,
+        crypto_records: vec![],
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::BrokerOperation;
+
+    #[test]
+    fn test_set_provenance() {
+        let record = |name: &str, isin: &str| Aeat720Record {
+            company: CompanyInfo {
+                name: name.to_string(),
+                isin: isin.to_string(),
+            },
+            quantity: Decimal::ONE,
+            value_in_euro: Decimal::TEN,
+            first_tx_date: 20230101,
+            broker: IB_BROKER.clone(),
+            percentage: Decimal::ONE_HUNDRED,
+            first_tx_date_source: FirstTxDateSource::Report,
+            category: AssetCategory::Stock,
+            account: String::new(),
+            provenance: Provenance::default(),
+        };
+        let content = "Symbol,Name,ISIN\nADYEN,ADYEN NV,NL0012969182\nACME,ACME CORP,\n";
+        let mut records = vec![
+            record("ADYEN NV", "NL0012969182"),
+            record("ACME CORP", ""),
+            record("MISSING", "US0000000001"),
+        ];
+
+        set_provenance(&mut records, content.as_bytes(), "CSV");
+        assert_eq!(records[0].provenance.parser, "Interactive Brokers CSV");
+        assert_eq!(records[0].provenance.location, Some(Location::line(2)));
+        assert_eq!(records[1].provenance.location, Some(Location::line(3)));
+        assert_eq!(records[2].provenance.location, None);
+        assert!(records[0].provenance.imported_at.is_some());
+        assert!(!records[0].provenance.is_manual());
+        assert!(!records[0].provenance.edited);
+    }
+
+    #[test]
+    fn test_trades_selling_shares_bought_before_the_history() {
+        let company = CompanyInfo {
+            name: String::from("ADYEN NV"),
+            isin: String::from("NL0012969182"),
+        };
+        let note = |date: (i32, u32, u32), operation: BrokerOperation, quantity: i64| {
+            AccountNote::new(
+                NaiveDate::from_ymd_opt(date.0, date.1, date.2).unwrap(),
+                company.clone(),
+                operation,
+                Decimal::new(quantity, 0),
+                Decimal::ONE,
+                Decimal::new(quantity, 0),
+                Decimal::ZERO,
+                &IB_BROKER,
+            )
+        };
+        let notes = vec![
+            note((2023, 2, 1), BrokerOperation::Sell, 5),
+            note((2023, 6, 1), BrokerOperation::Buy, 3),
+        ];
+        let mut ledger = Ledger::new();
+        let mut warnings = vec![];
+
+        let records =
+            transform_to_aeat720_records((vec![], notes, vec![]), &mut ledger, 2023, &mut warnings)
+                .unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].quantity, Decimal::new(3, 0));
+        assert_eq!(
+            warnings[0],
+            ImportWarning::IncompleteHistory {
+                name: String::from("ADYEN NV"),
+                quantity: Decimal::new(5, 0),
+            }
+        );
+    }
+
+    #[test]
+    fn test_import_ofx() {
+        const OFX: &str = "OFXHEADER:100
+<OFX>
+<SIGNONMSGSRSV1><SONRS><FI><ORG>Example Securities<FID>1</FI></SONRS></SIGNONMSGSRSV1>
+<INVSTMTMSGSRSV1><INVSTMTTRNRS><INVSTMTRS>
+<DTASOF>20231231<CURDEF>GBP
+<INVPOSLIST>
+<POSSTOCK><INVPOS>
+<SECID><UNIQUEID>037833100<UNIQUEIDTYPE>CUSIP</SECID>
+<UNITS>10<UNITPRICE>150<MKTVAL>1500
+</INVPOS></POSSTOCK>
+<POSSTOCK><INVPOS>
+<SECID><UNIQUEID>G0403H108<UNIQUEIDTYPE>CUSIP</SECID>
+<UNITS>2<UNITPRICE>300<MKTVAL>600
+</INVPOS></POSSTOCK>
+</INVPOSLIST>
+</INVSTMTRS></INVSTMTTRNRS></INVSTMTMSGSRSV1>
+<SECLISTMSGSRSV1><SECLIST>
+<STOCKINFO><SECINFO>
+<SECID><UNIQUEID>037833100<UNIQUEIDTYPE>CUSIP</SECID>
+<SECNAME>APPLE INC<TICKER>AAPL
+</SECINFO></STOCKINFO>
+<STOCKINFO><SECINFO>
+<SECID><UNIQUEID>G0403H108<UNIQUEIDTYPE>CUSIP</SECID>
+<SECNAME>AON PLC<TICKER>AON
+</SECINFO></STOCKINFO>
+</SECLIST></SECLISTMSGSRSV1>
+</OFX>
+";
+
+        let mut result = file_importer(OFX.as_bytes().to_vec(), &mut Ledger::new(), 2023).unwrap();
+        assert_eq!(result.records.len(), 2);
+        assert_eq!(result.records[0].company.isin, "US0378331005");
+        assert_eq!(result.records[1].company.isin, "");
+        assert!(
+            result
+                .warnings
+                .contains(&ImportWarning::MissingIsin(vec![String::from("AON PLC")]))
+        );
+        assert_eq!(result.records[0].value_in_euro, Decimal::new(1726_02, 2));
+        assert_eq!(result.records[1].value_in_euro, Decimal::new(690_41, 2));
+
+        let unvalued = file_importer(OFX.as_bytes().to_vec(), &mut Ledger::new(), 2018).unwrap();
+        assert_eq!(
+            unvalued.warnings,
+            vec![
+                ImportWarning::MissingExchangeRate {
+                    currency: String::from("GBP"),
+                    year: 2018
+                },
+                ImportWarning::MissingIsin(vec![String::from("AON PLC")]),
+                ImportWarning::UnvaluedPositions(vec![
+                    String::from("APPLE INC"),
+                    String::from("AON PLC")
+                ]),
+            ]
+        );
+
+        assert_eq!(
+            result.brokers_without_country(),
+            vec![String::from("Example Securities")]
+        );
+        result.set_broker_country("Example Securities", "gb");
+        assert_eq!(result.records[0].broker.country_code, "GB");
+        assert!(result.brokers_without_country().is_empty());
+    }
+
+    #[test]
+    fn test_crypto_notes_to_aeat721_records() {
+        let note = |(year, month, day), currency: &str, quantity| {
+            CryptoNote::new(
+                NaiveDate::from_ymd_opt(year, month, day).unwrap(),
+                currency.to_string(),
+                quantity,
+                &KRAKEN_CUSTODIAN,
+            )
+        };
+        let notes = vec![
+            note((2022, 5, 1), "ETH", Decimal::ONE),
+            note((2022, 6, 1), "ETH", Decimal::NEGATIVE_ONE),
+            note((2023, 3, 1), "BTC", Decimal::new(-5, 3)),
+            note((2023, 1, 12), "BTC", Decimal::new(2, 2)),
+            note((2023, 7, 1), "ETH", Decimal::new(5, 1)),
+            note((2023, 8, 1), "SOL", Decimal::TEN),
+            note((2023, 9, 1), "SOL", -Decimal::TEN),
+            note((2024, 1, 5), "BTC", Decimal::ONE),
+            note((2021, 3, 1), "ADA", Decimal::TEN),
+            note((2021, 4, 1), "DOGE", Decimal::TEN),
+            note((2021, 5, 1), "DOGE", -Decimal::TEN),
+        ];
+
+        assert_eq!(
+            crypto_notes_to_aeat721_records(&notes, 2023),
+            vec![
+                Aeat721Record {
+                    name: String::from("Cardano"),
+                    currency: String::from("ADA"),
+                    quantity: Decimal::TEN,
+                    value_in_euro: Decimal::ZERO,
+                    first_tx_date: 20210301,
+                    extinction_date: 0,
+                    custodian: KRAKEN_CUSTODIAN.clone(),
+                    percentage: Decimal::ONE_HUNDRED,
+                },
+                Aeat721Record {
+                    name: String::from("Ethereum"),
+                    currency: String::from("ETH"),
+                    quantity: Decimal::new(5, 1),
+                    value_in_euro: Decimal::ZERO,
+                    first_tx_date: 20230701,
+                    extinction_date: 0,
+                    custodian: KRAKEN_CUSTODIAN.clone(),
+                    percentage: Decimal::ONE_HUNDRED,
+                },
+                Aeat721Record {
+                    name: String::from("Bitcoin"),
+                    currency: String::from("BTC"),
+                    quantity: Decimal::new(15, 3),
+                    value_in_euro: Decimal::ZERO,
+                    first_tx_date: 20230112,
+                    extinction_date: 0,
+                    custodian: KRAKEN_CUSTODIAN.clone(),
+                    percentage: Decimal::ONE_HUNDRED,
+                },
+                Aeat721Record {
+                    name: String::from("Solana"),
+                    currency: String::from("SOL"),
+                    quantity: Decimal::ZERO,
+                    value_in_euro: Decimal::ZERO,
+                    first_tx_date: 20230801,
+                    extinction_date: 20230901,
+                    custodian: KRAKEN_CUSTODIAN.clone(),
+                    percentage: Decimal::ONE_HUNDRED,
+                },
+            ]
+        );
     }
 }
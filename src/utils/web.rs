@@ -1,54 +1,160 @@
-use crate::{data::Aeat720Information, reports::aeat_720::Aeat720Report};
+use crate::{
+    data::{Aeat720Information, Aeat721Information},
+    project::Project,
+    reports::{aeat_720::Aeat720Report, aeat_721::Aeat721Report},
+};
 
-use anyhow::{Result, bail};
+use anyhow::{Result, anyhow, bail};
 use js_sys::{Array, Uint8Array};
+use serde::{Serialize, de::DeserializeOwned};
 use wasm_bindgen::JsValue;
-use web_sys::{Blob, BlobPropertyBag, Url};
+use web_sys::{Blob, BlobPropertyBag, Storage, Url};
+
+fn local_storage() -> Result<Storage> {
+    gloo_utils::window()
+        .local_storage()
+        .map_err(|err| anyhow!("Error accessing local storage: {:?}", err))?
+        .ok_or_else(|| anyhow!("Local storage not available"))
+}
+
+pub fn store_text(key: &str, value: &str) -> Result<()> {
+    if let Err(err) = local_storage()?.set_item(key, value) {
+        log::error!("Error storing {}: {:?}", key, err);
+        bail!("Error storing {} in local storage", key);
+    }
+
+    Ok(())
+}
+
+pub fn store_item<T: Serialize>(key: &str, value: &T) -> Result<()> {
+    store_text(key, &serde_json::to_string(value)?)
+}
+
+/// Text saved under `key`, `None` when nothing was saved.
+pub fn load_text(key: &str) -> Result<Option<String>> {
+    local_storage()?
+        .get_item(key)
+        .map_err(|err| anyhow!("Error loading {} from local storage: {:?}", key, err))
+}
+
+/// Item saved under `key`, failing when it can not be read so the caller
+/// does not overwrite it.
+pub fn try_load_item<T: DeserializeOwned>(key: &str) -> Result<Option<T>> {
+    match load_text(key)? {
+        Some(value) => serde_json::from_str(&value)
+            .map(Some)
+            .map_err(|err| anyhow!("Error loading {} from local storage: {}", key, err)),
+        None => Ok(None),
+    }
+}
+
+pub fn load_item<T: DeserializeOwned>(key: &str) -> Option<T> {
+    match try_load_item(key) {
+        Ok(item) => item,
+        Err(err) => {
+            log::error!("{}", err);
+            None
+        }
+    }
+}
+
+pub fn delete_item(key: &str) -> Result<()> {
+    if let Err(err) = local_storage()?.remove_item(key) {
+        log::error!("Error deleting {}: {:?}", key, err);
+        bail!("Error deleting {} from local storage", key);
+    }
+
+    Ok(())
+}
 
 pub fn delete_path(path: String) -> Result<()> {
     if let Err(err) = Url::revoke_object_url(&path) {
-        log::error!("Error deleting old aeat 720 form: {:?}", err);
-        bail!("Error deleting old AEAT 720 form");
+        log::error!("Error deleting old form: {:?}", err);
+        bail!("Error deleting old form");
     }
 
     Ok(())
 }
 
-pub fn generate_720(info: &Aeat720Information) -> Result<String> {
-    let result;
-    let aeat720report = match Aeat720Report::new(info) {
+/// Object URL of a generated form, ready to be downloaded.
+fn form_url(form: &[u8]) -> Result<String> {
+    let blob_properties = BlobPropertyBag::new();
+    blob_properties.set_type("application/octet-stream");
+    let form_array = Array::new_with_length(1);
+    form_array.set(0, JsValue::from(Uint8Array::from(form)));
+
+    let blob =
+        Blob::new_with_u8_array_sequence_and_options(&JsValue::from(form_array), &blob_properties);
+    match blob {
+        Ok(blob_data) => Url::create_object_url_with_blob(&blob_data)
+            .map_err(|err| anyhow!("Unable to create form url: {:?}", err)),
+        Err(err) => {
+            log::error!("Unable to generate form blob: {:?}", err);
+            bail!("Unable to generate form blob");
+        }
+    }
+}
+
+/// Object URL of the AEAT 720 form, records valued at zero are only
+/// accepted when `accept_unvalued`.
+pub fn generate_720(info: &Aeat720Information, accept_unvalued: bool) -> Result<String> {
+    let report = if accept_unvalued {
+        Aeat720Report::new_accepting_unvalued(info)
+    } else {
+        Aeat720Report::new(info)
+    };
+    let aeat720report = match report {
         Ok(report) => report,
         Err(err) => {
             log::error!("Unable to generate Aeat720 report: {}", err);
-            bail!("unable to create AEAT 720 report");
+            return Err(err);
         }
     };
     match aeat720report.generate() {
-        Ok(aeat720_form) => {
-            let blob_properties = BlobPropertyBag::new();
-            blob_properties.set_type("application/octet-stream");
-            let aeat720_array = Array::new_with_length(1);
-            aeat720_array.set(0, JsValue::from(Uint8Array::from(&aeat720_form[..])));
-
-            let blob = Blob::new_with_u8_array_sequence_and_options(
-                &JsValue::from(aeat720_array),
-                &blob_properties,
-            );
-            match blob {
-                Ok(blob_data) => {
-                    result = Url::create_object_url_with_blob(&blob_data).unwrap();
-                }
-                Err(err) => {
-                    log::error!("Unable to generate aeat 720 form: {:?}", err);
-                    bail!("Unable to generate AEAT 720 form blob");
-                }
-            }
-        }
+        Ok(aeat720_form) => form_url(&aeat720_form),
         Err(err) => {
             log::error!("Unable to generate Aeat 720 report: {}", err);
-            bail!("Unable to generate AEAT 720 from data")
+            Err(err)
         }
     }
+}
 
-    Ok(result)
+/// Object URL of the AEAT 721 form, records valued at zero are only
+/// accepted when `accept_unvalued`.
+pub fn generate_721(info: &Aeat721Information, accept_unvalued: bool) -> Result<String> {
+    let report = if accept_unvalued {
+        Aeat721Report::new_accepting_unvalued(info)
+    } else {
+        Aeat721Report::new(info)
+    };
+    let aeat721report = match report {
+        Ok(report) => report,
+        Err(err) => {
+            log::error!("Unable to generate Aeat721 report: {}", err);
+            return Err(err);
+        }
+    };
+    match aeat721report.generate() {
+        Ok(aeat721_form) => form_url(&aeat721_form),
+        Err(err) => {
+            log::error!("Unable to generate Aeat 721 report: {}", err);
+            Err(err)
+        }
+    }
+}
+
+/// Object URL of the project file, encrypted unless `password` is empty.
+pub fn generate_project(project: &Project, password: &str) -> Result<String> {
+    let content = if password.is_empty() {
+        project.to_json()
+    } else {
+        project.to_encrypted_json(password)
+    };
+    match content {
+        Ok(content) => form_url(&content),
+        Err(err) => {
+            log::error!("Unable to generate project file: {}", err);
+            bail!("Unable to generate project file")
+        }
+    }
 }
@@ -0,0 +1,301 @@
+use anyhow::{Context, Result, bail};
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use xml::reader::{EventReader, XmlEvent};
+use zip::ZipArchive;
+
+use crate::error::{ImportError, Location, RowIssue};
+
+const WORKBOOK_PATH: &str = "xl/workbook.xml";
+const WORKBOOK_RELS_PATH: &str = "xl/_rels/workbook.xml.rels";
+const SHARED_STRINGS_PATH: &str = "xl/sharedStrings.xml";
+/// Columns of a worksheet, the last one being XFD.
+const MAX_COLUMNS: usize = 16384;
+
+/// Worksheet of a XLSX workbook with every cell read as text, empty cells
+/// included so each value keeps its column index.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sheet {
+    pub name: String,
+    pub rows: Vec<Vec<String>>,
+}
+
+fn read_entry(archive: &mut ZipArchive<Cursor<&[u8]>>, path: &str) -> Result<Option<String>> {
+    let mut file = match archive.by_name(path) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+
+    Ok(Some(contents))
+}
+
+fn attribute(attributes: &[xml::attribute::OwnedAttribute], name: &str) -> Option<String> {
+    attributes
+        .iter()
+        .find(|attribute| attribute.name.local_name == name)
+        .map(|attribute| attribute.value.clone())
+}
+
+/// Sheet names and their relationship ids, in workbook order.
+fn read_workbook(content: &str) -> Result<Vec<(String, String)>> {
+    let mut sheets = vec![];
+    for event in EventReader::new(content.as_bytes()) {
+        if let XmlEvent::StartElement {
+            name, attributes, ..
+        } = event?
+            && name.local_name == "sheet"
+        {
+            let sheet_name = attribute(&attributes, "name").context("Sheet without name")?;
+            let id = attributes
+                .iter()
+                .find(|attribute| {
+                    attribute.name.local_name == "id" && attribute.name.prefix.is_some()
+                })
+                .map(|attribute| attribute.value.clone())
+                .context("Sheet without relationship id")?;
+            sheets.push((sheet_name, id));
+        }
+    }
+
+    Ok(sheets)
+}
+
+fn read_relationships(content: &str) -> Result<HashMap<String, String>> {
+    let mut relationships = HashMap::new();
+    for event in EventReader::new(content.as_bytes()) {
+        if let XmlEvent::StartElement {
+            name, attributes, ..
+        } = event?
+            && name.local_name == "Relationship"
+            && let (Some(id), Some(target)) = (
+                attribute(&attributes, "Id"),
+                attribute(&attributes, "Target"),
+            )
+        {
+            let target = match target.strip_prefix('/') {
+                Some(absolute) => absolute.to_string(),
+                None => format!("xl/{}", target),
+            };
+            relationships.insert(id, target);
+        }
+    }
+
+    Ok(relationships)
+}
+
+fn read_shared_strings(content: &str) -> Result<Vec<String>> {
+    let mut strings = vec![];
+    let mut current = String::new();
+    let mut in_text = false;
+    for event in EventReader::new(content.as_bytes()) {
+        match event? {
+            XmlEvent::StartElement { name, .. } if name.local_name == "si" => current.clear(),
+            XmlEvent::StartElement { name, .. } if name.local_name == "t" => in_text = true,
+            XmlEvent::EndElement { name } if name.local_name == "t" => in_text = false,
+            XmlEvent::EndElement { name } if name.local_name == "si" => {
+                strings.push(current.clone())
+            }
+            XmlEvent::Characters(text) | XmlEvent::Whitespace(text) if in_text => {
+                current.push_str(&text)
+            }
+            _ => {}
+        }
+    }
+
+    Ok(strings)
+}
+
+/// Zero based column index of a cell reference like `AB12`, `None` when it
+/// has no column. References beyond the last column of a worksheet are
+/// malformed.
+fn column_index(reference: &str) -> Result<Option<usize>> {
+    let letters: Vec<_> = reference
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect();
+    if letters.is_empty() {
+        return Ok(None);
+    }
+
+    match letters.iter().try_fold(0, |acc, c| {
+        let column = acc * 26 + (c.to_ascii_uppercase() as usize - 'A' as usize + 1);
+        (column <= MAX_COLUMNS).then_some(column)
+    }) {
+        Some(column) => Ok(Some(column - 1)),
+        None => bail!(ImportError::MalformedRow {
+            location: Location {
+                line: reference[letters.len()..].parse().ok(),
+                ..Default::default()
+            },
+            raw: reference.to_string(),
+            issue: RowIssue::UnexpectedLayout,
+        }),
+    }
+}
+
+fn read_sheet(content: &str, shared_strings: &[String]) -> Result<Vec<Vec<String>>> {
+    let mut rows = vec![];
+    let mut row: Vec<String> = vec![];
+    let mut cell_type = String::new();
+    let mut cell_column = 0;
+    let mut value = String::new();
+    let mut in_value = false;
+
+    for event in EventReader::new(content.as_bytes()) {
+        match event? {
+            XmlEvent::StartElement { name, .. } if name.local_name == "row" => row.clear(),
+            XmlEvent::EndElement { name } if name.local_name == "row" => rows.push(row.clone()),
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } if name.local_name == "c" => {
+                cell_type = attribute(&attributes, "t").unwrap_or_default();
+                cell_column = match attribute(&attributes, "r") {
+                    Some(reference) => column_index(&reference)?,
+                    None => None,
+                }
+                .unwrap_or(row.len());
+                value.clear();
+            }
+            XmlEvent::StartElement { name, .. }
+                if name.local_name == "v" || name.local_name == "t" =>
+            {
+                in_value = true
+            }
+            XmlEvent::EndElement { name } if name.local_name == "v" || name.local_name == "t" => {
+                in_value = false
+            }
+            XmlEvent::Characters(text) | XmlEvent::Whitespace(text) if in_value => {
+                value.push_str(&text)
+            }
+            XmlEvent::EndElement { name } if name.local_name == "c" => {
+                let text = if cell_type == "s" {
+                    let index: usize = value.trim().parse().context("Invalid shared string")?;
+                    shared_strings
+                        .get(index)
+                        .cloned()
+                        .context("Shared string not found")?
+                } else {
+                    value.clone()
+                };
+                if row.len() <= cell_column {
+                    row.resize(cell_column + 1, String::new());
+                }
+                row[cell_column] = text;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(rows)
+}
+
+/// Date of a cell holding a spreadsheet serial number, the days elapsed
+/// since 1899-12-30 with the time of the day as fraction.
+pub fn serial_date(value: &str) -> Option<NaiveDate> {
+    let days = value.split('.').next()?.parse::<i64>().ok()?;
+    NaiveDate::from_ymd_opt(1899, 12, 30)?.checked_add_signed(chrono::Duration::days(days))
+}
+
+/// Reads every worksheet of a XLSX workbook.
+pub fn read_xlsx(data: &[u8]) -> Result<Vec<Sheet>> {
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+
+    let Some(workbook) = read_entry(&mut archive, WORKBOOK_PATH)? else {
+        bail!("Not a XLSX workbook");
+    };
+    let relationships = match read_entry(&mut archive, WORKBOOK_RELS_PATH)? {
+        Some(content) => read_relationships(&content)?,
+        None => bail!("XLSX workbook without relationships"),
+    };
+    let shared_strings = match read_entry(&mut archive, SHARED_STRINGS_PATH)? {
+        Some(content) => read_shared_strings(&content)?,
+        None => vec![],
+    };
+
+    let mut sheets = vec![];
+    for (name, id) in read_workbook(&workbook)? {
+        let path = relationships
+            .get(&id)
+            .with_context(|| format!("Sheet {} not found", name))?;
+        let content =
+            read_entry(&mut archive, path)?.with_context(|| format!("Sheet {} not found", name))?;
+        let rows = read_sheet(&content, &shared_strings).map_err(|err| {
+            match err.downcast::<ImportError>() {
+                Ok(ImportError::MalformedRow {
+                    location,
+                    raw,
+                    issue,
+                }) => ImportError::MalformedRow {
+                    location: location.in_section(&name),
+                    raw,
+                    issue,
+                }
+                .into(),
+                Ok(err) => err.into(),
+                Err(err) => err,
+            }
+        })?;
+        sheets.push(Sheet { name, rows });
+    }
+
+    Ok(sheets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_index() {
+        assert_eq!(column_index("A1").unwrap(), Some(0));
+        assert_eq!(column_index("C12").unwrap(), Some(2));
+        assert_eq!(column_index("AB3").unwrap(), Some(27));
+        assert_eq!(column_index("XFD1").unwrap(), Some(16383));
+        assert_eq!(column_index("12").unwrap(), None);
+        assert_eq!(
+            column_index("ZZZZZZZZ1")
+                .unwrap_err()
+                .downcast::<ImportError>()
+                .unwrap(),
+            ImportError::MalformedRow {
+                location: Location::line(1),
+                raw: String::from("ZZZZZZZZ1"),
+                issue: RowIssue::UnexpectedLayout,
+            }
+        );
+        assert!(column_index("XFE1").is_err());
+    }
+
+    #[test]
+    fn test_serial_date() {
+        assert_eq!(
+            serial_date("44936.6458333333"),
+            NaiveDate::from_ymd_opt(2023, 1, 10)
+        );
+        assert_eq!(serial_date("10/01/2023"), None);
+    }
+
+    #[test]
+    fn test_read_sheet() {
+        const SHEET: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">
+<sheetData>
+<row r="1"><c r="A1" t="s"><v>0</v></c><c r="C1" t="s"><v>1</v></c></row>
+<row r="2"><c r="A2"><v>12.5</v></c><c r="B2" t="inlineStr"><is><t>inline</t></is></c></row>
+</sheetData>
+</worksheet>"#;
+        let shared_strings = vec![String::from("Date"), String::from("Amount")];
+
+        assert_eq!(
+            read_sheet(SHEET, &shared_strings).unwrap(),
+            vec![
+                vec![String::from("Date"), String::new(), String::from("Amount")],
+                vec![String::from("12.5"), String::from("inline")],
+            ]
+        );
+    }
+}
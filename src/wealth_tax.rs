@@ -0,0 +1,368 @@
+use std::{collections::HashMap, sync::Arc};
+
+use dominator::{Dom, clone, events, html, with_node};
+use encoding_rs::ISO_8859_15;
+use futures_signals::{map_ref, signal::Mutable};
+use gloo_file::{Blob, futures::read_as_bytes};
+use rust_decimal::Decimal;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+
+use crate::{
+    crypto_table::CryptoTable,
+    css::{
+        FLEX_CONTAINER_CLASS, FLEX_CONTAINER_ITEM_20_CLASS, TABLE_CAPTION, TABLE_HEADER, TABLE_ROW,
+    },
+    data::{Aeat720Record, DEFAULT_LOCALE},
+    error::ImportError,
+    parsers::{generic_csv::DecimalSeparator, q4_prices::Q4PricesParser},
+    reports::aeat_714::{Aeat714Category, Aeat714Deposits, Aeat714Report},
+    table::Table,
+    utils::{
+        decimal::{decimal_to_str_locale, transform_i18n_es_str},
+        web,
+    },
+};
+
+const Q4_PRICES_STORAGE_KEY: &str = "burocratin.q4_prices";
+
+/// Summary of the imported holdings for the wealth tax (AEAT 714 form).
+pub struct WealthTaxViewer {
+    table: Arc<Table>,
+    crypto_table: Arc<CryptoTable>,
+    securities: Mutable<Vec<Aeat720Record>>,
+    q4_prices: Mutable<HashMap<String, Decimal>>,
+    /// Decimal separator of the prices list to import.
+    decimal_separator: Mutable<DecimalSeparator>,
+    deposits: Mutable<Aeat714Deposits>,
+    report: Mutable<Option<Aeat714Report>>,
+    error: Mutable<Option<String>>,
+}
+
+impl WealthTaxViewer {
+    pub fn new(table: Arc<Table>, crypto_table: Arc<CryptoTable>) -> Arc<Self> {
+        Arc::new(Self {
+            table,
+            crypto_table,
+            securities: Mutable::new(vec![]),
+            q4_prices: Mutable::new(web::load_item(Q4_PRICES_STORAGE_KEY).unwrap_or_default()),
+            decimal_separator: Mutable::new(DecimalSeparator::Comma),
+            deposits: Mutable::new(Aeat714Deposits::default()),
+            report: Mutable::new(None),
+            error: Mutable::new(None),
+        })
+    }
+
+    fn parse_amount(value: &str) -> Option<Decimal> {
+        transform_i18n_es_str(value.trim()).parse::<Decimal>().ok()
+    }
+
+    fn update_report(this: &Arc<Self>) {
+        let report = Aeat714Report::new(
+            &this.securities.lock_ref(),
+            &this.crypto_table.get_records(),
+            &this.q4_prices.lock_ref(),
+            &this.deposits.lock_ref(),
+        );
+        this.report.set(Some(report));
+    }
+
+    fn set_q4_prices(this: &Arc<Self>, prices: HashMap<String, Decimal>) {
+        if let Err(err) = web::store_item(Q4_PRICES_STORAGE_KEY, &prices) {
+            log::error!("Unable to save fourth quarter average prices: {}", err);
+        }
+        this.q4_prices.set(prices);
+        Self::update_report(this);
+    }
+
+    fn import_q4_prices(this: &Arc<Self>, content: Vec<u8>) {
+        let content = match String::from_utf8(content) {
+            Ok(content) => content,
+            Err(err) => ISO_8859_15.decode(err.as_bytes()).0.into_owned(),
+        };
+        match Q4PricesParser::new(content, this.decimal_separator.get()).parse_prices() {
+            Ok(prices) => {
+                let mut q4_prices = this.q4_prices.get_cloned();
+                q4_prices.extend(prices);
+                this.error.set(None);
+                Self::set_q4_prices(this, q4_prices);
+            }
+            Err(err) => this.error.set(Some(ImportError::from(err).localized())),
+        }
+    }
+
+    fn render_calculate_button(this: &Arc<Self>) -> Dom {
+        html!("span", {
+          .child(html!("input" => HtmlInputElement, {
+            .attr("type", "button")
+            .attr("value", "Calcular resumen modelo 714")
+            .with_node!(_element => {
+              .event(clone!(this => move |_: events::Click| {
+                this.securities.set(this.table.get_records());
+                Self::update_report(&this);
+              }))
+            })
+          }))
+        })
+    }
+
+    fn render_import_button(this: &Arc<Self>) -> Dom {
+        html!("span", {
+          .child(html!("select" => HtmlSelectElement, {
+            .attr("title", "Separador decimal de las cotizaciones")
+            .child(html!("option", {
+              .attr("value", "comma")
+              .attr("selected", "")
+              .text("Coma (1.234,56)")
+            }))
+            .child(html!("option", {
+              .attr("value", "point")
+              .text("Punto (1,234.56)")
+            }))
+            .with_node!(element => {
+              .event(clone!(this => move |_: events::Change| {
+                this.decimal_separator.set(match element.value().as_str() {
+                  "point" => DecimalSeparator::Point,
+                  _ => DecimalSeparator::Comma,
+                });
+              }))
+            })
+          }))
+          .child(
+            html!("button", {
+              .child(
+                html!("label", {
+                  .style("cursor", "pointer")
+                  .attr("for", "import_q4_prices")
+                  .text("Importar cotizaciones medias 4º trimestre")
+              })
+            )})
+          )
+          .child(
+            html!("input" => HtmlInputElement, {
+              .attr("id", "import_q4_prices")
+              .attr("alt", "Botón para importar las cotizaciones medias del cuarto trimestre")
+              .attr("accept", "text/csv,text/plain,.csv,.txt")
+              .attr("type", "file")
+              .style("display", "none")
+              .with_node!(element => {
+                .event(clone!(this => move |_: events::Change| {
+                  let Some(file_data) = element.files().and_then(|files| files.get(0)) else {
+                    this.error.set(Some("Error obteniendo fichero".to_string()));
+                    return;
+                  };
+                  let blob = Blob::from(file_data);
+                  spawn_local(clone!(this => async move {
+                    match read_as_bytes(&blob).await {
+                      Ok(content) => WealthTaxViewer::import_q4_prices(&this, content),
+                      Err(_) => this.error.set(Some("Error subiendo fichero".to_string())),
+                    }
+                  }));
+                  element.set_value("");
+                }))
+              })
+            })
+          )
+        })
+    }
+
+    fn render_deposit_input(
+        this: &Arc<Self>,
+        id: &str,
+        placeholder: &str,
+        update: fn(&mut Aeat714Deposits, Decimal),
+    ) -> Dom {
+        html!("span", {
+            .class(&*FLEX_CONTAINER_ITEM_20_CLASS)
+            .child(html!("input" => HtmlInputElement, {
+                .attr("id", id)
+                .attr("alt", placeholder)
+                .attr("type", "text")
+                .attr("placeholder", placeholder)
+                .style("height", "24px")
+                .style("width", "95%")
+                .with_node!(element => {
+                    .event(clone!(this => move |_: events::Change| {
+                        let amount = Self::parse_amount(&element.value()).unwrap_or(Decimal::ZERO);
+                        update(&mut this.deposits.lock_mut(), amount);
+                        Self::update_report(&this);
+                    }))
+                })
+            }))
+        })
+    }
+
+    fn render_deposits(this: &Arc<Self>) -> Dom {
+        html!("section", {
+            .class(&*FLEX_CONTAINER_CLASS)
+            .child(Self::render_deposit_input(
+                this,
+                "deposits_year_end",
+                "Saldo de cuentas a 31/12 (€)",
+                |deposits, amount| deposits.year_end_balance = amount,
+            ))
+            .child(Self::render_deposit_input(
+                this,
+                "deposits_q4",
+                "Saldo medio de cuentas 4º trimestre (€)",
+                |deposits, amount| deposits.q4_average_balance = amount,
+            ))
+        })
+    }
+
+    fn render_price_row(this: &Arc<Self>, record: &Aeat720Record, price: Option<Decimal>) -> Dom {
+        let isin = record.company.isin.clone();
+        html!("tr", {
+            .class(&*TABLE_ROW)
+            .child(html!("td", { .text(&record.company.name) }))
+            .child(html!("td", { .text(&record.company.isin) }))
+            .child(html!("td", {
+                .child(html!("input" => HtmlInputElement, {
+                    .style("text-align", "right")
+                    .attr("type", "text")
+                    .attr("size", "9")
+                    .attr("value", &price.map_or(String::new(), |price| decimal_to_str_locale(&price, DEFAULT_LOCALE)))
+                    .with_node!(element => {
+                        .event(clone!(this, isin => move |_: events::Change| {
+                            let mut q4_prices = this.q4_prices.get_cloned();
+                            match Self::parse_amount(&element.value()) {
+                                Some(price) => q4_prices.insert(isin.clone(), price),
+                                None => q4_prices.remove(&isin),
+                            };
+                            Self::set_q4_prices(&this, q4_prices);
+                        }))
+                    })
+                }))
+            }))
+        })
+    }
+
+    fn render_prices(this: &Arc<Self>) -> Dom {
+        html!("table", {
+            .child(html!("caption", {
+                .class(&*TABLE_CAPTION)
+                .text("Cotización media del 4º trimestre (€) de los valores negociados.")
+            }))
+            .child(html!("thead", {
+                .class(&*TABLE_HEADER)
+                .child(html!("tr", {
+                    .children(["Nombre compañía", "ISIN", "Cotización media"].iter().map(|header| {
+                        html!("th", {
+                            .attr("scope", "col")
+                            .style("background-color", "#ddd")
+                            .text(header)
+                        })
+                    }))
+                }))
+            }))
+            .child_signal(clone!(this => map_ref! {
+                let securities = this.securities.signal_cloned(),
+                let q4_prices = this.q4_prices.signal_cloned() => {
+                    let mut isins: Vec<&str> = vec![];
+                    let rows: Vec<Dom> = securities
+                        .iter()
+                        .filter(|record| {
+                            Aeat714Category::from_asset_category(record.category)
+                                .is_some_and(|category| category.uses_q4_average_price())
+                        })
+                        .filter(|record| {
+                            let new_isin = !isins.contains(&record.company.isin.as_str());
+                            isins.push(&record.company.isin);
+                            new_isin
+                        })
+                        .map(|record| {
+                            Self::render_price_row(&this, record, q4_prices.get(&record.company.isin).copied())
+                        })
+                        .collect();
+                    Some(html!("tbody", { .children(rows) }))
+                }
+            }))
+        })
+    }
+
+    fn render_summary(this: &Arc<Self>) -> Dom {
+        html!("section", {
+            .child_signal(this.report.signal_ref(|report| {
+                let report = report.as_ref()?;
+                let missing: Vec<String> = report
+                    .missing_prices()
+                    .iter()
+                    .map(|company| company.name.clone())
+                    .collect();
+                let unclassified: Vec<String> = report
+                    .unclassified()
+                    .iter()
+                    .map(|company| company.name.clone())
+                    .collect();
+                Some(html!("div", {
+                    .child(html!("table", {
+                        .child(html!("caption", {
+                            .class(&*TABLE_CAPTION)
+                            .text("Resumen para el modelo 714.")
+                        }))
+                        .child(html!("tbody", {
+                            .children(report.totals().iter().map(|total| {
+                                html!("tr", {
+                                    .class(&*TABLE_ROW)
+                                    .child(html!("td", { .text(total.category.description()) }))
+                                    .child(html!("td", {
+                                        .style("text-align", "right")
+                                        .text(&total.assets.to_string())
+                                    }))
+                                    .child(html!("td", {
+                                        .style("text-align", "right")
+                                        .text(&decimal_to_str_locale(&total.value, DEFAULT_LOCALE))
+                                    }))
+                                })
+                            }))
+                            .child(html!("tr", {
+                                .style("font-weight", "bold")
+                                .child(html!("td", { .text("Total") }))
+                                .child(html!("td", {}))
+                                .child(html!("td", {
+                                    .style("text-align", "right")
+                                    .text(&decimal_to_str_locale(&report.total(), DEFAULT_LOCALE))
+                                }))
+                            }))
+                        }))
+                    }))
+                    .apply_if(!missing.is_empty(), |dom| {
+                        dom.child(html!("p", {
+                            .style("color", "red")
+                            .style("font-size", "small")
+                            .text(&format!(
+                                "Sin cotización media del 4º trimestre, valorados a 31/12: {}",
+                                missing.join(", ")
+                            ))
+                        }))
+                    })
+                    .apply_if(!unclassified.is_empty(), |dom| {
+                        dom.child(html!("p", {
+                            .style("color", "red")
+                            .style("font-size", "small")
+                            .text(&format!(
+                                "Sin incluir, elige su tipo en la tabla de valores: {}",
+                                unclassified.join(", ")
+                            ))
+                        }))
+                    })
+                }))
+            }))
+        })
+    }
+
+    pub fn render(this: &Arc<Self>) -> Dom {
+        html!("section", {
+            .child(Self::render_deposits(this))
+            .child(Self::render_prices(this))
+            .child(Self::render_calculate_button(this))
+            .child(Self::render_import_button(this))
+            .child(html!("span", {
+                .style("color", "red")
+                .style("font-size", "small")
+                .text_signal(this.error.signal_ref(|error| error.clone().unwrap_or_default()))
+            }))
+            .child(Self::render_summary(this))
+        })
+    }
+}